@@ -15,6 +15,7 @@ use mitsuha_core::{
 };
 use mitsuha_core_types::module::ModuleInfo;
 use mitsuha_storage::UnifiedStorage;
+use mitsuha_wasm_runtime::wasmtime::WasmtimeConfig;
 
 pub fn make_system_channel() -> Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> {
     Arc::new(Box::new(SystemChannel::new()))
@@ -81,3 +82,13 @@ pub fn make_wasmtime_channel(
 ) -> Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> {
     Arc::new(Box::new(WasmtimeChannel::new(make_kernel(chan.clone()))))
 }
+
+pub fn make_wasmtime_channel_with_config(
+    chan: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>,
+    config: WasmtimeConfig,
+) -> Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> {
+    Arc::new(Box::new(
+        WasmtimeChannel::new_with_config(make_kernel(chan.clone()), config, Default::default())
+            .unwrap(),
+    ))
+}
@@ -2,13 +2,17 @@ use std::sync::Arc;
 
 mod setup;
 use criterion::{criterion_group, criterion_main, Criterion};
-use mitsuha_core::channel::{ChannelContext, ComputeChannel};
+use mitsuha_core::{
+    channel::{ChannelContext, ComputeChannel},
+    constants::Constants,
+};
 use mitsuha_core_types::{
     channel::{ComputeInput, ComputeOutput},
     kernel::{JobSpec, StorageSpec},
     module::{ModuleInfo, ModuleType},
     symbol::Symbol,
 };
+use mitsuha_wasm_runtime::wasmtime::WasmtimeConfig;
 use musubi_api::{
     types::{Data, Value},
     DataBuilder,
@@ -16,10 +20,12 @@ use musubi_api::{
 use rand::{distributions::Alphanumeric, Rng};
 use setup::*;
 
-pub async fn make_channel() -> Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> {
+pub async fn make_channel(
+    config: WasmtimeConfig,
+) -> Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> {
     let system_channel = make_system_channel();
     let labeled_storage_channel = make_labeled_storage_channel().await;
-    let wasmtime_channel = make_wasmtime_channel(system_channel.clone());
+    let wasmtime_channel = make_wasmtime_channel_with_config(system_channel.clone(), config);
 
     labeled_storage_channel.connect(wasmtime_channel).await;
 
@@ -104,16 +110,30 @@ pub async fn upload_artifacts(channel: Arc<Box<dyn ComputeChannel<Context = Chan
         .unwrap();
 }
 
+fn make_random_string() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(10)
+        .map(char::from)
+        .collect()
+}
+
 async fn run_hello_world(
     ctx: ChannelContext,
     channel: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>,
     mut spec: JobSpec,
+    cold: bool,
 ) {
-    let handle: String = rand::thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(10)
-        .map(char::from)
-        .collect();
+    let handle = make_random_string();
+
+    // The module resolver prefix is part of the linker cache keys, a fresh one makes the job
+    // compile its modules and resolve its imports as if it was the first to run them.
+    if cold {
+        spec.extensions.insert(
+            Constants::ModuleResolverPrefix.to_string(),
+            format!("cold/{}/", make_random_string()),
+        );
+    }
 
     spec.handle = handle.clone();
     spec.output_handle = handle.clone();
@@ -146,17 +166,7 @@ async fn run_hello_world(
     }
 }
 
-fn criterion_benchmark(c: &mut Criterion) {
-    let tokio_rt = tokio::runtime::Builder::new_current_thread()
-        .enable_time()
-        .build()
-        .unwrap();
-
-    let channel = tokio_rt.block_on(make_channel());
-    let ctx = ChannelContext::default();
-
-    tokio_rt.block_on(upload_artifacts(channel.clone()));
-
+fn make_specs() -> (StorageSpec, JobSpec, JobSpec) {
     let input = DataBuilder::new()
         .add(Value::String("Hello world!".to_string()))
         .build();
@@ -172,10 +182,6 @@ fn criterion_benchmark(c: &mut Criterion) {
         extensions: Default::default(),
     };
 
-    tokio_rt
-        .block_on(channel.compute(ctx.clone(), ComputeInput::Store { spec: input_spec }))
-        .unwrap();
-
     let single_dep_spec = JobSpec {
         handle: job_handle.clone(),
         symbol: Symbol {
@@ -208,18 +214,52 @@ fn criterion_benchmark(c: &mut Criterion) {
         extensions: Default::default(),
     };
 
-    c.bench_function("run_single_dep_job", |b| {
+    (input_spec, single_dep_spec, multi_dep_spec)
+}
+
+fn bench_with_config(c: &mut Criterion, prefix: &str, config: WasmtimeConfig, cold: bool) {
+    let tokio_rt = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+
+    let channel = tokio_rt.block_on(make_channel(config));
+    let ctx = ChannelContext::default();
+
+    tokio_rt.block_on(upload_artifacts(channel.clone()));
+
+    let (input_spec, single_dep_spec, multi_dep_spec) = make_specs();
+
+    tokio_rt
+        .block_on(channel.compute(ctx.clone(), ComputeInput::Store { spec: input_spec }))
+        .unwrap();
+
+    c.bench_function(&format!("{}_run_single_dep_job", prefix), |b| {
         b.to_async(&tokio_rt)
-            .iter(|| run_hello_world(ctx.clone(), channel.clone(), single_dep_spec.clone()))
+            .iter(|| run_hello_world(ctx.clone(), channel.clone(), single_dep_spec.clone(), cold))
     });
 
-    c.bench_function("run_multi_dep_job", |b| {
+    c.bench_function(&format!("{}_run_multi_dep_job", prefix), |b| {
         b.to_async(&tokio_rt)
-            .iter(|| run_hello_world(ctx.clone(), channel.clone(), multi_dep_spec.clone()))
+            .iter(|| run_hello_world(ctx.clone(), channel.clone(), multi_dep_spec.clone(), cold))
     });
 
     tokio_rt.shutdown_background();
 }
 
+fn criterion_benchmark(c: &mut Criterion) {
+    // Cold start: every job is the first one to run its modules, which are compiled and have
+    // their imports resolved before being added to the linker caches.
+    bench_with_config(c, "cold", WasmtimeConfig::default(), true);
+
+    // Warm start: modules and pre-instantiated imports are served from the linker caches.
+    bench_with_config(c, "warm", WasmtimeConfig::default(), false);
+
+    let mut pooling_config = WasmtimeConfig::default();
+    pooling_config.pooling_allocator = true;
+
+    bench_with_config(c, "warm_pooling", pooling_config, false);
+}
+
 criterion_group!(benches, criterion_benchmark);
 criterion_main!(benches);
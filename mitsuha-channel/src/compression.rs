@@ -0,0 +1,334 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use mitsuha_core::{
+    channel::{ChannelContext, ComputeChannel, ComputeInputExt},
+    err_unknown,
+    errors::{Error, ToUnknownErrorResult},
+    types,
+};
+use mitsuha_core_types::{
+    channel::{ComputeInput, ComputeOutput},
+    kernel::StorageSpec,
+};
+use mitsuha_filesystem::{
+    constant::NativeFileSystemConstants,
+    event::{NativeFileSystemEvent, NativeFileSystemEventContext},
+};
+use tokio::sync::RwLock;
+
+use crate::{NextComputeChannel, WrappedComputeChannel};
+
+/// Prefix of blobs written by a [CompressionChannel], followed by a tag byte identifying the
+/// algorithm the rest of the blob is compressed with.
+const COMPRESSION_MAGIC: &[u8] = b"\xffMZC";
+
+/// Suffix of the handle which marks a blob as compressed, see [CompressionChannel].
+const MARKER_SUFFIX: &str = ".compression";
+
+const TAG_NONE: u8 = 0;
+const TAG_ZSTD: u8 = 1;
+const TAG_LZ4: u8 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompressionAlgorithm {
+    Zstd { level: i32 },
+    Lz4,
+}
+
+impl CompressionAlgorithm {
+    fn tag(&self) -> u8 {
+        match self {
+            Self::Zstd { .. } => TAG_ZSTD,
+            Self::Lz4 => TAG_LZ4,
+        }
+    }
+
+    fn compress(&self, data: &[u8]) -> types::Result<Vec<u8>> {
+        match self {
+            Self::Zstd { level } => zstd::encode_all(data, *level).to_unknown_err_result(),
+            Self::Lz4 => Ok(lz4_flex::compress_prepend_size(data)),
+        }
+    }
+}
+
+/// Compresses the data of stored blobs at least `min_size` bytes long and decompresses
+/// loaded blobs. Compressed blobs carry an in-band header, so they are recognized on every
+/// storage backend, and blobs written elsewhere are loaded unchanged. Blobs starting with the
+/// header by chance are wrapped in an uncompressed header so that they load back intact.
+///
+/// Inputs in native file system mode pass through unchanged, since file parts are written
+/// and read at offsets of the uncompressed file. Every compressed blob is paired with a marker
+/// blob, stored, persisted and cleared along with it, so that reading any part of a compressed
+/// blob, or its size, fails instead of returning compressed data.
+pub struct CompressionChannel {
+    id: String,
+    next: NextComputeChannel<ChannelContext>,
+    algorithm: CompressionAlgorithm,
+    min_size: usize,
+}
+
+#[async_trait]
+impl ComputeChannel for CompressionChannel {
+    type Context = ChannelContext;
+
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    async fn compute(
+        &self,
+        ctx: ChannelContext,
+        mut elem: ComputeInput,
+    ) -> types::Result<ComputeOutput> {
+        if Self::is_mnfs_call(&elem) {
+            return self.compute_mnfs(ctx, elem).await;
+        }
+
+        if let ComputeInput::Store { spec } = &mut elem {
+            let data = std::mem::take(&mut spec.data);
+            spec.data = Self::encode(self.algorithm, self.min_size, data).await?;
+        }
+
+        let marker_input = Self::make_marker_input(&elem);
+
+        let output = match self.forward(ctx.clone(), elem).await? {
+            ComputeOutput::Loaded { data } => ComputeOutput::Loaded {
+                data: Self::decode(data).await?,
+            },
+            output => output,
+        };
+
+        match marker_input {
+            Some(input @ ComputeInput::Store { .. }) => {
+                self.forward(ctx, input).await?;
+            }
+            // The marker only exists for compressed blobs.
+            Some(input) => _ = self.forward(ctx, input).await,
+            None => {}
+        }
+
+        Ok(output)
+    }
+
+    async fn connect(&self, next: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>) {
+        *self.next.write().await = Some(next);
+    }
+}
+
+impl CompressionChannel {
+    pub fn get_identifier_type() -> &'static str {
+        "mitsuha/channel/compression"
+    }
+
+    pub fn new(algorithm: CompressionAlgorithm, min_size: usize) -> WrappedComputeChannel<Self> {
+        WrappedComputeChannel::new(Self {
+            id: Self::get_identifier_type().to_string(),
+            next: Arc::new(RwLock::new(None)),
+            algorithm,
+            min_size,
+        })
+    }
+
+    async fn forward(
+        &self,
+        ctx: ChannelContext,
+        elem: ComputeInput,
+    ) -> types::Result<ComputeOutput> {
+        match self.next.read().await.clone() {
+            Some(chan) => chan.compute(ctx, elem).await,
+            None => Err(Error::ComputeChannelEOF),
+        }
+    }
+
+    fn is_mnfs_call(elem: &ComputeInput) -> bool {
+        matches!(
+            elem.get_extensions()
+                .get(&NativeFileSystemConstants::EnableFileSystemMode.to_string())
+                .map(|x| x.as_str()),
+            Some("true")
+        )
+    }
+
+    fn is_encoded(data: &[u8]) -> bool {
+        data.len() > COMPRESSION_MAGIC.len() && data.starts_with(COMPRESSION_MAGIC)
+    }
+
+    fn get_marker_handle(handle: &str) -> String {
+        format!(
+            "{}{}{}",
+            handle,
+            NativeFileSystemConstants::MnfsSuffix,
+            MARKER_SUFFIX
+        )
+    }
+
+    /// Returns the input applying `elem` to the marker of its blob. The marker is stored with
+    /// compressed blobs, holding their algorithm tag, and cleared when a blob is overwritten
+    /// uncompressed.
+    fn make_marker_input(elem: &ComputeInput) -> Option<ComputeInput> {
+        match elem {
+            ComputeInput::Store { spec } if Self::is_encoded(&spec.data) => {
+                Some(ComputeInput::Store {
+                    spec: StorageSpec {
+                        handle: Self::get_marker_handle(&spec.handle),
+                        data: vec![spec.data[COMPRESSION_MAGIC.len()]],
+                        ttl: spec.ttl,
+                        extensions: spec.extensions.clone(),
+                    },
+                })
+            }
+            ComputeInput::Store { spec } => Some(ComputeInput::Clear {
+                handle: Self::get_marker_handle(&spec.handle),
+                extensions: spec.extensions.clone(),
+            }),
+            ComputeInput::Persist {
+                handle,
+                ttl,
+                extensions,
+            } => Some(ComputeInput::Persist {
+                handle: Self::get_marker_handle(handle),
+                ttl: *ttl,
+                extensions: extensions.clone(),
+            }),
+            ComputeInput::Clear { handle, extensions } => Some(ComputeInput::Clear {
+                handle: Self::get_marker_handle(handle),
+                extensions: extensions.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the blob at `handle` was stored compressed, by loading its marker
+    /// outside of native file system mode.
+    async fn is_compressed(
+        &self,
+        ctx: ChannelContext,
+        handle: &str,
+        extensions: &HashMap<String, String>,
+    ) -> bool {
+        let mut extensions = extensions.clone();
+        extensions.remove(&NativeFileSystemConstants::EnableFileSystemMode.to_string());
+
+        let input = ComputeInput::Load {
+            handle: Self::get_marker_handle(handle),
+            extensions,
+        };
+
+        self.forward(ctx, input).await.is_ok()
+    }
+
+    async fn compute_mnfs(
+        &self,
+        ctx: ChannelContext,
+        elem: ComputeInput,
+    ) -> types::Result<ComputeOutput> {
+        let mut first_part = false;
+
+        if let ComputeInput::Load { handle, extensions } = &elem {
+            let event = NativeFileSystemEventContext::Load {
+                handle: handle.clone(),
+                extensions: extensions.clone(),
+            }
+            .get_event()
+            .to_unknown_err_result()?;
+
+            let blob_handle = match event {
+                Some(NativeFileSystemEvent::LoadPart {
+                    handle, part_index, ..
+                }) => {
+                    first_part = part_index == 0;
+                    Some(handle)
+                }
+                Some(NativeFileSystemEvent::GetPartCount { handle })
+                | Some(NativeFileSystemEvent::GetMetadata { handle }) => Some(handle),
+                _ => None,
+            };
+
+            if let Some(blob_handle) = blob_handle {
+                if self
+                    .is_compressed(ctx.clone(), &blob_handle, extensions)
+                    .await
+                {
+                    return Err(Self::make_compressed_error(&blob_handle));
+                }
+            }
+        }
+
+        let handle = elem.get_handle();
+        let output = self.forward(ctx, elem).await?;
+
+        // Blobs compressed before markers were stored are only recognized by their header.
+        match &output {
+            ComputeOutput::Loaded { data } if first_part && Self::is_encoded(data) => {
+                Err(Self::make_compressed_error(&handle))
+            }
+            _ => Ok(output),
+        }
+    }
+
+    fn make_compressed_error(handle: &str) -> Error {
+        err_unknown!(format!(
+            "blob '{}' is compressed and cannot be read in parts",
+            handle
+        ))
+    }
+
+    async fn encode(
+        algorithm: CompressionAlgorithm,
+        min_size: usize,
+        data: Vec<u8>,
+    ) -> types::Result<Vec<u8>> {
+        if data.len() < min_size && !data.starts_with(COMPRESSION_MAGIC) {
+            return Ok(data);
+        }
+
+        tokio::task::spawn_blocking(move || {
+            let mut output = COMPRESSION_MAGIC.to_vec();
+
+            let compressed = if data.len() >= min_size {
+                Some(algorithm.compress(&data)?)
+            } else {
+                None
+            };
+
+            match compressed {
+                Some(compressed) if compressed.len() < data.len() => {
+                    output.push(algorithm.tag());
+                    output.extend(compressed);
+                }
+                _ if data.starts_with(COMPRESSION_MAGIC) => {
+                    output.push(TAG_NONE);
+                    output.extend(data);
+                }
+                _ => return Ok(data),
+            }
+
+            Ok(output)
+        })
+        .await
+        .to_unknown_err_result()?
+    }
+
+    async fn decode(data: Vec<u8>) -> types::Result<Vec<u8>> {
+        if !Self::is_encoded(&data) {
+            return Ok(data);
+        }
+
+        tokio::task::spawn_blocking(move || {
+            let payload = &data[COMPRESSION_MAGIC.len() + 1..];
+
+            match data[COMPRESSION_MAGIC.len()] {
+                TAG_NONE => Ok(payload.to_vec()),
+                TAG_ZSTD => zstd::decode_all(payload).to_unknown_err_result(),
+                TAG_LZ4 => lz4_flex::decompress_size_prepended(payload).to_unknown_err_result(),
+                tag => Err(err_unknown!(format!(
+                    "unknown compression algorithm tag '{}'",
+                    tag
+                ))),
+            }
+        })
+        .await
+        .to_unknown_err_result()?
+    }
+}
@@ -1,8 +1,12 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use mitsuha_core::{
     channel::{ComputeChannel, ComputeInputExt},
+    constants::Constants,
     errors::Error,
+    kernel::LabelExtensionExt,
+    selector::Label,
     types,
 };
 use mitsuha_core_types::channel::{ComputeInput, ComputeOutput};
@@ -14,11 +18,16 @@ use async_trait::async_trait;
 use mitsuha_core::channel::ChannelContext;
 use mitsuha_core::errors::ToUnknownErrorResult;
 
+/// Authorizes compute inputs against the policy blob named by the `policy_blob_key`
+/// extension, bound by the policies of the parent job if any. Policy blobs are loaded with
+/// the namespace of the input and the `policy_selector` only, so that the caller cannot
+/// pick the storage the policies are read from.
 pub struct EnforcerChannel {
     next: NextComputeChannel<ChannelContext>,
     id: String,
     policy_engine: Arc<Box<dyn PolicyEngine>>,
     policy_blob_key: String,
+    policy_selector: Option<Label>,
 }
 
 #[async_trait]
@@ -32,23 +41,99 @@ impl ComputeChannel for EnforcerChannel {
     async fn compute(
         &self,
         ctx: ChannelContext,
-        elem: ComputeInput,
+        mut elem: ComputeInput,
     ) -> types::Result<ComputeOutput> {
-        if !elem.get_extensions().contains_key(&self.policy_blob_key) {
-            tracing::warn!(
-                "could not find policy blob key: '{}', bypassing enforcer",
-                self.policy_blob_key
-            );
+        let identity = elem
+            .get_extensions()
+            .get(&Constants::JobIdentity.to_string())
+            .cloned();
+
+        let policy_blob_handle = elem.get_extensions().get(&self.policy_blob_key).cloned();
+        let parent_policy_blob_handle = elem
+            .get_extensions()
+            .get(&Constants::JobParentPolicyBlob.to_string())
+            .cloned();
+
+        // Operations of a job are bound by the policies of the job, a child job may only
+        // narrow them with policies of its own.
+        let (policy_blob_handle, policies) = match (policy_blob_handle, parent_policy_blob_handle) {
+            (None, None) => {
+                tracing::warn!(
+                    "could not find policy blob key: '{}', bypassing enforcer",
+                    self.policy_blob_key
+                );
+
+                return self.forward_next(ctx, elem).await;
+            }
+            (Some(handle), None) | (None, Some(handle)) => {
+                let policies = self.load_policies(&ctx, &elem, &handle).await?;
+                (handle, policies)
+            }
+            (Some(handle), Some(parent_handle)) => {
+                let parent_policies = self.load_policies(&ctx, &elem, &parent_handle).await?;
+                let policies = self.load_policies(&ctx, &elem, &handle).await?;
+
+                if !self
+                    .policy_engine
+                    .contains(&parent_policies, &policies)
+                    .await?
+                {
+                    return Err(Error::InvalidOperation {
+                        message: format!(
+                            "policies defined in blob '{}' exceed the policies of the parent job{} defined in blob '{}'",
+                            handle,
+                            identity.as_ref().map(|x| format!(" '{}'", x)).unwrap_or_default(),
+                            parent_handle
+                        ),
+                    });
+                }
+
+                (handle, policies)
+            }
+        };
+
+        let policy_eval = self.policy_engine.evaluate(&elem, &policies).await?;
+
+        if !policy_eval {
+            return Err(Error::InvalidOperation {
+                message: format!(
+                    "policies defined in blob '{}' forbids the compute operation{}",
+                    policy_blob_handle,
+                    Self::describe_identity(&identity)
+                ),
+            });
+        }
 
-            return self.forward_next(ctx, elem).await;
+        tracing::info!(
+            "policies defined in blob '{}' allows the compute operation{}",
+            policy_blob_handle,
+            Self::describe_identity(&identity)
+        );
+
+        // Record the policies the job runs under, they bound the kernel calls it makes.
+        if let ComputeInput::Run { spec } = &mut elem {
+            spec.extensions
+                .insert(Constants::JobPolicyBlob.to_string(), policy_blob_handle);
         }
 
-        let policy_blob_handle = elem.get_extensions().get(&self.policy_blob_key).unwrap();
-        let policies: Vec<Policy>;
+        self.forward_next(ctx, elem).await
+    }
+
+    async fn connect(&self, next: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>) {
+        *self.next.write().await = Some(next);
+    }
+}
 
+impl EnforcerChannel {
+    async fn load_policies(
+        &self,
+        ctx: &ChannelContext,
+        elem: &ComputeInput,
+        policy_blob_handle: &String,
+    ) -> types::Result<Vec<Policy>> {
         let policy_blob_input = ComputeInput::Load {
             handle: policy_blob_handle.clone(),
-            extensions: elem.get_extensions().clone(),
+            extensions: self.get_policy_extensions(elem),
         };
 
         let policy_blob_output = self.forward_next(ctx.clone(), policy_blob_input).await?;
@@ -56,38 +141,39 @@ impl ComputeChannel for EnforcerChannel {
         if let ComputeOutput::Loaded { data } = policy_blob_output {
             let value = musubi_api::types::Value::try_from(data).to_unknown_err_result()?;
 
-            policies = musubi_api::types::from_value(&value).to_unknown_err_result()?;
+            musubi_api::types::from_value(&value).to_unknown_err_result()
         } else {
-            return Err(Error::UnknownWithMsgOnly {
+            Err(Error::UnknownWithMsgOnly {
                 message: "expected to find data in policy blob compute output".to_string(),
-            });
+            })
         }
+    }
 
-        let policy_eval = self.policy_engine.evaluate(&elem, &policies).await?;
+    /// Extensions of policy blob loads, nothing but the namespace is taken from the input.
+    fn get_policy_extensions(&self, elem: &ComputeInput) -> HashMap<String, String> {
+        let mut extensions = HashMap::new();
 
-        if !policy_eval {
-            return Err(Error::InvalidOperation {
-                message: format!(
-                    "policies defined in blob '{}' forbids the compute operation",
-                    policy_blob_handle
-                ),
-            });
+        if let Some(namespace) = elem
+            .get_extensions()
+            .get(&Constants::ChannelNamespace.to_string())
+        {
+            extensions.insert(Constants::ChannelNamespace.to_string(), namespace.clone());
         }
 
-        tracing::info!(
-            "policies defined in blob '{}' allows the compute operation",
-            policy_blob_handle
-        );
+        if let Some(selector) = &self.policy_selector {
+            extensions.add_selector(selector);
+        }
 
-        self.forward_next(ctx, elem).await
+        extensions
     }
 
-    async fn connect(&self, next: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>) {
-        *self.next.write().await = Some(next);
+    fn describe_identity(identity: &Option<String>) -> String {
+        match identity {
+            Some(identity) => format!(" of job '{}'", identity),
+            None => String::new(),
+        }
     }
-}
 
-impl EnforcerChannel {
     async fn forward_next(
         &self,
         ctx: ChannelContext,
@@ -103,12 +189,16 @@ impl EnforcerChannel {
         "mitsuha/channel/enforcer"
     }
 
-    pub fn new(policy_blob_key: String) -> WrappedComputeChannel<Self> {
+    pub fn new(
+        policy_blob_key: String,
+        policy_selector: Option<Label>,
+    ) -> WrappedComputeChannel<Self> {
         WrappedComputeChannel::new(Self {
             next: Arc::new(tokio::sync::RwLock::new(None)),
             id: Self::get_identifier_type().to_string(),
             policy_engine: Arc::new(Box::new(StandardPolicyEngine)),
             policy_blob_key,
+            policy_selector,
         })
     }
 }
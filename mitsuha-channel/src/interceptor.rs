@@ -1,19 +1,119 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
-use mitsuha_core::{channel::ComputeChannel, errors::Error, types};
+use mitsuha_core::{channel::ComputeChannel, err_unknown, errors::Error, types};
 use mitsuha_core_types::channel::{ComputeInput, ComputeOutput};
-use mitsuha_runtime_rpc::proto::channel::{interceptor_client::InterceptorClient, ComputeRequest};
+use mitsuha_runtime_rpc::proto::channel::{
+    interceptor_client::InterceptorClient, ComputeRequest, ComputeResponse,
+    InterceptResponseRequest,
+};
 
-use crate::{NextComputeChannel, WrappedComputeChannel};
+use crate::{metric, util, NextComputeChannel, WrappedComputeChannel};
 
 use async_trait::async_trait;
 use mitsuha_core::channel::ChannelContext;
 use mitsuha_core::errors::ToUnknownErrorResult;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Phase {
+    Request,
+    Response,
+}
+
+impl Phase {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Request => "request",
+            Self::Response => "response",
+        }
+    }
+}
+
+/// A service implementing the `Interceptor` gRPC service. An interceptor answering a phase
+/// with `UNIMPLEMENTED` is skipped in that phase until [InterceptorPolicy::reprobe_interval]
+/// has passed, so that it may implement only one of the two and start implementing the
+/// other without a restart of the runtime.
+pub struct InterceptorEndpoint {
+    address: String,
+    client: InterceptorClient<tonic::transport::Channel>,
+    request_phase_disabled_until: AtomicI64,
+    response_phase_disabled_until: AtomicI64,
+}
+
+impl InterceptorEndpoint {
+    /// Endpoints connect lazily and start out intercepting both phases.
+    pub fn new(address: String) -> types::Result<Self> {
+        let conn = tonic::transport::Endpoint::new(address.clone())
+            .to_unknown_err_result()?
+            .connect_lazy();
+
+        Ok(Self {
+            address,
+            client: InterceptorClient::new(conn),
+            request_phase_disabled_until: AtomicI64::new(0),
+            response_phase_disabled_until: AtomicI64::new(0),
+        })
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Epoch milliseconds until which the phase is skipped.
+    fn phase_disabled_until(&self, phase: Phase) -> &AtomicI64 {
+        match phase {
+            Phase::Request => &self.request_phase_disabled_until,
+            Phase::Response => &self.response_phase_disabled_until,
+        }
+    }
+
+    fn is_phase_enabled(&self, phase: Phase) -> bool {
+        self.phase_disabled_until(phase).load(Ordering::Relaxed)
+            <= chrono::Utc::now().timestamp_millis()
+    }
+
+    fn disable_phase(&self, phase: Phase, interval: Duration) {
+        let until = chrono::Utc::now().timestamp_millis() + interval.as_millis() as i64;
+
+        self.phase_disabled_until(phase)
+            .store(until, Ordering::Relaxed);
+    }
+}
+
+/// Bounds every interceptor call by `timeout`. An interceptor failing or timing out is
+/// bypassed when `fail_open` is set, and fails the input otherwise, with
+/// [Error::ServiceUnavailable] when it timed out or could not be reached. Rejections are never
+/// bypassed. A phase an interceptor does not implement is probed again after
+/// `reprobe_interval`.
+#[derive(Clone, Debug)]
+pub struct InterceptorPolicy {
+    pub timeout: Duration,
+    pub fail_open: bool,
+    pub reprobe_interval: Duration,
+}
+
+impl Default for InterceptorPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            fail_open: false,
+            reprobe_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Passes every input through the request phase of its interceptors in order before
+/// forwarding it, and the successful output through their response phase in reverse order.
+/// The response phase sees the request as it was forwarded. An interceptor rejects an input
+/// by failing with `PERMISSION_DENIED`, which fails the input with
+/// [Error::InterceptorRejected].
 pub struct InterceptorChannel {
     next: NextComputeChannel<ChannelContext>,
     id: String,
-    client: InterceptorClient<tonic::transport::Channel>,
+    endpoints: Vec<InterceptorEndpoint>,
+    policy: InterceptorPolicy,
 }
 
 #[async_trait]
@@ -29,22 +129,40 @@ impl ComputeChannel for InterceptorChannel {
         ctx: ChannelContext,
         elem: ComputeInput,
     ) -> types::Result<ComputeOutput> {
-        let compute_request: ComputeRequest = elem.try_into().to_unknown_err_result()?;
+        let mut compute_request: ComputeRequest = elem.try_into().to_unknown_err_result()?;
 
-        let compute_request = self
-            .client
-            .clone()
-            .intercept(compute_request)
-            .await
-            .to_unknown_err_result()?
-            .into_inner();
+        for endpoint in self.endpoints.iter() {
+            compute_request = self.intercept_request(endpoint, compute_request).await?;
+        }
+
+        let compute_input: ComputeInput =
+            compute_request.clone().try_into().to_unknown_err_result()?;
+
+        let compute_output = match self.next.read().await.clone() {
+            Some(chan) => chan.compute(ctx, compute_input).await?,
+            None => return Err(Error::ComputeChannelEOF),
+        };
+
+        if !self
+            .endpoints
+            .iter()
+            .any(|x| x.is_phase_enabled(Phase::Response))
+        {
+            return Ok(compute_output);
+        }
 
-        let compute_input: ComputeInput = compute_request.try_into().to_unknown_err_result()?;
+        let mut compute_response: ComputeResponse =
+            compute_output.try_into().to_unknown_err_result()?;
 
-        match self.next.read().await.clone() {
-            Some(chan) => chan.compute(ctx, compute_input).await,
-            None => Err(Error::ComputeChannelEOF),
+        for endpoint in self.endpoints.iter().rev() {
+            compute_response = self
+                .intercept_response(endpoint, &compute_request, compute_response)
+                .await?;
         }
+
+        let compute_output: ComputeOutput = compute_response.try_into().to_unknown_err_result()?;
+
+        Ok(compute_output)
     }
 
     async fn connect(&self, next: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>) {
@@ -58,12 +176,129 @@ impl InterceptorChannel {
     }
 
     pub fn new(
-        client: InterceptorClient<tonic::transport::Channel>,
+        endpoints: Vec<InterceptorEndpoint>,
+        policy: InterceptorPolicy,
     ) -> WrappedComputeChannel<Self> {
         WrappedComputeChannel::new(Self {
             next: Arc::new(tokio::sync::RwLock::new(None)),
             id: Self::get_identifier_type().to_string(),
-            client,
+            endpoints,
+            policy,
         })
     }
+
+    async fn intercept_request(
+        &self,
+        endpoint: &InterceptorEndpoint,
+        compute_request: ComputeRequest,
+    ) -> types::Result<ComputeRequest> {
+        if !endpoint.is_phase_enabled(Phase::Request) {
+            return Ok(compute_request);
+        }
+
+        let mut client = endpoint.client.clone();
+        let call = client.intercept(compute_request.clone());
+
+        Ok(self
+            .call(endpoint, Phase::Request, call)
+            .await?
+            .unwrap_or(compute_request))
+    }
+
+    async fn intercept_response(
+        &self,
+        endpoint: &InterceptorEndpoint,
+        compute_request: &ComputeRequest,
+        compute_response: ComputeResponse,
+    ) -> types::Result<ComputeResponse> {
+        if !endpoint.is_phase_enabled(Phase::Response) {
+            return Ok(compute_response);
+        }
+
+        let mut client = endpoint.client.clone();
+        let call = client.intercept_response(InterceptResponseRequest {
+            request: Some(compute_request.clone()),
+            response: Some(compute_response.clone()),
+        });
+
+        Ok(self
+            .call(endpoint, Phase::Response, call)
+            .await?
+            .unwrap_or(compute_response))
+    }
+
+    /// Returns [None] when the interceptor is bypassed.
+    async fn call<T>(
+        &self,
+        endpoint: &InterceptorEndpoint,
+        phase: Phase,
+        call: impl Future<Output = Result<tonic::Response<T>, tonic::Status>>,
+    ) -> types::Result<Option<T>> {
+        let (reason, message, transient) =
+            match tokio::time::timeout(self.policy.timeout, call).await {
+                Ok(Ok(response)) => return Ok(Some(response.into_inner())),
+                Ok(Err(status)) if status.code() == tonic::Code::PermissionDenied => {
+                    return Err(Error::InterceptorRejected {
+                        interceptor: endpoint.address.clone(),
+                        reason: status.message().to_string(),
+                    });
+                }
+                Ok(Err(status)) if status.code() == tonic::Code::Unimplemented => {
+                    tracing::warn!(
+                        "interceptor '{}' does not implement the {} phase, skipping it for {}s",
+                        endpoint.address,
+                        phase.as_str(),
+                        self.policy.reprobe_interval.as_secs()
+                    );
+
+                    endpoint.disable_phase(phase, self.policy.reprobe_interval);
+
+                    return Ok(None);
+                }
+                Ok(Err(status)) => {
+                    let transient = matches!(
+                        status.code(),
+                        tonic::Code::Unavailable | tonic::Code::DeadlineExceeded
+                    ) || util::is_connect_error(&status);
+
+                    ("error", status.to_string(), transient)
+                }
+                Err(_) => (
+                    "timeout",
+                    format!("timed out after {}ms", self.policy.timeout.as_millis()),
+                    true,
+                ),
+            };
+
+        metric::interceptor_failure_count_metric()
+            .with_label_values(&[endpoint.address.as_str(), phase.as_str(), reason])
+            .inc();
+
+        if self.policy.fail_open {
+            tracing::warn!(
+                "bypassing interceptor '{}' in the {} phase, {}",
+                endpoint.address,
+                phase.as_str(),
+                message
+            );
+
+            return Ok(None);
+        }
+
+        let message = format!(
+            "interceptor '{}' failed in the {} phase, {}",
+            endpoint.address,
+            phase.as_str(),
+            message
+        );
+
+        if transient {
+            return Err(Error::ServiceUnavailable {
+                target: endpoint.address.clone(),
+                message,
+            });
+        }
+
+        Err(err_unknown!(message))
+    }
 }
@@ -8,12 +8,20 @@ use mitsuha_core_types::channel::{ComputeInput, ComputeOutput};
 use tokio::sync::RwLock;
 use tracing::Instrument;
 
+pub mod compression;
 pub mod delegator;
 pub mod enforcer;
 pub mod interceptor;
 pub mod labeled_storage;
+pub mod metric;
+pub mod mirror;
 pub mod muxed_storage;
 pub mod namespacer;
+pub mod quota;
+pub mod rate_limiter;
+pub mod remote;
+pub mod resilience;
+pub mod router;
 pub mod scheduler;
 pub mod system;
 mod util;
@@ -0,0 +1,60 @@
+use lazy_static::lazy_static;
+use prometheus::{IntCounterVec, IntGaugeVec, Opts};
+
+lazy_static! {
+    static ref CIRCUIT_BREAKER_STATE: IntGaugeVec = IntGaugeVec::new(
+        Opts::new(
+            "circuit_breaker_state",
+            "Circuit Breaker State (0: closed, 1: half open, 2: open)"
+        )
+        .namespace("mitsuha_channel"),
+        &["target"]
+    )
+    .expect("failed to initialize metric: CIRCUIT_BREAKER_STATE");
+    static ref CIRCUIT_BREAKER_TRANSITION_COUNT: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "circuit_breaker_transition_count",
+            "Circuit Breaker Transition Count"
+        )
+        .namespace("mitsuha_channel"),
+        &["target", "state"]
+    )
+    .expect("failed to initialize metric: CIRCUIT_BREAKER_TRANSITION_COUNT");
+    static ref COMPUTE_RETRY_COUNT: IntCounterVec = IntCounterVec::new(
+        Opts::new("compute_retry_count", "Compute Retry Count").namespace("mitsuha_channel"),
+        &["target", "opkind"]
+    )
+    .expect("failed to initialize metric: COMPUTE_RETRY_COUNT");
+    static ref MIRROR_DIVERGENCE_COUNT: IntCounterVec = IntCounterVec::new(
+        Opts::new("mirror_divergence_count", "Mirror Divergence Count")
+            .namespace("mitsuha_channel"),
+        &["opkind", "reason"]
+    )
+    .expect("failed to initialize metric: MIRROR_DIVERGENCE_COUNT");
+    static ref INTERCEPTOR_FAILURE_COUNT: IntCounterVec = IntCounterVec::new(
+        Opts::new("interceptor_failure_count", "Interceptor Failure Count")
+            .namespace("mitsuha_channel"),
+        &["interceptor", "phase", "reason"]
+    )
+    .expect("failed to initialize metric: INTERCEPTOR_FAILURE_COUNT");
+}
+
+pub fn circuit_breaker_state_metric() -> &'static IntGaugeVec {
+    &CIRCUIT_BREAKER_STATE
+}
+
+pub fn circuit_breaker_transition_count_metric() -> &'static IntCounterVec {
+    &CIRCUIT_BREAKER_TRANSITION_COUNT
+}
+
+pub fn compute_retry_count_metric() -> &'static IntCounterVec {
+    &COMPUTE_RETRY_COUNT
+}
+
+pub fn mirror_divergence_count_metric() -> &'static IntCounterVec {
+    &MIRROR_DIVERGENCE_COUNT
+}
+
+pub fn interceptor_failure_count_metric() -> &'static IntCounterVec {
+    &INTERCEPTOR_FAILURE_COUNT
+}
@@ -0,0 +1,198 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use mitsuha_core::{
+    channel::ComputeChannel, errors::Error, kernel::LabelExtensionExt, selector::Label,
+    storage::Storage, types,
+};
+use mitsuha_core_types::channel::{ComputeInput, ComputeOutput};
+use tokio::sync::RwLock;
+
+use crate::{metric, NextComputeChannel, WrappedComputeChannel};
+
+/// Sends every store, persist and clear to the storages selected by both a primary and a
+/// secondary label, to dual-write while migrating between storage classes. The primary is
+/// authoritative: its result is returned, and failures of the secondary are only logged and
+/// counted as divergences.
+///
+/// Loads are served by the primary and fall back to the secondary. With `verify_loads`, the
+/// secondary is also loaded on every successful primary load and mismatches are counted.
+pub struct MirrorChannel<Context: Send> {
+    storage: Arc<Box<dyn Storage>>,
+    primary_selector: Label,
+    secondary_selector: Label,
+    verify_loads: bool,
+    next: NextComputeChannel<Context>,
+    id: String,
+}
+
+#[async_trait]
+impl<Context> ComputeChannel for MirrorChannel<Context>
+where
+    Context: Send,
+{
+    type Context = Context;
+
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    async fn compute(&self, ctx: Context, elem: ComputeInput) -> types::Result<ComputeOutput> {
+        let storage = self.storage.clone();
+
+        match elem {
+            ComputeInput::Store { spec } => {
+                let mut primary_spec = spec.clone();
+                primary_spec.extensions.add_selector(&self.primary_selector);
+
+                let mut secondary_spec = spec;
+                secondary_spec
+                    .extensions
+                    .add_selector(&self.secondary_selector);
+
+                let (primary, secondary) =
+                    futures::join!(storage.store(primary_spec), storage.store(secondary_spec));
+
+                self.reconcile("store", primary, secondary)?;
+
+                Ok(ComputeOutput::Completed)
+            }
+            ComputeInput::Load { handle, extensions } => self.load(handle, extensions).await,
+            ComputeInput::Persist {
+                handle,
+                ttl,
+                extensions,
+            } => {
+                let (primary_extensions, secondary_extensions) = self.get_extensions(extensions);
+
+                let (primary, secondary) = futures::join!(
+                    storage.persist(handle.clone(), ttl, primary_extensions),
+                    storage.persist(handle, ttl, secondary_extensions)
+                );
+
+                self.reconcile("persist", primary, secondary)?;
+
+                Ok(ComputeOutput::Completed)
+            }
+            ComputeInput::Clear { handle, extensions } => {
+                let (primary_extensions, secondary_extensions) = self.get_extensions(extensions);
+
+                let (primary, secondary) = futures::join!(
+                    storage.clear(handle.clone(), primary_extensions),
+                    storage.clear(handle, secondary_extensions)
+                );
+
+                self.reconcile("clear", primary, secondary)?;
+
+                Ok(ComputeOutput::Completed)
+            }
+            _ => match self.next.read().await.clone() {
+                Some(chan) => chan.compute(ctx, elem).await,
+                None => Err(Error::ComputeChannelEOF),
+            },
+        }
+    }
+
+    async fn connect(&self, next: Arc<Box<dyn ComputeChannel<Context = Context>>>) {
+        *self.next.write().await = Some(next);
+    }
+}
+
+impl<Context> MirrorChannel<Context>
+where
+    Context: Send,
+{
+    pub fn get_identifier_type() -> &'static str {
+        "mitsuha/channel/mirror"
+    }
+
+    pub fn new(
+        storage: Arc<Box<dyn Storage>>,
+        primary_selector: Label,
+        secondary_selector: Label,
+        verify_loads: bool,
+    ) -> WrappedComputeChannel<Self> {
+        WrappedComputeChannel::new(Self {
+            storage,
+            primary_selector,
+            secondary_selector,
+            verify_loads,
+            next: Arc::new(RwLock::new(None)),
+            id: Self::get_identifier_type().to_string(),
+        })
+    }
+
+    fn get_extensions(
+        &self,
+        extensions: HashMap<String, String>,
+    ) -> (HashMap<String, String>, HashMap<String, String>) {
+        (
+            extensions.clone().with_selector(&self.primary_selector),
+            extensions.with_selector(&self.secondary_selector),
+        )
+    }
+
+    fn report_divergence(&self, opkind: &str, reason: &str) {
+        metric::mirror_divergence_count_metric()
+            .with_label_values(&[opkind, reason])
+            .inc();
+    }
+
+    fn reconcile(
+        &self,
+        opkind: &str,
+        primary: types::Result<()>,
+        secondary: types::Result<()>,
+    ) -> types::Result<()> {
+        match (&primary, secondary) {
+            (Ok(_), Err(e)) => {
+                tracing::warn!("mirrored {} failed on secondary storage: {}", opkind, e);
+
+                self.report_divergence(opkind, "secondary_failed");
+            }
+            (Err(_), Ok(_)) => self.report_divergence(opkind, "primary_failed"),
+            _ => {}
+        }
+
+        primary
+    }
+
+    async fn load(
+        &self,
+        handle: String,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<ComputeOutput> {
+        let (primary_extensions, secondary_extensions) = self.get_extensions(extensions);
+
+        let primary = self.storage.load(handle.clone(), primary_extensions).await;
+
+        let data = match primary {
+            Ok(data) => data,
+            Err(e) => {
+                tracing::debug!(
+                    "falling back to secondary storage to load '{}': {}",
+                    handle,
+                    e
+                );
+
+                let data = self.storage.load(handle, secondary_extensions).await?;
+
+                self.report_divergence("load", "primary_missing");
+
+                return Ok(ComputeOutput::Loaded { data });
+            }
+        };
+
+        if self.verify_loads {
+            match self.storage.load(handle, secondary_extensions).await {
+                Ok(secondary_data) if secondary_data != data => {
+                    self.report_divergence("load", "mismatch")
+                }
+                Ok(_) => {}
+                Err(_) => self.report_divergence("load", "secondary_missing"),
+            }
+        }
+
+        Ok(ComputeOutput::Loaded { data })
+    }
+}
@@ -0,0 +1,629 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use mitsuha_core::{
+    channel::{ChannelContext, ComputeChannel, ComputeInputExt, StateProvider},
+    constants::Constants,
+    errors::Error,
+    job::{ctrl::PostJobHook, mgr::JobManagerProvider},
+    storage::GarbageCollectionHook,
+    types,
+};
+use mitsuha_core_types::channel::{ComputeInput, ComputeOutput};
+use mitsuha_persistence::{quota_blob, quota_job, quota_usage};
+use sea_orm::sea_query::{LockType, OnConflict};
+use sea_orm::ActiveValue::Set;
+use sea_orm::{
+    ActiveModelTrait, DatabaseConnection, DatabaseTransaction, EntityTrait, QuerySelect,
+    TransactionTrait,
+};
+use tokio::sync::RwLock;
+
+use crate::{NextComputeChannel, WrappedComputeChannel};
+
+/// Limits of a namespace, unset limits are not enforced.
+#[derive(Clone, Debug, Default)]
+pub struct Quota {
+    pub max_bytes: Option<u64>,
+    pub max_blobs: Option<u64>,
+    pub max_ttl: Option<u64>,
+    pub max_compute: Option<u64>,
+}
+
+/// Bytes and blobs stored, and the compute cost of running jobs, of a namespace.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QuotaUsage {
+    pub bytes: u64,
+    pub blobs: u64,
+    pub compute: u64,
+}
+
+impl QuotaUsage {
+    pub fn check(&self, namespace: &str, quota: &Quota) -> types::Result<()> {
+        let checks = [
+            ("bytes", self.bytes, quota.max_bytes),
+            ("blobs", self.blobs, quota.max_blobs),
+            ("compute", self.compute, quota.max_compute),
+        ];
+
+        for (resource, requested, limit) in checks {
+            match limit {
+                Some(limit) if requested > limit => {
+                    return Err(Error::QuotaExceeded {
+                        namespace: namespace.to_string(),
+                        resource: resource.to_string(),
+                        requested,
+                        limit,
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+pub trait QuotaStore: Send + Sync {
+    async fn get_usage(&self, namespace: &str) -> types::Result<QuotaUsage>;
+
+    /// Records the blob `handle` as holding `bytes`, replacing its previous size which is
+    /// returned. Fails without recording anything if the usage would exceed `quota`.
+    async fn put_blob(
+        &self,
+        namespace: &str,
+        handle: &str,
+        bytes: u64,
+        quota: &Quota,
+    ) -> types::Result<Option<u64>>;
+
+    /// Releases the blob `handle`, doing nothing if it is not recorded.
+    async fn remove_blob(&self, handle: &str) -> types::Result<()>;
+
+    /// Records the job `handle` as running with a cost of `compute`. Fails without recording
+    /// anything if the usage would exceed `quota`.
+    async fn put_job(
+        &self,
+        namespace: &str,
+        handle: &str,
+        compute: u64,
+        quota: &Quota,
+    ) -> types::Result<()>;
+
+    /// Releases the job `handle`, doing nothing if it is not recorded.
+    async fn remove_job(&self, handle: &str) -> types::Result<()>;
+}
+
+#[derive(Default)]
+struct MemoryQuotaState {
+    usage: HashMap<String, QuotaUsage>,
+    blobs: HashMap<String, (String, u64)>,
+    jobs: HashMap<String, (String, u64)>,
+}
+
+/// Keeps usage in memory, it is lost on restart and applies to this runtime instance only.
+#[derive(Default)]
+pub struct MemoryQuotaStore {
+    state: Mutex<MemoryQuotaState>,
+}
+
+#[async_trait]
+impl QuotaStore for MemoryQuotaStore {
+    async fn get_usage(&self, namespace: &str) -> types::Result<QuotaUsage> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state.usage.get(namespace).cloned().unwrap_or_default())
+    }
+
+    async fn put_blob(
+        &self,
+        namespace: &str,
+        handle: &str,
+        bytes: u64,
+        quota: &Quota,
+    ) -> types::Result<Option<u64>> {
+        let mut state = self.state.lock().unwrap();
+
+        let previous = state.blobs.get(handle).map(|(_, bytes)| *bytes);
+        let mut usage = state.usage.get(namespace).cloned().unwrap_or_default();
+
+        match previous {
+            Some(previous) => usage.bytes = usage.bytes.saturating_sub(previous) + bytes,
+            None => {
+                usage.bytes += bytes;
+                usage.blobs += 1;
+            }
+        }
+
+        usage.check(namespace, quota)?;
+
+        state.usage.insert(namespace.to_string(), usage);
+        state
+            .blobs
+            .insert(handle.to_string(), (namespace.to_string(), bytes));
+
+        Ok(previous)
+    }
+
+    async fn remove_blob(&self, handle: &str) -> types::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some((namespace, bytes)) = state.blobs.remove(handle) {
+            let usage = state.usage.entry(namespace).or_default();
+
+            usage.bytes = usage.bytes.saturating_sub(bytes);
+            usage.blobs = usage.blobs.saturating_sub(1);
+        }
+
+        Ok(())
+    }
+
+    async fn put_job(
+        &self,
+        namespace: &str,
+        handle: &str,
+        compute: u64,
+        quota: &Quota,
+    ) -> types::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        let previous = state.jobs.get(handle).map(|(_, compute)| *compute);
+        let mut usage = state.usage.get(namespace).cloned().unwrap_or_default();
+
+        usage.compute = usage.compute.saturating_sub(previous.unwrap_or(0)) + compute;
+
+        usage.check(namespace, quota)?;
+
+        state.usage.insert(namespace.to_string(), usage);
+        state
+            .jobs
+            .insert(handle.to_string(), (namespace.to_string(), compute));
+
+        Ok(())
+    }
+
+    async fn remove_job(&self, handle: &str) -> types::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some((namespace, compute)) = state.jobs.remove(handle) {
+            let usage = state.usage.entry(namespace).or_default();
+
+            usage.compute = usage.compute.saturating_sub(compute);
+        }
+
+        Ok(())
+    }
+}
+
+/// Keeps usage in the persistence layer, quotas apply across all runtime instances sharing
+/// the database.
+pub struct PersistentQuotaStore {
+    connection: DatabaseConnection,
+}
+
+impl PersistentQuotaStore {
+    pub fn new(connection: DatabaseConnection) -> Self {
+        Self { connection }
+    }
+
+    /// Locks the usage row of `namespace`, creating it first if missing. Locking a missing
+    /// row does not serialize concurrent transactions, which would then both insert it.
+    async fn lock_usage(tx: &DatabaseTransaction, namespace: &str) -> types::Result<QuotaUsage> {
+        quota_usage::Entity::insert(quota_usage::ActiveModel {
+            namespace: Set(namespace.to_string()),
+            bytes: Set(0),
+            blobs: Set(0),
+            compute: Set(0),
+        })
+        .on_conflict(
+            OnConflict::column(quota_usage::Column::Namespace)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(tx)
+        .await?;
+
+        let model = quota_usage::Entity::find_by_id(namespace.to_string())
+            .lock(LockType::Update)
+            .one(tx)
+            .await?;
+
+        Ok(model
+            .map(|x| QuotaUsage {
+                bytes: x.bytes as u64,
+                blobs: x.blobs as u64,
+                compute: x.compute as u64,
+            })
+            .unwrap_or_default())
+    }
+
+    async fn save_usage(
+        tx: &DatabaseTransaction,
+        namespace: &str,
+        usage: QuotaUsage,
+    ) -> types::Result<()> {
+        quota_usage::ActiveModel {
+            namespace: Set(namespace.to_string()),
+            bytes: Set(usage.bytes as i64),
+            blobs: Set(usage.blobs as i64),
+            compute: Set(usage.compute as i64),
+        }
+        .update(tx)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl QuotaStore for PersistentQuotaStore {
+    async fn get_usage(&self, namespace: &str) -> types::Result<QuotaUsage> {
+        let model = quota_usage::Entity::find_by_id(namespace.to_string())
+            .one(&self.connection)
+            .await?;
+
+        Ok(model
+            .map(|x| QuotaUsage {
+                bytes: x.bytes as u64,
+                blobs: x.blobs as u64,
+                compute: x.compute as u64,
+            })
+            .unwrap_or_default())
+    }
+
+    async fn put_blob(
+        &self,
+        namespace: &str,
+        handle: &str,
+        bytes: u64,
+        quota: &Quota,
+    ) -> types::Result<Option<u64>> {
+        let tx = self.connection.begin().await?;
+
+        let mut usage = Self::lock_usage(&tx, namespace).await?;
+
+        let previous = quota_blob::Entity::find_by_id(handle.to_string())
+            .lock(LockType::Update)
+            .one(&tx)
+            .await?
+            .map(|x| x.bytes as u64);
+
+        match previous {
+            Some(previous) => usage.bytes = usage.bytes.saturating_sub(previous) + bytes,
+            None => {
+                usage.bytes += bytes;
+                usage.blobs += 1;
+            }
+        }
+
+        usage.check(namespace, quota)?;
+
+        Self::save_usage(&tx, namespace, usage).await?;
+
+        quota_blob::Entity::insert(quota_blob::ActiveModel {
+            handle: Set(handle.to_string()),
+            namespace: Set(namespace.to_string()),
+            bytes: Set(bytes as i64),
+        })
+        .on_conflict(
+            OnConflict::column(quota_blob::Column::Handle)
+                .update_columns([quota_blob::Column::Namespace, quota_blob::Column::Bytes])
+                .to_owned(),
+        )
+        .exec_without_returning(&tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(previous)
+    }
+
+    async fn remove_blob(&self, handle: &str) -> types::Result<()> {
+        let namespace = match quota_blob::Entity::find_by_id(handle.to_string())
+            .one(&self.connection)
+            .await?
+        {
+            Some(model) => model.namespace,
+            None => return Ok(()),
+        };
+
+        let tx = self.connection.begin().await?;
+
+        let mut usage = Self::lock_usage(&tx, &namespace).await?;
+
+        let model = quota_blob::Entity::find_by_id(handle.to_string())
+            .lock(LockType::Update)
+            .one(&tx)
+            .await?;
+
+        if let Some(model) = model {
+            usage.bytes = usage.bytes.saturating_sub(model.bytes as u64);
+            usage.blobs = usage.blobs.saturating_sub(1);
+
+            Self::save_usage(&tx, &namespace, usage).await?;
+
+            quota_blob::Entity::delete_by_id(handle.to_string())
+                .exec(&tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn put_job(
+        &self,
+        namespace: &str,
+        handle: &str,
+        compute: u64,
+        quota: &Quota,
+    ) -> types::Result<()> {
+        let tx = self.connection.begin().await?;
+
+        let mut usage = Self::lock_usage(&tx, namespace).await?;
+
+        let previous = quota_job::Entity::find_by_id(handle.to_string())
+            .lock(LockType::Update)
+            .one(&tx)
+            .await?
+            .map(|x| x.compute as u64);
+
+        usage.compute = usage.compute.saturating_sub(previous.unwrap_or(0)) + compute;
+
+        usage.check(namespace, quota)?;
+
+        Self::save_usage(&tx, namespace, usage).await?;
+
+        quota_job::Entity::insert(quota_job::ActiveModel {
+            handle: Set(handle.to_string()),
+            namespace: Set(namespace.to_string()),
+            compute: Set(compute as i64),
+        })
+        .on_conflict(
+            OnConflict::column(quota_job::Column::Handle)
+                .update_columns([quota_job::Column::Namespace, quota_job::Column::Compute])
+                .to_owned(),
+        )
+        .exec_without_returning(&tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn remove_job(&self, handle: &str) -> types::Result<()> {
+        let namespace = match quota_job::Entity::find_by_id(handle.to_string())
+            .one(&self.connection)
+            .await?
+        {
+            Some(model) => model.namespace,
+            None => return Ok(()),
+        };
+
+        let tx = self.connection.begin().await?;
+
+        let mut usage = Self::lock_usage(&tx, &namespace).await?;
+
+        let model = quota_job::Entity::find_by_id(handle.to_string())
+            .lock(LockType::Update)
+            .one(&tx)
+            .await?;
+
+        if let Some(model) = model {
+            usage.compute = usage.compute.saturating_sub(model.compute as u64);
+
+            Self::save_usage(&tx, &namespace, usage).await?;
+
+            quota_job::Entity::delete_by_id(handle.to_string())
+                .exec(&tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+/// Enforces the [Quota] of the namespace of compute inputs, inputs without a namespace are
+/// not limited. Blob handles are expected to be unique across namespaces, which holds when
+/// the channel follows a namespacer.
+///
+/// Blobs are released when cleared and when deleted by garbage collection, through
+/// [QuotaGarbageCollectionHook], and jobs are released on completion, through
+/// [QuotaPostJobHook].
+pub struct QuotaChannel {
+    id: String,
+    next: NextComputeChannel<ChannelContext>,
+    default_quota: Quota,
+    quotas: HashMap<String, Quota>,
+    store: Arc<Box<dyn QuotaStore>>,
+}
+
+#[async_trait]
+impl ComputeChannel for QuotaChannel {
+    type Context = ChannelContext;
+
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    async fn compute(
+        &self,
+        ctx: ChannelContext,
+        elem: ComputeInput,
+    ) -> types::Result<ComputeOutput> {
+        let namespace = match elem
+            .get_extensions()
+            .get(&Constants::ChannelNamespace.to_string())
+            .cloned()
+        {
+            Some(namespace) => namespace,
+            None => return self.forward(ctx, elem).await,
+        };
+
+        let quota = self.quotas.get(&namespace).unwrap_or(&self.default_quota);
+
+        if let (Some(ttl), Some(max_ttl)) = (elem.get_optional_ttl(), quota.max_ttl) {
+            if ttl > max_ttl {
+                return Err(Error::QuotaExceeded {
+                    namespace,
+                    resource: "ttl".to_string(),
+                    requested: ttl,
+                    limit: max_ttl,
+                });
+            }
+        }
+
+        match &elem {
+            ComputeInput::Store { spec } => {
+                let handle = spec.handle.clone();
+
+                let previous = self
+                    .store
+                    .put_blob(&namespace, &handle, spec.data.len() as u64, quota)
+                    .await?;
+
+                let result = self.forward(ctx, elem).await;
+
+                if result.is_err() {
+                    let rollback = match previous {
+                        Some(bytes) => self
+                            .store
+                            .put_blob(&namespace, &handle, bytes, &Quota::default())
+                            .await
+                            .map(|_| ()),
+                        None => self.store.remove_blob(&handle).await,
+                    };
+
+                    if let Err(e) = rollback {
+                        tracing::error!("failed to release quota of blob '{}': {}", handle, e);
+                    }
+                }
+
+                result
+            }
+            ComputeInput::Clear { handle, .. } => {
+                let handle = handle.clone();
+
+                let result = self.forward(ctx, elem).await;
+
+                if result.is_ok() {
+                    self.store.remove_blob(&handle).await?;
+                }
+
+                result
+            }
+            ComputeInput::Run { spec } => {
+                let handle = spec.handle.clone();
+
+                let cost = ctx
+                    .get_job_mgr()
+                    .await
+                    .get_job_cost_evaluator()
+                    .get_cost(spec)?;
+
+                self.store
+                    .put_job(&namespace, &handle, cost.compute, quota)
+                    .await?;
+
+                ctx.set_value(Constants::ChannelQuotaJobHandle.to_string(), handle.clone());
+
+                let result = self.forward(ctx, elem).await;
+
+                if result.is_err() {
+                    if let Err(e) = self.store.remove_job(&handle).await {
+                        tracing::error!("failed to release quota of job '{}': {}", handle, e);
+                    }
+                }
+
+                result
+            }
+            _ => self.forward(ctx, elem).await,
+        }
+    }
+
+    async fn connect(&self, next: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>) {
+        *self.next.write().await = Some(next);
+    }
+}
+
+impl QuotaChannel {
+    pub fn get_identifier_type() -> &'static str {
+        "mitsuha/channel/quota"
+    }
+
+    /// Namespaces missing from `quotas` are held to `default_quota`.
+    pub fn new(
+        default_quota: Quota,
+        quotas: HashMap<String, Quota>,
+        store: Arc<Box<dyn QuotaStore>>,
+    ) -> WrappedComputeChannel<Self> {
+        WrappedComputeChannel::new(Self {
+            id: Self::get_identifier_type().to_string(),
+            next: Arc::new(RwLock::new(None)),
+            default_quota,
+            quotas,
+            store,
+        })
+    }
+
+    async fn forward(
+        &self,
+        ctx: ChannelContext,
+        elem: ComputeInput,
+    ) -> types::Result<ComputeOutput> {
+        match self.next.read().await.clone() {
+            Some(chan) => chan.compute(ctx, elem).await,
+            None => Err(Error::ComputeChannelEOF),
+        }
+    }
+}
+
+/// Releases the compute cost of jobs started through a [QuotaChannel] once they complete.
+pub struct QuotaPostJobHook {
+    store: Arc<Box<dyn QuotaStore>>,
+}
+
+impl QuotaPostJobHook {
+    pub fn new(store: Arc<Box<dyn QuotaStore>>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl PostJobHook<ChannelContext> for QuotaPostJobHook {
+    async fn run(&self, ctx: ChannelContext) -> types::Result<()> {
+        if let Some(handle) = ctx.get_value(&Constants::ChannelQuotaJobHandle.to_string()) {
+            self.store.remove_job(&handle).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Releases blobs deleted by garbage collection.
+pub struct QuotaGarbageCollectionHook {
+    store: Arc<Box<dyn QuotaStore>>,
+}
+
+impl QuotaGarbageCollectionHook {
+    pub fn new(store: Arc<Box<dyn QuotaStore>>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl GarbageCollectionHook for QuotaGarbageCollectionHook {
+    async fn run(&self, handles: &[String]) -> types::Result<()> {
+        for handle in handles.iter() {
+            self.store.remove_blob(handle).await?;
+        }
+
+        Ok(())
+    }
+}
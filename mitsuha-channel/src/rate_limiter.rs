@@ -0,0 +1,289 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use mitsuha_core::{
+    channel::{ChannelContext, ComputeChannel, ComputeInputExt},
+    constants::Constants,
+    errors::Error,
+    types,
+};
+use mitsuha_core_types::channel::{ComputeInput, ComputeOutput};
+use mitsuha_persistence::rate_limit_bucket::{ActiveModel, Column, Entity};
+use sea_orm::sea_query::{LockType, OnConflict};
+use sea_orm::ActiveValue::Set;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, QuerySelect, TransactionTrait};
+use tokio::sync::RwLock;
+
+use crate::{NextComputeChannel, WrappedComputeChannel};
+
+/// Token bucket holding up to `capacity` tokens, refilled at `refill_rate` tokens per second.
+#[derive(Clone, Debug)]
+pub struct RateLimit {
+    pub capacity: f64,
+    pub refill_rate: f64,
+}
+
+/// Part of a compute input which identifies the bucket it is charged to.
+#[derive(Clone, Debug)]
+pub enum RateLimitKey {
+    Namespace,
+    HandlePrefix { delimiter: String },
+    Extension(String),
+}
+
+impl RateLimitKey {
+    /// Inputs without the key share a single bucket.
+    pub fn get(&self, elem: &ComputeInput) -> String {
+        match self {
+            Self::Namespace => elem
+                .get_extensions()
+                .get(&Constants::ChannelNamespace.to_string())
+                .cloned()
+                .unwrap_or_default(),
+            Self::HandlePrefix { delimiter } => {
+                let handle = elem.get_original_handle();
+
+                match handle.split_once(delimiter.as_str()) {
+                    Some((prefix, _)) => prefix.to_string(),
+                    None => handle,
+                }
+            }
+            Self::Extension(key) => elem.get_extensions().get(key).cloned().unwrap_or_default(),
+        }
+    }
+}
+
+/// Limits inputs of `opkind`, or of every opkind if unset, per value of `key`.
+#[derive(Clone, Debug)]
+pub struct Rule {
+    pub opkind: Option<String>,
+    pub key: RateLimitKey,
+    pub limit: RateLimit,
+}
+
+#[derive(Clone, Debug)]
+pub struct TokenBucket {
+    pub tokens: f64,
+    pub last_refill: DateTime<Utc>,
+}
+
+impl TokenBucket {
+    pub fn full(limit: &RateLimit, now: DateTime<Utc>) -> Self {
+        Self {
+            tokens: limit.capacity,
+            last_refill: now,
+        }
+    }
+
+    /// Takes a token, returning how long it takes for one to be available if the bucket is
+    /// empty.
+    pub fn take(&mut self, limit: &RateLimit, now: DateTime<Utc>) -> Option<Duration> {
+        let elapsed = (now - self.last_refill).num_milliseconds().max(0) as f64 / 1000.0;
+
+        self.tokens = (self.tokens + elapsed * limit.refill_rate).min(limit.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return None;
+        }
+
+        if limit.refill_rate <= 0.0 {
+            return Some(Duration::MAX);
+        }
+
+        Some(Duration::from_secs_f64(
+            (1.0 - self.tokens) / limit.refill_rate,
+        ))
+    }
+}
+
+#[async_trait]
+pub trait TokenBucketStore: Send + Sync {
+    /// Takes a token from the bucket `id`, creating it full if it does not exist yet.
+    async fn take(&self, id: &str, limit: &RateLimit) -> types::Result<Option<Duration>>;
+}
+
+/// Keeps buckets in memory, limits apply to this runtime instance only.
+///
+/// Bucket ids come from client controlled values, so at most `max_buckets` buckets are kept
+/// and buckets unused for `idle_ttl` are dropped. A dropped bucket starts over full, which
+/// changes nothing for buckets idle long enough to be refilled.
+pub struct MemoryTokenBucketStore {
+    buckets: moka::future::Cache<String, Arc<Mutex<TokenBucket>>>,
+}
+
+impl Default for MemoryTokenBucketStore {
+    fn default() -> Self {
+        Self::new(100_000, Duration::from_secs(3600))
+    }
+}
+
+impl MemoryTokenBucketStore {
+    pub fn new(max_buckets: u64, idle_ttl: Duration) -> Self {
+        Self {
+            buckets: moka::future::Cache::builder()
+                .max_capacity(max_buckets)
+                .time_to_idle(idle_ttl)
+                .build(),
+        }
+    }
+}
+
+#[async_trait]
+impl TokenBucketStore for MemoryTokenBucketStore {
+    async fn take(&self, id: &str, limit: &RateLimit) -> types::Result<Option<Duration>> {
+        let now = Utc::now();
+
+        let bucket = self
+            .buckets
+            .get_with(id.to_string(), async {
+                Arc::new(Mutex::new(TokenBucket::full(limit, now)))
+            })
+            .await;
+
+        let retry_after = bucket.lock().unwrap().take(limit, now);
+
+        Ok(retry_after)
+    }
+}
+
+/// Keeps buckets in the persistence layer, limits apply across all runtime instances sharing
+/// the database.
+pub struct PersistentTokenBucketStore {
+    connection: DatabaseConnection,
+}
+
+impl PersistentTokenBucketStore {
+    pub fn new(connection: DatabaseConnection) -> Self {
+        Self { connection }
+    }
+}
+
+#[async_trait]
+impl TokenBucketStore for PersistentTokenBucketStore {
+    async fn take(&self, id: &str, limit: &RateLimit) -> types::Result<Option<Duration>> {
+        let tx = self.connection.begin().await?;
+        let now = Utc::now();
+
+        // Locking a missing row does not serialize concurrent transactions, which would then
+        // both insert it, so the bucket is created before it is locked.
+        Entity::insert(ActiveModel {
+            id: Set(id.to_string()),
+            tokens: Set(limit.capacity),
+            last_refill: Set(now.timestamp_millis()),
+        })
+        .on_conflict(OnConflict::column(Column::Id).do_nothing().to_owned())
+        .exec_without_returning(&tx)
+        .await?;
+
+        let mut bucket = match Entity::find_by_id(id.to_string())
+            .lock(LockType::Update)
+            .one(&tx)
+            .await?
+        {
+            Some(model) => TokenBucket {
+                tokens: model.tokens,
+                last_refill: Utc
+                    .timestamp_millis_opt(model.last_refill)
+                    .single()
+                    .unwrap_or(now),
+            },
+            None => TokenBucket::full(limit, now),
+        };
+
+        let retry_after = bucket.take(limit, now);
+
+        ActiveModel {
+            id: Set(id.to_string()),
+            tokens: Set(bucket.tokens),
+            last_refill: Set(bucket.last_refill.timestamp_millis()),
+        }
+        .update(&tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(retry_after)
+    }
+}
+
+/// Rejects inputs exceeding the token bucket of the first matching [Rule] with
+/// [Error::RateLimitExceeded], inputs matching no rule are not limited.
+pub struct RateLimiterChannel {
+    id: String,
+    next: NextComputeChannel<ChannelContext>,
+    scope: String,
+    rules: Vec<Rule>,
+    store: Arc<Box<dyn TokenBucketStore>>,
+}
+
+#[async_trait]
+impl ComputeChannel for RateLimiterChannel {
+    type Context = ChannelContext;
+
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    async fn compute(
+        &self,
+        ctx: ChannelContext,
+        elem: ComputeInput,
+    ) -> types::Result<ComputeOutput> {
+        let opkind = elem.get_opkind();
+
+        let matched = self
+            .rules
+            .iter()
+            .enumerate()
+            .find(|(_, rule)| rule.opkind.as_ref().map_or(true, |x| *x == opkind));
+
+        if let Some((index, rule)) = matched {
+            let key = rule.key.get(&elem);
+            let bucket_id = format!("{}/{}/{}", self.scope, index, key);
+
+            if let Some(retry_after) = self.store.take(&bucket_id, &rule.limit).await? {
+                tracing::debug!("rate limit exceeded for bucket '{}'", bucket_id);
+
+                return Err(Error::RateLimitExceeded {
+                    key,
+                    retry_after_ms: retry_after.as_millis().try_into().unwrap_or(u64::MAX),
+                });
+            }
+        }
+
+        match self.next.read().await.clone() {
+            Some(chan) => chan.compute(ctx, elem).await,
+            None => Err(Error::ComputeChannelEOF),
+        }
+    }
+
+    async fn connect(&self, next: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>) {
+        *self.next.write().await = Some(next);
+    }
+}
+
+impl RateLimiterChannel {
+    pub fn get_identifier_type() -> &'static str {
+        "mitsuha/channel/rate_limiter"
+    }
+
+    /// `scope` prefixes the ids of all buckets, keeping those of rate limiters sharing a
+    /// store apart.
+    pub fn new(
+        scope: String,
+        rules: Vec<Rule>,
+        store: Arc<Box<dyn TokenBucketStore>>,
+    ) -> WrappedComputeChannel<Self> {
+        WrappedComputeChannel::new(Self {
+            id: Self::get_identifier_type().to_string(),
+            next: Arc::new(RwLock::new(None)),
+            scope,
+            rules,
+            store,
+        })
+    }
+}
@@ -0,0 +1,296 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use mitsuha_core::{
+    channel::{ChannelContext, ComputeChannel, ComputeInputExt},
+    constants::Constants,
+    errors::{Error, ToUnknownErrorResult},
+    types,
+};
+use mitsuha_core_types::channel::{ComputeInput, ComputeOutput};
+use mitsuha_runtime_rpc::proto::channel::{channel_client::ChannelClient, ComputeRequest};
+use rand::seq::SliceRandom;
+use tokio::sync::RwLock;
+use tonic_health::pb::{
+    health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest,
+};
+
+use crate::{util, NextComputeChannel, WrappedComputeChannel};
+
+/// How a [RemoteChannel] orders healthy endpoints before trying them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LoadBalancingStrategy {
+    RoundRobin,
+    LeastRequests,
+    Random,
+}
+
+/// A peer runtime serving the `Channel` gRPC service.
+pub struct RemoteEndpoint {
+    address: String,
+    client: ChannelClient<tonic::transport::Channel>,
+    health_client: HealthClient<tonic::transport::Channel>,
+    healthy: AtomicBool,
+    in_flight: AtomicUsize,
+}
+
+impl RemoteEndpoint {
+    /// Endpoints connect lazily and start out healthy.
+    pub fn new(address: String) -> types::Result<Self> {
+        let conn = tonic::transport::Endpoint::new(address.clone())
+            .to_unknown_err_result()?
+            .connect_lazy();
+
+        Ok(Self {
+            address,
+            client: ChannelClient::new(conn.clone()),
+            health_client: HealthClient::new(conn),
+            healthy: AtomicBool::new(true),
+            in_flight: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    fn set_healthy(&self, healthy: bool) {
+        if self.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+            tracing::info!(
+                "remote endpoint '{}' is now {}",
+                self.address,
+                if healthy { "healthy" } else { "unhealthy" }
+            );
+        }
+    }
+
+    /// A check which does not complete within `timeout` marks the endpoint unhealthy.
+    async fn check_health(&self, timeout: Duration) {
+        let request = HealthCheckRequest {
+            service: String::new(),
+        };
+
+        let mut health_client = self.health_client.clone();
+
+        let healthy = match tokio::time::timeout(timeout, health_client.check(request)).await {
+            Ok(Ok(response)) => response.into_inner().status == ServingStatus::Serving as i32,
+            Ok(Err(_)) | Err(_) => false,
+        };
+
+        self.set_healthy(healthy);
+    }
+
+    async fn compute(&self, request: ComputeRequest) -> tonic::Result<ComputeOutput> {
+        let _guard = InFlightGuard::new(&self.in_flight);
+
+        self.client
+            .clone()
+            .compute(request)
+            .await?
+            .into_inner()
+            .try_into()
+            .map_err(|e: anyhow::Error| tonic::Status::internal(e.to_string()))
+    }
+}
+
+/// Counts a request as in flight on an endpoint until dropped, so that requests whose future
+/// is cancelled are not counted forever.
+struct InFlightGuard<'a> {
+    in_flight: &'a AtomicUsize,
+}
+
+impl<'a> InFlightGuard<'a> {
+    fn new(in_flight: &'a AtomicUsize) -> Self {
+        in_flight.fetch_add(1, Ordering::Relaxed);
+
+        Self { in_flight }
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Forwards compute inputs to the `Channel.Compute` endpoint of peer runtimes, ending the
+/// chain. Endpoints are health checked periodically and skipped while unhealthy, an endpoint
+/// which is unavailable is also marked unhealthy. The next endpoint is only tried when the input
+/// can be safely repeated, or when the request never reached the unavailable endpoint; stores
+/// and runs which may already have been applied are not sent twice.
+///
+/// Forwarded inputs are marked with [Constants::ChannelRemoteForwarded] and are not forwarded
+/// again by the peer, so that overloaded peers cannot bounce inputs between each other.
+pub struct RemoteChannel {
+    id: String,
+    next: NextComputeChannel<ChannelContext>,
+    endpoints: Arc<Vec<RemoteEndpoint>>,
+    strategy: LoadBalancingStrategy,
+    counter: AtomicUsize,
+}
+
+#[async_trait]
+impl ComputeChannel for RemoteChannel {
+    type Context = ChannelContext;
+
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    async fn compute(
+        &self,
+        _ctx: ChannelContext,
+        mut elem: ComputeInput,
+    ) -> types::Result<ComputeOutput> {
+        let forwarded = Constants::ChannelRemoteForwarded.to_string();
+
+        if elem.get_extensions().contains_key(&forwarded) {
+            return Err(Error::UnknownWithMsgOnly {
+                message: format!(
+                    "compute input for '{}' was already forwarded by a remote channel",
+                    elem.get_handle()
+                ),
+            });
+        }
+
+        elem.get_extensions_mut()
+            .insert(forwarded, "true".to_string());
+
+        let retryable = matches!(
+            elem,
+            ComputeInput::Load { .. }
+                | ComputeInput::Persist { .. }
+                | ComputeInput::Clear { .. }
+                | ComputeInput::Extend { .. }
+                | ComputeInput::Status { .. }
+                | ComputeInput::Abort { .. }
+        );
+
+        let request: ComputeRequest = elem.try_into().to_unknown_err_result()?;
+
+        for endpoint in self.select_endpoints() {
+            tracing::debug!(
+                "forwarding compute to remote endpoint '{}'",
+                endpoint.address
+            );
+
+            match endpoint.compute(request.clone()).await {
+                Ok(output) => return Ok(output),
+                Err(status) if status.code() == tonic::Code::Unavailable => {
+                    tracing::warn!(
+                        "remote endpoint '{}' is unavailable: {}",
+                        endpoint.address,
+                        status.message()
+                    );
+
+                    endpoint.set_healthy(false);
+
+                    if !retryable && !util::is_connect_error(&status) {
+                        return Err(Error::ServiceUnavailable {
+                            target: self.id.clone(),
+                            message: format!(
+                                "remote endpoint '{}' became unavailable during compute: {}",
+                                endpoint.address,
+                                status.message()
+                            ),
+                        });
+                    }
+                }
+                Err(status) => {
+                    return Err(Error::UnknownWithMsgOnly {
+                        message: format!(
+                            "remote compute on '{}' failed: {}",
+                            endpoint.address,
+                            status.message()
+                        ),
+                    })
+                }
+            }
+        }
+
+        Err(Error::ServiceUnavailable {
+            target: self.id.clone(),
+            message: "no healthy remote endpoint is available".to_string(),
+        })
+    }
+
+    async fn connect(&self, next: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>) {
+        *self.next.write().await = Some(next);
+    }
+}
+
+impl RemoteChannel {
+    pub fn get_identifier_type() -> &'static str {
+        "mitsuha/channel/remote"
+    }
+
+    /// Health checks run every `health_check_interval` for as long as the channel is alive, all
+    /// endpoints are checked concurrently and each check times out after one interval.
+    pub fn new(
+        endpoints: Vec<RemoteEndpoint>,
+        strategy: LoadBalancingStrategy,
+        health_check_interval: Duration,
+    ) -> WrappedComputeChannel<Self> {
+        let endpoints = Arc::new(endpoints);
+
+        Self::start_health_check(Arc::downgrade(&endpoints), health_check_interval);
+
+        WrappedComputeChannel::new(Self {
+            id: Self::get_identifier_type().to_string(),
+            next: Arc::new(RwLock::new(None)),
+            endpoints,
+            strategy,
+            counter: AtomicUsize::new(0),
+        })
+    }
+
+    fn select_endpoints(&self) -> Vec<&RemoteEndpoint> {
+        let mut endpoints: Vec<&RemoteEndpoint> =
+            self.endpoints.iter().filter(|x| x.is_healthy()).collect();
+
+        if endpoints.is_empty() {
+            return endpoints;
+        }
+
+        match self.strategy {
+            LoadBalancingStrategy::RoundRobin => {
+                let start = self.counter.fetch_add(1, Ordering::Relaxed) % endpoints.len();
+                endpoints.rotate_left(start);
+            }
+            LoadBalancingStrategy::LeastRequests => {
+                endpoints.sort_by_key(|x| x.in_flight.load(Ordering::Relaxed));
+            }
+            LoadBalancingStrategy::Random => {
+                endpoints.shuffle(&mut rand::thread_rng());
+            }
+        }
+
+        endpoints
+    }
+
+    fn start_health_check(endpoints: Weak<Vec<RemoteEndpoint>>, interval: Duration) {
+        tokio::task::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+
+                let endpoints = match endpoints.upgrade() {
+                    Some(endpoints) => endpoints,
+                    None => break,
+                };
+
+                futures::future::join_all(
+                    endpoints
+                        .iter()
+                        .map(|endpoint| endpoint.check_health(interval)),
+                )
+                .await;
+            }
+        });
+    }
+}
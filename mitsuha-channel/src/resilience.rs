@@ -0,0 +1,346 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use backoff::{backoff::Backoff, ExponentialBackoff};
+use dashmap::DashMap;
+use mitsuha_core::{
+    channel::{ChannelContext, ComputeChannel, ComputeInputExt},
+    errors::Error,
+    types,
+};
+use mitsuha_core_types::channel::{ComputeInput, ComputeOutput};
+use tokio::sync::RwLock;
+
+use crate::{metric, NextComputeChannel, WrappedComputeChannel};
+
+/// Retries inputs of `opkinds` failing with a transient error up to `max_retries` times, with
+/// an exponential backoff randomized by `randomization_factor`.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub opkinds: Vec<String>,
+    pub max_retries: u32,
+    pub initial_interval: Duration,
+    pub max_interval: Duration,
+    pub multiplier: f64,
+    pub randomization_factor: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            opkinds: vec![
+                "load".to_string(),
+                "status".to_string(),
+                "persist".to_string(),
+            ],
+            max_retries: 3,
+            initial_interval: Duration::from_millis(100),
+            max_interval: Duration::from_secs(2),
+            multiplier: 2.0,
+            randomization_factor: 0.5,
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn make_backoff(&self) -> ExponentialBackoff {
+        ExponentialBackoff {
+            current_interval: self.initial_interval,
+            initial_interval: self.initial_interval,
+            randomization_factor: self.randomization_factor,
+            multiplier: self.multiplier,
+            max_interval: self.max_interval,
+            max_elapsed_time: None,
+            ..Default::default()
+        }
+    }
+}
+
+/// Opens the circuit of a downstream channel after `failure_threshold` consecutive transient
+/// failures, rejecting inputs for `open_duration` before letting a single trial input through.
+#[derive(Clone, Debug)]
+pub struct CircuitBreakerPolicy {
+    pub failure_threshold: u32,
+    pub open_duration: Duration,
+}
+
+impl Default for CircuitBreakerPolicy {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    HalfOpen,
+    Open,
+}
+
+impl CircuitState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Closed => "closed",
+            Self::HalfOpen => "half_open",
+            Self::Open => "open",
+        }
+    }
+
+    fn as_metric_value(&self) -> i64 {
+        match self {
+            Self::Closed => 0,
+            Self::HalfOpen => 1,
+            Self::Open => 2,
+        }
+    }
+}
+
+/// The generation is bumped on every transition, so that results of inputs admitted before
+/// the last transition are ignored.
+struct CircuitBreaker {
+    state: CircuitState,
+    generation: u64,
+    failures: u32,
+    opened_at: Instant,
+    trial_in_flight: bool,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self {
+            state: CircuitState::Closed,
+            generation: 0,
+            failures: 0,
+            opened_at: Instant::now(),
+            trial_in_flight: false,
+        }
+    }
+}
+
+/// An input admitted by a circuit breaker. Dropping the permit of a trial input without
+/// recording its result, e.g. when the compute future is cancelled, lets another trial
+/// input through.
+struct BreakerPermit<'a> {
+    channel: &'a ResilienceChannel,
+    target: &'a str,
+    generation: u64,
+    trial: bool,
+}
+
+impl BreakerPermit<'_> {
+    fn record(mut self, result: &types::Result<ComputeOutput>) {
+        self.channel.record(self.target, self.generation, result);
+        self.trial = false;
+    }
+}
+
+impl Drop for BreakerPermit<'_> {
+    fn drop(&mut self) {
+        if !self.trial {
+            return;
+        }
+
+        if let Some(mut breaker) = self.channel.breakers.get_mut(self.target) {
+            if breaker.generation == self.generation {
+                breaker.trial_in_flight = false;
+            }
+        }
+    }
+}
+
+/// Retries idempotent inputs failing with a transient error and keeps a circuit breaker per
+/// downstream channel id, so that a failing downstream is not hammered with requests.
+/// Errors which are not transient count as successful responses of the downstream.
+pub struct ResilienceChannel {
+    id: String,
+    next: NextComputeChannel<ChannelContext>,
+    retry_policy: RetryPolicy,
+    breaker_policy: CircuitBreakerPolicy,
+    breakers: DashMap<String, CircuitBreaker>,
+}
+
+#[async_trait]
+impl ComputeChannel for ResilienceChannel {
+    type Context = ChannelContext;
+
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    async fn compute(
+        &self,
+        ctx: ChannelContext,
+        elem: ComputeInput,
+    ) -> types::Result<ComputeOutput> {
+        let next = match self.next.read().await.clone() {
+            Some(chan) => chan,
+            None => return Err(Error::ComputeChannelEOF),
+        };
+
+        let target = next.id();
+        let opkind = elem.get_opkind();
+
+        if !self.retry_policy.opkinds.contains(&opkind) {
+            let permit = self.acquire(&target)?;
+
+            let result = next.compute(ctx, elem).await;
+            permit.record(&result);
+
+            return result;
+        }
+
+        let mut backoff = self.retry_policy.make_backoff();
+        let mut retries = 0;
+
+        loop {
+            let permit = self.acquire(&target)?;
+
+            let result = next.compute(ctx.clone(), elem.clone()).await;
+            permit.record(&result);
+
+            match result {
+                Err(e) if e.is_transient() && retries < self.retry_policy.max_retries => {
+                    let delay = backoff
+                        .next_backoff()
+                        .unwrap_or(self.retry_policy.max_interval);
+
+                    tracing::debug!(
+                        "retrying '{}' compute on channel '{}' in {}ms, error: {}",
+                        opkind,
+                        target,
+                        delay.as_millis(),
+                        e
+                    );
+
+                    metric::compute_retry_count_metric()
+                        .with_label_values(&[target.as_str(), opkind.as_str()])
+                        .inc();
+
+                    tokio::time::sleep(delay).await;
+
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn connect(&self, next: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>) {
+        *self.next.write().await = Some(next);
+    }
+}
+
+impl ResilienceChannel {
+    pub fn get_identifier_type() -> &'static str {
+        "mitsuha/channel/resilience"
+    }
+
+    pub fn new(
+        retry_policy: RetryPolicy,
+        breaker_policy: CircuitBreakerPolicy,
+    ) -> WrappedComputeChannel<Self> {
+        WrappedComputeChannel::new(Self {
+            id: Self::get_identifier_type().to_string(),
+            next: Arc::new(RwLock::new(None)),
+            retry_policy,
+            breaker_policy,
+            breakers: Default::default(),
+        })
+    }
+
+    fn transition(&self, target: &str, breaker: &mut CircuitBreaker, state: CircuitState) {
+        tracing::info!("circuit of channel '{}' is now {}", target, state.as_str());
+
+        breaker.state = state;
+        breaker.generation += 1;
+
+        metric::circuit_breaker_state_metric()
+            .with_label_values(&[target])
+            .set(state.as_metric_value());
+
+        metric::circuit_breaker_transition_count_metric()
+            .with_label_values(&[target, state.as_str()])
+            .inc();
+    }
+
+    fn acquire<'a>(&'a self, target: &'a str) -> types::Result<BreakerPermit<'a>> {
+        let mut breaker = self.breakers.entry(target.to_string()).or_default();
+
+        let mut permit = BreakerPermit {
+            channel: self,
+            target,
+            generation: breaker.generation,
+            trial: false,
+        };
+
+        let retry_after = match breaker.state {
+            CircuitState::Closed => return Ok(permit),
+            CircuitState::Open => {
+                let elapsed = breaker.opened_at.elapsed();
+
+                if elapsed >= self.breaker_policy.open_duration {
+                    self.transition(target, &mut breaker, CircuitState::HalfOpen);
+                    breaker.trial_in_flight = true;
+
+                    permit.generation = breaker.generation;
+                    permit.trial = true;
+
+                    return Ok(permit);
+                }
+
+                self.breaker_policy.open_duration - elapsed
+            }
+            CircuitState::HalfOpen => {
+                if !breaker.trial_in_flight {
+                    breaker.trial_in_flight = true;
+
+                    permit.trial = true;
+
+                    return Ok(permit);
+                }
+
+                self.breaker_policy.open_duration
+            }
+        };
+
+        Err(Error::CircuitOpen {
+            target: target.to_string(),
+            retry_after_ms: retry_after.as_millis().try_into().unwrap_or(u64::MAX),
+        })
+    }
+
+    fn record(&self, target: &str, generation: u64, result: &types::Result<ComputeOutput>) {
+        let mut breaker = self.breakers.entry(target.to_string()).or_default();
+
+        if breaker.generation != generation {
+            return;
+        }
+
+        breaker.trial_in_flight = false;
+
+        match result {
+            Err(e) if e.is_transient() => {
+                breaker.failures += 1;
+
+                if breaker.state == CircuitState::HalfOpen
+                    || (breaker.state == CircuitState::Closed
+                        && breaker.failures >= self.breaker_policy.failure_threshold)
+                {
+                    breaker.opened_at = Instant::now();
+                    self.transition(target, &mut breaker, CircuitState::Open);
+                }
+            }
+            _ => {
+                breaker.failures = 0;
+
+                if breaker.state != CircuitState::Closed {
+                    self.transition(target, &mut breaker, CircuitState::Closed);
+                }
+            }
+        }
+    }
+}
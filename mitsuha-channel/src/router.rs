@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use mitsuha_core::{
+    channel::{ChannelContext, ComputeChannel, ComputeInputExt},
+    constants::Constants,
+    errors::{Error, ToUnknownErrorResult},
+    types,
+};
+use mitsuha_core_types::channel::{ComputeInput, ComputeOutput};
+use regex::Regex;
+use tokio::sync::RwLock;
+
+use crate::{NextComputeChannel, WrappedComputeChannel};
+
+/// Sends compute inputs matching every condition of the rule to the sub-chain starting at the
+/// channel with id `target`. Conditions left unset match any input. Patterns must match the
+/// whole value, so that a rule for `tenant-a/.*` does not also route `other/tenant-a/...`.
+#[derive(Clone, Debug)]
+pub struct Rule {
+    target: String,
+    opkind: Option<String>,
+    handle: Option<Regex>,
+    namespace: Option<Regex>,
+    extensions: Vec<(String, Regex)>,
+}
+
+impl Rule {
+    pub fn new(target: String) -> Self {
+        Self {
+            target,
+            opkind: None,
+            handle: None,
+            namespace: None,
+            extensions: vec![],
+        }
+    }
+
+    fn make_regex(expr: &str) -> types::Result<Regex> {
+        Regex::new(&format!("^(?:{})$", expr)).to_unknown_err_result()
+    }
+
+    pub fn with_opkind(mut self, opkind: String) -> Self {
+        self.opkind = Some(opkind);
+        self
+    }
+
+    pub fn with_handle(mut self, expr: &str) -> types::Result<Self> {
+        self.handle = Some(Self::make_regex(expr)?);
+        Ok(self)
+    }
+
+    pub fn with_namespace(mut self, expr: &str) -> types::Result<Self> {
+        self.namespace = Some(Self::make_regex(expr)?);
+        Ok(self)
+    }
+
+    pub fn with_extension(mut self, key: String, expr: &str) -> types::Result<Self> {
+        self.extensions.push((key, Self::make_regex(expr)?));
+        Ok(self)
+    }
+
+    pub fn matches(&self, elem: &ComputeInput) -> bool {
+        if let Some(opkind) = &self.opkind {
+            if *opkind != elem.get_opkind() {
+                return false;
+            }
+        }
+
+        if let Some(handle) = &self.handle {
+            if !handle.is_match(&elem.get_original_handle()) {
+                return false;
+            }
+        }
+
+        let extensions = elem.get_extensions();
+
+        if let Some(namespace) = &self.namespace {
+            match extensions.get(&Constants::ChannelNamespace.to_string()) {
+                Some(value) if namespace.is_match(value) => {}
+                _ => return false,
+            }
+        }
+
+        self.extensions
+            .iter()
+            .all(|(key, expr)| matches!(extensions.get(key), Some(value) if expr.is_match(value)))
+    }
+}
+
+/// Routes compute inputs to named sub-chains by the first matching [Rule], inputs matching no
+/// rule continue down the chain. Sub-chains are looked up by channel id on every compute, so
+/// they may be initialized after the router.
+pub struct RouterChannel {
+    id: String,
+    next: NextComputeChannel<ChannelContext>,
+    rules: Vec<Rule>,
+}
+
+#[async_trait]
+impl ComputeChannel for RouterChannel {
+    type Context = ChannelContext;
+
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    async fn compute(
+        &self,
+        ctx: ChannelContext,
+        elem: ComputeInput,
+    ) -> types::Result<ComputeOutput> {
+        if let Some(rule) = self.rules.iter().find(|x| x.matches(&elem)) {
+            let target = ctx
+                .get_mgr()
+                .read()
+                .await
+                .channel_map
+                .get(&rule.target)
+                .map(|x| x.value().clone())
+                .ok_or(Error::UnknownWithMsgOnly {
+                    message: format!("could not find routing target channel '{}'", rule.target),
+                })?;
+
+            tracing::debug!("routing compute to channel '{}'", rule.target);
+
+            return target.compute(ctx, elem).await;
+        }
+
+        match self.next.read().await.clone() {
+            Some(chan) => chan.compute(ctx, elem).await,
+            None => Err(Error::ComputeChannelEOF),
+        }
+    }
+
+    async fn connect(&self, next: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>) {
+        *self.next.write().await = Some(next);
+    }
+}
+
+impl RouterChannel {
+    pub fn get_identifier_type() -> &'static str {
+        "mitsuha/channel/router"
+    }
+
+    pub fn new(rules: Vec<Rule>) -> WrappedComputeChannel<Self> {
+        WrappedComputeChannel::new(Self {
+            id: Self::get_identifier_type().to_string(),
+            next: Arc::new(RwLock::new(None)),
+            rules,
+        })
+    }
+}
@@ -38,3 +38,24 @@ pub fn make_compute_input_span(elem: &ComputeInput) -> tracing::span::Span {
 pub fn make_job_span(executor: &str) -> tracing::span::Span {
     tracing::info_span!("job_run", job_executor = executor)
 }
+
+/// Whether the request failed before reaching the peer, in which case it is always safe to try
+/// it again.
+pub fn is_connect_error(status: &tonic::Status) -> bool {
+    let mut source = std::error::Error::source(status);
+
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<std::io::Error>() {
+            return matches!(
+                err.kind(),
+                std::io::ErrorKind::ConnectionRefused
+                    | std::io::ErrorKind::AddrNotAvailable
+                    | std::io::ErrorKind::NotConnected
+            );
+        }
+
+        source = err.source();
+    }
+
+    false
+}
@@ -11,18 +11,23 @@ use mitsuha_core::job::mgr::JobManagerProvider;
 use mitsuha_core::{
     channel::ComputeChannel,
     constants::Constants,
+    err_unsupported_op,
     errors::Error,
     kernel::{JobSpecExt, Kernel, KernelBinding, KernelBridge},
     linker::{Linker, LinkerContext},
-    resolver::{blob::BlobResolver, Resolver},
+    native::NativeModuleRegistry,
+    registry::{ModuleRegistry, PublisherVerifier},
+    resolver::{blob::BlobResolver, registry::RegistryResolver, Resolver},
     types,
 };
 use mitsuha_core_types::{
     channel::{ComputeInput, ComputeOutput},
-    kernel::JobSpec,
+    kernel::{JobSpec, StorageSpec},
     module::{ModuleInfo, ModuleType},
 };
-use mitsuha_wasm_runtime::wasmtime::WasmtimeLinker;
+use mitsuha_wasm_runtime::wasmtime::{
+    ComponentLinker, JobMonitor, PrecompiledModuleStore, WasmtimeConfig, WasmtimeLinker,
+};
 use tokio::{sync::RwLock, task::JoinHandle};
 use tracing::Instrument;
 
@@ -31,11 +36,23 @@ use crate::{
     NextComputeChannel, WrappedComputeChannel,
 };
 
+/// Optional collaborators of the [WasmtimeChannel], wired up by the runtime plugin.
+#[derive(Clone, Default)]
+pub struct WasmtimeChannelDependencies {
+    pub precompiled_store: Option<Arc<PrecompiledModuleStore>>,
+    pub module_registry: Option<Arc<Box<dyn ModuleRegistry>>>,
+    pub publisher_verifier: Option<PublisherVerifier>,
+    pub native_modules: NativeModuleRegistry,
+}
+
 pub struct WasmtimeChannel {
     id: String,
     next: NextComputeChannel<ChannelContext>,
     linker: Arc<WasmtimeLinker>,
+    component_linker: Arc<ComponentLinker>,
     kernel: Arc<Box<dyn Kernel>>,
+    module_registry: Option<Arc<Box<dyn ModuleRegistry>>>,
+    publisher_verifier: Option<PublisherVerifier>,
 }
 
 #[async_trait]
@@ -59,15 +76,30 @@ impl ComputeChannel for WasmtimeChannel {
                 let handle = spec.handle.clone();
 
                 let raw_module_resolver: Arc<Box<dyn Resolver<ModuleInfo, Vec<u8>>>> =
-                    Arc::new(Box::new(
-                        BlobResolver::new(ctx.get_channel_start())
-                            .with_extensions(spec.extensions.clone()),
-                    ));
+                    match self.module_registry.clone() {
+                        Some(registry) => {
+                            let mut resolver = RegistryResolver::new(registry);
+
+                            if let Some(verifier) = self.publisher_verifier.clone() {
+                                resolver = resolver.with_verifier(verifier);
+                            }
+
+                            Arc::new(Box::new(resolver))
+                        }
+                        None => Arc::new(Box::new(
+                            BlobResolver::new(ctx.get_channel_start())
+                                .with_extensions(spec.extensions.clone()),
+                        )),
+                    };
+
+                let module_registry = self.module_registry.clone();
 
                 let linker = self.linker.clone();
+                let component_linker = self.component_linker.clone();
 
                 let kernel_binding: Arc<Box<dyn KernelBinding>> = Arc::new(Box::new(
-                    KernelBridge::new(self.kernel.clone(), spec.make_kernel_bridge_metadata()?),
+                    KernelBridge::new(self.kernel.clone(), spec.make_kernel_bridge_metadata()?)
+                        .with_capabilities(spec.get_capabilities()?),
                 ));
 
                 let kernel = self.kernel.clone();
@@ -96,9 +128,11 @@ impl ComputeChannel for WasmtimeChannel {
                         async move {
                             Self::run(
                                 linker,
+                                component_linker,
                                 kernel_binding,
                                 kernel,
                                 raw_module_resolver,
+                                module_registry,
                                 job_task_spec,
                             )
                             .await?;
@@ -183,29 +217,44 @@ impl WasmtimeChannel {
     }
 
     pub fn new(kernel: Arc<Box<dyn Kernel>>) -> WrappedComputeChannel<Self> {
-        // TODO: Make this configurable
+        Self::new_with_config(kernel, WasmtimeConfig::default(), Default::default()).unwrap()
+    }
 
-        let mut config = wasmtime::Config::default();
-        config.async_support(true);
-        config.epoch_interruption(true);
+    pub fn new_with_config(
+        kernel: Arc<Box<dyn Kernel>>,
+        config: WasmtimeConfig,
+        dependencies: WasmtimeChannelDependencies,
+    ) -> types::Result<WrappedComputeChannel<Self>> {
+        let engine = config.make_engine()?;
 
-        let engine = wasmtime::Engine::new(&config).unwrap();
+        let component_linker = ComponentLinker::new(engine.clone(), &config)?;
 
-        let linker = Arc::new(WasmtimeLinker::new(engine).unwrap());
+        let mut linker = WasmtimeLinker::new(engine, &config)?;
 
-        WrappedComputeChannel::new(Self {
+        if let Some(store) = dependencies.precompiled_store {
+            linker = linker.with_precompiled_store(store);
+        }
+
+        linker = linker.with_native_modules(dependencies.native_modules);
+
+        Ok(WrappedComputeChannel::new(Self {
             id: Self::get_identifier_type().to_string(),
             next: Arc::new(RwLock::new(None)),
-            linker,
+            linker: Arc::new(linker),
+            component_linker: Arc::new(component_linker),
             kernel,
-        })
+            module_registry: dependencies.module_registry,
+            publisher_verifier: dependencies.publisher_verifier,
+        }))
     }
 
     async fn run(
         linker: Arc<WasmtimeLinker>,
+        component_linker: Arc<ComponentLinker>,
         kernel_binding: Arc<Box<dyn KernelBinding>>,
         kernel: Arc<Box<dyn Kernel>>,
         resolver: Arc<Box<dyn Resolver<ModuleInfo, Vec<u8>>>>,
+        module_registry: Option<Arc<Box<dyn ModuleRegistry>>>,
         spec: JobSpec,
     ) -> types::Result<()> {
         let symbol = spec.symbol.clone();
@@ -213,17 +262,119 @@ impl WasmtimeChannel {
 
         let mut linker_ctx = LinkerContext::new(kernel_binding, resolver);
 
+        if let Some(registry) = module_registry {
+            linker_ctx = linker_ctx.with_module_registry(registry);
+        }
+
         linker_ctx.load_extensions_from_job(&spec);
 
-        linker.load(&mut linker_ctx, &module_info).await?;
+        let mut monitor = JobMonitor::new(linker.make_cpu_meter(&linker_ctx.extensions)?);
+
+        let profile_handle = spec
+            .extensions
+            .get(&Constants::JobProfileHandle.to_string())
+            .cloned();
+
+        // Components share the WASM module type, they are told apart by their encoding.
+        let exec_ctx = if component_linker
+            .is_component(&mut linker_ctx, &module_info)
+            .await?
+        {
+            if profile_handle.is_some() {
+                return Err(err_unsupported_op!(
+                    "guest profiles are only supported for core modules, not components"
+                ));
+            }
 
-        let exec_ctx = Arc::new(linker.link(&mut linker_ctx, &module_info).await?);
+            component_linker.load(&mut linker_ctx, &module_info).await?;
+            component_linker
+                .link_monitored(&linker_ctx, &module_info, &monitor)
+                .await?
+        } else {
+            linker.load(&mut linker_ctx, &module_info).await?;
+
+            // A profile that cannot be made is skipped, it never fails the job.
+            match linker.make_profiler(&linker_ctx, &module_info).await {
+                Ok(Some(profiler)) => monitor = monitor.with_profiler(profiler),
+                Ok(None) => {}
+                Err(e) => tracing::warn!(
+                    "skipping guest profile of job '{}', failed to create profiler: {}",
+                    spec.handle,
+                    e
+                ),
+            }
+
+            linker
+                .link_monitored(&linker_ctx, &module_info, &monitor)
+                .await?
+        };
+
+        let exec_ctx = Arc::new(exec_ctx);
 
         let input = kernel
             .load_data(spec.input_handle.clone(), spec.extensions.clone())
             .await?;
 
-        let output = exec_ctx.call(&symbol, input).await?;
+        let output = exec_ctx.call(&symbol, input).await;
+
+        if let (Some(profiler), Some(handle)) = (&monitor.profiler, profile_handle) {
+            match profiler.finish() {
+                Ok(data) => {
+                    kernel
+                        .store_data(StorageSpec {
+                            handle,
+                            data,
+                            ttl: spec.get_output_ttl()?,
+                            extensions: spec.extensions.clone(),
+                        })
+                        .await?
+                }
+                Err(e) => tracing::warn!(
+                    "skipping guest profile of job '{}', failed to write profile: {}",
+                    spec.handle,
+                    e
+                ),
+            }
+        }
+
+        let meter = &monitor.meter;
+
+        if meter.is_exceeded() {
+            return Err(Error::JobCpuExceeded {
+                handle: spec.handle.clone(),
+                budget: format!("{} epochs", meter.get_budget().unwrap_or_default()),
+            });
+        }
+
+        // A trap in any linked instance fails the job, even if the caller recovered from it.
+        if let Some(recorded) = monitor.traps.take() {
+            let mut trap = recorded.trap;
+
+            let core_dump_handle = spec
+                .extensions
+                .get(&Constants::JobCoreDumpHandle.to_string())
+                .cloned();
+
+            if let (Some(core_dump), Some(handle)) = (recorded.core_dump, core_dump_handle) {
+                kernel
+                    .store_data(StorageSpec {
+                        handle: handle.clone(),
+                        data: core_dump,
+                        ttl: spec.get_output_ttl()?,
+                        extensions: spec.extensions.clone(),
+                    })
+                    .await?;
+
+                trap.core_dump_handle = Some(handle);
+            }
+
+            return Err(Error::JobTrapped {
+                handle: spec.handle.clone(),
+                trap: Box::new(trap),
+            });
+        }
+
+        let output = output?;
 
         kernel
             .store_data(make_output_storage_spec(spec, output)?)
@@ -0,0 +1,114 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use mitsuha_channel::compression::{CompressionAlgorithm, CompressionChannel};
+use mitsuha_core::channel::{ChannelContext, ComputeChannel, ComputeKernel, MusubiKernelWrapper};
+use mitsuha_core_types::kernel::AsyncKernel;
+use mitsuha_filesystem::{
+    async_fs::AsyncNativeFileSystemBuilder, constant::NativeFileSystemConstants, AsyncFileSystem,
+};
+use tokio::io::ReadBuf;
+
+mod setup;
+use setup::*;
+
+async fn make_channel(
+    algorithm: CompressionAlgorithm,
+    storage: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>,
+) -> Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> {
+    let channel: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> = Arc::new(Box::new(
+        CompressionChannel::new(algorithm, 64).with_id("compression-0".to_string()),
+    ));
+
+    channel.connect(storage).await;
+
+    channel
+}
+
+async fn compression_roundtrip(algorithm: CompressionAlgorithm) {
+    let storage = make_labeled_storage_channel().await;
+    let channel = make_channel(algorithm, storage.clone()).await;
+
+    let large = b"mitsuha".repeat(1000);
+    store(&channel, "compression/large", large.clone()).await;
+
+    let raw = load(&storage, "compression/large").await.unwrap();
+    assert!(raw.starts_with(b"\xffMZC"));
+    assert!(raw.len() < large.len());
+    assert_eq!(load(&channel, "compression/large").await.unwrap(), large);
+
+    let small = b"mitsuha".to_vec();
+    store(&channel, "compression/small", small.clone()).await;
+
+    assert_eq!(load(&storage, "compression/small").await.unwrap(), small);
+    assert_eq!(load(&channel, "compression/small").await.unwrap(), small);
+
+    // Blobs which happen to start with the header must load back unchanged.
+    let tricky = b"\xffMZC\x01mitsuha".to_vec();
+    store(&channel, "compression/tricky", tricky.clone()).await;
+
+    assert_eq!(load(&channel, "compression/tricky").await.unwrap(), tricky);
+}
+
+#[tokio::test]
+async fn compression_zstd_roundtrip() {
+    compression_roundtrip(CompressionAlgorithm::Zstd { level: 3 }).await;
+}
+
+#[tokio::test]
+async fn compression_lz4_roundtrip() {
+    compression_roundtrip(CompressionAlgorithm::Lz4).await;
+}
+
+#[tokio::test]
+async fn compression_rejects_file_parts() {
+    let storage = make_labeled_storage_channel().await;
+    let channel = make_channel(CompressionAlgorithm::Lz4, storage).await;
+
+    let kernel: Arc<Box<dyn AsyncKernel>> = Arc::new(Box::new(MusubiKernelWrapper::new(Box::new(
+        ComputeKernel::new(channel.clone()),
+    ))));
+
+    let extensions: HashMap<String, String> = [(
+        NativeFileSystemConstants::FilePartMaxSize.to_string(),
+        1024.to_string(),
+    )]
+    .into_iter()
+    .collect();
+
+    let fs = AsyncNativeFileSystemBuilder::new(kernel)
+        .with_extensions(&extensions)
+        .unwrap()
+        .build();
+
+    let file_path = Path::new("/compression.txt");
+
+    fs.create_dir(Path::new("/")).await.unwrap();
+    fs.create_empty_file(file_path).await.unwrap();
+    fs.write_to_offset(file_path, 0u64, vec![1u8; 4096].as_slice())
+        .await
+        .unwrap();
+
+    let mut buf = vec![0u8; 1024];
+
+    fs.read_from_offset(file_path, 1024u64, &mut ReadBuf::new(&mut buf))
+        .await
+        .unwrap();
+
+    assert_eq!(buf, vec![1u8; 1024]);
+
+    // Once the file is stored whole, and so compressed, none of its parts can be read.
+    store(&channel, "/compression.txt", vec![1u8; 4096]).await;
+
+    for part_index in 0..4u64 {
+        let result = fs
+            .read_from_offset(file_path, part_index * 1024, &mut ReadBuf::new(&mut buf))
+            .await;
+
+        assert!(result.is_err());
+    }
+
+    assert_eq!(
+        load(&channel, "/compression.txt").await.unwrap(),
+        vec![1u8; 4096]
+    );
+}
@@ -0,0 +1,205 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use mitsuha_channel::enforcer::EnforcerChannel;
+use mitsuha_core::{
+    channel::{ChannelContext, ComputeChannel},
+    config,
+    constants::Constants,
+    errors::Error,
+    kernel::LabelExtensionExt,
+    storage::Storage,
+    types,
+};
+use mitsuha_core_types::{
+    channel::{ComputeInput, ComputeOutput},
+    kernel::StorageSpec,
+};
+use mitsuha_policy_engine::{Action, Permission, Policy};
+use mitsuha_storage::UnifiedStorage;
+
+mod setup;
+use setup::*;
+
+const POLICY_BLOB_KEY: &str = "policy";
+
+/// Loads blobs from the storage selected by the extensions of the input, like a chain
+/// without a labeled storage channel does.
+struct SelectedStorageChannel {
+    storage: Arc<Box<dyn Storage>>,
+}
+
+#[async_trait]
+impl ComputeChannel for SelectedStorageChannel {
+    type Context = ChannelContext;
+
+    fn id(&self) -> String {
+        "storage".to_string()
+    }
+
+    async fn compute(
+        &self,
+        _ctx: ChannelContext,
+        elem: ComputeInput,
+    ) -> types::Result<ComputeOutput> {
+        match elem {
+            ComputeInput::Load { handle, extensions } => Ok(ComputeOutput::Loaded {
+                data: self.storage.load(handle, extensions).await?,
+            }),
+            _ => Ok(ComputeOutput::Completed),
+        }
+    }
+
+    async fn connect(&self, _next: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>) {}
+}
+
+fn allow_load(handle: &str) -> Policy {
+    Policy {
+        permission: Permission::Allow,
+        action: Action::LoadBlob {
+            handle: handle.to_string(),
+        },
+    }
+}
+
+async fn store_blob(storage: &Arc<Box<dyn Storage>>, class: &str, handle: &str, data: Vec<u8>) {
+    storage
+        .store(StorageSpec {
+            handle: handle.to_string(),
+            data,
+            ttl: 100,
+            extensions: HashMap::new().with_selector(&make_label(class)),
+        })
+        .await
+        .unwrap();
+}
+
+async fn store_policies(
+    storage: &Arc<Box<dyn Storage>>,
+    class: &str,
+    handle: &str,
+    policies: Vec<Policy>,
+) {
+    let value = musubi_api::types::to_value(&policies).unwrap();
+    let data: Vec<u8> = value.try_into().unwrap();
+
+    store_blob(storage, class, handle, data).await;
+}
+
+async fn make_enforcer_channel() -> (
+    Arc<Box<dyn Storage>>,
+    Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>,
+) {
+    let config = config::storage::Storage {
+        classes: vec![make_class("policies"), make_class("guest")],
+    };
+
+    let storage = UnifiedStorage::new(&config).await.unwrap();
+
+    let channel: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> = Arc::new(Box::new(
+        EnforcerChannel::new(POLICY_BLOB_KEY.to_string(), Some(make_label("policies")))
+            .with_id("enforcer-0".to_string()),
+    ));
+
+    channel
+        .connect(Arc::new(Box::new(SelectedStorageChannel {
+            storage: storage.clone(),
+        })))
+        .await;
+
+    (storage, channel)
+}
+
+/// A load made by a job running under `parent_policy`, as scoped by the kernel bridge. The
+/// guest picks its own policy and storage selector.
+fn make_guest_load(handle: &str, policy: &str, parent_policy: &str) -> ComputeInput {
+    let mut extensions = HashMap::new().with_selector(&make_label("guest"));
+
+    extensions.insert(POLICY_BLOB_KEY.to_string(), policy.to_string());
+    extensions.insert(
+        Constants::JobParentPolicyBlob.to_string(),
+        parent_policy.to_string(),
+    );
+    extensions.insert(Constants::JobIdentity.to_string(), "parent-job".to_string());
+
+    ComputeInput::Load {
+        handle: handle.to_string(),
+        extensions,
+    }
+}
+
+#[tokio::test]
+async fn child_cannot_widen_parent_policies() {
+    let (storage, channel) = make_enforcer_channel().await;
+
+    store_policies(
+        &storage,
+        "policies",
+        "policies/parent",
+        vec![allow_load("data/*")],
+    )
+    .await;
+    store_policies(
+        &storage,
+        "policies",
+        "policies/narrow",
+        vec![allow_load("data/a*")],
+    )
+    .await;
+    store_policies(&storage, "policies", "policies/wide", vec![allow_load("*")]).await;
+    store_blob(&storage, "guest", "data/a1", vec![1]).await;
+
+    let output = channel
+        .compute(
+            ChannelContext::default(),
+            make_guest_load("data/a1", "policies/narrow", "policies/parent"),
+        )
+        .await;
+
+    assert!(matches!(output, Ok(ComputeOutput::Loaded { data }) if data == vec![1]));
+
+    let output = channel
+        .compute(
+            ChannelContext::default(),
+            make_guest_load("data/a1", "policies/wide", "policies/parent"),
+        )
+        .await;
+
+    assert!(
+        matches!(output, Err(Error::InvalidOperation { message }) if message.contains("parent-job"))
+    );
+}
+
+#[tokio::test]
+async fn parent_policy_blob_cannot_be_redirected() {
+    let (storage, channel) = make_enforcer_channel().await;
+
+    store_policies(
+        &storage,
+        "policies",
+        "policies/parent",
+        vec![allow_load("data/*")],
+    )
+    .await;
+    store_policies(
+        &storage,
+        "policies",
+        "policies/child",
+        vec![allow_load("data/*")],
+    )
+    .await;
+    store_blob(&storage, "guest", "secret/x", vec![1]).await;
+
+    // Forged blobs under the same handles in a storage the guest can write to.
+    store_policies(&storage, "guest", "policies/parent", vec![allow_load("*")]).await;
+    store_policies(&storage, "guest", "policies/child", vec![allow_load("*")]).await;
+
+    let output = channel
+        .compute(
+            ChannelContext::default(),
+            make_guest_load("secret/x", "policies/child", "policies/parent"),
+        )
+        .await;
+
+    assert!(matches!(output, Err(Error::InvalidOperation { .. })));
+}
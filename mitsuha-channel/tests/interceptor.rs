@@ -0,0 +1,222 @@
+use std::net::SocketAddr;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use mitsuha_channel::interceptor::{InterceptorChannel, InterceptorEndpoint, InterceptorPolicy};
+use mitsuha_core::{
+    channel::{ChannelContext, ComputeChannel},
+    errors::Error,
+    types,
+};
+use mitsuha_core_types::channel::{ComputeInput, ComputeOutput};
+use mitsuha_runtime_rpc::proto::channel::{
+    interceptor_server::{Interceptor, InterceptorServer},
+    ComputeRequest, ComputeResponse, InterceptResponseRequest,
+};
+
+mod setup;
+use setup::*;
+
+/// Loads the handle it is asked for as data.
+struct EchoChannel;
+
+#[async_trait]
+impl ComputeChannel for EchoChannel {
+    type Context = ChannelContext;
+
+    fn id(&self) -> String {
+        "echo".to_string()
+    }
+
+    async fn compute(
+        &self,
+        _ctx: ChannelContext,
+        elem: ComputeInput,
+    ) -> types::Result<ComputeOutput> {
+        match elem {
+            ComputeInput::Load { handle, .. } => Ok(ComputeOutput::Loaded {
+                data: handle.into_bytes(),
+            }),
+            _ => Ok(ComputeOutput::Completed),
+        }
+    }
+
+    async fn connect(&self, _next: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>) {}
+}
+
+/// Appends its name to the handle of loads and to the loaded data, and rejects handles
+/// starting with `forbidden`.
+#[derive(Clone)]
+struct AppendingInterceptor {
+    name: String,
+    delay: Duration,
+    intercept_responses: Arc<AtomicBool>,
+}
+
+#[tonic::async_trait]
+impl Interceptor for AppendingInterceptor {
+    async fn intercept(
+        &self,
+        request: tonic::Request<ComputeRequest>,
+    ) -> tonic::Result<tonic::Response<ComputeRequest>> {
+        tokio::time::sleep(self.delay).await;
+
+        let input: ComputeInput = request.into_inner().try_into().unwrap();
+
+        let input = match input {
+            ComputeInput::Load { handle, .. } if handle.starts_with("forbidden") => {
+                return Err(tonic::Status::permission_denied("forbidden handle"));
+            }
+            ComputeInput::Load { handle, extensions } => ComputeInput::Load {
+                handle: format!("{}/{}", handle, self.name),
+                extensions,
+            },
+            input => input,
+        };
+
+        Ok(tonic::Response::new(input.try_into().unwrap()))
+    }
+
+    async fn intercept_response(
+        &self,
+        request: tonic::Request<InterceptResponseRequest>,
+    ) -> tonic::Result<tonic::Response<ComputeResponse>> {
+        if !self.intercept_responses.load(Ordering::SeqCst) {
+            return Err(tonic::Status::unimplemented("not implemented"));
+        }
+
+        let output: ComputeOutput = request.into_inner().response.unwrap().try_into().unwrap();
+
+        let output = match output {
+            ComputeOutput::Loaded { mut data } => {
+                data.extend(format!("|{}", self.name).into_bytes());
+                ComputeOutput::Loaded { data }
+            }
+            output => output,
+        };
+
+        Ok(tonic::Response::new(output.try_into().unwrap()))
+    }
+}
+
+async fn start_server(
+    name: &str,
+    delay: Duration,
+    intercept_responses: Arc<AtomicBool>,
+) -> SocketAddr {
+    let service = AppendingInterceptor {
+        name: name.to_string(),
+        delay,
+        intercept_responses,
+    };
+
+    let (addr, incoming) = bind_incoming().await;
+
+    tokio::task::spawn(
+        tonic::transport::Server::builder()
+            .add_service(InterceptorServer::new(service))
+            .serve_with_incoming(incoming),
+    );
+
+    addr
+}
+
+async fn make_channel(
+    addrs: &[SocketAddr],
+    policy: InterceptorPolicy,
+) -> Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> {
+    let endpoints = addrs
+        .iter()
+        .map(|addr| InterceptorEndpoint::new(format!("http://{}", addr)).unwrap())
+        .collect();
+
+    let channel: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> = Arc::new(Box::new(
+        InterceptorChannel::new(endpoints, policy).with_id("interceptor-0".to_string()),
+    ));
+
+    channel.connect(Arc::new(Box::new(EchoChannel))).await;
+
+    channel
+}
+
+#[tokio::test]
+async fn intercept_requests_and_responses() {
+    let a = start_server("a", Duration::ZERO, Arc::new(AtomicBool::new(true))).await;
+    let b = start_server("b", Duration::ZERO, Arc::new(AtomicBool::new(false))).await;
+
+    let channel = make_channel(&[a, b], InterceptorPolicy::default()).await;
+
+    // Responses pass through the interceptors in reverse order, skipping the ones which do
+    // not intercept responses.
+    assert_eq!(try_load(&channel, "spec1").await.unwrap(), b"spec1/a/b|a");
+    assert_eq!(try_load(&channel, "spec2").await.unwrap(), b"spec2/a/b|a");
+
+    let result = try_load(&channel, "forbidden").await;
+
+    assert!(matches!(
+        result,
+        Err(Error::InterceptorRejected { reason, .. }) if reason == "forbidden handle"
+    ));
+}
+
+#[tokio::test]
+async fn fail_open_and_fail_closed() {
+    let slow = start_server(
+        "slow",
+        Duration::from_millis(500),
+        Arc::new(AtomicBool::new(true)),
+    )
+    .await;
+
+    let policy = InterceptorPolicy {
+        timeout: Duration::from_millis(50),
+        fail_open: false,
+        ..Default::default()
+    };
+
+    let channel = make_channel(&[slow], policy.clone()).await;
+
+    assert!(try_load(&channel, "spec1").await.is_err());
+
+    let channel = make_channel(
+        &[slow, unused_addr().await],
+        InterceptorPolicy {
+            fail_open: true,
+            ..policy
+        },
+    )
+    .await;
+
+    // Only requests are delayed, so the slow interceptor still intercepts the response.
+    assert_eq!(try_load(&channel, "spec1").await.unwrap(), b"spec1|slow");
+}
+
+#[tokio::test]
+async fn reprobe_unimplemented_phases() {
+    let intercept_responses = Arc::new(AtomicBool::new(false));
+    let a = start_server("a", Duration::ZERO, intercept_responses.clone()).await;
+
+    let channel = make_channel(
+        &[a],
+        InterceptorPolicy {
+            reprobe_interval: Duration::from_millis(200),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    assert_eq!(try_load(&channel, "spec1").await.unwrap(), b"spec1/a");
+
+    // The response phase stays skipped until the reprobe interval has passed.
+    intercept_responses.store(true, Ordering::SeqCst);
+
+    assert_eq!(try_load(&channel, "spec1").await.unwrap(), b"spec1/a");
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    assert_eq!(try_load(&channel, "spec1").await.unwrap(), b"spec1/a|a");
+}
@@ -0,0 +1,86 @@
+use std::{collections::HashMap, sync::Arc};
+
+use mitsuha_channel::{metric, mirror::MirrorChannel};
+use mitsuha_core::{
+    channel::{ChannelContext, ComputeChannel},
+    config,
+    kernel::LabelExtensionExt,
+    storage::Storage,
+};
+use mitsuha_core_types::channel::ComputeInput;
+use mitsuha_storage::UnifiedStorage;
+
+mod setup;
+use setup::*;
+
+async fn make_mirror_channel() -> (
+    Arc<Box<dyn Storage>>,
+    Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>,
+) {
+    let config = config::storage::Storage {
+        classes: vec![make_class("old"), make_class("new")],
+    };
+
+    let storage = UnifiedStorage::new(&config).await.unwrap();
+
+    let channel: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> = Arc::new(Box::new(
+        MirrorChannel::new(storage.clone(), make_label("old"), make_label("new"), true)
+            .with_id("mirror-0".to_string()),
+    ));
+
+    (storage, channel)
+}
+
+#[tokio::test]
+async fn mirror_writes_and_falls_back() {
+    let (storage, channel) = make_mirror_channel().await;
+
+    store(&channel, "mirror/spec1", vec![1, 2, 3]).await;
+
+    for label in ["old", "new"] {
+        let data = storage
+            .load(
+                "mirror/spec1".to_string(),
+                HashMap::new().with_selector(&make_label(label)),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(data, vec![1, 2, 3]);
+    }
+
+    // Loads fall back to the secondary when the primary lost the blob.
+    storage
+        .clear(
+            "mirror/spec1".to_string(),
+            HashMap::new().with_selector(&make_label("old")),
+        )
+        .await
+        .unwrap();
+
+    let fallbacks = metric::mirror_divergence_count_metric()
+        .with_label_values(&["load", "primary_missing"])
+        .get();
+
+    assert_eq!(load(&channel, "mirror/spec1").await, Some(vec![1, 2, 3]));
+
+    assert_eq!(
+        metric::mirror_divergence_count_metric()
+            .with_label_values(&["load", "primary_missing"])
+            .get(),
+        fallbacks + 1
+    );
+
+    channel
+        .compute(
+            ChannelContext::default(),
+            ComputeInput::Clear {
+                handle: "mirror/spec1".to_string(),
+                extensions: HashMap::new(),
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(load(&channel, "mirror/spec1").await, None);
+}
@@ -0,0 +1,202 @@
+use std::{collections::HashMap, sync::Arc};
+
+use mitsuha_channel::quota::{
+    MemoryQuotaStore, PersistentQuotaStore, Quota, QuotaChannel, QuotaGarbageCollectionHook,
+    QuotaStore, QuotaUsage,
+};
+use mitsuha_core::{
+    channel::{ChannelContext, ComputeChannel},
+    constants::Constants,
+    errors::Error,
+    storage::GarbageCollectionHook,
+};
+
+mod setup;
+use mitsuha_core_types::channel::ComputeInput;
+use setup::*;
+
+#[tokio::test]
+async fn enforce_storage_quota() {
+    let store: Arc<Box<dyn QuotaStore>> = Arc::new(Box::new(MemoryQuotaStore::default()));
+
+    let quota = Quota {
+        max_bytes: Some(100),
+        max_blobs: Some(2),
+        max_ttl: Some(60),
+        max_compute: None,
+    };
+
+    let channel: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> = Arc::new(Box::new(
+        QuotaChannel::new(quota, HashMap::new(), store.clone()).with_id("quota-0".to_string()),
+    ));
+
+    channel.connect(make_labeled_storage_channel().await).await;
+
+    let result = channel
+        .compute(
+            ChannelContext::default(),
+            make_store_input("quota/spec1", 10, 120, Some("tenant1")),
+        )
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(Error::QuotaExceeded { resource, .. }) if resource == "ttl"
+    ));
+
+    for handle in ["quota/spec1", "quota/spec2"] {
+        channel
+            .compute(
+                ChannelContext::default(),
+                make_store_input(handle, 40, 30, Some("tenant1")),
+            )
+            .await
+            .unwrap();
+    }
+
+    let result = channel
+        .compute(
+            ChannelContext::default(),
+            make_store_input("quota/spec3", 10, 30, Some("tenant1")),
+        )
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(Error::QuotaExceeded { resource, .. }) if resource == "blobs"
+    ));
+
+    // Overwriting a blob only charges the difference in size.
+    let result = channel
+        .compute(
+            ChannelContext::default(),
+            make_store_input("quota/spec1", 70, 30, Some("tenant1")),
+        )
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(Error::QuotaExceeded { resource, .. }) if resource == "bytes"
+    ));
+
+    channel
+        .compute(
+            ChannelContext::default(),
+            make_store_input("quota/spec1", 60, 30, Some("tenant1")),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store.get_usage("tenant1").await.unwrap(),
+        QuotaUsage {
+            bytes: 100,
+            blobs: 2,
+            compute: 0
+        }
+    );
+
+    channel
+        .compute(
+            ChannelContext::default(),
+            ComputeInput::Clear {
+                handle: "quota/spec1".to_string(),
+                extensions: [(
+                    Constants::ChannelNamespace.to_string(),
+                    "tenant1".to_string(),
+                )]
+                .into_iter()
+                .collect(),
+            },
+        )
+        .await
+        .unwrap();
+
+    QuotaGarbageCollectionHook::new(store.clone())
+        .run(&["quota/spec2".to_string()])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store.get_usage("tenant1").await.unwrap(),
+        QuotaUsage::default()
+    );
+}
+
+/// Requires the database configured for the runtime.
+#[tokio::test(flavor = "multi_thread")]
+async fn count_concurrent_first_writes() {
+    mitsuha_persistence::apply_migrations().await;
+
+    let store = Arc::new(PersistentQuotaStore::new(
+        mitsuha_persistence::database_connection(),
+    ));
+
+    // The namespace is new on every run, so that every write below is among the first.
+    let namespace = format!("quota-test-{}", chrono::Utc::now().timestamp_micros());
+
+    let quota = Quota {
+        max_bytes: None,
+        max_blobs: Some(8),
+        max_ttl: None,
+        max_compute: None,
+    };
+
+    let tasks = (0..8).map(|i| {
+        let store = store.clone();
+        let namespace = namespace.clone();
+        let quota = quota.clone();
+
+        tokio::spawn(async move {
+            let handle = format!("{}/spec{}", namespace, i);
+
+            store.put_blob(&namespace, &handle, 10, &quota).await?;
+            store.put_job(&namespace, &handle, 5, &quota).await
+        })
+    });
+
+    for result in futures::future::join_all(tasks).await {
+        result.unwrap().unwrap();
+    }
+
+    assert_eq!(
+        store.get_usage(&namespace).await.unwrap(),
+        QuotaUsage {
+            bytes: 80,
+            blobs: 8,
+            compute: 40
+        }
+    );
+
+    let result = store
+        .put_blob(&namespace, &format!("{}/spec8", namespace), 10, &quota)
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(Error::QuotaExceeded { resource, .. }) if resource == "blobs"
+    ));
+
+    // Overwrites replace the recorded size of a blob.
+    let handle = format!("{}/spec0", namespace);
+
+    assert_eq!(
+        store
+            .put_blob(&namespace, &handle, 30, &quota)
+            .await
+            .unwrap(),
+        Some(10)
+    );
+
+    for i in 0..8 {
+        let handle = format!("{}/spec{}", namespace, i);
+
+        store.remove_blob(&handle).await.unwrap();
+        store.remove_job(&handle).await.unwrap();
+    }
+
+    assert_eq!(
+        store.get_usage(&namespace).await.unwrap(),
+        QuotaUsage::default()
+    );
+}
@@ -0,0 +1,130 @@
+use std::{sync::Arc, time::Duration};
+
+use mitsuha_channel::rate_limiter::{
+    MemoryTokenBucketStore, PersistentTokenBucketStore, RateLimit, RateLimitKey,
+    RateLimiterChannel, Rule, TokenBucketStore,
+};
+use mitsuha_core::{
+    channel::{ChannelContext, ComputeChannel},
+    errors::Error,
+};
+
+mod setup;
+use mitsuha_core_types::channel::ComputeInput;
+use setup::*;
+
+#[tokio::test]
+async fn limit_by_handle_prefix() {
+    let store: Arc<Box<dyn TokenBucketStore>> =
+        Arc::new(Box::new(MemoryTokenBucketStore::default()));
+
+    let rule = Rule {
+        opkind: Some("store".to_string()),
+        key: RateLimitKey::HandlePrefix {
+            delimiter: "/".to_string(),
+        },
+        limit: RateLimit {
+            capacity: 2.0,
+            refill_rate: 0.0,
+        },
+    };
+
+    let limiter: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> = Arc::new(Box::new(
+        RateLimiterChannel::new("limiter-0".to_string(), vec![rule], store)
+            .with_id("limiter-0".to_string()),
+    ));
+
+    limiter.connect(make_labeled_storage_channel().await).await;
+
+    for handle in ["tenant1/spec1", "tenant1/spec2", "tenant2/spec1"] {
+        limiter
+            .compute(
+                ChannelContext::default(),
+                make_store_input(handle, 12, 100, None),
+            )
+            .await
+            .unwrap();
+    }
+
+    let result = limiter
+        .compute(
+            ChannelContext::default(),
+            make_store_input("tenant1/spec3", 12, 100, None),
+        )
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(Error::RateLimitExceeded { key, .. }) if key == "tenant1"
+    ));
+
+    // Loads match no rule and are not limited.
+    for _ in 0..3 {
+        limiter
+            .compute(
+                ChannelContext::default(),
+                ComputeInput::Load {
+                    handle: "tenant1/spec1".to_string(),
+                    extensions: Default::default(),
+                },
+            )
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn drop_idle_buckets() {
+    let store = MemoryTokenBucketStore::new(16, Duration::from_millis(200));
+
+    let limit = RateLimit {
+        capacity: 1.0,
+        refill_rate: 0.0,
+    };
+
+    assert!(store.take("bucket", &limit).await.unwrap().is_none());
+    assert!(store.take("bucket", &limit).await.unwrap().is_some());
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    assert!(store.take("bucket", &limit).await.unwrap().is_none());
+}
+
+/// Requires the database configured for the runtime.
+#[tokio::test(flavor = "multi_thread")]
+async fn share_persistent_buckets() {
+    mitsuha_persistence::apply_migrations().await;
+
+    let store = Arc::new(PersistentTokenBucketStore::new(
+        mitsuha_persistence::database_connection(),
+    ));
+
+    // The bucket is new on every run, so that the takes below race to create it.
+    let id = format!(
+        "rate-limiter-test/{}",
+        chrono::Utc::now().timestamp_micros()
+    );
+
+    let limit = RateLimit {
+        capacity: 4.0,
+        refill_rate: 0.0,
+    };
+
+    let tasks = (0..8).map(|_| {
+        let store = store.clone();
+        let id = id.clone();
+        let limit = limit.clone();
+
+        tokio::spawn(async move { store.take(&id, &limit).await })
+    });
+
+    let mut granted = 0;
+
+    for result in futures::future::join_all(tasks).await {
+        if result.unwrap().unwrap().is_none() {
+            granted += 1;
+        }
+    }
+
+    assert_eq!(granted, 4);
+}
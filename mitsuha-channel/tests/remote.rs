@@ -0,0 +1,176 @@
+mod setup;
+use setup::*;
+
+use std::net::SocketAddr;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
+use mitsuha_channel::remote::{LoadBalancingStrategy, RemoteChannel, RemoteEndpoint};
+use mitsuha_core::{
+    channel::{ChannelContext, ComputeChannel},
+    constants::Constants,
+    errors::Error,
+};
+use mitsuha_core_types::channel::{ComputeInput, ComputeOutput};
+use mitsuha_runtime_rpc::proto::channel::{
+    channel_server::{Channel, ChannelServer},
+    ComputeRequest, ComputeResponse,
+};
+
+#[derive(Clone, Default)]
+struct CountingChannelService {
+    count: Arc<AtomicUsize>,
+}
+
+#[tonic::async_trait]
+impl Channel for CountingChannelService {
+    async fn compute(
+        &self,
+        request: tonic::Request<ComputeRequest>,
+    ) -> tonic::Result<tonic::Response<ComputeResponse>> {
+        let input: ComputeInput = request.into_inner().try_into().unwrap();
+
+        assert!(matches!(
+            input,
+            ComputeInput::Clear { extensions, .. }
+                if extensions.contains_key(&Constants::ChannelRemoteForwarded.to_string())
+        ));
+
+        self.count.fetch_add(1, Ordering::SeqCst);
+
+        Ok(tonic::Response::new(
+            ComputeOutput::Completed.try_into().unwrap(),
+        ))
+    }
+}
+
+/// Rejects every input as if the peer was shutting down after receiving it.
+#[derive(Clone, Default)]
+struct DrainingChannelService {
+    count: Arc<AtomicUsize>,
+}
+
+#[tonic::async_trait]
+impl Channel for DrainingChannelService {
+    async fn compute(
+        &self,
+        _request: tonic::Request<ComputeRequest>,
+    ) -> tonic::Result<tonic::Response<ComputeResponse>> {
+        self.count.fetch_add(1, Ordering::SeqCst);
+
+        Err(tonic::Status::unavailable("draining"))
+    }
+}
+
+async fn serve<S: Channel>(service: S) -> SocketAddr {
+    let (addr, incoming) = bind_incoming().await;
+
+    tokio::task::spawn(
+        tonic::transport::Server::builder()
+            .add_service(ChannelServer::new(service))
+            .serve_with_incoming(incoming),
+    );
+
+    addr
+}
+
+async fn start_server() -> (SocketAddr, Arc<AtomicUsize>) {
+    let service = CountingChannelService::default();
+    let count = service.count.clone();
+
+    (serve(service).await, count)
+}
+
+async fn start_draining_server() -> (SocketAddr, Arc<AtomicUsize>) {
+    let service = DrainingChannelService::default();
+    let count = service.count.clone();
+
+    (serve(service).await, count)
+}
+
+fn make_channel(addrs: &[SocketAddr]) -> Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> {
+    let endpoints = addrs
+        .iter()
+        .map(|addr| RemoteEndpoint::new(format!("http://{}", addr)).unwrap())
+        .collect();
+
+    Arc::new(Box::new(
+        RemoteChannel::new(
+            endpoints,
+            LoadBalancingStrategy::RoundRobin,
+            Duration::from_secs(60),
+        )
+        .with_id("remote-0".to_string()),
+    ))
+}
+
+#[tokio::test]
+async fn forward_with_round_robin_and_failover() {
+    let (addr1, count1) = start_server().await;
+    let (addr2, count2) = start_server().await;
+
+    let channel = make_channel(&[addr1, unused_addr().await, addr2]);
+
+    // The second endpoint is unreachable, inputs sent to it fail over to the next one.
+    for _ in 0..6 {
+        let output = channel
+            .compute(ChannelContext::default(), make_clear_input("remote/spec1"))
+            .await
+            .unwrap();
+
+        assert!(matches!(output, ComputeOutput::Completed));
+    }
+
+    assert_eq!(count1.load(Ordering::SeqCst), 3);
+    assert_eq!(count2.load(Ordering::SeqCst), 3);
+
+    // Inputs which were already forwarded are not forwarded again.
+    let mut input = make_clear_input("remote/spec1");
+
+    if let ComputeInput::Clear { extensions, .. } = &mut input {
+        extensions.insert(
+            Constants::ChannelRemoteForwarded.to_string(),
+            "true".to_string(),
+        );
+    }
+
+    assert!(channel
+        .compute(ChannelContext::default(), input)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn fail_over_only_safe_retries() {
+    let (draining_addr, draining_count) = start_draining_server().await;
+    let (addr, count) = start_server().await;
+
+    // The draining endpoint may have applied the store, so it is not repeated elsewhere.
+    let channel = make_channel(&[draining_addr, addr]);
+
+    let result = channel
+        .compute(
+            ChannelContext::default(),
+            make_store_input("remote/spec1", 4, 60, None),
+        )
+        .await;
+
+    assert!(matches!(result, Err(Error::ServiceUnavailable { .. })));
+    assert_eq!(draining_count.load(Ordering::SeqCst), 1);
+    assert_eq!(count.load(Ordering::SeqCst), 0);
+
+    // Clears can be repeated safely and fail over to the next endpoint.
+    let channel = make_channel(&[draining_addr, addr]);
+
+    let output = channel
+        .compute(ChannelContext::default(), make_clear_input("remote/spec1"))
+        .await
+        .unwrap();
+
+    assert!(matches!(output, ComputeOutput::Completed));
+    assert_eq!(draining_count.load(Ordering::SeqCst), 2);
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+}
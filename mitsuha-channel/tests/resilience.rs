@@ -0,0 +1,255 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use mitsuha_channel::{
+    interceptor::{InterceptorChannel, InterceptorEndpoint, InterceptorPolicy},
+    resilience::{CircuitBreakerPolicy, ResilienceChannel, RetryPolicy},
+};
+use mitsuha_core::{
+    channel::{ChannelContext, ComputeChannel},
+    errors::Error,
+    types,
+};
+use mitsuha_core_types::channel::{ComputeInput, ComputeOutput};
+
+mod setup;
+use setup::*;
+
+/// Fails the first `failures` computes with a transient error, and loads of `missing` with a
+/// permanent one. Clears of `slow` take 5 seconds.
+struct FlakyChannel {
+    failures: usize,
+    calls: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl ComputeChannel for FlakyChannel {
+    type Context = ChannelContext;
+
+    fn id(&self) -> String {
+        "flaky".to_string()
+    }
+
+    async fn compute(
+        &self,
+        _ctx: ChannelContext,
+        elem: ComputeInput,
+    ) -> types::Result<ComputeOutput> {
+        if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+            return Err(Error::ServiceUnavailable {
+                target: "flaky".to_string(),
+                message: "flaky".to_string(),
+            });
+        }
+
+        match elem {
+            ComputeInput::Clear { handle, .. } if handle == "slow" => {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+
+                Ok(ComputeOutput::Completed)
+            }
+            ComputeInput::Load { handle, .. } if handle == "missing" => {
+                Err(Error::StorageLoadFailed {
+                    message: "blob not found".to_string(),
+                    source: anyhow::anyhow!(""),
+                })
+            }
+            _ => Ok(ComputeOutput::Loaded { data: vec![] }),
+        }
+    }
+
+    async fn connect(&self, _next: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>) {}
+}
+
+async fn make_channel(
+    next: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>,
+) -> Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> {
+    let retry_policy = RetryPolicy {
+        initial_interval: Duration::from_millis(1),
+        max_interval: Duration::from_millis(5),
+        ..Default::default()
+    };
+
+    let breaker_policy = CircuitBreakerPolicy {
+        failure_threshold: 3,
+        open_duration: Duration::from_millis(200),
+    };
+
+    let channel: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> = Arc::new(Box::new(
+        ResilienceChannel::new(retry_policy, breaker_policy).with_id("resilience-0".to_string()),
+    ));
+
+    channel.connect(next).await;
+
+    channel
+}
+
+async fn make_flaky_channel(
+    failures: usize,
+) -> (
+    Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>,
+    Arc<AtomicUsize>,
+) {
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    let channel = make_channel(Arc::new(Box::new(FlakyChannel {
+        failures,
+        calls: calls.clone(),
+    })))
+    .await;
+
+    (channel, calls)
+}
+
+#[tokio::test]
+async fn retry_idempotent_opkinds() {
+    let (channel, calls) = make_flaky_channel(2).await;
+
+    channel
+        .compute(ChannelContext::default(), make_load_input("spec1"))
+        .await
+        .unwrap();
+
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    let (channel, calls) = make_flaky_channel(1).await;
+
+    assert!(channel
+        .compute(ChannelContext::default(), make_clear_input("spec1"))
+        .await
+        .is_err());
+
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn open_circuit_after_repeated_failures() {
+    let (channel, calls) = make_flaky_channel(3).await;
+
+    for _ in 0..3 {
+        assert!(channel
+            .compute(ChannelContext::default(), make_clear_input("spec1"))
+            .await
+            .is_err());
+    }
+
+    let result = channel
+        .compute(ChannelContext::default(), make_clear_input("spec1"))
+        .await;
+
+    assert!(matches!(result, Err(Error::CircuitOpen { target, .. }) if target == "flaky"));
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    tokio::time::sleep(Duration::from_millis(250)).await;
+
+    // The trial input succeeds and closes the circuit.
+    for _ in 0..2 {
+        channel
+            .compute(ChannelContext::default(), make_clear_input("spec1"))
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(calls.load(Ordering::SeqCst), 5);
+}
+
+#[tokio::test]
+async fn permanent_errors_are_not_retried() {
+    let (channel, calls) = make_flaky_channel(0).await;
+
+    for _ in 0..5 {
+        let result = channel
+            .compute(ChannelContext::default(), make_load_input("missing"))
+            .await;
+
+        assert!(matches!(result, Err(Error::StorageLoadFailed { .. })));
+    }
+
+    // Missing blobs neither are retried nor open the circuit.
+    assert_eq!(calls.load(Ordering::SeqCst), 5);
+
+    channel
+        .compute(ChannelContext::default(), make_load_input("spec1"))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn cancelled_trial_does_not_hold_the_circuit() {
+    let (channel, calls) = make_flaky_channel(3).await;
+
+    for _ in 0..3 {
+        assert!(channel
+            .compute(ChannelContext::default(), make_clear_input("spec1"))
+            .await
+            .is_err());
+    }
+
+    tokio::time::sleep(Duration::from_millis(250)).await;
+
+    // The trial input is cancelled before completing.
+    assert!(tokio::time::timeout(
+        Duration::from_millis(50),
+        channel.compute(ChannelContext::default(), make_clear_input("slow"))
+    )
+    .await
+    .is_err());
+
+    channel
+        .compute(ChannelContext::default(), make_clear_input("spec1"))
+        .await
+        .unwrap();
+
+    assert_eq!(calls.load(Ordering::SeqCst), 5);
+}
+
+#[tokio::test]
+async fn retry_unavailable_interceptors() {
+    // Connections to a listener which never accepts them are not answered.
+    let silent = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+    for addr in [unused_addr().await, silent.local_addr().unwrap()] {
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let endpoint = InterceptorEndpoint::new(format!("http://{}", addr)).unwrap();
+        let policy = InterceptorPolicy {
+            timeout: Duration::from_millis(50),
+            ..Default::default()
+        };
+
+        let interceptor: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> =
+            Arc::new(Box::new(
+                InterceptorChannel::new(vec![endpoint], policy)
+                    .with_id("interceptor-0".to_string()),
+            ));
+
+        interceptor
+            .connect(Arc::new(Box::new(FlakyChannel {
+                failures: 0,
+                calls: calls.clone(),
+            })))
+            .await;
+
+        let channel = make_channel(interceptor).await;
+
+        let result = channel
+            .compute(ChannelContext::default(), make_clear_input("spec1"))
+            .await;
+
+        assert!(matches!(result, Err(Error::ServiceUnavailable { .. })));
+
+        // Loads are retried until the circuit opens.
+        let result = channel
+            .compute(ChannelContext::default(), make_load_input("spec1"))
+            .await;
+
+        assert!(
+            matches!(result, Err(Error::CircuitOpen { target, .. }) if target == "interceptor-0")
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,56 @@
+use std::{collections::HashMap, sync::Arc};
+
+use mitsuha_channel::router::{RouterChannel, Rule};
+use mitsuha_core::channel::{ChannelContext, ChannelManager, ComputeChannel};
+
+mod setup;
+use mitsuha_core_types::{channel::ComputeInput, kernel::StorageSpec};
+use setup::*;
+
+#[tokio::test]
+async fn route_by_opkind_and_handle() {
+    let branch_channel = make_labeled_storage_channel().await;
+    let main_channel = make_labeled_storage_channel().await;
+
+    ChannelManager::global_rw()
+        .read()
+        .await
+        .channel_map
+        .insert("router-branch-0".to_string(), branch_channel.clone());
+
+    // Handle patterns match whole handles, "copy-routed/spec1" is not routed.
+    let rule = Rule::new("router-branch-0".to_string())
+        .with_opkind("store".to_string())
+        .with_handle("routed/.*")
+        .unwrap();
+
+    let router: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> = Arc::new(Box::new(
+        RouterChannel::new(vec![rule]).with_id("router-0".to_string()),
+    ));
+
+    router.connect(main_channel.clone()).await;
+
+    for handle in ["routed/spec1", "copy-routed/spec1", "spec1"] {
+        let spec = StorageSpec {
+            handle: handle.to_string(),
+            data: "Hello world!".bytes().collect(),
+            ttl: 100,
+            extensions: HashMap::new(),
+        };
+
+        router
+            .compute(ChannelContext::default(), ComputeInput::Store { spec })
+            .await
+            .unwrap();
+    }
+
+    assert!(load(&branch_channel, "routed/spec1").await.is_some());
+    assert!(load(&main_channel, "routed/spec1").await.is_none());
+    assert!(load(&branch_channel, "spec1").await.is_none());
+    assert!(load(&branch_channel, "copy-routed/spec1").await.is_none());
+    assert!(load(&main_channel, "copy-routed/spec1").await.is_some());
+
+    // Loads match no rule and continue down the chain.
+    assert!(load(&router, "spec1").await.is_some());
+    assert!(load(&router, "routed/spec1").await.is_none());
+}
@@ -1,4 +1,6 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+
+use futures::Stream;
 
 use mitsuha_channel::{
     labeled_storage::LabeledStorageChannel, system::SystemChannel, wasmtime::WasmtimeChannel,
@@ -11,12 +13,18 @@ use mitsuha_core::job::mgr::JobManager;
 use mitsuha_core::{
     channel::{ComputeChannel, ComputeKernel},
     config,
+    constants::Constants,
     kernel::Kernel,
     resolver::{blob::BlobResolver, Resolver},
     selector::Label,
     storage::{Storage, StorageClass, StorageLocality},
+    types,
+};
+use mitsuha_core_types::{
+    channel::{ComputeInput, ComputeOutput},
+    kernel::StorageSpec,
+    module::ModuleInfo,
 };
-use mitsuha_core_types::module::ModuleInfo;
 use mitsuha_storage::UnifiedStorage;
 use std::sync::Once;
 
@@ -106,6 +114,142 @@ pub async fn make_labeled_storage_channel() -> Arc<Box<dyn ComputeChannel<Contex
     ))
 }
 
+#[allow(dead_code)]
+pub fn make_label(value: &str) -> Label {
+    Label {
+        key: "storage".to_string(),
+        value: value.to_string(),
+    }
+}
+
+/// A memory storage class labeled with its own name.
+#[allow(dead_code)]
+pub fn make_class(name: &str) -> StorageClass {
+    StorageClass {
+        kind: mitsuha_core::storage::StorageKind::Memory,
+        locality: StorageLocality::Solid { cache_name: None },
+        name: name.to_string(),
+        labels: vec![make_label(name)],
+        properties: HashMap::new(),
+    }
+}
+
+#[allow(dead_code)]
+pub async fn store(
+    chan: &Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>,
+    handle: &str,
+    data: Vec<u8>,
+) {
+    chan.compute(
+        ChannelContext::default(),
+        ComputeInput::Store {
+            spec: StorageSpec {
+                handle: handle.to_string(),
+                data,
+                ttl: 100,
+                extensions: Default::default(),
+            },
+        },
+    )
+    .await
+    .unwrap();
+}
+
+#[allow(dead_code)]
+pub async fn try_load(
+    chan: &Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>,
+    handle: &str,
+) -> types::Result<Vec<u8>> {
+    let output = chan
+        .compute(
+            ChannelContext::default(),
+            ComputeInput::Load {
+                handle: handle.to_string(),
+                extensions: Default::default(),
+            },
+        )
+        .await?;
+
+    match output {
+        ComputeOutput::Loaded { data } => Ok(data),
+        _ => panic!("expected loaded output"),
+    }
+}
+
+/// Returns [None] when the load fails.
+#[allow(dead_code)]
+pub async fn load(
+    chan: &Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>,
+    handle: &str,
+) -> Option<Vec<u8>> {
+    try_load(chan, handle).await.ok()
+}
+
+#[allow(dead_code)]
+pub fn make_load_input(handle: &str) -> ComputeInput {
+    ComputeInput::Load {
+        handle: handle.to_string(),
+        extensions: Default::default(),
+    }
+}
+
+#[allow(dead_code)]
+pub fn make_clear_input(handle: &str) -> ComputeInput {
+    ComputeInput::Clear {
+        handle: handle.to_string(),
+        extensions: Default::default(),
+    }
+}
+
+/// Binds an ephemeral port for a test server, returning its address and the stream of
+/// connections to serve.
+#[allow(dead_code)]
+pub async fn bind_incoming() -> (
+    SocketAddr,
+    impl Stream<Item = std::io::Result<tokio::net::TcpStream>>,
+) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let incoming = futures::stream::unfold(listener, |listener| async move {
+        let conn = listener.accept().await.map(|(stream, _)| stream);
+        Some((conn, listener))
+    });
+
+    (addr, incoming)
+}
+
+/// An address nothing listens on, connections to it are refused.
+#[allow(dead_code)]
+pub async fn unused_addr() -> SocketAddr {
+    tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+/// Stores `size` bytes under `handle`, in `namespace` if given.
+#[allow(dead_code)]
+pub fn make_store_input(
+    handle: &str,
+    size: usize,
+    ttl: u64,
+    namespace: Option<&str>,
+) -> ComputeInput {
+    ComputeInput::Store {
+        spec: StorageSpec {
+            handle: handle.to_string(),
+            data: vec![0u8; size],
+            ttl,
+            extensions: namespace
+                .map(|x| (Constants::ChannelNamespace.to_string(), x.to_string()))
+                .into_iter()
+                .collect(),
+        },
+    }
+}
+
 #[allow(dead_code)]
 pub fn make_kernel(
     chan: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>,
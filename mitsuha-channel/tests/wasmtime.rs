@@ -2,7 +2,7 @@ use std::{sync::Arc, time::Duration};
 
 mod setup;
 use mitsuha_core::channel::ChannelContext;
-use mitsuha_core::{channel::ComputeChannel, constants::Constants};
+use mitsuha_core::{channel::ComputeChannel, constants::Constants, errors::Error};
 use mitsuha_core_types::{
     channel::{ComputeInput, ComputeOutput},
     kernel::{JobSpec, StorageSpec},
@@ -357,6 +357,63 @@ async fn internal_run_and_abort_mugen_loop() {
     tokio::time::sleep(Duration::from_secs(1)).await;
 }
 
+async fn internal_run_mugen_loop_with_cpu_budget() {
+    let channel = make_channel().await;
+    let mut ctx = ChannelContext::default();
+
+    ctx.set_channel_start(channel.clone());
+
+    upload_artifacts(channel.clone()).await;
+
+    let input = DataBuilder::new()
+        .add(Value::String("Hello world!".to_string()))
+        .build();
+
+    let job_handle = "run_mugen_loop_with_cpu_budget_job_1".to_string();
+    let input_handle = "run_mugen_loop_with_cpu_budget_input_1".to_string();
+    let output_handle = "run_mugen_loop_with_cpu_budget_output_1".to_string();
+
+    let input_spec = StorageSpec {
+        handle: input_handle.clone(),
+        data: input.clone().try_into().unwrap(),
+        ttl: 120,
+        extensions: Default::default(),
+    };
+
+    let job_spec = JobSpec {
+        handle: job_handle,
+        symbol: Symbol {
+            name: "run".to_string(),
+            module_info: ModuleInfo {
+                name: "mitsuha.test.loop".to_string(),
+                version: "0.1.0".to_string(),
+                modtype: ModuleType::WASM,
+            },
+        },
+        ttl: 86400,
+        input_handle,
+        output_handle: output_handle.clone(),
+        extensions: [
+            (Constants::JobOutputTTL.to_string(), "120".to_string()),
+            (Constants::JobChannelAwait.to_string(), "true".to_string()),
+            (Constants::JobCpuBudget.to_string(), "2000".to_string()),
+        ]
+        .into_iter()
+        .collect(),
+    };
+
+    channel
+        .compute(ctx.clone(), ComputeInput::Store { spec: input_spec })
+        .await
+        .unwrap();
+
+    let result = channel
+        .compute(ctx.clone(), ComputeInput::Run { spec: job_spec })
+        .await;
+
+    assert!(matches!(result, Err(Error::JobCpuExceeded { .. })));
+}
+
 async fn internal_run_wasm_with_deps() {
     let channel = make_channel().await;
     let mut ctx = ChannelContext::default();
@@ -542,6 +599,13 @@ fn run_mugen_loop() {
     });
 }
 
+#[test]
+fn run_mugen_loop_with_cpu_budget() {
+    graceless_async_test!({
+        internal_run_mugen_loop_with_cpu_budget().await;
+    });
+}
+
 #[test]
 fn run_wasi_hello_world() {
     graceless_async_test!({
@@ -0,0 +1,125 @@
+use anyhow::bail;
+use musubi_api::types::{Data, HashableValue, Value};
+
+/// Builds the musubi input of a job from a JSON array, one value per element.
+pub fn to_data(input: serde_json::Value) -> anyhow::Result<Data> {
+    let serde_json::Value::Array(values) = input else {
+        bail!("expected the job input to be a JSON array");
+    };
+
+    let mut builder = musubi_api::DataBuilder::new();
+
+    for value in values {
+        builder = builder.add(to_value(value)?);
+    }
+
+    Ok(builder.build())
+}
+
+/// Renders the musubi output of a job as a JSON array, one element per value.
+pub fn from_data(data: &Data) -> serde_json::Value {
+    serde_json::Value::Array(data.values().iter().map(from_value).collect())
+}
+
+pub fn to_value(value: serde_json::Value) -> anyhow::Result<Value> {
+    let value = match value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(x) => Value::Bool(x),
+        serde_json::Value::Number(x) => match x.as_u64() {
+            Some(x) => Value::U64(x),
+            None => bail!(
+                "unsupported number '{}', only unsigned integers are allowed",
+                x
+            ),
+        },
+        serde_json::Value::String(x) => Value::String(x),
+        serde_json::Value::Array(x) => {
+            Value::Array(x.into_iter().map(to_value).collect::<anyhow::Result<_>>()?)
+        }
+        serde_json::Value::Object(x) => Value::Map(
+            x.into_iter()
+                .map(|(key, value)| Ok((HashableValue::String(key), to_value(value)?)))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+    };
+
+    Ok(value)
+}
+
+pub fn from_value(value: &Value) -> serde_json::Value {
+    // Bytes are rendered as an array of numbers, errors as an object under the `error` key.
+    #[allow(unreachable_patterns)]
+    match value {
+        Value::Null => serde_json::Value::Null,
+        Value::Bool(x) => serde_json::Value::Bool(*x),
+        Value::U64(x) => serde_json::Value::from(*x),
+        Value::String(x) => serde_json::Value::String(x.clone()),
+        Value::Bytes(x) => serde_json::Value::from(x.clone()),
+        Value::Array(x) => serde_json::Value::Array(x.iter().map(from_value).collect()),
+        Value::Map(x) => serde_json::Value::Object(
+            x.iter()
+                .map(|(key, value)| {
+                    let key = match key {
+                        HashableValue::String(key) => key.clone(),
+                        other => format!("{:?}", other),
+                    };
+
+                    (key, from_value(value))
+                })
+                .collect(),
+        ),
+        Value::Error { code, message } => serde_json::json!({
+            "error": {
+                "code": code,
+                "message": message,
+            }
+        }),
+        other => serde_json::Value::String(format!("{:?}", other)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use musubi_api::types::Value;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let input = json!([
+            null,
+            true,
+            42,
+            "spec1",
+            [1, 2, 3],
+            { "a": { "b": [false, "c"] } }
+        ]);
+
+        let data = to_data(input.clone()).unwrap();
+
+        assert_eq!(data.values().len(), 6);
+        assert_eq!(from_data(&data), input);
+    }
+
+    #[test]
+    fn test_reject_unsupported_input() {
+        assert!(to_data(json!({ "a": 1 })).is_err());
+        assert!(to_data(json!([-1])).is_err());
+        assert!(to_data(json!([1.5])).is_err());
+        assert!(to_data(json!([[{ "a": -1 }]])).is_err());
+    }
+
+    #[test]
+    fn test_render_bytes_and_errors() {
+        assert_eq!(from_value(&Value::Bytes(vec![1, 2])), json!([1, 2]));
+
+        assert_eq!(
+            from_value(&Value::Error {
+                code: "mitsuha.test".to_string(),
+                message: "failed".to_string(),
+            }),
+            json!({ "error": { "code": "mitsuha.test", "message": "failed" } })
+        );
+    }
+}
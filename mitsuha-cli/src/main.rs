@@ -0,0 +1,33 @@
+mod json;
+mod run;
+
+use clap::{Parser, Subcommand};
+
+/// Runs mitsuha jobs in-process, without persistence, configuration or a running server.
+#[derive(Parser)]
+#[command(name = "mitsuha", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    Run(run::RunArgs),
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+
+    let cli = Cli::parse();
+
+    let result = match cli.command {
+        Command::Run(args) => run::run(args).await,
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {:#}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,350 @@
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, bail, Context};
+use clap::{Args, ValueEnum};
+use mitsuha_channel::{
+    labeled_storage::LabeledStorageChannel,
+    system::SystemChannel,
+    wasmtime::{WasmtimeChannel, WasmtimeChannelDependencies},
+    EntrypointChannel,
+};
+use mitsuha_core::{
+    channel::{ChannelContext, ChannelManager, ComputeChannel, ComputeKernel},
+    config,
+    constants::Constants,
+    job::{
+        cost::{JobCost, StandardJobCostEvaluator},
+        mgr::JobManager,
+    },
+    kernel::Kernel,
+    native::NativeModuleRegistry,
+    selector::Label,
+    storage::{StorageClass, StorageKind, StorageLocality},
+};
+use mitsuha_core_types::{
+    channel::{ComputeInput, ComputeOutput},
+    kernel::{JobSpec, StorageSpec},
+    module::{ModuleInfo, ModuleType},
+    symbol::Symbol,
+};
+use mitsuha_storage::UnifiedStorage;
+use mitsuha_wasm_runtime::wasmtime::WasmtimeConfig;
+use musubi_api::types::Data;
+
+use crate::json;
+
+type Channel = Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>;
+
+const STORAGE_LABEL_KEY: &str = "storage";
+const STORAGE_LABEL_VALUE: &str = "local";
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum StorageBackend {
+    /// Keep all data in memory, it is discarded when the run ends.
+    Memory,
+
+    /// Keep all data in `--storage-dir`.
+    Local,
+}
+
+/// Runs a symbol exported by a WASM module and prints its output as JSON.
+#[derive(Args)]
+pub struct RunArgs {
+    /// Module to run, as `<name>@<version>`.
+    module: String,
+
+    /// Exported function to call.
+    symbol: String,
+
+    /// Directory containing modules laid out as `<name>/<version>.wasm`.
+    #[arg(short, long, default_value = ".")]
+    modules: PathBuf,
+
+    /// JSON array holding the input values, `-` reads it from stdin.
+    #[arg(short, long, default_value = "-")]
+    input: String,
+
+    #[arg(long, value_enum, default_value_t = StorageBackend::Memory)]
+    storage: StorageBackend,
+
+    /// Root directory of the local storage backend.
+    #[arg(long, required_if_eq("storage", "local"))]
+    storage_dir: Option<PathBuf>,
+
+    /// Time to live of the job and its output, in seconds.
+    #[arg(long, default_value_t = 3600)]
+    ttl: u64,
+
+    /// Job spec extension as `<key>=<value>`, may be repeated.
+    #[arg(short, long = "extension", value_parser = parse_key_value)]
+    extensions: Vec<(String, String)>,
+
+    /// Wasmtime channel property as `<key>=<value>`, may be repeated.
+    #[arg(short, long = "property", value_parser = parse_key_value)]
+    properties: Vec<(String, String)>,
+}
+
+pub async fn run(args: RunArgs) -> anyhow::Result<()> {
+    let module_info = parse_module(&args.module)?;
+    let input = read_input(&args.input)?;
+
+    let channel = make_channel(&args).await?;
+
+    let mut ctx = ChannelContext::default();
+    ctx.set_channel_start(channel.clone());
+
+    upload_modules(&channel, &ctx, &args.modules, args.ttl).await?;
+
+    let run_id = uuid::Uuid::new_v4().to_string();
+    let input_handle = format!("mitsuha.cli.{}.input", run_id);
+    let output_handle = format!("mitsuha.cli.{}.output", run_id);
+
+    channel
+        .compute(
+            ctx.clone(),
+            ComputeInput::Store {
+                spec: StorageSpec {
+                    handle: input_handle.clone(),
+                    data: input.try_into().map_err(|e| anyhow!("{}", e))?,
+                    ttl: args.ttl,
+                    extensions: Default::default(),
+                },
+            },
+        )
+        .await?;
+
+    let mut extensions: HashMap<String, String> = args.extensions.into_iter().collect();
+
+    extensions.insert(Constants::JobOutputTTL.to_string(), args.ttl.to_string());
+    extensions.insert(Constants::JobChannelAwait.to_string(), "true".to_string());
+
+    let spec = JobSpec {
+        handle: format!("mitsuha.cli.{}.job", run_id),
+        symbol: Symbol {
+            name: args.symbol,
+            module_info,
+        },
+        ttl: args.ttl,
+        input_handle,
+        output_handle: output_handle.clone(),
+        extensions,
+    };
+
+    channel
+        .compute(ctx.clone(), ComputeInput::Run { spec })
+        .await?;
+
+    let output = channel
+        .compute(
+            ctx,
+            ComputeInput::Load {
+                handle: output_handle,
+                extensions: Default::default(),
+            },
+        )
+        .await?;
+
+    let ComputeOutput::Loaded { data } = output else {
+        bail!("expected the job output to be loaded");
+    };
+
+    let data = Data::try_from(data).map_err(|e| anyhow!("{}", e))?;
+
+    println!("{}", serde_json::to_string_pretty(&json::from_data(&data))?);
+
+    Ok(())
+}
+
+/// Wires up the same chain the integration tests use, with nothing outside the process.
+async fn make_channel(args: &RunArgs) -> anyhow::Result<Channel> {
+    let entrypoint_channel: Channel = Arc::new(Box::new(EntrypointChannel::new()));
+
+    let system_channel: Channel = Arc::new(Box::new(
+        SystemChannel::new().with_id("system-0".to_string()),
+    ));
+
+    let storage = UnifiedStorage::new(&make_storage_config(args)?).await?;
+
+    let labeled_storage_channel: Channel = Arc::new(Box::new(
+        LabeledStorageChannel::new(
+            storage,
+            Label {
+                key: STORAGE_LABEL_KEY.to_string(),
+                value: STORAGE_LABEL_VALUE.to_string(),
+            },
+        )
+        .with_id("store-0".to_string()),
+    ));
+
+    let kernel: Arc<Box<dyn Kernel>> =
+        Arc::new(Box::new(ComputeKernel::new(entrypoint_channel.clone())));
+
+    let config = WasmtimeConfig::from_properties(&args.properties.iter().cloned().collect())?;
+
+    let dependencies = WasmtimeChannelDependencies {
+        native_modules: if config.native_modules {
+            NativeModuleRegistry::builtin(config.native_max_output_size)?
+        } else {
+            Default::default()
+        },
+        ..Default::default()
+    };
+
+    let wasmtime_channel: Channel = Arc::new(Box::new(
+        WasmtimeChannel::new_with_config(kernel, config, dependencies)?
+            .with_id("wasmtime-0".to_string()),
+    ));
+
+    labeled_storage_channel.connect(wasmtime_channel).await;
+    system_channel.connect(labeled_storage_channel).await;
+    entrypoint_channel.connect(system_channel).await;
+
+    let job_manager = JobManager::new(
+        entrypoint_channel.clone(),
+        Arc::new(Box::new(ChannelContext::default())),
+        JobCost { compute: u64::MAX },
+        Arc::new(Box::new(StandardJobCostEvaluator)),
+        "mitsuha-cli".to_string(),
+    )?;
+
+    ChannelManager::global_rw().write().await.job_manager = Some(job_manager);
+    ChannelManager::global_rw().write().await.channel_start = Some(entrypoint_channel.clone());
+
+    Ok(entrypoint_channel)
+}
+
+fn make_storage_config(args: &RunArgs) -> anyhow::Result<config::storage::Storage> {
+    let (kind, properties) = match args.storage {
+        StorageBackend::Memory => (StorageKind::Memory, HashMap::new()),
+        StorageBackend::Local => {
+            let root_dir = args
+                .storage_dir
+                .as_ref()
+                .ok_or(anyhow!("--storage-dir is required for local storage"))?;
+
+            std::fs::create_dir_all(root_dir)
+                .with_context(|| format!("failed to create '{}'", root_dir.display()))?;
+
+            let properties = [
+                ("root_dir".to_string(), root_dir.display().to_string()),
+                ("enable_gc".to_string(), "false".to_string()),
+            ]
+            .into_iter()
+            .collect();
+
+            (StorageKind::Local, properties)
+        }
+    };
+
+    Ok(config::storage::Storage {
+        classes: vec![StorageClass {
+            kind,
+            locality: StorageLocality::Solid { cache_name: None },
+            name: "cli_storage".to_string(),
+            labels: vec![Label {
+                key: STORAGE_LABEL_KEY.to_string(),
+                value: STORAGE_LABEL_VALUE.to_string(),
+            }],
+            properties,
+        }],
+    })
+}
+
+/// Stores every `<name>/<version>.wasm` file below `dir` under the identifier of its module,
+/// where the [mitsuha_core::resolver::blob::BlobResolver] looks for it.
+async fn upload_modules(
+    channel: &Channel,
+    ctx: &ChannelContext,
+    dir: &Path,
+    ttl: u64,
+) -> anyhow::Result<()> {
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("failed to read module directory '{}'", dir.display()))?;
+
+    for entry in entries {
+        let module_dir = entry?.path();
+
+        if !module_dir.is_dir() {
+            continue;
+        }
+
+        let Some(name) = module_dir.file_name().and_then(|x| x.to_str()) else {
+            continue;
+        };
+
+        for entry in std::fs::read_dir(&module_dir)? {
+            let path = entry?.path();
+
+            if path.extension().and_then(|x| x.to_str()) != Some("wasm") {
+                continue;
+            }
+
+            let Some(version) = path.file_stem().and_then(|x| x.to_str()) else {
+                continue;
+            };
+
+            let module_info = ModuleInfo {
+                name: name.to_string(),
+                version: version.to_string(),
+                modtype: ModuleType::WASM,
+            };
+
+            let data = std::fs::read(&path)
+                .with_context(|| format!("failed to read module '{}'", path.display()))?;
+
+            channel
+                .compute(
+                    ctx.clone(),
+                    ComputeInput::Store {
+                        spec: StorageSpec {
+                            handle: module_info.get_identifier(),
+                            data,
+                            ttl,
+                            extensions: Default::default(),
+                        },
+                    },
+                )
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_module(value: &str) -> anyhow::Result<ModuleInfo> {
+    let Some((name, version)) = value.split_once('@') else {
+        bail!("expected module as '<name>@<version>', found '{}'", value);
+    };
+
+    Ok(ModuleInfo {
+        name: name.to_string(),
+        version: version.to_string(),
+        modtype: ModuleType::WASM,
+    })
+}
+
+fn read_input(path: &str) -> anyhow::Result<Data> {
+    let raw = if path == "-" {
+        let mut raw = String::new();
+        std::io::stdin()
+            .read_to_string(&mut raw)
+            .context("failed to read input from stdin")?;
+        raw
+    } else {
+        std::fs::read_to_string(path).with_context(|| format!("failed to read input '{}'", path))?
+    };
+
+    json::to_data(serde_json::from_str(&raw).context("input is not valid JSON")?)
+}
+
+fn parse_key_value(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or(format!("expected '<key>=<value>', found '{}'", value))
+}
@@ -0,0 +1,161 @@
+use std::{collections::HashMap, path::Path};
+
+use mitsuha_core_types::symbol::Symbol;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constants::Constants,
+    errors::{Error, ToUnknownErrorResult},
+    types,
+};
+
+/// Capabilities granted to a module, as declared in its manifest and narrowed per job.
+///
+/// Each field is an allow-list, `None` leaves the capability unrestricted while an empty list
+/// denies it entirely.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// Absolute filesystem paths (and everything below them) visible through WASI.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fs: Option<Vec<String>>,
+
+    /// Functions of the `mitsuha.core` kernel module, such as `load` or `run`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kernel: Option<Vec<String>>,
+
+    /// Names of the modules which may be imported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modules: Option<Vec<String>>,
+}
+
+impl Capabilities {
+    pub fn unrestricted() -> Self {
+        Default::default()
+    }
+
+    pub fn is_unrestricted(&self) -> bool {
+        self.fs.is_none() && self.kernel.is_none() && self.modules.is_none()
+    }
+
+    pub fn parse(raw: &[u8]) -> types::Result<Self> {
+        serde_json::from_slice(raw).to_unknown_err_result()
+    }
+
+    /// Reads the capabilities stored under [Constants::JobCapabilities], if any.
+    pub fn from_extensions(extensions: &HashMap<String, String>) -> types::Result<Option<Self>> {
+        match extensions.get(&Constants::JobCapabilities.to_string()) {
+            Some(value) => Ok(Some(serde_json::from_str(value).to_unknown_err_result()?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn to_extension(&self) -> (String, String) {
+        (
+            Constants::JobCapabilities.to_string(),
+            serde_json::to_string(self).unwrap(),
+        )
+    }
+
+    /// Returns the capabilities granted by both `self` and `other`.
+    pub fn narrow(&self, other: &Capabilities) -> Self {
+        Self {
+            fs: match (&self.fs, &other.fs) {
+                (Some(a), Some(b)) => Some(
+                    a.iter()
+                        .filter(|x| is_path_allowed(b, x))
+                        .chain(b.iter().filter(|x| is_path_allowed(a, x)))
+                        .cloned()
+                        .collect(),
+                ),
+                (a, b) => a.clone().or(b.clone()),
+            },
+            kernel: intersect(&self.kernel, &other.kernel),
+            modules: intersect(&self.modules, &other.modules),
+        }
+    }
+
+    pub fn allows_path(&self, path: &Path) -> bool {
+        match &self.fs {
+            Some(paths) => paths.iter().any(|x| path.starts_with(x)),
+            None => true,
+        }
+    }
+
+    pub fn allows_kernel_call(&self, function: &str) -> bool {
+        match &self.kernel {
+            Some(functions) => functions.iter().any(|x| x == function),
+            None => true,
+        }
+    }
+
+    pub fn allows_module(&self, name: &str) -> bool {
+        match &self.modules {
+            Some(modules) => modules.iter().any(|x| x == name),
+            None => true,
+        }
+    }
+
+    /// Checks a call to `symbol`, which is either a kernel call or a call into another module.
+    pub fn check_symbol(&self, symbol: &Symbol) -> types::Result<()> {
+        if symbol.module_info.name == Constants::CoreModuleName.to_string() {
+            if !self.allows_kernel_call(&symbol.name) {
+                return Err(Error::CapabilityNotGranted {
+                    capability: "kernel".to_string(),
+                    target: symbol.name.clone(),
+                });
+            }
+        } else if !self.allows_module(&symbol.module_info.name) {
+            return Err(Error::CapabilityNotGranted {
+                capability: "modules".to_string(),
+                target: symbol.module_info.name.clone(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+fn is_path_allowed(paths: &[String], path: &str) -> bool {
+    paths.iter().any(|x| Path::new(path).starts_with(x))
+}
+
+fn intersect(a: &Option<Vec<String>>, b: &Option<Vec<String>>) -> Option<Vec<String>> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.iter().filter(|x| b.contains(x)).cloned().collect()),
+        (a, b) => a.clone().or(b.clone()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::Capabilities;
+
+    #[test]
+    fn test_narrow() {
+        let manifest = Capabilities::parse(
+            br#"{"fs": ["/data"], "kernel": ["load", "store"], "modules": ["mitsuha.native.json"]}"#,
+        )
+        .unwrap();
+
+        let job = Capabilities {
+            fs: Some(vec!["/data/in".to_string(), "/tmp".to_string()]),
+            kernel: Some(vec!["load".to_string(), "run".to_string()]),
+            modules: None,
+        };
+
+        let capabilities = manifest.narrow(&job);
+
+        assert!(capabilities.allows_path(Path::new("/data/in/a.txt")));
+        assert!(!capabilities.allows_path(Path::new("/data/out/a.txt")));
+        assert!(!capabilities.allows_path(Path::new("/tmp/a.txt")));
+        assert!(capabilities.allows_kernel_call("load"));
+        assert!(!capabilities.allows_kernel_call("store"));
+        assert!(!capabilities.allows_kernel_call("run"));
+        assert!(capabilities.allows_module("mitsuha.native.json"));
+        assert!(!capabilities.allows_module("mitsuha.native.hash"));
+
+        assert_eq!(Capabilities::unrestricted().narrow(&job), job);
+    }
+}
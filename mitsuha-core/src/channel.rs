@@ -447,11 +447,14 @@ lazy_static! {
         Arc::new(RwLock::new(ChannelManager::default()));
 }
 
+/// Channels of the chain by id, used to resolve channels referenced by others.
+pub type ChannelMap = DashMap<String, Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>>;
+
 #[derive(Clone)]
 pub struct ChannelManager {
     pub job_manager: Option<JobManager<ChannelContext>>,
     pub channel_start: Option<Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>>,
-    pub channel_map: Arc<DashMap<String, Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>>>,
+    pub channel_map: Arc<ChannelMap>,
     pub signature: String,
 }
 
@@ -3,6 +3,7 @@ mod instance;
 mod job;
 mod persistence;
 pub mod plugin;
+pub mod registry;
 pub mod storage;
 pub mod telemetry;
 
@@ -20,7 +21,9 @@ use std::{
 };
 use tokio::sync::RwLock;
 
-use self::{api::Api, plugin::Plugin, storage::Storage, telemetry::Telemetry};
+use self::{
+    api::Api, plugin::Plugin, registry::Registry, storage::Storage, telemetry::Telemetry,
+};
 
 #[derive(Debug, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -32,6 +35,9 @@ pub struct Config {
     pub plugins: Vec<Plugin>,
     pub telemetry: Telemetry,
     pub persistence: Persistence,
+
+    #[serde(default)]
+    pub registry: Option<Registry>,
 }
 
 lazy_static! {
@@ -114,8 +120,13 @@ impl Config {
             let config = GLOBAL_CONFIG.clone();
 
             tokio::task::spawn(async move {
-                *config.write().await = Config::new().ok();
-                tokio::time::sleep(Duration::from_secs(1)).await;
+                loop {
+                    if let Ok(value) = Config::new() {
+                        *config.write().await = Some(value);
+                    }
+
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
             });
         });
     }
@@ -0,0 +1,34 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::selector::Label;
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Registry {
+    /// Selects the storage class holding published module binaries.
+    pub storage_selector: Label,
+
+    #[serde(default = "Registry::default_storage_ttl")]
+    pub storage_ttl: u64,
+
+    #[serde(default)]
+    pub verify_signatures: bool,
+
+    /// Publisher names mapped to hex encoded ed25519 public keys.
+    #[serde(default)]
+    pub trusted_publishers: HashMap<String, String>,
+
+    /// Bearer tokens allowed to upload, yank and deprecate module versions. Nothing can be
+    /// changed through the http api if empty.
+    #[serde(default)]
+    pub api_tokens: Vec<String>,
+}
+
+impl Registry {
+    fn default_storage_ttl() -> u64 {
+        // Published modules are immutable and should outlive any job referencing them.
+        10 * 365 * 86400
+    }
+}
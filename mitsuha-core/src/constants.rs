@@ -33,6 +33,33 @@ pub enum Constants {
     #[strum(serialize = "mitsuha.job.status.last_updated")]
     JobStatusLastUpdated,
 
+    #[strum(serialize = "mitsuha.job.status.reason")]
+    JobStatusReason,
+
+    #[strum(serialize = "mitsuha.job.status.trap")]
+    JobStatusTrap,
+
+    #[strum(serialize = "mitsuha.job.coredump.handle")]
+    JobCoreDumpHandle,
+
+    #[strum(serialize = "mitsuha.job.profile.handle")]
+    JobProfileHandle,
+
+    #[strum(serialize = "mitsuha.job.cpu.budget")]
+    JobCpuBudget,
+
+    #[strum(serialize = "mitsuha.job.capabilities")]
+    JobCapabilities,
+
+    #[strum(serialize = "mitsuha.job.identity")]
+    JobIdentity,
+
+    #[strum(serialize = "mitsuha.job.policy.blob")]
+    JobPolicyBlob,
+
+    #[strum(serialize = "mitsuha.job.parent.policy.blob")]
+    JobParentPolicyBlob,
+
     #[strum(serialize = "mitsuha.channel.skiplist")]
     ChannelSkipList,
 
@@ -45,6 +72,12 @@ pub enum Constants {
     #[strum(serialize = "mitsuha.channel.original.handle")]
     OriginalHandle,
 
+    #[strum(serialize = "mitsuha.channel.quota.job.handle")]
+    ChannelQuotaJobHandle,
+
+    #[strum(serialize = "mitsuha.channel.remote.forwarded")]
+    ChannelRemoteForwarded,
+
     #[strum(serialize = "channel_id")]
     ChannelId,
 
@@ -53,4 +86,24 @@ pub enum Constants {
 
     #[strum(serialize = "mitsuha.scheduler.computeinput.queued")]
     SchedulerComputeInputQueued,
+
+    #[strum(serialize = "mitsuha.core")]
+    CoreModuleName,
+}
+
+/// Why a job stopped before completing, recorded under [Constants::JobStatusReason] in the
+/// job status extensions.
+#[derive(strum_macros::Display)]
+pub enum JobStatusReason {
+    #[strum(serialize = "aborted")]
+    Aborted,
+
+    #[strum(serialize = "expired")]
+    Expired,
+
+    #[strum(serialize = "cpu_exceeded")]
+    CpuExceeded,
+
+    #[strum(serialize = "trapped")]
+    Trapped,
 }
@@ -1,5 +1,7 @@
 use mitsuha_core_types::{module::ModuleInfo, symbol::Symbol};
 
+use crate::trap::GuestTrap;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     // symbol errors
@@ -51,6 +53,32 @@ pub enum Error {
         source: anyhow::Error,
     },
 
+    // registry errors
+    #[error(
+        "could not resolve version requirement '{requirement}' for module '{name}', {message}"
+    )]
+    ModuleVersionResolutionFailed {
+        name: String,
+        requirement: String,
+        message: String,
+    },
+
+    #[error(
+        "integrity check failed for {target:?}, expected digest '{expected}', found '{actual}'"
+    )]
+    ModuleIntegrityCheckFailed {
+        target: ModuleInfo,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("signature verification failed for module '{name}@{version}', {reason}")]
+    ModuleSignatureVerificationFailed {
+        name: String,
+        version: String,
+        reason: String,
+    },
+
     // linker errors
     #[error("failed to load module during linking, target: {target:?}, {message}")]
     LinkerLoadFailed {
@@ -70,6 +98,9 @@ pub enum Error {
         source: anyhow::Error,
     },
 
+    #[error("capability '{capability}' was not granted for '{target}'")]
+    CapabilityNotGranted { capability: String, target: String },
+
     // executor errors
     #[error("execution failed, {message}")]
     ExecutorRunFailed {
@@ -173,6 +204,15 @@ pub enum Error {
     #[error("job with handle '{handle}' was aborted")]
     JobAborted { handle: String },
 
+    #[error("job with handle '{handle}' exceeded its cpu time budget of {budget}")]
+    JobCpuExceeded { handle: String, budget: String },
+
+    #[error("job with handle '{handle}' trapped, {}", trap.message)]
+    JobTrapped {
+        handle: String,
+        trap: Box<GuestTrap>,
+    },
+
     // Compute channel errors
     #[error("reached compute channel EOF")]
     ComputeChannelEOF,
@@ -180,6 +220,26 @@ pub enum Error {
     #[error("unsupported operation {op}")]
     UnsupportedOperation { op: String },
 
+    #[error("rate limit exceeded for '{key}', retry after {retry_after_ms}ms")]
+    RateLimitExceeded { key: String, retry_after_ms: u64 },
+
+    #[error("service '{target}' is unavailable, {message}")]
+    ServiceUnavailable { target: String, message: String },
+
+    #[error("circuit open for channel '{target}', retry after {retry_after_ms}ms")]
+    CircuitOpen { target: String, retry_after_ms: u64 },
+
+    #[error("rejected by interceptor '{interceptor}', {reason}")]
+    InterceptorRejected { interceptor: String, reason: String },
+
+    #[error("quota exceeded for namespace '{namespace}', {resource} would reach {requested} out of {limit}")]
+    QuotaExceeded {
+        namespace: String,
+        resource: String,
+        requested: u64,
+        limit: u64,
+    },
+
     #[error("error occured at the persistence layer, details: {source}")]
     PersistenceLayerError {
         #[from]
@@ -207,6 +267,22 @@ pub enum Error {
     UnknownWithMsgOnly { message: String },
 }
 
+impl Error {
+    /// Whether the error is caused by a service or backend being temporarily unreachable, so
+    /// that the operation may succeed if retried. Errors which may as well be permanent, such
+    /// as missing blobs, are not transient.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::ServiceUnavailable { .. } => true,
+            Error::PersistenceLayerError { source } => matches!(
+                source,
+                sea_orm::DbErr::Conn(_) | sea_orm::DbErr::ConnectionAcquire(_)
+            ),
+            _ => false,
+        }
+    }
+}
+
 pub trait ToUnknownErrorResult<T> {
     fn to_unknown_err_result(self) -> Result<T, Error>;
 }
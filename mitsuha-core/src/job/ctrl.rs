@@ -1,9 +1,9 @@
 use crate::channel::{ComputeChannel, StateProvider};
-use crate::constants::Constants;
+use crate::constants::{Constants, JobStatusReason};
 use crate::errors::{Error, ToUnknownErrorResult};
 use crate::job::ctx::JobState;
 use crate::job::mgr::JobManagerProvider;
-use crate::types;
+use crate::types::{self, Extensions};
 use anyhow::anyhow;
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
@@ -96,6 +96,8 @@ where
         channel: Arc<Box<dyn ComputeChannel<Context = Context>>>,
         channel_context: &Context,
         status_type: JobStatusType,
+        reason: Option<JobStatusReason>,
+        details: Extensions,
         current_time: DateTime<Utc>,
     ) -> types::Result<()> {
         tracing::debug!(
@@ -104,7 +106,7 @@ where
             status_type
         );
 
-        let status = JobStatus {
+        let mut status = JobStatus {
             status: status_type,
             extensions: [(
                 Constants::JobStatusLastUpdated.to_string(),
@@ -114,6 +116,14 @@ where
             .collect(),
         };
 
+        if let Some(reason) = reason {
+            status
+                .extensions
+                .insert(Constants::JobStatusReason.to_string(), reason.to_string());
+        }
+
+        status.extensions.extend(details);
+
         let status_data = musubi_api::types::to_value(&status)
             .to_unknown_err_result()?
             .try_into()
@@ -188,6 +198,8 @@ where
                             JobStatusType::ExpiredAt {
                                 datetime: x.clone(),
                             },
+                            Some(JobStatusReason::Expired),
+                            Default::default(),
                             current_time,
                         )
                         .await?;
@@ -216,6 +228,8 @@ where
                             self.channel.clone(),
                             &self.channel_context,
                             JobStatusType::Aborted,
+                            Some(JobStatusReason::Aborted),
+                            Default::default(),
                             current_time,
                         )
                         .await?;
@@ -227,10 +241,16 @@ where
                     JobState::Completed => {
                         let result = observable_task.await;
 
+                        let final_state = match &result {
+                            Ok(Err(Error::JobCpuExceeded { .. })) => JobState::CpuExceeded,
+                            Ok(Err(Error::JobTrapped { .. })) => JobState::Trapped,
+                            _ => JobState::Completed,
+                        };
+
                         Self::run_post_job_hooks(ctx, &post_job_hooks).await;
                         ctx.get_job_mgr().await.dequeue_job(&handle).await?;
 
-                        match status_updater.send(JobState::Completed).await {
+                        match status_updater.send(final_state).await {
                             Ok(_) => {
                                 tracing::debug!(
                                     "status_updater triggered for job '{}'!",
@@ -246,6 +266,49 @@ where
                             }
                         }
 
+                        // JobStatusType has no dedicated variants, the reason tells these
+                        // apart from a regular abort.
+                        match &result {
+                            Ok(Err(Error::JobCpuExceeded { .. })) => {
+                                Self::update_status(
+                                    &self.spec,
+                                    self.channel.clone(),
+                                    &self.channel_context,
+                                    JobStatusType::Aborted,
+                                    Some(JobStatusReason::CpuExceeded),
+                                    Default::default(),
+                                    current_time,
+                                )
+                                .await?;
+
+                                tracing::info!(
+                                    "job with handle '{}' exceeded its cpu time budget",
+                                    &handle
+                                );
+                            }
+                            Ok(Err(Error::JobTrapped { trap, .. })) => {
+                                let details = trap.to_status_details()?;
+
+                                Self::update_status(
+                                    &self.spec,
+                                    self.channel.clone(),
+                                    &self.channel_context,
+                                    JobStatusType::Aborted,
+                                    Some(JobStatusReason::Trapped),
+                                    details,
+                                    current_time,
+                                )
+                                .await?;
+
+                                tracing::info!(
+                                    "job with handle '{}' trapped, {}",
+                                    &handle,
+                                    trap.message
+                                );
+                            }
+                            _ => {}
+                        }
+
                         result.to_unknown_err_result()??;
 
                         Self::update_status(
@@ -253,6 +316,8 @@ where
                             self.channel.clone(),
                             &self.channel_context,
                             JobStatusType::Completed,
+                            None,
+                            Default::default(),
                             current_time,
                         )
                         .await?;
@@ -281,6 +346,8 @@ where
                                     self.channel.clone(),
                                     &self.channel_context,
                                     JobStatusType::Running,
+                                    None,
+                                    Default::default(),
                                     current_time,
                                 )
                                 .await?;
@@ -295,6 +362,8 @@ where
                                     self.channel.clone(),
                                     &self.channel_context,
                                     JobStatusType::Running,
+                                    None,
+                                    Default::default(),
                                     current_time,
                                 )
                                 .await?;
@@ -7,6 +7,8 @@ use tokio::sync::mpsc::{Receiver, Sender};
 pub enum JobState {
     Completed,
     Aborted,
+    CpuExceeded,
+    Trapped,
     ExpireAt(DateTime<Utc>),
 }
 
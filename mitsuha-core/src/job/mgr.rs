@@ -1,6 +1,6 @@
 use crate::channel::{ComputeChannel, StateProvider};
 use crate::config::Config;
-use crate::constants::Constants;
+use crate::constants::{Constants, JobStatusReason};
 use crate::errors::{Error, ToUnknownErrorResult};
 use crate::job::cost::{JobCost, JobCostEvaluator};
 use crate::job::ctrl::{JobController, PostJobHook};
@@ -120,16 +120,21 @@ where
                 // Get the observed state of the job
                 let obj = ctx.get_state().unwrap();
 
-                let job_status_type = match obj {
-                    JobState::Aborted => JobStatusType::Aborted,
-                    JobState::Completed => JobStatusType::Completed,
-                    JobState::ExpireAt(x) if x <= Utc::now() => {
-                        JobStatusType::ExpiredAt { datetime: x }
+                let (job_status_type, reason) = match obj {
+                    JobState::Aborted => (JobStatusType::Aborted, Some(JobStatusReason::Aborted)),
+                    JobState::CpuExceeded => {
+                        (JobStatusType::Aborted, Some(JobStatusReason::CpuExceeded))
                     }
-                    _ => JobStatusType::Running,
+                    JobState::Trapped => (JobStatusType::Aborted, Some(JobStatusReason::Trapped)),
+                    JobState::Completed => (JobStatusType::Completed, None),
+                    JobState::ExpireAt(x) if x <= Utc::now() => (
+                        JobStatusType::ExpiredAt { datetime: x },
+                        Some(JobStatusReason::Expired),
+                    ),
+                    _ => (JobStatusType::Running, None),
                 };
 
-                Ok(JobStatus {
+                let mut status = JobStatus {
                     status: job_status_type,
                     extensions: [(
                         Constants::JobStatusLastUpdated.to_string(),
@@ -137,7 +142,15 @@ where
                     )]
                     .into_iter()
                     .collect(),
-                })
+                };
+
+                if let Some(reason) = reason {
+                    status
+                        .extensions
+                        .insert(Constants::JobStatusReason.to_string(), reason.to_string());
+                }
+
+                Ok(status)
             }
             None => Err(Error::JobNotFound {
                 handle: handle.clone(),
@@ -284,6 +297,15 @@ where
         self.post_job_hooks.write().await.push(hook);
     }
 
+    /// Replaces the post job hooks injected into jobs started from now on and returns the
+    /// previous ones. Running jobs keep the hooks they were started with.
+    pub async fn replace_post_job_hooks(
+        &self,
+        hooks: Vec<Arc<dyn PostJobHook<Context>>>,
+    ) -> Vec<Arc<dyn PostJobHook<Context>>> {
+        std::mem::replace(&mut *self.post_job_hooks.write().await, hooks)
+    }
+
     pub async fn inject_post_job_hooks(&self, ctrl: &mut JobController<Context>) {
         self.post_job_hooks
             .read()
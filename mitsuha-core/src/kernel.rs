@@ -1,4 +1,5 @@
 use crate::{
+    capability::Capabilities,
     constants::{Constants, StorageControlConstants},
     errors::Error,
     selector::Label,
@@ -32,6 +33,14 @@ pub trait JobSpecExt {
     fn make_kernel_bridge_metadata(&self) -> types::Result<KernelBridgeMetadata>;
 
     fn load_kernel_bridge_metadata(&mut self, metadata: &KernelBridgeMetadata);
+
+    /// Extensions identifying the job (namespace, policy and identity), which are attached to
+    /// every kernel call the job makes.
+    fn get_job_scope(&self) -> HashMap<String, String>;
+
+    fn get_capabilities(&self) -> types::Result<Capabilities>;
+
+    fn narrow_capabilities(&mut self, capabilities: &Capabilities) -> types::Result<()>;
 }
 
 impl JobSpecExt for JobSpec {
@@ -64,6 +73,7 @@ impl JobSpecExt for JobSpec {
             job_ttl: self.ttl,
             job_output_ttl: self.get_output_ttl()?,
             job_start_time: Utc::now(),
+            job_scope: self.get_job_scope(),
             extensions: Default::default(),
         };
 
@@ -81,6 +91,50 @@ impl JobSpecExt for JobSpec {
             metadata.job_output_ttl.to_string(),
         );
     }
+
+    fn get_job_scope(&self) -> HashMap<String, String> {
+        let mut scope = HashMap::new();
+
+        if let Some(namespace) = self
+            .extensions
+            .get(&Constants::ChannelNamespace.to_string())
+        {
+            scope.insert(Constants::ChannelNamespace.to_string(), namespace.clone());
+        }
+
+        if let Some(policy_blob) = self.extensions.get(&Constants::JobPolicyBlob.to_string()) {
+            scope.insert(
+                Constants::JobParentPolicyBlob.to_string(),
+                policy_blob.clone(),
+            );
+        }
+
+        let identity = self
+            .extensions
+            .get(&Constants::JobIdentity.to_string())
+            .or(self.extensions.get(&Constants::OriginalHandle.to_string()))
+            .cloned()
+            .unwrap_or(self.handle.clone());
+
+        scope.insert(Constants::JobIdentity.to_string(), identity);
+
+        scope
+    }
+
+    fn get_capabilities(&self) -> types::Result<Capabilities> {
+        Ok(Capabilities::from_extensions(&self.extensions)?.unwrap_or_default())
+    }
+
+    fn narrow_capabilities(&mut self, capabilities: &Capabilities) -> types::Result<()> {
+        if capabilities.is_unrestricted() {
+            return Ok(());
+        }
+
+        let (key, value) = self.get_capabilities()?.narrow(capabilities).to_extension();
+        self.extensions.insert(key, value);
+
+        Ok(())
+    }
 }
 
 pub trait LabelExtensionExt {
@@ -235,12 +289,17 @@ pub struct KernelBridgeMetadata {
     #[serde(default)]
     pub job_start_time: DateTime<Utc>,
 
+    #[serde(skip_deserializing)]
+    #[serde(default)]
+    pub job_scope: HashMap<String, String>,
+
     pub extensions: HashMap<String, String>,
 }
 
 pub struct KernelBridge {
     kernel: Arc<Box<dyn Kernel>>,
     metadata: KernelBridgeMetadata,
+    capabilities: Capabilities,
 }
 
 const CORE_SYMBOL_RUN: &str = "run";
@@ -272,6 +331,8 @@ impl KernelBinding for KernelBridge {
     }
 
     async fn run(&self, symbol: &Symbol, input: Vec<u8>) -> types::Result<Vec<u8>> {
+        self.capabilities.check_symbol(symbol)?;
+
         if self.is_core_symbol(symbol) {
             self.kernel_call(symbol, input).await
         } else {
@@ -282,12 +343,33 @@ impl KernelBinding for KernelBridge {
 
 impl KernelBridge {
     pub fn new(kernel: Arc<Box<dyn Kernel>>, metadata: KernelBridgeMetadata) -> Self {
-        Self { kernel, metadata }
+        Self {
+            kernel,
+            metadata,
+            capabilities: Capabilities::unrestricted(),
+        }
+    }
+
+    /// Refuse kernel calls and module calls not granted by `capabilities`. Jobs started
+    /// through the bridge inherit the capabilities.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Ties a kernel call to the calling job. Scope extensions set by the guest are replaced, so
+    /// that a job can neither leave its namespace nor pick another policy.
+    fn apply_job_scope(&self, extensions: &mut HashMap<String, String>) {
+        extensions.remove(&Constants::JobPolicyBlob.to_string());
+        extensions.remove(&Constants::JobParentPolicyBlob.to_string());
+
+        for (key, value) in self.metadata.job_scope.iter() {
+            extensions.insert(key.clone(), value.clone());
+        }
     }
 
     fn is_core_symbol(&self, symbol: &Symbol) -> bool {
-        // TODO: Use Constant here
-        if symbol.module_info.name != "mitsuha.core" {
+        if symbol.module_info.name != Constants::CoreModuleName.to_string() {
             return false;
         }
 
@@ -310,10 +392,13 @@ impl KernelBridge {
                     });
                 }
 
-                let spec: JobSpec =
+                let mut spec: JobSpec =
                     musubi_api::types::from_value(&data.values().get(0).unwrap().clone())
                         .to_unknown_err_result()?;
 
+                spec.narrow_capabilities(&self.capabilities)?;
+                self.apply_job_scope(&mut spec.extensions);
+
                 self.kernel.run_job(spec).await?;
 
                 data_builder = data_builder.add(Value::Null);
@@ -380,6 +465,8 @@ impl KernelBridge {
                     });
                 }
 
+                self.apply_job_scope(&mut extensions);
+
                 self.kernel.extend_job(handle, ttl, extensions).await?;
 
                 data_builder = data_builder.add(Value::Null);
@@ -434,6 +521,8 @@ impl KernelBridge {
                     });
                 }
 
+                self.apply_job_scope(&mut extensions);
+
                 self.kernel.abort_job(handle, extensions).await?;
 
                 data_builder = data_builder.add(Value::Null);
@@ -488,6 +577,8 @@ impl KernelBridge {
                     });
                 }
 
+                self.apply_job_scope(&mut extensions);
+
                 let status = self.kernel.get_job_status(handle, extensions).await?;
 
                 data_builder =
@@ -504,10 +595,12 @@ impl KernelBridge {
                     });
                 }
 
-                let spec: StorageSpec =
+                let mut spec: StorageSpec =
                     musubi_api::types::from_value(&data.values().get(0).unwrap().clone())
                         .to_unknown_err_result()?;
 
+                self.apply_job_scope(&mut spec.extensions);
+
                 self.kernel.store_data(spec).await?;
 
                 data_builder = data_builder.add(Value::Null);
@@ -562,6 +655,8 @@ impl KernelBridge {
                     });
                 }
 
+                self.apply_job_scope(&mut extensions);
+
                 let data = self.kernel.load_data(handle, extensions).await?;
 
                 data_builder = data_builder.add(Value::Bytes(data));
@@ -628,6 +723,8 @@ impl KernelBridge {
                     });
                 }
 
+                self.apply_job_scope(&mut extensions);
+
                 self.kernel.persist_data(handle, ttl, extensions).await?;
 
                 data_builder = data_builder.add(Value::Null);
@@ -682,6 +779,8 @@ impl KernelBridge {
                     });
                 }
 
+                self.apply_job_scope(&mut extensions);
+
                 self.kernel.clear_data(handle, extensions).await?;
 
                 data_builder = data_builder.add(Value::Null);
@@ -698,13 +797,15 @@ impl KernelBridge {
         let input_handle = Uuid::new_v4().to_string();
         let output_handle = Uuid::new_v4().to_string();
 
-        let input_spec = StorageSpec {
+        let mut input_spec = StorageSpec {
             handle: input_handle.clone(),
             data: input,
             ttl: self.metadata.job_output_ttl,
             extensions: Default::default(),
         };
 
+        self.apply_job_scope(&mut input_spec.extensions);
+
         self.kernel.store_data(input_spec).await?;
 
         let mut job_spec = JobSpec {
@@ -722,11 +823,16 @@ impl KernelBridge {
         };
 
         job_spec.load_kernel_bridge_metadata(&self.metadata);
+        job_spec.narrow_capabilities(&self.capabilities)?;
+        self.apply_job_scope(&mut job_spec.extensions);
 
         self.kernel.run_job(job_spec).await?;
 
+        let mut output_extensions = Default::default();
+        self.apply_job_scope(&mut output_extensions);
+
         self.kernel
-            .load_data(output_handle, Default::default())
+            .load_data(output_handle, output_extensions)
             .await
     }
 }
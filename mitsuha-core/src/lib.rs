@@ -1,3 +1,4 @@
+pub mod capability;
 pub mod channel;
 pub mod config;
 pub mod constants;
@@ -8,8 +9,11 @@ pub mod kernel;
 pub mod linker;
 pub mod metric;
 pub mod module;
+pub mod native;
+pub mod registry;
 pub mod resolver;
 pub mod selector;
 pub mod storage;
 pub mod symbol;
+pub mod trap;
 pub mod types;
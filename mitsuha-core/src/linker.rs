@@ -4,14 +4,15 @@ use async_trait::async_trait;
 use mitsuha_core_types::{kernel::JobSpec, module::ModuleInfo};
 
 use crate::{
-    constants::Constants, executor::ExecutorContext, kernel::KernelBinding, resolver::Resolver,
-    types,
+    constants::Constants, executor::ExecutorContext, kernel::KernelBinding,
+    registry::ModuleRegistry, resolver::Resolver, types,
 };
 
 pub struct LinkerContext {
     pub dependency_graph: HashMap<ModuleInfo, HashMap<String, ModuleInfo>>,
     pub kernel_binding: Arc<Box<dyn KernelBinding>>,
     pub module_resolver: Arc<Box<dyn Resolver<ModuleInfo, Vec<u8>>>>,
+    pub module_registry: Option<Arc<Box<dyn ModuleRegistry>>>,
     pub extensions: HashMap<String, String>,
 }
 
@@ -24,10 +25,17 @@ impl LinkerContext {
             dependency_graph: HashMap::new(),
             kernel_binding,
             module_resolver: resolver,
+            module_registry: None,
             extensions: Default::default(),
         }
     }
 
+    /// Resolve dependency version requirements to concrete versions through the given registry.
+    pub fn with_module_registry(mut self, registry: Arc<Box<dyn ModuleRegistry>>) -> Self {
+        self.module_registry = Some(registry);
+        self
+    }
+
     pub fn with_extension(mut self, key: String, value: String) -> Self {
         self.extensions.insert(key, value);
         self
@@ -41,6 +49,32 @@ impl LinkerContext {
     pub fn load_extensions_from_job(&mut self, spec: &JobSpec) {
         self.extensions
             .insert(Constants::JobHandle.to_string(), spec.handle.clone());
+
+        if let Some(budget) = spec.extensions.get(&Constants::JobCpuBudget.to_string()) {
+            self.extensions
+                .insert(Constants::JobCpuBudget.to_string(), budget.clone());
+        }
+
+        if let Some(capabilities) = spec.extensions.get(&Constants::JobCapabilities.to_string()) {
+            self.extensions
+                .insert(Constants::JobCapabilities.to_string(), capabilities.clone());
+        }
+
+        if let Some(handle) = spec
+            .extensions
+            .get(&Constants::JobProfileHandle.to_string())
+        {
+            self.extensions
+                .insert(Constants::JobProfileHandle.to_string(), handle.clone());
+        }
+
+        if let Some(prefix) = spec
+            .extensions
+            .get(&Constants::ModuleResolverPrefix.to_string())
+        {
+            self.extensions
+                .insert(Constants::ModuleResolverPrefix.to_string(), prefix.clone());
+        }
     }
 }
 
@@ -0,0 +1,297 @@
+use std::io::{Read, Write};
+
+use musubi_api::{
+    types::{Data, Value},
+    DataBuilder,
+};
+use sha2::{Digest, Sha256};
+
+use super::{NativeModule, NativeModuleBuilder};
+use crate::{
+    errors::{Error, ToUnknownErrorResult},
+    types,
+};
+
+fn invalid_input(message: String) -> Error {
+    Error::InvalidOperation { message }
+}
+
+fn get_bytes(data: &Data, index: usize) -> types::Result<Vec<u8>> {
+    match data.values().get(index) {
+        Some(Value::Bytes(x)) => Ok(x.clone()),
+        Some(Value::String(x)) => Ok(x.as_bytes().to_vec()),
+        _ => Err(invalid_input(format!(
+            "expected value {} to be bytes or a string",
+            index
+        ))),
+    }
+}
+
+fn get_string(data: &Data, index: usize) -> types::Result<String> {
+    match data.values().get(index) {
+        Some(Value::String(x)) => Ok(x.clone()),
+        _ => Err(invalid_input(format!(
+            "expected value {} to be a string",
+            index
+        ))),
+    }
+}
+
+fn parse_json(data: &Data, index: usize) -> types::Result<serde_json::Value> {
+    serde_json::from_str(&get_string(data, index)?)
+        .map_err(|e| invalid_input(format!("value {} is not valid json: {}", index, e)))
+}
+
+fn single(value: Value) -> types::Result<Data> {
+    Ok(DataBuilder::new().add(value).build())
+}
+
+/// Runs CPU bound work off the async executor, inputs are arbitrarily large guest data.
+async fn run_blocking<F>(func: F) -> types::Result<Data>
+where
+    F: FnOnce() -> types::Result<Data> + Send + 'static,
+{
+    tokio::task::spawn_blocking(func)
+        .await
+        .to_unknown_err_result()?
+}
+
+fn merge_patch(target: &mut serde_json::Value, patch: serde_json::Value) {
+    match patch {
+        serde_json::Value::Object(patch) => {
+            if !target.is_object() {
+                *target = serde_json::Value::Object(Default::default());
+            }
+
+            let target = target.as_object_mut().unwrap();
+
+            for (key, value) in patch {
+                if value.is_null() {
+                    target.remove(&key);
+                } else {
+                    merge_patch(target.entry(key).or_insert(serde_json::Value::Null), value);
+                }
+            }
+        }
+        patch => *target = patch,
+    }
+}
+
+/// `mitsuha.native.hash`: SHA-256 digests of bytes or strings.
+fn make_hash_module() -> types::Result<NativeModule> {
+    Ok(NativeModuleBuilder::new("mitsuha.native.hash", "0.1.0")
+        .with_function("sha256", |data: Data| async move {
+            let input = get_bytes(&data, 0)?;
+
+            run_blocking(move || single(Value::Bytes(Sha256::digest(input).to_vec()))).await
+        })?
+        .with_function("sha256_hex", |data: Data| async move {
+            let input = get_bytes(&data, 0)?;
+
+            run_blocking(move || single(Value::String(hex::encode(Sha256::digest(input))))).await
+        })?
+        .build())
+}
+
+/// `mitsuha.native.json`: JSON pointer lookups and merge patches (RFC 7386) over JSON text.
+fn make_json_module() -> types::Result<NativeModule> {
+    Ok(NativeModuleBuilder::new("mitsuha.native.json", "0.1.0")
+        .with_function("pointer", |data: Data| async move {
+            run_blocking(move || {
+                let value = parse_json(&data, 0)?;
+                let pointer = get_string(&data, 1)?;
+
+                match value.pointer(&pointer) {
+                    Some(x) => single(Value::String(x.to_string())),
+                    None => single(Value::Null),
+                }
+            })
+            .await
+        })?
+        .with_function("merge", |data: Data| async move {
+            run_blocking(move || {
+                let mut target = parse_json(&data, 0)?;
+                merge_patch(&mut target, parse_json(&data, 1)?);
+
+                single(Value::String(target.to_string()))
+            })
+            .await
+        })?
+        .build())
+}
+
+/// `mitsuha.native.compression`: gzip compression and decompression. Decompressed output
+/// is capped at `max_output_size` bytes.
+fn make_compression_module(max_output_size: u64) -> types::Result<NativeModule> {
+    Ok(
+        NativeModuleBuilder::new("mitsuha.native.compression", "0.1.0")
+            .with_function("gzip", |data: Data| async move {
+                let input = get_bytes(&data, 0)?;
+
+                run_blocking(move || {
+                    let mut encoder =
+                        flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+
+                    encoder
+                        .write_all(&input)
+                        .map_err(|e| invalid_input(e.to_string()))?;
+
+                    single(Value::Bytes(
+                        encoder.finish().map_err(|e| invalid_input(e.to_string()))?,
+                    ))
+                })
+                .await
+            })?
+            .with_function("gunzip", move |data: Data| async move {
+                let input = get_bytes(&data, 0)?;
+
+                run_blocking(move || {
+                    let mut output = Vec::new();
+
+                    // Reading one byte past the limit tells a full output from a truncated one.
+                    flate2::read::GzDecoder::new(input.as_slice())
+                        .take(max_output_size.saturating_add(1))
+                        .read_to_end(&mut output)
+                        .map_err(|e| invalid_input(format!("failed to decompress input: {}", e)))?;
+
+                    if output.len() as u64 > max_output_size {
+                        return Err(invalid_input(format!(
+                            "decompressed output exceeds {} bytes",
+                            max_output_size
+                        )));
+                    }
+
+                    single(Value::Bytes(output))
+                })
+                .await
+            })?
+            .build(),
+    )
+}
+
+pub fn make_modules(max_output_size: u64) -> types::Result<Vec<NativeModule>> {
+    Ok(vec![
+        make_hash_module()?,
+        make_json_module()?,
+        make_compression_module(max_output_size)?,
+    ])
+}
+
+#[cfg(test)]
+mod test {
+    use mitsuha_core_types::symbol::Symbol;
+    use musubi_api::{
+        types::{Data, Value},
+        DataBuilder,
+    };
+
+    use crate::native::NativeModuleRegistry;
+
+    async fn call(
+        registry: &NativeModuleRegistry,
+        module: &str,
+        function: &str,
+        data: Data,
+    ) -> Data {
+        let module = registry.resolve(module, "^0.1").unwrap();
+
+        let symbol = Symbol {
+            name: function.to_string(),
+            module_info: module.get_module_info(),
+        };
+
+        let output = module
+            .get_executor_context()
+            .call(&symbol, data.try_into().unwrap())
+            .await
+            .unwrap();
+
+        Data::try_from(output).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_builtin_modules() {
+        let registry = NativeModuleRegistry::builtin(1024).unwrap();
+
+        let output = call(
+            &registry,
+            "mitsuha.native.json",
+            "merge",
+            DataBuilder::new()
+                .add(Value::String(r#"{"a":1,"b":{"c":2}}"#.to_string()))
+                .add(Value::String(r#"{"a":null,"b":{"d":3}}"#.to_string()))
+                .build(),
+        )
+        .await;
+
+        match output.values().first() {
+            Some(Value::String(x)) => {
+                let value: serde_json::Value = serde_json::from_str(x).unwrap();
+                assert_eq!(value, serde_json::json!({"b": {"c": 2, "d": 3}}));
+            }
+            _ => panic!("expected string"),
+        }
+
+        let compressed = call(
+            &registry,
+            "mitsuha.native.compression",
+            "gzip",
+            DataBuilder::new()
+                .add(Value::String("Hello world!".to_string()))
+                .build(),
+        )
+        .await;
+
+        let decompressed = call(
+            &registry,
+            "mitsuha.native.compression",
+            "gunzip",
+            compressed,
+        )
+        .await;
+
+        match decompressed.values().first() {
+            Some(Value::Bytes(x)) => assert_eq!(x.as_slice(), b"Hello world!"),
+            _ => panic!("expected bytes"),
+        }
+
+        let output = call(
+            &registry,
+            "mitsuha.native.hash",
+            "sha256_hex",
+            DataBuilder::new().add(Value::U64(1)).build(),
+        )
+        .await;
+
+        assert!(matches!(output.values().first(), Some(Value::Error { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_gunzip_output_limit() {
+        let registry = NativeModuleRegistry::builtin(1024).unwrap();
+
+        for (size, allowed) in [(1024, true), (1025, false)] {
+            let compressed = call(
+                &registry,
+                "mitsuha.native.compression",
+                "gzip",
+                DataBuilder::new().add(Value::Bytes(vec![0; size])).build(),
+            )
+            .await;
+
+            let output = call(
+                &registry,
+                "mitsuha.native.compression",
+                "gunzip",
+                compressed,
+            )
+            .await;
+
+            match output.values().first() {
+                Some(Value::Bytes(x)) => assert!(allowed && x.len() == size),
+                Some(Value::Error { .. }) => assert!(!allowed),
+                _ => panic!("expected bytes or an error"),
+            }
+        }
+    }
+}
@@ -0,0 +1,181 @@
+pub mod builtin;
+
+use std::{collections::HashMap, future::Future, sync::Arc};
+
+use futures::FutureExt;
+use lazy_static::lazy_static;
+use mitsuha_core_types::{
+    module::{ModuleInfo, ModuleType},
+    symbol::Symbol,
+};
+use musubi_api::types::{Data, Value};
+
+use crate::{errors::Error, executor::ExecutorContext, types};
+
+lazy_static! {
+    pub static ref KIND: String = "NativeModule".to_string();
+}
+
+/// A musubi module implemented by the host. Guests import its functions like those of any
+/// other module, but calls are served in-process by the [ExecutorContext] built at startup.
+#[derive(Clone)]
+pub struct NativeModule {
+    module_info: ModuleInfo,
+    executor_context: Arc<ExecutorContext>,
+}
+
+impl NativeModule {
+    pub fn get_module_info(&self) -> ModuleInfo {
+        self.module_info.clone()
+    }
+
+    pub fn get_executor_context(&self) -> Arc<ExecutorContext> {
+        self.executor_context.clone()
+    }
+}
+
+pub struct NativeModuleBuilder {
+    module_info: ModuleInfo,
+    executor_context: ExecutorContext,
+}
+
+impl NativeModuleBuilder {
+    pub fn new(name: &str, version: &str) -> Self {
+        Self {
+            module_info: ModuleInfo {
+                name: name.to_string(),
+                version: version.to_string(),
+                modtype: ModuleType::WASM,
+            },
+            executor_context: ExecutorContext::new(),
+        }
+    }
+
+    /// Declares a function of the module. Inputs and outputs are musubi [Data], errors are
+    /// returned to the caller as an error value.
+    pub fn with_function<F, Fut>(mut self, name: &str, func: F) -> types::Result<Self>
+    where
+        F: Fn(Data) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = types::Result<Data>> + Send + 'static,
+    {
+        let symbol = Symbol {
+            name: name.to_string(),
+            module_info: self.module_info.clone(),
+        };
+
+        let func = Arc::new(func);
+        let module_name = self.module_info.name.clone();
+
+        let symbol_func = move |input: Vec<u8>| {
+            let func = func.clone();
+            let module_name = module_name.clone();
+
+            async move {
+                let result = match Data::try_from(input) {
+                    Ok(data) => func(data).await,
+                    Err(e) => Err(Error::InvalidOperation {
+                        message: format!("failed to decode input: {}", e),
+                    }),
+                };
+
+                let data = result.unwrap_or_else(|e| {
+                    musubi_api::DataBuilder::new()
+                        .add(Value::Error {
+                            code: module_name,
+                            message: e.to_string(),
+                        })
+                        .build()
+                });
+
+                data.try_into().unwrap()
+            }
+            .boxed()
+        };
+
+        self.executor_context
+            .add_symbol(symbol, Arc::new(tokio::sync::RwLock::new(symbol_func)))?;
+
+        Ok(self)
+    }
+
+    pub fn build(self) -> NativeModule {
+        NativeModule {
+            module_info: self.module_info,
+            executor_context: Arc::new(self.executor_context),
+        }
+    }
+}
+
+/// The native modules available to the linker, looked up by name and version.
+#[derive(Clone, Default)]
+pub struct NativeModuleRegistry {
+    modules: Arc<HashMap<String, Vec<NativeModule>>>,
+}
+
+impl NativeModuleRegistry {
+    pub fn new(modules: Vec<NativeModule>) -> types::Result<Self> {
+        let mut map: HashMap<String, Vec<NativeModule>> = HashMap::new();
+
+        for module in modules {
+            let versions = map.entry(module.module_info.name.clone()).or_default();
+
+            if versions
+                .iter()
+                .any(|x| x.module_info.version == module.module_info.version)
+            {
+                return Err(Error::EntityConflictError {
+                    name: module.module_info.get_identifier(),
+                    kind: KIND.clone(),
+                    reason: "native module version is already registered".to_string(),
+                });
+            }
+
+            versions.push(module);
+        }
+
+        Ok(Self {
+            modules: Arc::new(map),
+        })
+    }
+
+    /// Registry with the native modules shipped with the runtime, see [builtin]. Functions
+    /// producing data of guest controlled size fail past `max_output_size` bytes.
+    pub fn builtin(max_output_size: u64) -> types::Result<Self> {
+        Self::new(builtin::make_modules(max_output_size)?)
+    }
+
+    /// Returns the native module matching `name` and `version` exactly.
+    pub fn get(&self, module_info: &ModuleInfo) -> Option<NativeModule> {
+        self.modules.get(&module_info.name).and_then(|versions| {
+            versions
+                .iter()
+                .find(|x| x.module_info.version == module_info.version)
+                .cloned()
+        })
+    }
+
+    /// Resolves a version (or semver requirement) to the highest matching native module.
+    pub fn resolve(&self, name: &str, requirement: &str) -> Option<NativeModule> {
+        let versions = self.modules.get(name)?;
+
+        if let Some(module) = versions
+            .iter()
+            .find(|x| x.module_info.version == requirement)
+        {
+            return Some(module.clone());
+        }
+
+        let req = semver::VersionReq::parse(requirement).ok()?;
+
+        versions
+            .iter()
+            .filter_map(|x| {
+                semver::Version::parse(&x.module_info.version)
+                    .ok()
+                    .map(|version| (version, x))
+            })
+            .filter(|(version, _)| req.matches(version))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, x)| x.clone())
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use lazy_static::lazy_static;
+use mitsuha_core_types::module::ModuleInfo;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{errors::Error, types};
+
+lazy_static! {
+    pub static ref KIND: String = "ModuleVersion".to_string();
+}
+
+/// A published version of a module as tracked by the [ModuleRegistry].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleRecord {
+    pub name: String,
+    pub version: String,
+    /// Hex encoded SHA-256 digest of the module binary.
+    pub digest: String,
+    /// Storage handle under which the module binary is kept.
+    pub handle: String,
+    pub yanked: bool,
+    pub deprecation: Option<String>,
+    pub publisher: Option<String>,
+    /// Hex encoded ed25519 signature of the raw digest bytes, made by the publisher.
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ModuleUpload {
+    pub name: String,
+    pub version: String,
+    pub data: Vec<u8>,
+    pub publisher: Option<String>,
+    pub signature: Option<String>,
+}
+
+#[async_trait]
+pub trait ModuleRegistry: Send + Sync {
+    /// Publish a new module version. Published versions are immutable.
+    async fn upload(&self, upload: ModuleUpload) -> types::Result<ModuleRecord>;
+
+    /// List all published versions of a module, including yanked ones.
+    async fn list(&self, name: String) -> types::Result<Vec<ModuleRecord>>;
+
+    /// Read a single published version of a module.
+    async fn get(&self, name: String, version: String) -> types::Result<Option<ModuleRecord>>;
+
+    /// Mark a version as yanked (or restore it). Yanked versions are never picked while resolving
+    /// version requirements, but can still be loaded when pinned exactly.
+    async fn yank(&self, name: String, version: String, yanked: bool) -> types::Result<()>;
+
+    /// Set (or clear) the deprecation message of a version.
+    async fn deprecate(
+        &self,
+        name: String,
+        version: String,
+        message: Option<String>,
+    ) -> types::Result<()>;
+
+    /// Load the module binary of a published version, without any integrity checks.
+    async fn fetch(&self, record: &ModuleRecord) -> types::Result<Vec<u8>>;
+
+    /// Resolve a semver requirement to the highest matching, non-yanked version of a module.
+    async fn resolve(&self, name: String, requirement: String) -> types::Result<ModuleRecord> {
+        let req = semver::VersionReq::parse(&requirement).map_err(|e| {
+            Error::ModuleVersionResolutionFailed {
+                name: name.clone(),
+                requirement: requirement.clone(),
+                message: e.to_string(),
+            }
+        })?;
+
+        let mut candidates: Vec<(semver::Version, ModuleRecord)> = self
+            .list(name.clone())
+            .await?
+            .into_iter()
+            .filter(|record| !record.yanked)
+            .filter_map(|record| {
+                semver::Version::parse(&record.version)
+                    .ok()
+                    .map(|version| (version, record))
+            })
+            .filter(|(version, _)| req.matches(version))
+            .collect();
+
+        candidates.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        match candidates.pop() {
+            Some((_, record)) => {
+                if let Some(message) = &record.deprecation {
+                    tracing::warn!(
+                        "resolved deprecated module version '{}@{}': {}",
+                        record.name,
+                        record.version,
+                        message
+                    );
+                }
+
+                Ok(record)
+            }
+            None => Err(Error::ModuleVersionResolutionFailed {
+                name,
+                requirement,
+                message: "no published version satisfies the requirement".to_string(),
+            }),
+        }
+    }
+}
+
+pub fn compute_digest(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+pub fn record_to_module_info(record: &ModuleRecord, template: &ModuleInfo) -> ModuleInfo {
+    let mut module_info = template.clone();
+    module_info.name = record.name.clone();
+    module_info.version = record.version.clone();
+    module_info
+}
+
+/// Verifies publisher signatures of module records against a set of trusted ed25519 keys.
+#[derive(Clone, Default)]
+pub struct PublisherVerifier {
+    trusted_keys: HashMap<String, VerifyingKey>,
+}
+
+impl PublisherVerifier {
+    /// Creates a verifier from a map of publisher names to hex encoded ed25519 public keys.
+    pub fn new(trusted_keys: &HashMap<String, String>) -> types::Result<Self> {
+        let mut keys = HashMap::new();
+
+        for (publisher, key) in trusted_keys {
+            let raw: [u8; 32] = hex::decode(key)
+                .ok()
+                .and_then(|x| x.try_into().ok())
+                .ok_or(Error::InvalidOperation {
+                    message: format!("invalid public key for publisher '{}'", publisher),
+                })?;
+
+            let key = VerifyingKey::from_bytes(&raw).map_err(|e| Error::InvalidOperation {
+                message: format!("invalid public key for publisher '{}': {}", publisher, e),
+            })?;
+
+            keys.insert(publisher.clone(), key);
+        }
+
+        Ok(Self { trusted_keys: keys })
+    }
+
+    pub fn verify(&self, record: &ModuleRecord) -> types::Result<()> {
+        let fail = |reason: &str| Error::ModuleSignatureVerificationFailed {
+            name: record.name.clone(),
+            version: record.version.clone(),
+            reason: reason.to_string(),
+        };
+
+        let publisher = record
+            .publisher
+            .as_ref()
+            .ok_or_else(|| fail("module has no publisher"))?;
+
+        let key = self
+            .trusted_keys
+            .get(publisher)
+            .ok_or_else(|| fail("publisher is not trusted"))?;
+
+        let signature: [u8; 64] = record
+            .signature
+            .as_ref()
+            .and_then(|x| hex::decode(x).ok())
+            .and_then(|x| x.try_into().ok())
+            .ok_or_else(|| fail("module has no valid signature"))?;
+
+        let digest = hex::decode(&record.digest).map_err(|_| fail("malformed digest"))?;
+
+        key.verify(&digest, &Signature::from_bytes(&signature))
+            .map_err(|_| fail("signature does not match"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use async_trait::async_trait;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    use super::{compute_digest, ModuleRecord, ModuleRegistry, ModuleUpload, PublisherVerifier};
+    use crate::{err_unsupported_op, errors::Error, types};
+
+    /// A read only registry.
+    struct TestRegistry {
+        records: Vec<ModuleRecord>,
+    }
+
+    #[async_trait]
+    impl ModuleRegistry for TestRegistry {
+        async fn upload(&self, _upload: ModuleUpload) -> types::Result<ModuleRecord> {
+            Err(err_unsupported_op!("upload"))
+        }
+
+        async fn list(&self, name: String) -> types::Result<Vec<ModuleRecord>> {
+            Ok(self
+                .records
+                .iter()
+                .filter(|x| x.name == name)
+                .cloned()
+                .collect())
+        }
+
+        async fn get(&self, name: String, version: String) -> types::Result<Option<ModuleRecord>> {
+            Ok(self
+                .records
+                .iter()
+                .find(|x| x.name == name && x.version == version)
+                .cloned())
+        }
+
+        async fn yank(&self, _name: String, _version: String, _yanked: bool) -> types::Result<()> {
+            Err(err_unsupported_op!("yank"))
+        }
+
+        async fn deprecate(
+            &self,
+            _name: String,
+            _version: String,
+            _message: Option<String>,
+        ) -> types::Result<()> {
+            Err(err_unsupported_op!("deprecate"))
+        }
+
+        async fn fetch(&self, record: &ModuleRecord) -> types::Result<Vec<u8>> {
+            Err(Error::EntityNotFoundError {
+                name: format!("{}@{}", record.name, record.version),
+                kind: "ModuleBinary".to_string(),
+            })
+        }
+    }
+
+    fn make_record(version: &str, yanked: bool) -> ModuleRecord {
+        let digest = compute_digest(version.as_bytes());
+
+        ModuleRecord {
+            name: "mitsuha.test.echo".to_string(),
+            version: version.to_string(),
+            handle: format!("/mitsuha/registry/mitsuha.test.echo/{}/{}", version, digest),
+            digest,
+            yanked,
+            deprecation: None,
+            publisher: None,
+            signature: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_highest_non_yanked_version() {
+        let registry = TestRegistry {
+            records: vec![
+                make_record("0.1.0", false),
+                make_record("0.1.2", false),
+                make_record("0.1.3", true),
+                make_record("0.2.0", false),
+            ],
+        };
+
+        let record = registry
+            .resolve("mitsuha.test.echo".to_string(), "^0.1".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(record.version, "0.1.2");
+
+        assert!(registry
+            .resolve("mitsuha.test.echo".to_string(), ">=0.3".to_string())
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_publisher_verifier() {
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+
+        let mut record = make_record("0.1.0", false);
+        let digest = hex::decode(&record.digest).unwrap();
+
+        record.publisher = Some("mitsuha".to_string());
+        record.signature = Some(hex::encode(signing_key.sign(&digest).to_bytes()));
+
+        let verifier = PublisherVerifier::new(&HashMap::from([(
+            "mitsuha".to_string(),
+            hex::encode(signing_key.verifying_key().to_bytes()),
+        )]))
+        .unwrap();
+
+        assert!(verifier.verify(&record).is_ok());
+
+        record.digest = compute_digest(b"tampered");
+        assert!(verifier.verify(&record).is_err());
+
+        record.publisher = Some("unknown".to_string());
+        assert!(verifier.verify(&record).is_err());
+    }
+}
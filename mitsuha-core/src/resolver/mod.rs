@@ -3,6 +3,7 @@ use async_trait::async_trait;
 use crate::types;
 
 pub mod blob;
+pub mod registry;
 
 #[async_trait]
 pub trait Resolver<Key, Value>: Send + Sync {
@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use mitsuha_core_types::module::ModuleInfo;
+
+use crate::{
+    errors::Error,
+    registry::{compute_digest, ModuleRecord, ModuleRegistry, ModuleUpload, PublisherVerifier},
+    resolver::Resolver,
+    types,
+};
+
+/// Resolves module binaries through the [ModuleRegistry], verifying their digests
+/// (and optionally publisher signatures) before handing them out.
+pub struct RegistryResolver {
+    registry: Arc<Box<dyn ModuleRegistry>>,
+    verifier: Option<PublisherVerifier>,
+}
+
+impl RegistryResolver {
+    pub fn new(registry: Arc<Box<dyn ModuleRegistry>>) -> Self {
+        Self {
+            registry,
+            verifier: None,
+        }
+    }
+
+    pub fn with_verifier(mut self, verifier: PublisherVerifier) -> Self {
+        self.verifier = Some(verifier);
+        self
+    }
+
+    async fn get_record(&self, key: &ModuleInfo) -> types::Result<ModuleRecord> {
+        self.registry
+            .get(key.name.clone(), key.version.clone())
+            .await?
+            .ok_or(Error::EntityNotFoundError {
+                name: key.get_identifier(),
+                kind: crate::registry::KIND.to_string(),
+            })
+    }
+}
+
+#[async_trait]
+impl Resolver<ModuleInfo, Vec<u8>> for RegistryResolver {
+    async fn resolve(&self, key: &ModuleInfo) -> types::Result<Vec<u8>> {
+        let record = self.get_record(key).await?;
+
+        if let Some(verifier) = &self.verifier {
+            verifier.verify(&record)?;
+        }
+
+        let data = self.registry.fetch(&record).await?;
+
+        let digest = compute_digest(data.as_slice());
+        if digest != record.digest {
+            return Err(Error::ModuleIntegrityCheckFailed {
+                target: key.clone(),
+                expected: record.digest,
+                actual: digest,
+            });
+        }
+
+        Ok(data)
+    }
+
+    async fn register(&self, key: &ModuleInfo, value: &Vec<u8>) -> types::Result<()> {
+        self.registry
+            .upload(ModuleUpload {
+                name: key.name.clone(),
+                version: key.version.clone(),
+                data: value.clone(),
+                publisher: None,
+                signature: None,
+            })
+            .await?;
+
+        Ok(())
+    }
+}
@@ -1,9 +1,12 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use async_trait::async_trait;
+use lazy_static::lazy_static;
 use mitsuha_core_types::{kernel::StorageSpec, storage::StorageCapability};
 use mitsuha_filesystem::{NativeFileLease, NativeFileMetadata};
 use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
 
 use crate::{err_unsupported_op, errors::Error, selector::Label, types};
 
@@ -98,6 +101,39 @@ pub trait GarbageCollectable: Send + Sync {
     async fn garbage_collect(&self) -> types::Result<Vec<String>>;
 }
 
+/// Hook run with the handles deleted by a garbage collection cycle.
+#[async_trait]
+pub trait GarbageCollectionHook: Send + Sync {
+    async fn run(&self, handles: &[String]) -> types::Result<()>;
+}
+
+lazy_static! {
+    static ref GLOBAL_GC_HOOKS: Arc<RwLock<Vec<Arc<dyn GarbageCollectionHook>>>> =
+        Arc::new(RwLock::new(Vec::new()));
+}
+
+pub async fn add_gc_hook(hook: Arc<dyn GarbageCollectionHook>) {
+    GLOBAL_GC_HOOKS.write().await.push(hook);
+}
+
+/// Replaces the registered hooks, returning the previous ones so they can be restored if
+/// needed.
+pub async fn replace_gc_hooks(
+    hooks: Vec<Arc<dyn GarbageCollectionHook>>,
+) -> Vec<Arc<dyn GarbageCollectionHook>> {
+    std::mem::replace(&mut *GLOBAL_GC_HOOKS.write().await, hooks)
+}
+
+pub async fn run_gc_hooks(handles: &[String]) {
+    let hooks = GLOBAL_GC_HOOKS.read().await.clone();
+
+    for hook in hooks.iter() {
+        if let Err(e) = hook.run(handles).await {
+            tracing::error!("failed to run gc hook, error: {}", e);
+        }
+    }
+}
+
 #[async_trait]
 pub trait FileSystem {
     #[allow(unused_variables)]
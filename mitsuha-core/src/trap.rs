@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{constants::Constants, errors::ToUnknownErrorResult, types, types::Extensions};
+
+/// A trap raised by guest code, recorded under [crate::constants::Constants::JobStatusTrap] in
+/// the status of the job which raised it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuestTrap {
+    /// Identifier of the module in which the trap was raised.
+    pub module: String,
+
+    /// Kind of trap, such as `wasm trap: integer divide by zero`. Absent for errors raised by
+    /// host functions called from the guest.
+    pub kind: Option<String>,
+
+    pub message: String,
+
+    /// Wasm backtrace, innermost frame first.
+    pub frames: Vec<GuestFrame>,
+
+    /// Storage handle of the wasm core dump written for the trap, if any.
+    pub core_dump_handle: Option<String>,
+}
+
+impl GuestTrap {
+    /// Returns the job status extensions describing this trap.
+    pub fn to_status_details(&self) -> types::Result<Extensions> {
+        let trap = serde_json::to_string(self).to_unknown_err_result()?;
+
+        Ok([(Constants::JobStatusTrap.to_string(), trap)]
+            .into_iter()
+            .collect())
+    }
+
+    /// Reads the trap recorded in the extensions of a job status, if the job trapped.
+    pub fn from_status_details(extensions: &Extensions) -> types::Result<Option<Self>> {
+        extensions
+            .get(&Constants::JobStatusTrap.to_string())
+            .map(|x| serde_json::from_str(x))
+            .transpose()
+            .to_unknown_err_result()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuestFrame {
+    pub module: Option<String>,
+    pub func_index: u32,
+    pub func_name: Option<String>,
+    pub module_offset: Option<usize>,
+
+    /// Source locations resolved from DWARF debug info, more than one when functions were
+    /// inlined.
+    pub locations: Vec<SourceLocation>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    pub function: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_status_details_round_trip() {
+        let trap = GuestTrap {
+            module: "mitsuha.test.app/0.1.0".to_string(),
+            kind: Some("wasm trap: integer divide by zero".to_string()),
+            message: "wasm trap: integer divide by zero".to_string(),
+            frames: vec![GuestFrame {
+                module: Some("app".to_string()),
+                func_index: 3,
+                func_name: Some("divide".to_string()),
+                module_offset: Some(42),
+                locations: vec![SourceLocation {
+                    function: Some("app::divide".to_string()),
+                    file: Some("src/lib.rs".to_string()),
+                    line: Some(7),
+                    column: Some(5),
+                }],
+            }],
+            core_dump_handle: Some("dumps/job1".to_string()),
+        };
+
+        let details = trap.to_status_details().unwrap();
+
+        assert!(details.contains_key(&Constants::JobStatusTrap.to_string()));
+        assert_eq!(
+            GuestTrap::from_status_details(&details).unwrap(),
+            Some(trap)
+        );
+
+        assert_eq!(
+            GuestTrap::from_status_details(&Default::default()).unwrap(),
+            None
+        );
+    }
+}
@@ -5,6 +5,11 @@ mod m20240126_114150_create_mitsuha_scheduler_job_queue_table;
 mod m20240128_030024_create_mitsuha_scheduler_job_command_queue_table;
 mod m20240216_022505_create_mitsuha_scheduler_partition_resource_table;
 mod m20240312_024922_create_mitsuha_module_table;
+mod m20240405_101512_create_mitsuha_registry_module_table;
+mod m20241018_093041_create_mitsuha_rate_limit_bucket_table;
+mod m20241019_101204_create_mitsuha_quota_usage_table;
+mod m20241019_101232_create_mitsuha_quota_blob_table;
+mod m20241019_101257_create_mitsuha_quota_job_table;
 
 pub struct Migrator;
 
@@ -17,6 +22,11 @@ impl MigratorTrait for Migrator {
             Box::new(m20240128_030024_create_mitsuha_scheduler_job_command_queue_table::Migration),
             Box::new(m20240216_022505_create_mitsuha_scheduler_partition_resource_table::Migration),
             Box::new(m20240312_024922_create_mitsuha_module_table::Migration),
+            Box::new(m20240405_101512_create_mitsuha_registry_module_table::Migration),
+            Box::new(m20241018_093041_create_mitsuha_rate_limit_bucket_table::Migration),
+            Box::new(m20241019_101204_create_mitsuha_quota_usage_table::Migration),
+            Box::new(m20241019_101232_create_mitsuha_quota_blob_table::Migration),
+            Box::new(m20241019_101257_create_mitsuha_quota_job_table::Migration),
         ]
     }
 }
@@ -0,0 +1,88 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MitsuhaRegistryModule::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(MitsuhaRegistryModule::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(MitsuhaRegistryModule::Name)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(MitsuhaRegistryModule::Version)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(MitsuhaRegistryModule::Digest)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(MitsuhaRegistryModule::StorageHandle)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(MitsuhaRegistryModule::Yanked)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(ColumnDef::new(MitsuhaRegistryModule::Deprecation).text())
+                    .col(ColumnDef::new(MitsuhaRegistryModule::Publisher).string())
+                    .col(ColumnDef::new(MitsuhaRegistryModule::Signature).string())
+                    .col(
+                        ColumnDef::new(MitsuhaRegistryModule::CreationTimestamp)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx_mitsuha_registry_module_name_version")
+                            .col(MitsuhaRegistryModule::Name)
+                            .col(MitsuhaRegistryModule::Version)
+                            .unique(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MitsuhaRegistryModule::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+pub enum MitsuhaRegistryModule {
+    Table,
+    Id,
+    Name,
+    Version,
+    Digest,
+    StorageHandle,
+    Yanked,
+    Deprecation,
+    Publisher,
+    Signature,
+    CreationTimestamp,
+}
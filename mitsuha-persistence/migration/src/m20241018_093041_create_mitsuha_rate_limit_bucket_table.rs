@@ -0,0 +1,53 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MitsuhaRateLimitBucket::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(MitsuhaRateLimitBucket::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(MitsuhaRateLimitBucket::Tokens)
+                            .double()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(MitsuhaRateLimitBucket::LastRefill)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(MitsuhaRateLimitBucket::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+pub enum MitsuhaRateLimitBucket {
+    Table,
+    Id,
+    Tokens,
+    LastRefill,
+}
@@ -7,6 +7,11 @@ use std::time::Duration;
 use tokio::runtime::Handle;
 
 pub mod module;
+pub mod quota_blob;
+pub mod quota_job;
+pub mod quota_usage;
+pub mod rate_limit_bucket;
+pub mod registry_module;
 pub mod scheduler_job_command_queue;
 pub mod scheduler_job_queue;
 pub mod scheduler_partition;
@@ -0,0 +1,17 @@
+use sea_orm::entity::prelude::*;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "mitsuha_quota_blob")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub handle: String,
+    pub namespace: String,
+    pub bytes: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
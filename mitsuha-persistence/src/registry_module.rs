@@ -0,0 +1,24 @@
+use sea_orm::entity::prelude::*;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "mitsuha_registry_module")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i64,
+    pub name: String,
+    pub version: String,
+    pub digest: String,
+    pub storage_handle: String,
+    pub yanked: bool,
+    pub deprecation: Option<String>,
+    pub publisher: Option<String>,
+    pub signature: Option<String>,
+    pub creation_timestamp: chrono::NaiveDateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,34 @@
+use std::sync::Arc;
+
+use mitsuha_core::{
+    config::Config,
+    registry::{ModuleRegistry, PublisherVerifier},
+    types,
+};
+use tokio::sync::OnceCell;
+
+pub mod service;
+
+static GLOBAL_REGISTRY: OnceCell<Arc<Box<dyn ModuleRegistry>>> = OnceCell::const_new();
+
+/// Returns the process wide module registry, if one is configured.
+pub async fn global(config: &Config) -> types::Result<Option<Arc<Box<dyn ModuleRegistry>>>> {
+    let registry_config = match &config.registry {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    let registry = GLOBAL_REGISTRY
+        .get_or_try_init(|| service::Service::new(registry_config, &config.storage))
+        .await?;
+
+    Ok(Some(registry.clone()))
+}
+
+/// Returns the publisher verifier, if signature verification is enabled.
+pub fn make_verifier(config: &Config) -> types::Result<Option<PublisherVerifier>> {
+    match &config.registry {
+        Some(v) if v.verify_signatures => Ok(Some(PublisherVerifier::new(&v.trusted_publishers)?)),
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,214 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use mitsuha_core::{
+    config,
+    errors::Error,
+    kernel::LabelExtensionExt,
+    registry::{compute_digest, ModuleRecord, ModuleRegistry, ModuleUpload, KIND},
+    selector::Label,
+    storage::Storage,
+    types,
+};
+use mitsuha_core_types::kernel::StorageSpec;
+use mitsuha_persistence::registry_module::{ActiveModel, Column, Entity, Model};
+use mitsuha_storage::UnifiedStorage;
+use sea_orm::ActiveValue::Set;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder,
+};
+
+pub struct Service {
+    connection: DatabaseConnection,
+    storage: Arc<Box<dyn Storage>>,
+    storage_selector: Label,
+    storage_ttl: u64,
+}
+
+impl Service {
+    pub async fn new(
+        registry_config: &config::registry::Registry,
+        storage_config: &config::storage::Storage,
+    ) -> types::Result<Arc<Box<dyn ModuleRegistry>>> {
+        Ok(Arc::new(Box::new(Self {
+            connection: mitsuha_persistence::database_connection(),
+            storage: UnifiedStorage::new(storage_config).await?,
+            storage_selector: registry_config.storage_selector.clone(),
+            storage_ttl: registry_config.storage_ttl,
+        })))
+    }
+
+    /// Handles are content addressed, so that concurrent uploads of the same version never
+    /// overwrite the binary of the one which gets published.
+    fn get_handle(name: &str, version: &str, digest: &str) -> String {
+        format!("/mitsuha/registry/{}/{}/{}", name, version, digest)
+    }
+
+    fn get_extensions(&self) -> HashMap<String, String> {
+        HashMap::<String, String>::new().with_selector(&self.storage_selector)
+    }
+
+    fn to_record(model: Model) -> ModuleRecord {
+        ModuleRecord {
+            name: model.name,
+            version: model.version,
+            digest: model.digest,
+            handle: model.storage_handle,
+            yanked: model.yanked,
+            deprecation: model.deprecation,
+            publisher: model.publisher,
+            signature: model.signature,
+        }
+    }
+
+    async fn find(&self, name: &str, version: &str) -> types::Result<Option<Model>> {
+        Ok(Entity::find()
+            .filter(Column::Name.eq(name))
+            .filter(Column::Version.eq(version))
+            .one(&self.connection)
+            .await?)
+    }
+
+    /// Clears the binary of an upload which could not be published, unless the published
+    /// version has the same binary.
+    async fn remove_orphan(&self, name: &str, version: &str, handle: &str) {
+        if let Ok(Some(model)) = self.find(name, version).await {
+            if model.storage_handle == handle {
+                return;
+            }
+        }
+
+        if let Err(e) = self
+            .storage
+            .clear(handle.to_string(), self.get_extensions())
+            .await
+        {
+            tracing::warn!("failed to clear orphaned module binary '{}': {}", handle, e);
+        }
+    }
+
+    async fn find_existing(&self, name: &str, version: &str) -> types::Result<Model> {
+        self.find(name, version)
+            .await?
+            .ok_or(Error::EntityNotFoundError {
+                name: format!("{}@{}", name, version),
+                kind: KIND.to_string(),
+            })
+    }
+}
+
+#[async_trait]
+impl ModuleRegistry for Service {
+    async fn upload(&self, upload: ModuleUpload) -> types::Result<ModuleRecord> {
+        if let Err(e) = semver::Version::parse(&upload.version) {
+            return Err(Error::InvalidOperation {
+                message: format!(
+                    "module version '{}' is not valid semver: {}",
+                    upload.version, e
+                ),
+            });
+        }
+
+        if self.find(&upload.name, &upload.version).await?.is_some() {
+            return Err(Error::EntityConflictError {
+                name: format!("{}@{}", upload.name, upload.version),
+                kind: KIND.to_string(),
+                reason: "module version has already been published".to_string(),
+            });
+        }
+
+        let digest = compute_digest(upload.data.as_slice());
+        let handle = Self::get_handle(&upload.name, &upload.version, &digest);
+
+        self.storage
+            .store(StorageSpec {
+                handle: handle.clone(),
+                data: upload.data,
+                ttl: self.storage_ttl,
+                extensions: self.get_extensions(),
+            })
+            .await?;
+
+        let utc_now = NaiveDateTime::from_timestamp_opt(Utc::now().timestamp(), 0).unwrap();
+
+        // The unique index on (name, version) rejects concurrent uploads of the same version.
+        let result = ActiveModel {
+            name: Set(upload.name.clone()),
+            version: Set(upload.version.clone()),
+            digest: Set(digest),
+            storage_handle: Set(handle.clone()),
+            yanked: Set(false),
+            deprecation: Set(None),
+            publisher: Set(upload.publisher),
+            signature: Set(upload.signature),
+            creation_timestamp: Set(utc_now),
+            ..Default::default()
+        }
+        .insert(&self.connection)
+        .await;
+
+        match result {
+            Ok(model) => Ok(Self::to_record(model)),
+            Err(e) => {
+                self.remove_orphan(&upload.name, &upload.version, &handle)
+                    .await;
+
+                Err(e.into())
+            }
+        }
+    }
+
+    async fn list(&self, name: String) -> types::Result<Vec<ModuleRecord>> {
+        let models = Entity::find()
+            .filter(Column::Name.eq(name))
+            .order_by_asc(Column::Id)
+            .all(&self.connection)
+            .await?;
+
+        Ok(models.into_iter().map(Self::to_record).collect())
+    }
+
+    async fn get(&self, name: String, version: String) -> types::Result<Option<ModuleRecord>> {
+        Ok(self.find(&name, &version).await?.map(Self::to_record))
+    }
+
+    async fn yank(&self, name: String, version: String, yanked: bool) -> types::Result<()> {
+        let model = self.find_existing(&name, &version).await?;
+
+        ActiveModel {
+            id: Set(model.id),
+            yanked: Set(yanked),
+            ..Default::default()
+        }
+        .update(&self.connection)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn deprecate(
+        &self,
+        name: String,
+        version: String,
+        message: Option<String>,
+    ) -> types::Result<()> {
+        let model = self.find_existing(&name, &version).await?;
+
+        ActiveModel {
+            id: Set(model.id),
+            deprecation: Set(message),
+            ..Default::default()
+        }
+        .update(&self.connection)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn fetch(&self, record: &ModuleRecord) -> types::Result<Vec<u8>> {
+        self.storage
+            .load(record.handle.clone(), self.get_extensions())
+            .await
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use mitsuha_core::{
+    config,
+    errors::Error,
+    registry::{compute_digest, ModuleRegistry, ModuleUpload},
+    selector::Label,
+    storage::{StorageClass, StorageLocality},
+};
+use mitsuha_registry::service::Service;
+
+fn make_label() -> Label {
+    Label {
+        key: "storage".to_string(),
+        value: "registry".to_string(),
+    }
+}
+
+/// Module names are unique per test run, as published versions are kept in the database.
+fn make_module_name(test: &str) -> String {
+    format!("mitsuha.test.{}.{}", test, Utc::now().timestamp_micros())
+}
+
+fn make_upload(name: &str, version: &str, data: &[u8]) -> ModuleUpload {
+    ModuleUpload {
+        name: name.to_string(),
+        version: version.to_string(),
+        data: data.to_vec(),
+        publisher: None,
+        signature: None,
+    }
+}
+
+/// Requires the database configured for the runtime.
+async fn make_registry() -> std::sync::Arc<Box<dyn ModuleRegistry>> {
+    mitsuha_persistence::apply_migrations().await;
+
+    let registry_config = config::registry::Registry {
+        storage_selector: make_label(),
+        storage_ttl: 3600,
+        verify_signatures: false,
+        trusted_publishers: HashMap::new(),
+        api_tokens: vec![],
+    };
+
+    let storage_config = config::storage::Storage {
+        classes: vec![StorageClass {
+            kind: mitsuha_core::storage::StorageKind::Memory,
+            locality: StorageLocality::Solid { cache_name: None },
+            name: "registry".to_string(),
+            labels: vec![make_label()],
+            properties: HashMap::new(),
+        }],
+    };
+
+    Service::new(&registry_config, &storage_config)
+        .await
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn upload_and_fetch() {
+    let registry = make_registry().await;
+    let name = make_module_name("upload");
+
+    let record = registry
+        .upload(make_upload(&name, "0.1.0", b"module-a"))
+        .await
+        .unwrap();
+
+    assert_eq!(record.digest, compute_digest(b"module-a"));
+    assert!(record.handle.ends_with(&record.digest));
+
+    // Published versions are immutable, and a rejected upload leaves the binary untouched.
+    let result = registry
+        .upload(make_upload(&name, "0.1.0", b"module-b"))
+        .await;
+
+    assert!(matches!(result, Err(Error::EntityConflictError { .. })));
+    assert_eq!(registry.fetch(&record).await.unwrap(), b"module-a");
+
+    assert!(registry
+        .upload(make_upload(&name, "latest", b"module-a"))
+        .await
+        .is_err());
+
+    assert_eq!(
+        registry
+            .get(name.clone(), "0.1.0".to_string())
+            .await
+            .unwrap(),
+        Some(record)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn yank_and_deprecate() {
+    let registry = make_registry().await;
+    let name = make_module_name("yank");
+
+    for version in ["0.1.0", "0.1.1"] {
+        registry
+            .upload(make_upload(&name, version, version.as_bytes()))
+            .await
+            .unwrap();
+    }
+
+    registry
+        .yank(name.clone(), "0.1.1".to_string(), true)
+        .await
+        .unwrap();
+    registry
+        .deprecate(
+            name.clone(),
+            "0.1.0".to_string(),
+            Some("use 0.2".to_string()),
+        )
+        .await
+        .unwrap();
+
+    let record = registry
+        .resolve(name.clone(), "^0.1".to_string())
+        .await
+        .unwrap();
+
+    assert_eq!(record.version, "0.1.0");
+    assert_eq!(record.deprecation, Some("use 0.2".to_string()));
+
+    let result = registry.yank(name.clone(), "0.3.0".to_string(), true).await;
+
+    assert!(matches!(result, Err(Error::EntityNotFoundError { .. })));
+}
@@ -57,6 +57,14 @@ pub mod compute_response {
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct InterceptResponseRequest {
+    #[prost(message, optional, tag = "1")]
+    pub request: ::core::option::Option<ComputeRequest>,
+    #[prost(message, optional, tag = "2")]
+    pub response: ::core::option::Option<ComputeResponse>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct StatusResponse {
     #[prost(message, optional, tag = "1")]
     pub status: ::core::option::Option<JobStatus>,
@@ -370,6 +378,12 @@ pub mod interceptor_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
     use tonic::codegen::*;
     use tonic::codegen::http::Uri;
+    /// An interceptor rejects a request or response by failing with PERMISSION_DENIED, the status
+    /// message being the reason given to the caller.
+    ///
+    /// An interceptor acting on a single phase answers the other RPC with UNIMPLEMENTED, and is
+    /// then skipped in that phase for a while before it is probed again. Servers generated with
+    /// tonic must still implement both methods, e.g. by returning `Status::unimplemented`.
     #[derive(Debug, Clone)]
     pub struct InterceptorClient<T> {
         inner: tonic::client::Grpc<T>,
@@ -472,6 +486,28 @@ pub mod interceptor_client {
                 .insert(GrpcMethod::new("channel.Interceptor", "Intercept"));
             self.inner.unary(req, path, codec).await
         }
+        pub async fn intercept_response(
+            &mut self,
+            request: impl tonic::IntoRequest<super::InterceptResponseRequest>,
+        ) -> std::result::Result<tonic::Response<super::ComputeResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/channel.Interceptor/InterceptResponse",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("channel.Interceptor", "InterceptResponse"));
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -653,12 +689,23 @@ pub mod interceptor_server {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
     use tonic::codegen::*;
     /// Generated trait containing gRPC methods that should be implemented for use with InterceptorServer.
+    ///
+    /// An interceptor rejects a request or response by failing with PERMISSION_DENIED, the status
+    /// message being the reason given to the caller.
+    ///
+    /// An interceptor acting on a single phase answers the other RPC with UNIMPLEMENTED, and is
+    /// then skipped in that phase for a while before it is probed again. Servers generated with
+    /// tonic must still implement both methods, e.g. by returning `Status::unimplemented`.
     #[async_trait]
     pub trait Interceptor: Send + Sync + 'static {
         async fn intercept(
             &self,
             request: tonic::Request<super::ComputeRequest>,
         ) -> std::result::Result<tonic::Response<super::ComputeRequest>, tonic::Status>;
+        async fn intercept_response(
+            &self,
+            request: tonic::Request<super::InterceptResponseRequest>,
+        ) -> std::result::Result<tonic::Response<super::ComputeResponse>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct InterceptorServer<T: Interceptor> {
@@ -783,6 +830,50 @@ pub mod interceptor_server {
                     };
                     Box::pin(fut)
                 }
+                "/channel.Interceptor/InterceptResponse" => {
+                    #[allow(non_camel_case_types)]
+                    struct InterceptResponseSvc<T: Interceptor>(pub Arc<T>);
+                    impl<
+                        T: Interceptor,
+                    > tonic::server::UnaryService<super::InterceptResponseRequest>
+                    for InterceptResponseSvc<T> {
+                        type Response = super::ComputeResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::InterceptResponseRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { (*inner).intercept_response(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = InterceptResponseSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         Ok(
@@ -3,6 +3,7 @@ use actix_web::{App, HttpServer};
 use mitsuha_core::config::Config;
 
 mod prometheus;
+mod registry;
 
 macro_rules! register_routes {
     ($app: ident, $b: block) => {
@@ -29,6 +30,7 @@ where
     T: ServiceFactory<ServiceRequest, Config = (), Error = actix_web::error::Error, InitError = ()>,
 {
     app = prometheus::register_routes(app);
+    app = registry::register_routes(app);
 
     app
 }
@@ -86,6 +86,48 @@ fn register_metrics() {
             mitsuha_scheduler::metric::partition_lease_renewal_duration_metric().clone(),
         ))
         .expect("failed to register metric");
+
+    REGISTRY
+        .register(Box::new(
+            mitsuha_wasm_runtime::metric::module_compile_duration_metric().clone(),
+        ))
+        .expect("failed to register metric");
+
+    REGISTRY
+        .register(Box::new(
+            mitsuha_wasm_runtime::metric::module_cache_request_count_metric().clone(),
+        ))
+        .expect("failed to register metric");
+
+    REGISTRY
+        .register(Box::new(
+            mitsuha_channel::metric::circuit_breaker_state_metric().clone(),
+        ))
+        .expect("failed to register metric");
+
+    REGISTRY
+        .register(Box::new(
+            mitsuha_channel::metric::circuit_breaker_transition_count_metric().clone(),
+        ))
+        .expect("failed to register metric");
+
+    REGISTRY
+        .register(Box::new(
+            mitsuha_channel::metric::compute_retry_count_metric().clone(),
+        ))
+        .expect("failed to register metric");
+
+    REGISTRY
+        .register(Box::new(
+            mitsuha_channel::metric::mirror_divergence_count_metric().clone(),
+        ))
+        .expect("failed to register metric");
+
+    REGISTRY
+        .register(Box::new(
+            mitsuha_channel::metric::interceptor_failure_count_metric().clone(),
+        ))
+        .expect("failed to register metric");
 }
 
 super::register_routes!(app, {
@@ -0,0 +1,351 @@
+use actix_web::{get, http::header, post, web, HttpRequest, HttpResponse};
+use lazy_static::lazy_static;
+use mitsuha_core::{
+    config::Config,
+    errors::{Error, ToUnknownErrorResult},
+    registry::{ModuleRegistry, ModuleUpload},
+    types,
+};
+use mitsuha_core_types::module::{ModuleInfo, ModuleType};
+use mitsuha_wasm_runtime::wasmtime::{ModuleValidator, ValidationReport, WasmtimeConfig};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::plugin::{wasmtime::WasmtimePlugin, Plugin};
+
+lazy_static! {
+    /// Validator built from the wasmtime plugin properties it was last requested with.
+    static ref MODULE_VALIDATOR: Mutex<Option<(HashMap<String, String>, Arc<ModuleValidator>)>> =
+        Default::default();
+}
+
+#[derive(Deserialize)]
+struct UploadQuery {
+    publisher: Option<String>,
+    signature: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct YankRequest {
+    yanked: bool,
+}
+
+#[derive(Deserialize)]
+struct DeprecateRequest {
+    message: Option<String>,
+}
+
+async fn get_registry() -> types::Result<Arc<Box<dyn ModuleRegistry>>> {
+    let config = Config::global()
+        .await
+        .map_err(|e| Error::Unknown { source: e })?;
+
+    mitsuha_registry::global(&config)
+        .await?
+        .ok_or(Error::InvalidOperation {
+            message: "module registry is not configured".to_string(),
+        })
+}
+
+fn get_bearer_token(request: &HttpRequest) -> Option<&str> {
+    request
+        .headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+/// Compares every token in constant time, so that tokens cannot be guessed from response
+/// times.
+fn is_authorized(api_tokens: &[String], token: &str) -> bool {
+    api_tokens.iter().fold(false, |authorized, api_token| {
+        let matches = api_token.len() == token.len()
+            && api_token
+                .bytes()
+                .zip(token.bytes())
+                .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                == 0;
+
+        authorized | matches
+    })
+}
+
+/// Checks the bearer token of a request changing the registry against the api tokens of the
+/// registry configuration.
+async fn authorize(request: &HttpRequest) -> Result<(), HttpResponse> {
+    let token = get_bearer_token(request)
+        .ok_or_else(|| HttpResponse::Unauthorized().body("missing bearer token"))?;
+
+    let api_tokens = Config::global()
+        .await
+        .ok()
+        .and_then(|x| x.registry)
+        .map(|x| x.api_tokens)
+        .unwrap_or_default();
+
+    if !is_authorized(&api_tokens, token) {
+        return Err(HttpResponse::Unauthorized().body("invalid bearer token"));
+    }
+
+    Ok(())
+}
+
+fn error_response(error: Error) -> HttpResponse {
+    let message = error.to_string();
+
+    match error {
+        Error::EntityNotFoundError { .. } => HttpResponse::NotFound().body(message),
+        Error::EntityConflictError { .. } => HttpResponse::Conflict().body(message),
+        Error::InvalidOperation { .. } => HttpResponse::BadRequest().body(message),
+        _ => {
+            tracing::error!("module registry operation failed: {}", message);
+            HttpResponse::InternalServerError().body(message)
+        }
+    }
+}
+
+/// Returns a validator accepting the modules linked by the first wasmtime plugin of the
+/// runtime configuration, or by a default wasmtime configuration if there is none.
+async fn get_validator() -> types::Result<Arc<ModuleValidator>> {
+    let properties = Config::global()
+        .await
+        .ok()
+        .and_then(|config| {
+            config
+                .plugins
+                .into_iter()
+                .find(|plugin| plugin.name == WasmtimePlugin.name())
+        })
+        .map(|plugin| plugin.properties)
+        .unwrap_or_default();
+
+    let mut cached = MODULE_VALIDATOR.lock().unwrap();
+
+    if let Some((cached_properties, validator)) = cached.as_ref() {
+        if *cached_properties == properties {
+            return Ok(validator.clone());
+        }
+    }
+
+    let validator = Arc::new(ModuleValidator::from_config(
+        &WasmtimeConfig::from_properties(&properties)?,
+    )?);
+
+    *cached = Some((properties, validator.clone()));
+
+    Ok(validator)
+}
+
+async fn validate(
+    name: String,
+    version: String,
+    body: web::Bytes,
+) -> types::Result<ValidationReport> {
+    let module_info = ModuleInfo {
+        name,
+        version,
+        modtype: ModuleType::WASM,
+    };
+
+    let validator = get_validator().await?;
+
+    // Validation compiles the module, keep it off the async workers.
+    web::block(move || validator.validate(&body, &module_info))
+        .await
+        .to_unknown_err_result()
+}
+
+#[post("/modules/{name}/{version}/validate")]
+async fn validate_module(
+    http_request: HttpRequest,
+    path: web::Path<(String, String)>,
+    body: web::Bytes,
+) -> HttpResponse {
+    if let Err(response) = authorize(&http_request).await {
+        return response;
+    }
+
+    let (name, version) = path.into_inner();
+
+    match validate(name, version, body).await {
+        Ok(report) if report.is_valid() => HttpResponse::Ok().json(report),
+        Ok(report) => HttpResponse::UnprocessableEntity().json(report),
+        Err(e) => error_response(e),
+    }
+}
+
+#[post("/modules/{name}/{version}")]
+async fn upload_module(
+    http_request: HttpRequest,
+    path: web::Path<(String, String)>,
+    query: web::Query<UploadQuery>,
+    body: web::Bytes,
+) -> HttpResponse {
+    if let Err(response) = authorize(&http_request).await {
+        return response;
+    }
+
+    let (name, version) = path.into_inner();
+    let query = query.into_inner();
+
+    match validate(name.clone(), version.clone(), body.clone()).await {
+        Ok(report) if !report.is_valid() => {
+            return HttpResponse::UnprocessableEntity().json(report)
+        }
+        Err(e) => return error_response(e),
+        _ => {}
+    }
+
+    let result = async {
+        get_registry()
+            .await?
+            .upload(ModuleUpload {
+                name,
+                version,
+                data: body.to_vec(),
+                publisher: query.publisher,
+                signature: query.signature,
+            })
+            .await
+    }
+    .await;
+
+    match result {
+        Ok(record) => HttpResponse::Created().json(record),
+        Err(e) => error_response(e),
+    }
+}
+
+#[get("/modules/{name}")]
+async fn list_modules(path: web::Path<String>) -> HttpResponse {
+    let name = path.into_inner();
+
+    let result = async { get_registry().await?.list(name).await }.await;
+
+    match result {
+        Ok(records) => HttpResponse::Ok().json(records),
+        Err(e) => error_response(e),
+    }
+}
+
+#[post("/modules/{name}/{version}/yank")]
+async fn yank_module(
+    http_request: HttpRequest,
+    path: web::Path<(String, String)>,
+    request: web::Json<YankRequest>,
+) -> HttpResponse {
+    if let Err(response) = authorize(&http_request).await {
+        return response;
+    }
+
+    let (name, version) = path.into_inner();
+
+    let result = async {
+        get_registry()
+            .await?
+            .yank(name, version, request.yanked)
+            .await
+    }
+    .await;
+
+    match result {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+#[post("/modules/{name}/{version}/deprecate")]
+async fn deprecate_module(
+    http_request: HttpRequest,
+    path: web::Path<(String, String)>,
+    request: web::Json<DeprecateRequest>,
+) -> HttpResponse {
+    if let Err(response) = authorize(&http_request).await {
+        return response;
+    }
+
+    let (name, version) = path.into_inner();
+    let message = request.into_inner().message;
+
+    let result = async {
+        get_registry()
+            .await?
+            .deprecate(name, version, message)
+            .await
+    }
+    .await;
+
+    match result {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+super::register_routes!(app, {
+    app.service(
+        web::scope("/registry")
+            .app_data(web::PayloadConfig::new(64 * 1024 * 1024))
+            .service(validate_module)
+            .service(upload_module)
+            .service(list_modules)
+            .service(yank_module)
+            .service(deprecate_module),
+    )
+});
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, App};
+
+    use super::*;
+
+    #[test]
+    fn check_bearer_tokens() {
+        let api_tokens = vec!["token-a".to_string(), "token-b".to_string()];
+
+        assert!(is_authorized(&api_tokens, "token-b"));
+        assert!(!is_authorized(&api_tokens, "token-c"));
+        assert!(!is_authorized(&api_tokens, "token"));
+        assert!(!is_authorized(&[], "token-a"));
+
+        let request = test::TestRequest::default()
+            .insert_header((header::AUTHORIZATION, "Bearer token-a"))
+            .to_http_request();
+
+        assert_eq!(get_bearer_token(&request), Some("token-a"));
+
+        let request = test::TestRequest::default()
+            .insert_header((header::AUTHORIZATION, "Basic token-a"))
+            .to_http_request();
+
+        assert_eq!(get_bearer_token(&request), None);
+    }
+
+    #[actix_web::test]
+    async fn reject_unauthenticated_changes() {
+        let app = test::init_service(register_routes(App::new())).await;
+
+        let requests = [
+            test::TestRequest::post()
+                .uri("/registry/modules/mitsuha.test.echo/0.1.0/validate")
+                .set_payload(b"\0asm\x01\0\0\0".to_vec()),
+            test::TestRequest::post()
+                .uri("/registry/modules/mitsuha.test.echo/0.1.0")
+                .set_payload(b"\0asm\x01\0\0\0".to_vec()),
+            test::TestRequest::post()
+                .uri("/registry/modules/mitsuha.test.echo/0.1.0/yank")
+                .set_json(serde_json::json!({ "yanked": true })),
+            test::TestRequest::post()
+                .uri("/registry/modules/mitsuha.test.echo/0.1.0/deprecate")
+                .set_json(serde_json::json!({ "message": "deprecated" })),
+        ];
+
+        for request in requests {
+            let response = test::call_service(&app, request.to_request()).await;
+
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use async_trait::async_trait;
+use mitsuha_channel::compression::{CompressionAlgorithm, CompressionChannel};
+use mitsuha_core::errors::ToUnknownErrorResult;
+use mitsuha_core::{err_unsupported_op, errors::Error, types};
+
+use super::{initialize_channel, Plugin, PluginContext};
+
+const ALGORITHM_PROPERTY: &str = "algorithm";
+const LEVEL_PROPERTY: &str = "level";
+const MIN_SIZE_PROPERTY: &str = "min_size";
+const DEFAULT_ZSTD_LEVEL: i32 = 3;
+const DEFAULT_MIN_SIZE: usize = 1024;
+
+/// Appends a [CompressionChannel] to the chain. The `algorithm` is either `zstd` (the
+/// default, compressed at `level`) or `lz4`, and blobs smaller than `min_size` bytes are
+/// stored uncompressed.
+#[derive(Clone)]
+pub struct CompressionPlugin;
+
+#[async_trait]
+impl Plugin for CompressionPlugin {
+    fn name(&self) -> &'static str {
+        "mitsuha.plugin.compression"
+    }
+
+    async fn run(&self, mut ctx: PluginContext) -> types::Result<PluginContext> {
+        let properties = &ctx.current_properties;
+
+        let algorithm = match properties
+            .get(ALGORITHM_PROPERTY)
+            .map(|x| x.as_str())
+            .unwrap_or("zstd")
+        {
+            "zstd" => CompressionAlgorithm::Zstd {
+                level: match properties.get(LEVEL_PROPERTY) {
+                    Some(x) => x.parse().to_unknown_err_result()?,
+                    None => DEFAULT_ZSTD_LEVEL,
+                },
+            },
+            "lz4" => CompressionAlgorithm::Lz4,
+            algorithm => {
+                return Err(err_unsupported_op!(
+                    "unknown compression algorithm '{}'",
+                    algorithm
+                ))
+            }
+        };
+
+        let min_size = match properties.get(MIN_SIZE_PROPERTY) {
+            Some(x) => x.parse().to_unknown_err_result()?,
+            None => DEFAULT_MIN_SIZE,
+        };
+
+        let raw_channel = CompressionChannel::new(algorithm, min_size);
+
+        let channel = initialize_channel(&ctx, raw_channel).await?;
+
+        ctx.channel_end.connect(channel.clone()).await;
+        ctx.channel_end = channel;
+
+        Ok(ctx)
+    }
+}
@@ -1,9 +1,16 @@
 use async_trait::async_trait;
 use mitsuha_channel::enforcer::EnforcerChannel;
-use mitsuha_core::types;
+use mitsuha_core::selector::Label;
+use mitsuha_core::{err_unknown, errors::Error, types};
 
 use super::{initialize_channel, Plugin, PluginContext};
 
+const POLICY_LABEL_KEY_PROPERTY: &str = "policy.label.key";
+const POLICY_LABEL_VALUE_PROPERTY: &str = "policy.label.value";
+
+/// Appends an [EnforcerChannel] to the chain, reading the policy blob handle from the
+/// `policy_blob_key` extension. Policy blobs are loaded from the storage selected by the
+/// `policy.label.key`/`policy.label.value` label if set.
 #[derive(Clone)]
 pub struct EnforcerPlugin;
 
@@ -16,7 +23,24 @@ impl Plugin for EnforcerPlugin {
     async fn run(&self, mut ctx: PluginContext) -> types::Result<PluginContext> {
         let policy_blob_key = ctx.current_properties.get("policy_blob_key").unwrap();
 
-        let raw_channel = EnforcerChannel::new(policy_blob_key.clone());
+        let policy_selector = match (
+            ctx.current_properties.get(POLICY_LABEL_KEY_PROPERTY),
+            ctx.current_properties.get(POLICY_LABEL_VALUE_PROPERTY),
+        ) {
+            (Some(key), Some(value)) => Some(Label {
+                key: key.clone(),
+                value: value.clone(),
+            }),
+            (None, None) => None,
+            _ => {
+                return Err(err_unknown!(format!(
+                    "enforcer plugin properties '{}' and '{}' must be set together",
+                    POLICY_LABEL_KEY_PROPERTY, POLICY_LABEL_VALUE_PROPERTY
+                )))
+            }
+        };
+
+        let raw_channel = EnforcerChannel::new(policy_blob_key.clone(), policy_selector);
         let channel = initialize_channel(&ctx, raw_channel).await?;
 
         ctx.channel_end.connect(channel.clone()).await;
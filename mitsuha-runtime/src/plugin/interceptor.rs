@@ -1,11 +1,23 @@
+use std::time::Duration;
+
 use async_trait::async_trait;
-use mitsuha_channel::interceptor::InterceptorChannel;
+use mitsuha_channel::interceptor::{InterceptorChannel, InterceptorEndpoint, InterceptorPolicy};
 use mitsuha_core::errors::ToUnknownErrorResult;
 use mitsuha_core::{errors::Error, types};
-use mitsuha_runtime_rpc::proto::channel::interceptor_client::InterceptorClient;
 
 use super::{initialize_channel, Plugin, PluginContext};
 
+const ADDRESS_PROPERTY: &str = "address";
+const ADDRESS_PREFIX: &str = "addresses.";
+const TIMEOUT_PROPERTY: &str = "timeout_ms";
+const FAIL_OPEN_PROPERTY: &str = "fail_open";
+const REPROBE_INTERVAL_PROPERTY: &str = "reprobe_interval_ms";
+
+/// Appends an [InterceptorChannel] to the chain, calling the interceptor at `address`
+/// followed by the ones at `addresses.<index>`. Every call is bounded by `timeout_ms`
+/// (defaults to 5000), and failing interceptors are bypassed when `fail_open` is `true`.
+/// A phase an interceptor answers with `UNIMPLEMENTED` is skipped for `reprobe_interval_ms`
+/// (defaults to 60000).
 #[derive(Clone)]
 pub struct InterceptorPlugin;
 
@@ -16,15 +28,42 @@ impl Plugin for InterceptorPlugin {
     }
 
     async fn run(&self, mut ctx: PluginContext) -> types::Result<PluginContext> {
-        let target_address = ctx.current_properties.get("address").unwrap();
+        let properties = &ctx.current_properties;
+
+        let mut endpoints = Vec::new();
+
+        if let Some(address) = properties.get(ADDRESS_PROPERTY) {
+            endpoints.push(InterceptorEndpoint::new(address.clone())?);
+        }
+
+        let mut index = 0;
+
+        while let Some(address) = properties.get(&format!("{}{}", ADDRESS_PREFIX, index)) {
+            endpoints.push(InterceptorEndpoint::new(address.clone())?);
+            index += 1;
+        }
+
+        if endpoints.is_empty() {
+            return Err(Error::UnknownWithMsgOnly {
+                message: "interceptor channel requires at least one address".to_string(),
+            });
+        }
+
+        let mut policy = InterceptorPolicy::default();
+
+        if let Some(x) = properties.get(TIMEOUT_PROPERTY) {
+            policy.timeout = Duration::from_millis(x.parse().to_unknown_err_result()?);
+        }
 
-        let conn = tonic::transport::Endpoint::new(target_address.clone())
-            .to_unknown_err_result()?
-            .connect_lazy();
+        if let Some(x) = properties.get(FAIL_OPEN_PROPERTY) {
+            policy.fail_open = x.parse().to_unknown_err_result()?;
+        }
 
-        let client = InterceptorClient::new(conn);
+        if let Some(x) = properties.get(REPROBE_INTERVAL_PROPERTY) {
+            policy.reprobe_interval = Duration::from_millis(x.parse().to_unknown_err_result()?);
+        }
 
-        let raw_channel = InterceptorChannel::new(client);
+        let raw_channel = InterceptorChannel::new(endpoints, policy);
         let channel = initialize_channel(&ctx, raw_channel).await?;
 
         ctx.channel_end.connect(channel.clone()).await;
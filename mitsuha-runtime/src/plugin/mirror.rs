@@ -0,0 +1,69 @@
+use async_trait::async_trait;
+use mitsuha_channel::mirror::MirrorChannel;
+use mitsuha_core::errors::ToUnknownErrorResult;
+use mitsuha_core::selector::Label;
+use mitsuha_core::{err_unknown, errors::Error, types};
+
+use super::{initialize_channel, Plugin, PluginContext};
+
+const PRIMARY_PREFIX: &str = "primary";
+const SECONDARY_PREFIX: &str = "secondary";
+const LABEL_KEY_SUFFIX: &str = ".label.key";
+const LABEL_VALUE_SUFFIX: &str = ".label.value";
+const VERIFY_LOADS_PROPERTY: &str = "verify_loads";
+
+/// Appends a [MirrorChannel] to the chain, writing to the storages selected by the
+/// `primary.label.key`/`primary.label.value` and `secondary.label.key`/`secondary.label.value`
+/// labels. Loads are compared against the secondary when `verify_loads` is `true`.
+#[derive(Clone)]
+pub struct MirrorPlugin;
+
+#[async_trait]
+impl Plugin for MirrorPlugin {
+    fn name(&self) -> &'static str {
+        "mitsuha.plugin.mirror"
+    }
+
+    async fn run(&self, mut ctx: PluginContext) -> types::Result<PluginContext> {
+        let storage = ctx.get_storage().await?;
+
+        let primary_selector = self.get_label(&ctx, PRIMARY_PREFIX)?;
+        let secondary_selector = self.get_label(&ctx, SECONDARY_PREFIX)?;
+
+        let verify_loads = match ctx.current_properties.get(VERIFY_LOADS_PROPERTY) {
+            Some(x) => x.parse().to_unknown_err_result()?,
+            None => false,
+        };
+
+        let raw_channel =
+            MirrorChannel::new(storage, primary_selector, secondary_selector, verify_loads);
+
+        let channel = initialize_channel(&ctx, raw_channel).await?;
+
+        ctx.channel_end.connect(channel.clone()).await;
+        ctx.channel_end = channel;
+
+        Ok(ctx)
+    }
+}
+
+impl MirrorPlugin {
+    fn get_label(&self, ctx: &PluginContext, prefix: &str) -> types::Result<Label> {
+        let get_property = |suffix: &str| {
+            let property = format!("{}{}", prefix, suffix);
+
+            ctx.current_properties
+                .get(&property)
+                .cloned()
+                .ok_or(err_unknown!(format!(
+                    "mirror plugin property '{}' is missing",
+                    property
+                )))
+        };
+
+        Ok(Label {
+            key: get_property(LABEL_KEY_SUFFIX)?,
+            value: get_property(LABEL_VALUE_SUFFIX)?,
+        })
+    }
+}
@@ -1,42 +1,85 @@
-use std::{collections::HashMap, sync::Arc};
+use std::future::Future;
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 
 use crate::plugin::muxed_storage::MuxedStoragePlugin;
 use crate::plugin::scheduler::SchedulerPlugin;
 use async_trait::async_trait;
-use mitsuha_channel::{EntrypointChannel, WrappedComputeChannel};
-use mitsuha_core::channel::{ChannelContext, ChannelManager};
-use mitsuha_core::job::mgr::JobManager;
+use lazy_static::lazy_static;
+use mitsuha_channel::{router::RouterChannel, EntrypointChannel, WrappedComputeChannel};
+use mitsuha_core::channel::{ChannelContext, ChannelManager, ChannelMap};
+use mitsuha_core::errors::ToUnknownErrorResult;
+use mitsuha_core::job::{ctrl::PostJobHook, mgr::JobManager};
+use mitsuha_core::storage::GarbageCollectionHook;
+use mitsuha_core::storage::Storage;
 use mitsuha_core::{
     channel::ComputeChannel, config::Config, constants::Constants, err_unsupported_op,
-    errors::Error, types,
+    errors::Error, storage, types,
 };
+use mitsuha_storage::UnifiedStorage;
 
 use self::{
     common::{EofPlugin, SystemPlugin},
+    compression::CompressionPlugin,
     delegator::DelegatorPlugin,
     enforcer::EnforcerPlugin,
     interceptor::InterceptorPlugin,
+    mirror::MirrorPlugin,
     namespacer::NamespacerPlugin,
     one_storage::OneStoragePlugin,
+    quota::QuotaPlugin,
+    rate_limiter::RateLimiterPlugin,
+    remote::RemotePlugin,
+    resilience::ResiliencePlugin,
+    router::{BranchPlugin, RouterPlugin},
     wasmtime::WasmtimePlugin,
 };
 
 pub mod common;
+pub mod compression;
 pub mod delegator;
 pub mod enforcer;
 pub mod interceptor;
+pub mod mirror;
 pub mod muxed_storage;
 pub mod namespacer;
 pub mod one_storage;
+pub mod quota;
+pub mod rate_limiter;
+pub mod remote;
+pub mod resilience;
+pub mod router;
 mod scheduler;
 pub mod wasmtime;
 
+/// A long-lived component started by a plugin, such as a storage or a scheduler.
+#[derive(Clone)]
+struct Component {
+    value: Arc<dyn Any + Send + Sync>,
+    stop: Arc<dyn Fn() + Send + Sync>,
+}
+
+lazy_static! {
+    /// Components used by the active chain, keyed by what they were started from.
+    static ref RUNNING_COMPONENTS: Mutex<HashMap<String, Component>> = Default::default();
+}
+
+/// Channels and hooks registered by plugins are kept in the context until
+/// [PluginContext::activate] makes them the ones in use.
 #[derive(Clone)]
 pub struct PluginContext {
     pub channel_start: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>,
     pub channel_end: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>,
     pub config: Config,
     pub current_properties: HashMap<String, String>,
+    pub channel_map: Arc<ChannelMap>,
+    pub post_job_hooks: Vec<Arc<dyn PostJobHook<ChannelContext>>>,
+    pub gc_hooks: Vec<Arc<dyn GarbageCollectionHook>>,
+    components: Arc<Mutex<HashMap<String, Component>>>,
+    head: Option<Arc<Box<dyn ComputeChannel<Context = ChannelContext>>>>,
 }
 
 impl PluginContext {
@@ -66,12 +109,169 @@ impl PluginContext {
             channel_end: init_channel.clone(),
             config,
             current_properties: properties,
+            channel_map: Default::default(),
+            post_job_hooks: vec![],
+            gc_hooks: vec![],
+            components: Default::default(),
+            head: None,
         })
     }
 
+    /// Creates a context for building a new chain behind the running entrypoint. The chain is
+    /// built from a detached head, so nothing is routed to it until the context is activated.
+    pub async fn reload(config: Config) -> types::Result<Self> {
+        let channel_start = ChannelManager::global_rw()
+            .read()
+            .await
+            .channel_start
+            .clone()
+            .ok_or(err_unsupported_op!(
+                "cannot reload plugins before the channel chain was loaded"
+            ))?;
+
+        let channel_end: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> =
+            Arc::new(Box::new(RouterChannel::new(vec![]).with_id(format!(
+                "{}/{}",
+                RouterChannel::get_identifier_type(),
+                uuid::Uuid::new_v4()
+            ))));
+
+        Ok(Self {
+            channel_start,
+            channel_end: channel_end.clone(),
+            config,
+            current_properties: Default::default(),
+            channel_map: Default::default(),
+            post_job_hooks: vec![],
+            gc_hooks: vec![],
+            components: Default::default(),
+            head: Some(channel_end),
+        })
+    }
+
+    pub fn add_post_job_hook(&mut self, hook: Arc<dyn PostJobHook<ChannelContext>>) {
+        self.post_job_hooks.push(hook);
+    }
+
+    pub fn add_gc_hook(&mut self, hook: Arc<dyn GarbageCollectionHook>) {
+        self.gc_hooks.push(hook);
+    }
+
+    /// Returns the component started under `key`. The component of the active chain is reused
+    /// if there is one, so that reloads keep storages and schedulers running instead of
+    /// starting them again. Otherwise it is started, and stopped with `stop` once an activated
+    /// chain no longer uses it.
+    pub async fn get_or_start<T, F, Fut>(
+        &self,
+        key: String,
+        start: F,
+        stop: fn(&T),
+    ) -> types::Result<T>
+    where
+        T: 'static + Clone + Send + Sync,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = types::Result<T>>,
+    {
+        let mut component = self.components.lock().unwrap().get(&key).cloned();
+
+        if component.is_none() {
+            component = RUNNING_COMPONENTS.lock().unwrap().get(&key).cloned();
+        }
+
+        let component = match component {
+            Some(component) => component,
+            None => {
+                let value = start().await?;
+                let stopped = value.clone();
+
+                Component {
+                    value: Arc::new(value),
+                    stop: Arc::new(move || stop(&stopped)),
+                }
+            }
+        };
+
+        let value = component
+            .value
+            .downcast_ref::<T>()
+            .cloned()
+            .ok_or(err_unsupported_op!(format!(
+                "component '{}' was started with a different type",
+                key
+            )))?;
+
+        self.components.lock().unwrap().insert(key, component);
+
+        Ok(value)
+    }
+
+    /// Returns the storage built from the storage config. It is shared by all plugins, and
+    /// kept across reloads as long as the storage config does not change, so that memory
+    /// storages keep their data.
+    pub async fn get_storage(&self) -> types::Result<Arc<Box<dyn Storage>>> {
+        let classes = serde_json::to_value(&self.config.storage.classes).to_unknown_err_result()?;
+
+        self.get_or_start(
+            format!("storage/{}", classes),
+            || UnifiedStorage::new(&self.config.storage),
+            |_| {},
+        )
+        .await
+    }
+
+    /// Replaces the registered channels, post job hooks and gc hooks with the ones of this
+    /// context, and connects the entrypoint to the head of a reloaded chain, all while holding
+    /// the channel manager lock so that routed inputs never see a mix of both chains.
+    ///
+    /// Components of the replaced chain that this context did not reuse are stopped.
+    pub async fn activate(self) {
+        let channel_manager = ChannelManager::global_rw();
+        let mut channel_manager = channel_manager.write().await;
+
+        channel_manager.channel_map = self.channel_map;
+
+        channel_manager
+            .get_job_mgr()
+            .replace_post_job_hooks(self.post_job_hooks)
+            .await;
+
+        storage::replace_gc_hooks(self.gc_hooks).await;
+
+        if let Some(head) = self.head {
+            self.channel_start.connect(head).await;
+        }
+
+        let components = std::mem::take(&mut *self.components.lock().unwrap());
+        let mut running = RUNNING_COMPONENTS.lock().unwrap();
+
+        for (key, component) in running.iter() {
+            if !components.contains_key(key) {
+                tracing::info!("stopping component '{}' of the replaced chain", key);
+
+                (component.stop)();
+            }
+        }
+
+        *running = components;
+    }
+
+    /// Stops the components started for a chain that is not going to be activated.
+    fn discard(&self) {
+        let components = std::mem::take(&mut *self.components.lock().unwrap());
+        let running = RUNNING_COMPONENTS.lock().unwrap();
+
+        for (key, component) in components {
+            if !running.contains_key(&key) {
+                (component.stop)();
+            }
+        }
+    }
+
     fn merge(&mut self, value: PluginContext) {
         self.channel_start = value.channel_start;
         self.channel_end = value.channel_end;
+        self.post_job_hooks = value.post_job_hooks;
+        self.gc_hooks = value.gc_hooks;
     }
 }
 
@@ -94,6 +294,14 @@ pub async fn load_plugins(mut ctx: PluginContext) -> types::Result<PluginContext
         Box::new(EnforcerPlugin),
         Box::new(MuxedStoragePlugin),
         Box::new(SchedulerPlugin),
+        Box::new(RouterPlugin),
+        Box::new(BranchPlugin),
+        Box::new(RateLimiterPlugin),
+        Box::new(QuotaPlugin),
+        Box::new(RemotePlugin),
+        Box::new(ResiliencePlugin),
+        Box::new(CompressionPlugin),
+        Box::new(MirrorPlugin),
     ];
 
     let plugin_map: HashMap<&'static str, Box<dyn Plugin>> = plugin_list
@@ -119,6 +327,31 @@ pub async fn load_plugins(mut ctx: PluginContext) -> types::Result<PluginContext
     Ok(ctx)
 }
 
+/// Builds a new channel chain from the plugins in `config` and swaps it in behind the
+/// entrypoint, which stays the same so that the job manager and the contexts of running jobs
+/// remain valid. Computations already past the entrypoint finish on the old chain.
+///
+/// Channels, post job hooks and gc hooks are replaced by the ones registered while loading,
+/// once the chain is built. Nothing is replaced if loading fails, leaving the old chain in
+/// place. Storages and schedulers started with the same configuration as before are reused,
+/// the ones the new chain does not use are stopped.
+pub async fn reload_plugins(config: Config) -> types::Result<()> {
+    let ctx = PluginContext::reload(config).await?;
+
+    match load_plugins(ctx.clone()).await {
+        Ok(ctx) => {
+            ctx.activate().await;
+
+            Ok(())
+        }
+        Err(e) => {
+            ctx.discard();
+
+            Err(e)
+        }
+    }
+}
+
 pub async fn initialize_channel<T>(
     ctx: &PluginContext,
     mut chan: WrappedComputeChannel<T>,
@@ -141,11 +374,155 @@ where
     let boxed_chan: Arc<Box<dyn ComputeChannel<Context = ChannelContext>>> =
         Arc::new(Box::new(chan));
 
-    ChannelManager::global_rw()
-        .read()
-        .await
-        .channel_map
-        .insert(boxed_chan.id(), boxed_chan.clone());
+    ctx.channel_map.insert(boxed_chan.id(), boxed_chan.clone());
 
     Ok(boxed_chan)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use mitsuha_core::config::plugin::Plugin as PluginConfig;
+
+    use super::*;
+
+    const QUOTA_PLUGIN: &str = "mitsuha.plugin.quota";
+    const EOF_PLUGIN: &str = "mitsuha.plugin.eof";
+
+    fn make_config(plugins: &[(&str, &str)]) -> Config {
+        let mut config: Config = serde_json::from_value(serde_json::json!({
+            "instance": { "id": "test" },
+            "api": { "address": "127.0.0.1", "rpcPort": 0, "httpPort": 0 },
+            "job": {
+                "maximumConcurrentCost": { "compute": 1024 },
+                "costEvaluatorType": "standard",
+                "scheduler": { "coreSchedulingCapacity": { "compute": 1024 } }
+            },
+            "storage": { "classes": [] },
+            "plugins": [],
+            "telemetry": {},
+            "persistence": { "databaseConnectionString": "" }
+        }))
+        .unwrap();
+
+        config.plugins = plugins
+            .iter()
+            .map(|(name, id)| PluginConfig {
+                name: name.to_string(),
+                properties: [
+                    (Constants::ChannelId.to_string(), id.to_string()),
+                    ("mode".to_string(), "local".to_string()),
+                ]
+                .into_iter()
+                .collect(),
+            })
+            .collect();
+
+        config
+    }
+
+    async fn get_channel_ids() -> HashSet<String> {
+        ChannelManager::global_rw()
+            .read()
+            .await
+            .channel_map
+            .iter()
+            .map(|x| x.key().clone())
+            .collect()
+    }
+
+    /// Returns the number of post job hooks and gc hooks in use.
+    async fn count_hooks() -> (usize, usize) {
+        let job_manager = ChannelManager::global().await.get_job_mgr().clone();
+
+        let post_job_hooks = job_manager.replace_post_job_hooks(vec![]).await;
+        let gc_hooks = storage::replace_gc_hooks(vec![]).await;
+
+        let counts = (post_job_hooks.len(), gc_hooks.len());
+
+        job_manager.replace_post_job_hooks(post_job_hooks).await;
+        storage::replace_gc_hooks(gc_hooks).await;
+
+        counts
+    }
+
+    #[tokio::test]
+    async fn reload_and_rollback() {
+        let config = make_config(&[(QUOTA_PLUGIN, "quota-0"), (EOF_PLUGIN, "eof-0")]);
+
+        let ctx = PluginContext::new(config, Default::default())
+            .await
+            .unwrap();
+        load_plugins(ctx).await.unwrap().activate().await;
+
+        assert_eq!(
+            get_channel_ids().await,
+            HashSet::from(["quota-0".to_string(), "eof-0".to_string()])
+        );
+        assert_eq!(count_hooks().await, (1, 1));
+
+        // Channels and hooks of removed plugins are dropped.
+        reload_plugins(make_config(&[(EOF_PLUGIN, "eof-1")]))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            get_channel_ids().await,
+            HashSet::from(["eof-1".to_string()])
+        );
+        assert_eq!(count_hooks().await, (0, 0));
+
+        // A failed reload leaves the previous chain in place.
+        let result = reload_plugins(make_config(&[
+            (QUOTA_PLUGIN, "quota-2"),
+            ("mitsuha.plugin.missing", "missing-2"),
+        ]))
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            get_channel_ids().await,
+            HashSet::from(["eof-1".to_string()])
+        );
+        assert_eq!(count_hooks().await, (0, 0));
+
+        // Components are reused by reloads that start them again, and stopped once the chain
+        // using them is replaced or never activated.
+        let start = || async { Ok(Arc::new(AtomicUsize::new(0))) };
+        let stop = |stops: &Arc<AtomicUsize>| {
+            stops.fetch_add(1, Ordering::Relaxed);
+        };
+
+        let ctx = PluginContext::reload(make_config(&[])).await.unwrap();
+        let first = ctx
+            .get_or_start("test/first".to_string(), start, stop)
+            .await
+            .unwrap();
+        ctx.activate().await;
+
+        let ctx = PluginContext::reload(make_config(&[])).await.unwrap();
+        let reused = ctx
+            .get_or_start("test/first".to_string(), start, stop)
+            .await
+            .unwrap();
+        let second = ctx
+            .get_or_start("test/second".to_string(), start, stop)
+            .await
+            .unwrap();
+        ctx.discard();
+
+        assert!(Arc::ptr_eq(&first, &reused));
+        assert_eq!(first.load(Ordering::Relaxed), 0);
+        assert_eq!(second.load(Ordering::Relaxed), 1);
+
+        PluginContext::reload(make_config(&[]))
+            .await
+            .unwrap()
+            .activate()
+            .await;
+
+        assert_eq!(first.load(Ordering::Relaxed), 1);
+    }
+}
@@ -4,7 +4,6 @@ use mitsuha_channel::muxed_storage::Rule;
 use mitsuha_core::errors::ToUnknownErrorResult;
 use mitsuha_core::selector::Label;
 use mitsuha_core::{err_unknown, errors::Error, types};
-use mitsuha_storage::UnifiedStorage;
 use regex::Regex;
 
 use super::{initialize_channel, Plugin, PluginContext};
@@ -24,7 +23,7 @@ impl Plugin for MuxedStoragePlugin {
     }
 
     async fn run(&self, mut ctx: PluginContext) -> types::Result<PluginContext> {
-        let storage = UnifiedStorage::new(&ctx.config.storage).await?;
+        let storage = ctx.get_storage().await?;
 
         let raw_channel = MuxedStorageChannel::new(storage, self.build_muxing_rules(&ctx)?);
 
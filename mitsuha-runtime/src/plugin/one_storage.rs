@@ -1,7 +1,6 @@
 use async_trait::async_trait;
 use mitsuha_channel::labeled_storage::LabeledStorageChannel;
 use mitsuha_core::types;
-use mitsuha_storage::UnifiedStorage;
 
 use super::{initialize_channel, Plugin, PluginContext};
 
@@ -15,7 +14,7 @@ impl Plugin for OneStoragePlugin {
     }
 
     async fn run(&self, mut ctx: PluginContext) -> types::Result<PluginContext> {
-        let storage = UnifiedStorage::new(&ctx.config.storage).await?;
+        let storage = ctx.get_storage().await?;
 
         let raw_channel = LabeledStorageChannel::new(
             storage,
@@ -0,0 +1,111 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use mitsuha_channel::quota::{
+    MemoryQuotaStore, PersistentQuotaStore, Quota, QuotaChannel, QuotaGarbageCollectionHook,
+    QuotaPostJobHook, QuotaStore,
+};
+use mitsuha_core::errors::ToUnknownErrorResult;
+use mitsuha_core::{err_unsupported_op, types};
+
+use super::{initialize_channel, Plugin, PluginContext};
+
+const MODE_PROPERTY: &str = "mode";
+const DEFAULT_PREFIX: &str = "default.";
+const NAMESPACE_PREFIX: &str = "namespaces.";
+
+/// Appends a [QuotaChannel] to the chain and registers the hooks releasing usage on job
+/// completion and garbage collection. Usage is kept in the persistence layer in the `cluster`
+/// mode (the default) and in memory in the `local` mode.
+///
+/// Limits are read from `default.<limit>` and overridden per namespace by
+/// `namespaces.<namespace>.<limit>`, where `<limit>` is one of `max_bytes`, `max_blobs`,
+/// `max_ttl` or `max_compute`.
+#[derive(Clone)]
+pub struct QuotaPlugin;
+
+#[async_trait]
+impl Plugin for QuotaPlugin {
+    fn name(&self) -> &'static str {
+        "mitsuha.plugin.quota"
+    }
+
+    async fn run(&self, mut ctx: PluginContext) -> types::Result<PluginContext> {
+        let properties = &ctx.current_properties;
+
+        let store: Arc<Box<dyn QuotaStore>> = match properties
+            .get(MODE_PROPERTY)
+            .map(|x| x.as_str())
+            .unwrap_or("cluster")
+        {
+            "local" => Arc::new(Box::new(MemoryQuotaStore::default())),
+            "cluster" => Arc::new(Box::new(PersistentQuotaStore::new(
+                mitsuha_persistence::database_connection(),
+            ))),
+            mode => return Err(err_unsupported_op!("unknown quota mode '{}'", mode)),
+        };
+
+        let (default_quota, quotas) = self.build_quotas(properties)?;
+
+        ctx.add_post_job_hook(Arc::new(QuotaPostJobHook::new(store.clone())));
+        ctx.add_gc_hook(Arc::new(QuotaGarbageCollectionHook::new(store.clone())));
+
+        let raw_channel = QuotaChannel::new(default_quota, quotas, store);
+
+        let channel = initialize_channel(&ctx, raw_channel).await?;
+
+        ctx.channel_end.connect(channel.clone()).await;
+        ctx.channel_end = channel;
+
+        Ok(ctx)
+    }
+}
+
+impl QuotaPlugin {
+    fn set_limit(&self, quota: &mut Quota, limit: &str, value: &str) -> types::Result<()> {
+        let value = Some(value.parse().to_unknown_err_result()?);
+
+        match limit {
+            "max_bytes" => quota.max_bytes = value,
+            "max_blobs" => quota.max_blobs = value,
+            "max_ttl" => quota.max_ttl = value,
+            "max_compute" => quota.max_compute = value,
+            limit => return Err(err_unsupported_op!("unknown quota limit '{}'", limit)),
+        }
+
+        Ok(())
+    }
+
+    fn build_quotas(
+        &self,
+        properties: &HashMap<String, String>,
+    ) -> types::Result<(Quota, HashMap<String, Quota>)> {
+        let mut default_quota = Quota::default();
+
+        for (property, value) in properties.iter() {
+            if let Some(limit) = property.strip_prefix(DEFAULT_PREFIX) {
+                self.set_limit(&mut default_quota, limit, value)?;
+            }
+        }
+
+        let mut quotas: HashMap<String, Quota> = HashMap::new();
+
+        for (property, value) in properties.iter() {
+            let (namespace, limit) = match property
+                .strip_prefix(NAMESPACE_PREFIX)
+                .and_then(|x| x.rsplit_once('.'))
+            {
+                Some(x) => x,
+                None => continue,
+            };
+
+            let quota = quotas
+                .entry(namespace.to_string())
+                .or_insert_with(|| default_quota.clone());
+
+            self.set_limit(quota, limit, value)?;
+        }
+
+        Ok((default_quota, quotas))
+    }
+}
@@ -0,0 +1,157 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use mitsuha_channel::rate_limiter::{
+    MemoryTokenBucketStore, PersistentTokenBucketStore, RateLimit, RateLimitKey,
+    RateLimiterChannel, Rule, TokenBucketStore,
+};
+use mitsuha_core::constants::Constants;
+use mitsuha_core::errors::ToUnknownErrorResult;
+use mitsuha_core::{err_unsupported_op, errors::Error, types};
+
+use super::{initialize_channel, Plugin, PluginContext};
+
+const MODE_PROPERTY: &str = "mode";
+const MAX_BUCKETS_PROPERTY: &str = "max_buckets";
+const BUCKET_IDLE_TTL_PROPERTY: &str = "bucket_idle_ttl";
+const RULE_PREFIX: &str = "rules.";
+const RULE_OPKIND_SUFFIX: &str = ".opkind";
+const RULE_KEY_SUFFIX: &str = ".key";
+const RULE_KEY_DELIMITER_SUFFIX: &str = ".key.delimiter";
+const RULE_KEY_EXTENSION_SUFFIX: &str = ".key.extension";
+const RULE_CAPACITY_SUFFIX: &str = ".capacity";
+const RULE_REFILL_RATE_SUFFIX: &str = ".refill_rate";
+
+/// Appends a [RateLimiterChannel] to the chain. Buckets are kept in memory in the `local`
+/// mode (the default) and in the persistence layer in the `cluster` mode. In the `local` mode,
+/// at most `max_buckets` buckets (100000 by default) are kept, for up to `bucket_idle_ttl`
+/// seconds (3600 by default) after their last use.
+///
+/// Rules are read from `rules.<index>.key`, which is one of `namespace`, `handle_prefix` or
+/// `extension`, along with `rules.<index>.capacity`, `rules.<index>.refill_rate` (tokens per
+/// second) and the optional `rules.<index>.opkind`, `rules.<index>.key.delimiter` (defaults to
+/// `/`) and `rules.<index>.key.extension`.
+#[derive(Clone)]
+pub struct RateLimiterPlugin;
+
+#[async_trait]
+impl Plugin for RateLimiterPlugin {
+    fn name(&self) -> &'static str {
+        "mitsuha.plugin.rate_limiter"
+    }
+
+    async fn run(&self, mut ctx: PluginContext) -> types::Result<PluginContext> {
+        let properties = &ctx.current_properties;
+
+        let store: Arc<Box<dyn TokenBucketStore>> = match properties
+            .get(MODE_PROPERTY)
+            .map(|x| x.as_str())
+            .unwrap_or("local")
+        {
+            "local" => Arc::new(Box::new(MemoryTokenBucketStore::new(
+                properties
+                    .get(MAX_BUCKETS_PROPERTY)
+                    .map(|x| x.parse())
+                    .transpose()
+                    .to_unknown_err_result()?
+                    .unwrap_or(100_000),
+                Duration::from_secs(
+                    properties
+                        .get(BUCKET_IDLE_TTL_PROPERTY)
+                        .map(|x| x.parse())
+                        .transpose()
+                        .to_unknown_err_result()?
+                        .unwrap_or(3600),
+                ),
+            ))),
+            "cluster" => Arc::new(Box::new(PersistentTokenBucketStore::new(
+                mitsuha_persistence::database_connection(),
+            ))),
+            mode => return Err(err_unsupported_op!("unknown rate limiter mode '{}'", mode)),
+        };
+
+        let scope = properties
+            .get(&Constants::ChannelId.to_string())
+            .cloned()
+            .unwrap_or_default();
+
+        let raw_channel = RateLimiterChannel::new(scope, self.build_rules(properties)?, store);
+
+        let channel = initialize_channel(&ctx, raw_channel).await?;
+
+        ctx.channel_end.connect(channel.clone()).await;
+        ctx.channel_end = channel;
+
+        Ok(ctx)
+    }
+}
+
+impl RateLimiterPlugin {
+    fn get_rule_property(&self, index: u64, suffix: &str) -> String {
+        format!("{}{}{}", RULE_PREFIX, index, suffix)
+    }
+
+    fn get_required_property(
+        &self,
+        properties: &HashMap<String, String>,
+        property: String,
+    ) -> types::Result<String> {
+        properties
+            .get(&property)
+            .cloned()
+            .ok_or(Error::UnknownWithMsgOnly {
+                message: format!("rate limiter property '{}' is missing", property),
+            })
+    }
+
+    fn build_rules(&self, properties: &HashMap<String, String>) -> types::Result<Vec<Rule>> {
+        let mut rules = Vec::new();
+        let mut index = 0u64;
+
+        while let Some(key) = properties.get(&self.get_rule_property(index, RULE_KEY_SUFFIX)) {
+            let key = match key.as_str() {
+                "namespace" => RateLimitKey::Namespace,
+                "handle_prefix" => RateLimitKey::HandlePrefix {
+                    delimiter: properties
+                        .get(&self.get_rule_property(index, RULE_KEY_DELIMITER_SUFFIX))
+                        .cloned()
+                        .unwrap_or_else(|| "/".to_string()),
+                },
+                "extension" => RateLimitKey::Extension(self.get_required_property(
+                    properties,
+                    self.get_rule_property(index, RULE_KEY_EXTENSION_SUFFIX),
+                )?),
+                key => return Err(err_unsupported_op!("unknown rate limit key '{}'", key)),
+            };
+
+            let limit = RateLimit {
+                capacity: self
+                    .get_required_property(
+                        properties,
+                        self.get_rule_property(index, RULE_CAPACITY_SUFFIX),
+                    )?
+                    .parse()
+                    .to_unknown_err_result()?,
+                refill_rate: self
+                    .get_required_property(
+                        properties,
+                        self.get_rule_property(index, RULE_REFILL_RATE_SUFFIX),
+                    )?
+                    .parse()
+                    .to_unknown_err_result()?,
+            };
+
+            rules.push(Rule {
+                opkind: properties
+                    .get(&self.get_rule_property(index, RULE_OPKIND_SUFFIX))
+                    .cloned(),
+                key,
+                limit,
+            });
+
+            index += 1;
+        }
+
+        Ok(rules)
+    }
+}
@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use mitsuha_channel::remote::{LoadBalancingStrategy, RemoteChannel, RemoteEndpoint};
+use mitsuha_core::errors::ToUnknownErrorResult;
+use mitsuha_core::{err_unsupported_op, errors::Error, types};
+
+use super::{initialize_channel, Plugin, PluginContext};
+
+const ENDPOINT_PREFIX: &str = "endpoints.";
+const STRATEGY_PROPERTY: &str = "strategy";
+const HEALTH_CHECK_INTERVAL_PROPERTY: &str = "health_check_interval_ms";
+const DEFAULT_HEALTH_CHECK_INTERVAL_MS: u64 = 5000;
+
+/// Appends a [RemoteChannel] to the chain, forwarding everything reaching it to the peer
+/// runtimes at `endpoints.<index>`. The `strategy` is one of `round_robin` (the default),
+/// `least_requests` or `random`, and endpoints are health checked every
+/// `health_check_interval_ms`.
+///
+/// Placed on a sub-chain started by the branch plugin, it can serve as the slave of a
+/// delegator to offload jobs to peers.
+#[derive(Clone)]
+pub struct RemotePlugin;
+
+#[async_trait]
+impl Plugin for RemotePlugin {
+    fn name(&self) -> &'static str {
+        "mitsuha.plugin.remote"
+    }
+
+    async fn run(&self, mut ctx: PluginContext) -> types::Result<PluginContext> {
+        let properties = &ctx.current_properties;
+
+        let mut endpoints = Vec::new();
+
+        while let Some(address) = properties.get(&format!("{}{}", ENDPOINT_PREFIX, endpoints.len()))
+        {
+            endpoints.push(RemoteEndpoint::new(address.clone())?);
+        }
+
+        if endpoints.is_empty() {
+            return Err(Error::UnknownWithMsgOnly {
+                message: "remote channel requires at least one endpoint".to_string(),
+            });
+        }
+
+        let strategy = match properties
+            .get(STRATEGY_PROPERTY)
+            .map(|x| x.as_str())
+            .unwrap_or("round_robin")
+        {
+            "round_robin" => LoadBalancingStrategy::RoundRobin,
+            "least_requests" => LoadBalancingStrategy::LeastRequests,
+            "random" => LoadBalancingStrategy::Random,
+            strategy => {
+                return Err(err_unsupported_op!(
+                    "unknown load balancing strategy '{}'",
+                    strategy
+                ))
+            }
+        };
+
+        let health_check_interval = match properties.get(HEALTH_CHECK_INTERVAL_PROPERTY) {
+            Some(x) => x.parse().to_unknown_err_result()?,
+            None => DEFAULT_HEALTH_CHECK_INTERVAL_MS,
+        };
+
+        let raw_channel = RemoteChannel::new(
+            endpoints,
+            strategy,
+            Duration::from_millis(health_check_interval),
+        );
+
+        let channel = initialize_channel(&ctx, raw_channel).await?;
+
+        ctx.channel_end.connect(channel.clone()).await;
+        ctx.channel_end = channel;
+
+        Ok(ctx)
+    }
+}
@@ -0,0 +1,94 @@
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+use async_trait::async_trait;
+use mitsuha_channel::resilience::{CircuitBreakerPolicy, ResilienceChannel, RetryPolicy};
+use mitsuha_core::errors::ToUnknownErrorResult;
+use mitsuha_core::types;
+
+use super::{initialize_channel, Plugin, PluginContext};
+
+/// Appends a [ResilienceChannel] to the chain. The retry policy is read from `retry.opkinds`
+/// (comma separated, defaults to `load,status,persist`), `retry.max_retries`,
+/// `retry.initial_interval_ms`, `retry.max_interval_ms`, `retry.multiplier` and
+/// `retry.randomization_factor`, and the circuit breaker policy from
+/// `breaker.failure_threshold` and `breaker.open_duration_ms`. Unset properties keep their
+/// defaults.
+#[derive(Clone)]
+pub struct ResiliencePlugin;
+
+#[async_trait]
+impl Plugin for ResiliencePlugin {
+    fn name(&self) -> &'static str {
+        "mitsuha.plugin.resilience"
+    }
+
+    async fn run(&self, mut ctx: PluginContext) -> types::Result<PluginContext> {
+        let properties = &ctx.current_properties;
+
+        let mut retry_policy = RetryPolicy::default();
+
+        if let Some(opkinds) = properties.get("retry.opkinds") {
+            retry_policy.opkinds = opkinds
+                .split(',')
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+                .collect();
+        }
+
+        if let Some(x) = self.get_property(properties, "retry.max_retries")? {
+            retry_policy.max_retries = x;
+        }
+
+        if let Some(x) = self.get_property(properties, "retry.initial_interval_ms")? {
+            retry_policy.initial_interval = Duration::from_millis(x);
+        }
+
+        if let Some(x) = self.get_property(properties, "retry.max_interval_ms")? {
+            retry_policy.max_interval = Duration::from_millis(x);
+        }
+
+        if let Some(x) = self.get_property(properties, "retry.multiplier")? {
+            retry_policy.multiplier = x;
+        }
+
+        if let Some(x) = self.get_property(properties, "retry.randomization_factor")? {
+            retry_policy.randomization_factor = x;
+        }
+
+        let mut breaker_policy = CircuitBreakerPolicy::default();
+
+        if let Some(x) = self.get_property(properties, "breaker.failure_threshold")? {
+            breaker_policy.failure_threshold = x;
+        }
+
+        if let Some(x) = self.get_property(properties, "breaker.open_duration_ms")? {
+            breaker_policy.open_duration = Duration::from_millis(x);
+        }
+
+        let raw_channel = ResilienceChannel::new(retry_policy, breaker_policy);
+
+        let channel = initialize_channel(&ctx, raw_channel).await?;
+
+        ctx.channel_end.connect(channel.clone()).await;
+        ctx.channel_end = channel;
+
+        Ok(ctx)
+    }
+}
+
+impl ResiliencePlugin {
+    fn get_property<T>(
+        &self,
+        properties: &HashMap<String, String>,
+        property: &str,
+    ) -> types::Result<Option<T>>
+    where
+        T: FromStr,
+        T::Err: ToString,
+    {
+        match properties.get(property) {
+            Some(value) => Ok(Some(value.parse().to_unknown_err_result()?)),
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use mitsuha_channel::router::{RouterChannel, Rule};
+use mitsuha_core::types;
+
+use super::{initialize_channel, Plugin, PluginContext};
+
+const RULE_PREFIX: &str = "rules.";
+const RULE_TARGET_SUFFIX: &str = ".target";
+const RULE_OPKIND_SUFFIX: &str = ".opkind";
+const RULE_HANDLE_SUFFIX: &str = ".handle";
+const RULE_NAMESPACE_SUFFIX: &str = ".namespace";
+const RULE_EXTENSION_INFIX: &str = ".extensions.";
+
+/// Appends a [RouterChannel] to the chain. Rules are read from properties of the form
+/// `rules.<index>.target`, `rules.<index>.opkind`, `rules.<index>.handle`,
+/// `rules.<index>.namespace` and `rules.<index>.extensions.<key>`, the last three being
+/// regular expressions which must match the whole value.
+#[derive(Clone)]
+pub struct RouterPlugin;
+
+#[async_trait]
+impl Plugin for RouterPlugin {
+    fn name(&self) -> &'static str {
+        "mitsuha.plugin.router"
+    }
+
+    async fn run(&self, mut ctx: PluginContext) -> types::Result<PluginContext> {
+        let raw_channel = RouterChannel::new(self.build_routing_rules(&ctx.current_properties)?);
+
+        let channel = initialize_channel(&ctx, raw_channel).await?;
+
+        ctx.channel_end.connect(channel.clone()).await;
+        ctx.channel_end = channel;
+
+        Ok(ctx)
+    }
+}
+
+impl RouterPlugin {
+    fn get_rule_property(&self, index: u64, suffix: &str) -> String {
+        format!("{}{}{}", RULE_PREFIX, index, suffix)
+    }
+
+    fn build_routing_rules(
+        &self,
+        properties: &HashMap<String, String>,
+    ) -> types::Result<Vec<Rule>> {
+        let mut rules = Vec::new();
+        let mut index = 0u64;
+
+        while let Some(target) = properties.get(&self.get_rule_property(index, RULE_TARGET_SUFFIX))
+        {
+            let mut rule = Rule::new(target.clone());
+
+            if let Some(opkind) = properties.get(&self.get_rule_property(index, RULE_OPKIND_SUFFIX))
+            {
+                rule = rule.with_opkind(opkind.clone());
+            }
+
+            if let Some(expr) = properties.get(&self.get_rule_property(index, RULE_HANDLE_SUFFIX)) {
+                rule = rule.with_handle(expr)?;
+            }
+
+            if let Some(expr) =
+                properties.get(&self.get_rule_property(index, RULE_NAMESPACE_SUFFIX))
+            {
+                rule = rule.with_namespace(expr)?;
+            }
+
+            let extension_prefix = self.get_rule_property(index, RULE_EXTENSION_INFIX);
+
+            for (property, expr) in properties.iter() {
+                if let Some(key) = property.strip_prefix(&extension_prefix) {
+                    rule = rule.with_extension(key.to_string(), expr)?;
+                }
+            }
+
+            rules.push(rule);
+
+            index += 1;
+        }
+
+        Ok(rules)
+    }
+}
+
+/// Starts a new sub-chain, detached from the current one, for routers to send inputs to.
+/// Plugins following it are appended to the sub-chain, whose head is a [RouterChannel] without
+/// rules that is registered under the channel id of this plugin.
+#[derive(Clone)]
+pub struct BranchPlugin;
+
+#[async_trait]
+impl Plugin for BranchPlugin {
+    fn name(&self) -> &'static str {
+        "mitsuha.plugin.branch"
+    }
+
+    async fn run(&self, mut ctx: PluginContext) -> types::Result<PluginContext> {
+        let channel = initialize_channel(&ctx, RouterChannel::new(vec![])).await?;
+
+        ctx.channel_end = channel;
+
+        Ok(ctx)
+    }
+}
@@ -1,7 +1,7 @@
 use crate::plugin::{initialize_channel, Plugin, PluginContext};
 use async_trait::async_trait;
 use mitsuha_channel::scheduler::SchedulerChannel;
-use mitsuha_core::channel::ChannelManager;
+use mitsuha_core::errors::ToUnknownErrorResult;
 use mitsuha_core::types;
 use mitsuha_scheduler::scheduler::{Scheduler, SchedulerPostJobHook};
 use std::sync::Arc;
@@ -16,15 +16,18 @@ impl Plugin for SchedulerPlugin {
     }
 
     async fn run(&self, mut ctx: PluginContext) -> types::Result<PluginContext> {
-        let scheduler =
-            Scheduler::new(ctx.channel_start.clone(), ctx.current_properties.clone()).await?;
-
-        ChannelManager::global_rw()
-            .read()
-            .await
-            .get_job_mgr()
-            .add_post_job_hook(Arc::new(SchedulerPostJobHook::new(scheduler.clone())))
-            .await;
+        // The properties include the channel id, so every scheduler channel has its own.
+        let properties = serde_json::to_value(&ctx.current_properties).to_unknown_err_result()?;
+
+        let scheduler = ctx
+            .get_or_start(
+                format!("scheduler/{}", properties),
+                || Scheduler::new(ctx.channel_start.clone(), ctx.current_properties.clone()),
+                Scheduler::stop,
+            )
+            .await?;
+
+        ctx.add_post_job_hook(Arc::new(SchedulerPostJobHook::new(scheduler.clone())));
 
         let raw_channel = SchedulerChannel::new(scheduler);
         let channel = initialize_channel(&ctx, raw_channel).await?;
@@ -1,8 +1,9 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use mitsuha_channel::wasmtime::WasmtimeChannel;
-use mitsuha_core::{channel::ComputeKernel, kernel::Kernel, types};
+use mitsuha_channel::wasmtime::{WasmtimeChannel, WasmtimeChannelDependencies};
+use mitsuha_core::{channel::ComputeKernel, kernel::Kernel, native::NativeModuleRegistry, types};
+use mitsuha_wasm_runtime::wasmtime::{PrecompiledModuleStore, WasmtimeConfig};
 
 use super::{initialize_channel, Plugin, PluginContext};
 
@@ -19,7 +20,39 @@ impl Plugin for WasmtimePlugin {
         let kernel: Arc<Box<dyn Kernel>> =
             Arc::new(Box::new(ComputeKernel::new(ctx.channel_start.clone())));
 
-        let raw_channel = WasmtimeChannel::new(kernel);
+        let config = WasmtimeConfig::from_properties(&ctx.current_properties)?;
+
+        let precompiled_store = match (
+            config.precompiled_module_selector.clone(),
+            config.precompiled_module_key.clone(),
+        ) {
+            (Some(selector), Some(key)) => {
+                let storage = ctx.get_storage().await?;
+
+                Some(Arc::new(PrecompiledModuleStore::new(
+                    storage,
+                    selector,
+                    config.precompiled_module_ttl,
+                    key.into_bytes(),
+                )))
+            }
+            _ => None,
+        };
+
+        let native_modules = if config.native_modules {
+            NativeModuleRegistry::builtin(config.native_max_output_size)?
+        } else {
+            Default::default()
+        };
+
+        let dependencies = WasmtimeChannelDependencies {
+            precompiled_store,
+            module_registry: mitsuha_registry::global(&ctx.config).await?,
+            publisher_verifier: mitsuha_registry::make_verifier(&ctx.config)?,
+            native_modules,
+        };
+
+        let raw_channel = WasmtimeChannel::new_with_config(kernel, config, dependencies)?;
         let channel = initialize_channel(&ctx, raw_channel).await?;
 
         ctx.channel_end.connect(channel.clone()).await;
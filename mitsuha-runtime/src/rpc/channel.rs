@@ -1,4 +1,5 @@
 use mitsuha_core::channel::{ChannelContext, ChannelManager};
+use mitsuha_core::errors::Error;
 use mitsuha_runtime_rpc::{model::channel::channel_proto, proto};
 
 use super::Service;
@@ -26,7 +27,7 @@ impl proto::channel::channel_server::Channel for ChannelService {
             .unwrap()
             .compute(ctx, compute_input)
             .await
-            .map_err(|e| tonic::Status::internal(e.to_string()))?;
+            .map_err(Self::to_status)?;
 
         let compute_response = compute_output
             .try_into()
@@ -40,6 +41,35 @@ impl ChannelService {
     pub fn new() -> Box<dyn Service> {
         Box::new(Self)
     }
+
+    fn to_status(error: Error) -> tonic::Status {
+        let (mut status, retry_after_ms) = match &error {
+            Error::RateLimitExceeded { retry_after_ms, .. } => (
+                tonic::Status::resource_exhausted(error.to_string()),
+                Some(*retry_after_ms),
+            ),
+            Error::CircuitOpen { retry_after_ms, .. } => (
+                tonic::Status::unavailable(error.to_string()),
+                Some(*retry_after_ms),
+            ),
+            Error::ServiceUnavailable { .. } => {
+                (tonic::Status::unavailable(error.to_string()), None)
+            }
+            Error::QuotaExceeded { .. } => {
+                (tonic::Status::resource_exhausted(error.to_string()), None)
+            }
+            Error::InterceptorRejected { .. } => {
+                (tonic::Status::permission_denied(error.to_string()), None)
+            }
+            _ => (tonic::Status::internal(error.to_string()), None),
+        };
+
+        if let Some(Ok(value)) = retry_after_ms.map(|x| x.to_string().parse()) {
+            status.metadata_mut().insert("retry-after-ms", value);
+        }
+
+        status
+    }
 }
 
 impl Service for ChannelService {
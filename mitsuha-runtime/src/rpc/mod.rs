@@ -1,4 +1,6 @@
-use crate::plugin::{load_plugins, PluginContext};
+use std::time::Duration;
+
+use crate::plugin::{load_plugins, reload_plugins, PluginContext};
 use crate::rpc::channel::ChannelService;
 use anyhow::anyhow;
 use mitsuha_core::config::Config;
@@ -6,6 +8,8 @@ use mitsuha_core::types;
 
 pub mod channel;
 
+const PLUGIN_TRACKER_INTERVAL: Duration = Duration::from_secs(5);
+
 pub trait Service: Send + Sync {
     fn register_rpc(
         &self,
@@ -14,13 +18,44 @@ pub trait Service: Send + Sync {
 }
 
 pub async fn init_channel_manager(config: &Config) -> types::Result<()> {
-    let mut plugin_ctx = PluginContext::new(config.clone(), Default::default()).await?;
+    let plugin_ctx = PluginContext::new(config.clone(), Default::default()).await?;
 
-    plugin_ctx = load_plugins(plugin_ctx).await?;
+    load_plugins(plugin_ctx).await?.activate().await;
 
     Ok(())
 }
 
+/// Reloads the channel chain whenever the plugins of the global configuration change.
+pub async fn track_plugins(config: Config) {
+    let mut plugins = config.plugins;
+
+    Config::start_global_tracker();
+
+    loop {
+        tokio::time::sleep(PLUGIN_TRACKER_INTERVAL).await;
+
+        let Ok(config) = Config::global().await else {
+            continue;
+        };
+
+        if config.plugins == plugins {
+            continue;
+        }
+
+        tracing::info!("plugin configuration changed, reloading channel chain");
+
+        let next_plugins = config.plugins.clone();
+
+        match reload_plugins(config).await {
+            Ok(_) => tracing::info!("reloaded channel chain"),
+            Err(e) => tracing::error!("failed to reload channel chain, {}", e),
+        }
+
+        // A failed reload is not retried until the plugin configuration changes again.
+        plugins = next_plugins;
+    }
+}
+
 pub async fn start(config: Config) {
     start_server(config).await.unwrap()
 }
@@ -33,6 +68,8 @@ pub async fn start_server(config: Config) -> anyhow::Result<()> {
         return Err(anyhow!("failed to initialize channel context, {}", e));
     }
 
+    tokio::task::spawn(track_plugins(config.clone()));
+
     let channel_service = ChannelService::new();
 
     let (_, health_service) = tonic_health::server::health_reporter();
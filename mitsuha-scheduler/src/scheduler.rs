@@ -18,6 +18,7 @@ use mitsuha_core::{err_unsupported_op, types};
 use mitsuha_core_types::channel::{ComputeInput, ComputeOutput};
 use mitsuha_core_types::kernel::{JobSpec, StorageSpec};
 use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::runtime::Handle;
@@ -36,6 +37,7 @@ struct SchedulerState<Context> {
     pub partition_poll_interval: Duration,
     pub channel: Arc<Box<dyn ComputeChannel<Context = Context>>>,
     pub removed_job_handles: Arc<RwLock<Vec<String>>>,
+    pub stopped: Arc<AtomicBool>,
 }
 
 impl<Context> SchedulerState<Context>
@@ -244,15 +246,16 @@ where
         let renew_partition_result = self.renew_partition(state).await;
         let process_batch_event_result = self.process_batch_event(state).await;
         let consume_from_job_queue_result = self.consume_from_job_queue(state).await;
-        let consume_from_job_command_queue_result = self.consume_from_job_command_queue(state).await;
-        
+        let consume_from_job_command_queue_result =
+            self.consume_from_job_command_queue(state).await;
+
         rotate_expired_partition_result?;
         remove_stale_partitions_result?;
         renew_partition_result?;
         process_batch_event_result?;
         consume_from_job_queue_result?;
         consume_from_job_command_queue_result?;
-        
+
         Ok(())
     }
 
@@ -290,9 +293,7 @@ where
         wait_for_eventslice!(self.remove_stale_partitions);
 
         let partition_repo = state.partition_repository.clone();
-        let task = async move { 
-            partition_repo.remove_stale_partitions().await
-        };
+        let task = async move { partition_repo.remove_stale_partitions().await };
 
         self.remove_stale_partitions = Some(tokio::task::spawn(task));
 
@@ -539,6 +540,7 @@ where
             channel,
             bypass_channel_ids,
             removed_job_handles: Default::default(),
+            stopped: Default::default(),
         };
 
         Self::partition_poller(state.clone()).await?;
@@ -550,7 +552,7 @@ where
         let event_loop = async move {
             let mut event_loop_slice = EventLoopSlice::<Context>::default();
 
-            loop {
+            while !state.stopped.load(Ordering::Relaxed) {
                 if let Err(e) = event_loop_slice.run(&state).await {
                     tracing::error!("failed to poll scheduler partition. error={}", e);
 
@@ -568,6 +570,11 @@ where
 
                 tokio::time::sleep(state.partition_poll_interval.clone()).await;
             }
+
+            tracing::info!(
+                "stopped polling scheduler partition '{}'",
+                state.partition_id.read().await
+            );
         };
 
         tokio::task::spawn_blocking(|| Handle::current().block_on(event_loop));
@@ -575,6 +582,12 @@ where
         Ok(())
     }
 
+    /// Stops polling the partition of this scheduler. Jobs already dispatched keep running, and
+    /// the partition is left to expire so that its queued jobs are picked up by other schedulers.
+    pub fn stop(&self) {
+        self.state.stopped.store(true, Ordering::Relaxed);
+    }
+
     pub(crate) async fn rotate_partition(&self) -> types::Result<()> {
         self.state.rotate_partition().await
     }
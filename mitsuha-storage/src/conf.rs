@@ -11,4 +11,16 @@ pub enum ConfKey {
 
     #[strum(serialize = "concurrency_mode")]
     ConcurrencyMode,
+
+    #[strum(serialize = "encryption.active_master_key")]
+    EncryptionActiveMasterKey,
+
+    #[strum(serialize = "encryption.master_key")]
+    EncryptionMasterKey,
+
+    #[strum(serialize = "encryption.master_key_file")]
+    EncryptionMasterKeyFile,
+
+    #[strum(serialize = "encryption.data_key_rotation_secs")]
+    EncryptionDataKeyRotationSecs,
 }
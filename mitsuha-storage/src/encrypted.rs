@@ -0,0 +1,1536 @@
+use std::{collections::HashMap, future::Future, pin::Pin, sync::Arc};
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use mitsuha_core::errors::ToUnknownErrorResult;
+use mitsuha_core::{
+    constants::{Constants, StorageControlConstants},
+    err_unknown, err_unsupported_op,
+    errors::Error,
+    storage::{FileSystem, GarbageCollectable, RawStorage, Storage, StorageClass},
+    types,
+};
+use mitsuha_core_types::{kernel::StorageSpec, storage::StorageCapability};
+use mitsuha_filesystem::{
+    constant::NativeFileSystemConstants, NativeFileLease, NativeFileMetadata, NativeFileType,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+
+use crate::conf::ConfKey;
+
+lazy_static! {
+    static ref KEYRING_EXT: String =
+        NativeFileSystemConstants::MnfsSuffix.to_string() + ".encryption.keyring";
+}
+
+const ENCRYPTION_MAGIC: &[u8] = b"\xffMZE";
+
+const KEYRING_ID_LEN: usize = 8;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Magic, keyring id, data key id and nonce.
+const HEADER_LEN: usize = ENCRYPTION_MAGIC.len() + KEYRING_ID_LEN + 4 + NONCE_LEN;
+
+/// Size of the plaintext chunks encrypted independently, so that file parts can be read and
+/// written without touching the rest of the blob.
+const CHUNK_SIZE: u64 = 16 * 1024;
+const SLOT_SIZE: u64 = HEADER_LEN as u64 + CHUNK_SIZE + TAG_LEN as u64;
+
+const KEYRING_TTL: u64 = 100 * 365 * 24 * 60 * 60;
+const FILE_LOCK_COUNT: usize = 64;
+const LIST_PAGE_SIZE: u64 = 256;
+
+type KeyringId = [u8; KEYRING_ID_LEN];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredDataKey {
+    id: u32,
+    master_key: String,
+    wrapped_key: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredKeyring {
+    namespace: String,
+    active: u32,
+    keys: Vec<u32>,
+}
+
+struct DataKey {
+    cipher: Aes256Gcm,
+    created_at: DateTime<Utc>,
+}
+
+struct Keyring {
+    id: KeyringId,
+    active: u32,
+    key: Arc<DataKey>,
+}
+
+/// When [EncryptedStorage::rotate_keyring] creates a new data key, checked while holding the
+/// keyring lock so that concurrent callers rotate a keyring once.
+enum Rotation {
+    Always,
+    IfMissing,
+    IfOlderThan(Duration),
+}
+
+/// Encrypts the blobs and file parts of a storage with AES-256-GCM, using a data key per
+/// namespace wrapped by a master key. Data is split into chunks of [CHUNK_SIZE] bytes, each
+/// sealed under its own header naming the keyring and data key it was encrypted with,
+/// so blobs stored whole can be read in parts and the other way around. Chunks are bound to
+/// their handle and index, so moved and copied files are sealed again under their destination.
+///
+/// Master keys are hex encoded 256-bit keys given in the `encryption.master_key.<id>` or
+/// `encryption.master_key_file.<id>` properties of the storage class, and new data keys are
+/// wrapped with the one named by `encryption.active_master_key`. Data keys wrapped with another
+/// master key are rewrapped when loaded, and data keys are rotated every
+/// `encryption.data_key_rotation_secs` if set. Blobs keep using the data key they were
+/// encrypted with until they are written again or re-encrypted, so older data keys are kept.
+///
+/// Keyrings and data keys are stored in the wrapped storage itself and cached by each runtime.
+/// Every data key gets a random id and a handle of its own which is never rewritten with
+/// another key, so runtimes sharing a storage may create and rotate data keys concurrently:
+/// the keyring only records which data key is active, and the last rotation stored wins.
+/// Keyring handles cannot be accessed through the storage.
+pub struct EncryptedStorage {
+    inner: Arc<Box<dyn Storage>>,
+    master_keys: HashMap<String, Aes256Gcm>,
+    active_master_key: String,
+    rotation_period: Option<Duration>,
+    keyrings: DashMap<KeyringId, Arc<Keyring>>,
+    data_keys: DashMap<(KeyringId, u32), Arc<DataKey>>,
+    keyring_lock: Mutex<()>,
+    file_locks: Vec<Mutex<()>>,
+}
+
+#[async_trait]
+impl RawStorage for EncryptedStorage {
+    async fn store(&self, mut spec: StorageSpec) -> types::Result<()> {
+        Self::check_handle(&spec.handle)?;
+
+        spec.data = self
+            .encrypt_blob(&spec.handle, &spec.data, &spec.extensions)
+            .await?;
+
+        self.inner.store(spec).await
+    }
+
+    async fn load(
+        &self,
+        handle: String,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<Vec<u8>> {
+        Self::check_handle(&handle)?;
+
+        let data = self.inner.load(handle.clone(), extensions.clone()).await?;
+
+        self.decrypt_blob(&handle, data, &extensions).await
+    }
+
+    async fn exists(
+        &self,
+        handle: String,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<bool> {
+        Self::check_handle(&handle)?;
+
+        self.inner.exists(handle, extensions).await
+    }
+
+    async fn persist(
+        &self,
+        handle: String,
+        time: u64,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<()> {
+        Self::check_handle(&handle)?;
+
+        self.inner.persist(handle, time, extensions).await
+    }
+
+    async fn clear(
+        &self,
+        handle: String,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<()> {
+        Self::check_handle(&handle)?;
+
+        self.inner.clear(handle, extensions).await
+    }
+
+    async fn capabilities(
+        &self,
+        handle: String,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<Vec<StorageCapability>> {
+        Self::check_handle(&handle)?;
+
+        self.inner.capabilities(handle, extensions).await
+    }
+}
+
+#[async_trait]
+impl FileSystem for EncryptedStorage {
+    async fn store_file_part(
+        &self,
+        handle: String,
+        part_index: u64,
+        part_size: u64,
+        ttl: u64,
+        data: Vec<u8>,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<()> {
+        Self::check_handle(&handle)?;
+
+        let _guard = self.lock_file(&handle).await;
+
+        self.encrypt_plaintext_file(&handle, &extensions).await?;
+
+        self.write_file(&handle, part_index * part_size, data, ttl, &extensions)
+            .await
+    }
+
+    async fn load_file_part(
+        &self,
+        handle: String,
+        part_index: u64,
+        part_size: u64,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<Vec<u8>> {
+        Self::check_handle(&handle)?;
+
+        if self.is_plaintext_file(&handle, &extensions).await? {
+            return self
+                .inner
+                .load_file_part(handle, part_index, part_size, extensions)
+                .await;
+        }
+
+        self.read_file(&handle, part_index * part_size, part_size, &extensions)
+            .await
+    }
+
+    async fn get_file_part_count(
+        &self,
+        handle: String,
+        part_size: u64,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<u64> {
+        Self::check_handle(&handle)?;
+
+        let data_len = self.get_plaintext_len(&handle, &extensions).await?;
+
+        Ok(data_len / part_size + (data_len % part_size > 0) as u64)
+    }
+
+    async fn get_metadata(
+        &self,
+        handle: String,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<NativeFileMetadata> {
+        Self::check_handle(&handle)?;
+
+        self.inner.get_metadata(handle, extensions).await
+    }
+
+    async fn set_metadata(
+        &self,
+        handle: String,
+        metadata: NativeFileMetadata,
+        ttl: u64,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<()> {
+        Self::check_handle(&handle)?;
+
+        self.inner
+            .set_metadata(handle, metadata, ttl, extensions)
+            .await
+    }
+
+    async fn path_exists(
+        &self,
+        handle: String,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<bool> {
+        Self::check_handle(&handle)?;
+
+        self.inner.path_exists(handle, extensions).await
+    }
+
+    async fn list(
+        &self,
+        handle: String,
+        page_index: u64,
+        page_size: u64,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<Vec<String>> {
+        Self::check_handle(&handle)?;
+
+        self.inner
+            .list(handle, page_index, page_size, extensions)
+            .await
+    }
+
+    async fn add_list_item(
+        &self,
+        handle: String,
+        item: String,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<()> {
+        Self::check_handle(&handle)?;
+        Self::check_handle(&item)?;
+
+        self.inner.add_list_item(handle, item, extensions).await
+    }
+
+    async fn remove_list_item(
+        &self,
+        handle: String,
+        item: String,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<()> {
+        Self::check_handle(&handle)?;
+        Self::check_handle(&item)?;
+
+        self.inner.remove_list_item(handle, item, extensions).await
+    }
+
+    async fn truncate(
+        &self,
+        handle: String,
+        len: u64,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<()> {
+        Self::check_handle(&handle)?;
+
+        let _guard = self.lock_file(&handle).await;
+
+        self.encrypt_plaintext_file(&handle, &extensions).await?;
+
+        let data_len = self.get_plaintext_len(&handle, &extensions).await?;
+
+        if len >= data_len {
+            let padding = vec![0u8; (len - data_len) as usize];
+
+            return self
+                .write_file(&handle, data_len, padding, 0, &extensions)
+                .await;
+        }
+
+        let chunk_index = len / CHUNK_SIZE;
+        let remainder = (len % CHUNK_SIZE) as usize;
+
+        let mut tail = if remainder > 0 {
+            self.load_chunk(&handle, chunk_index, &extensions).await?
+        } else {
+            vec![]
+        };
+
+        self.inner
+            .truncate(handle.clone(), chunk_index * SLOT_SIZE, extensions.clone())
+            .await?;
+
+        if remainder > 0 {
+            tail.truncate(remainder);
+
+            let keyring = self.get_active_keyring(&extensions).await?;
+
+            self.store_chunk(&handle, chunk_index, 0, &tail, &keyring, &extensions)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn acquire_lease(
+        &self,
+        handle: String,
+        lease: NativeFileLease,
+        ttl: u64,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<()> {
+        Self::check_handle(&handle)?;
+
+        self.inner
+            .acquire_lease(handle, lease, ttl, extensions)
+            .await
+    }
+
+    async fn renew_lease(
+        &self,
+        handle: String,
+        lease_id: String,
+        ttl: u64,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<()> {
+        Self::check_handle(&handle)?;
+
+        self.inner
+            .renew_lease(handle, lease_id, ttl, extensions)
+            .await
+    }
+
+    async fn release_lease(
+        &self,
+        handle: String,
+        lease_id: String,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<()> {
+        Self::check_handle(&handle)?;
+
+        self.inner.release_lease(handle, lease_id, extensions).await
+    }
+
+    async fn copy_path(
+        &self,
+        source_handle: String,
+        destination_handle: String,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<()> {
+        Self::check_handle(&source_handle)?;
+        Self::check_handle(&destination_handle)?;
+
+        self.inner
+            .copy_path(
+                source_handle.clone(),
+                destination_handle.clone(),
+                extensions.clone(),
+            )
+            .await?;
+
+        self.rebind_path(source_handle, destination_handle, &extensions)
+            .await
+    }
+
+    async fn move_path(
+        &self,
+        source_handle: String,
+        destination_handle: String,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<()> {
+        Self::check_handle(&source_handle)?;
+        Self::check_handle(&destination_handle)?;
+
+        self.inner
+            .move_path(
+                source_handle.clone(),
+                destination_handle.clone(),
+                extensions.clone(),
+            )
+            .await?;
+
+        self.rebind_path(source_handle, destination_handle, &extensions)
+            .await
+    }
+
+    async fn delete_path(
+        &self,
+        handle: String,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<()> {
+        Self::check_handle(&handle)?;
+
+        self.inner.delete_path(handle, extensions).await
+    }
+
+    async fn get_capabilities(
+        &self,
+        handle: String,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<Vec<StorageCapability>> {
+        Self::check_handle(&handle)?;
+
+        self.inner.get_capabilities(handle, extensions).await
+    }
+
+    async fn get_storage_class(
+        &self,
+        handle: String,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<String> {
+        Self::check_handle(&handle)?;
+
+        self.inner.get_storage_class(handle, extensions).await
+    }
+}
+
+#[async_trait]
+impl GarbageCollectable for EncryptedStorage {
+    async fn garbage_collect(&self) -> types::Result<Vec<String>> {
+        self.inner.garbage_collect().await
+    }
+}
+
+impl EncryptedStorage {
+    pub fn new(inner: Arc<Box<dyn Storage>>, class: &StorageClass) -> types::Result<Self> {
+        let active_master_key =
+            class.get_extension_property(&ConfKey::EncryptionActiveMasterKey.to_string())?;
+
+        let key_prefix = format!("{}.", ConfKey::EncryptionMasterKey);
+        let key_file_prefix = format!("{}.", ConfKey::EncryptionMasterKeyFile);
+
+        let mut master_keys = HashMap::new();
+
+        for (key, value) in class.properties.iter() {
+            let (id, encoded_key) = if let Some(id) = key.strip_prefix(&key_prefix) {
+                (id, value.clone())
+            } else if let Some(id) = key.strip_prefix(&key_file_prefix) {
+                (id, std::fs::read_to_string(value).to_unknown_err_result()?)
+            } else {
+                continue;
+            };
+
+            let key_bytes = hex::decode(encoded_key.trim()).to_unknown_err_result()?;
+            let cipher = Aes256Gcm::new_from_slice(&key_bytes)
+                .map_err(|_| err_unknown!(format!("master key '{}' is not a 256-bit key", id)))?;
+
+            master_keys.insert(id.to_string(), cipher);
+        }
+
+        if !master_keys.contains_key(&active_master_key) {
+            return Err(Error::StorageInitFailed {
+                message: format!(
+                    "active master key '{}' was not found in storage class '{}'",
+                    active_master_key, class.name
+                ),
+                source: anyhow::anyhow!(""),
+            });
+        }
+
+        let rotation_period = match class
+            .properties
+            .get(&ConfKey::EncryptionDataKeyRotationSecs.to_string())
+        {
+            Some(value) => Some(Duration::seconds(
+                value.parse::<i64>().to_unknown_err_result()?,
+            )),
+            None => None,
+        };
+
+        Ok(Self {
+            inner,
+            master_keys,
+            active_master_key,
+            rotation_period,
+            keyrings: DashMap::new(),
+            data_keys: DashMap::new(),
+            keyring_lock: Mutex::new(()),
+            file_locks: (0..FILE_LOCK_COUNT).map(|_| Mutex::new(())).collect(),
+        })
+    }
+
+    /// Makes a new data key the active one of `namespace`, returning its id.
+    pub async fn rotate_data_key(
+        &self,
+        namespace: &str,
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<u32> {
+        self.rotate_keyring(namespace, extensions, Rotation::Always)
+            .await
+    }
+
+    async fn rotate_keyring(
+        &self,
+        namespace: &str,
+        extensions: &HashMap<String, String>,
+        rotation: Rotation,
+    ) -> types::Result<u32> {
+        let keyring_id = Self::get_keyring_id(namespace);
+
+        let _guard = self.keyring_lock.lock().await;
+
+        let stored = self.load_stored_keyring(&keyring_id, extensions).await?;
+
+        if let Some(stored) = stored.as_ref() {
+            let key = self
+                .get_data_key(&keyring_id, stored.active, extensions, false)
+                .await?;
+
+            let rotate = match rotation {
+                Rotation::Always => true,
+                Rotation::IfMissing => false,
+                Rotation::IfOlderThan(period) => Utc::now() - key.created_at >= period,
+            };
+
+            // Another caller rotated the keyring since it was checked.
+            if !rotate {
+                self.keyrings.insert(
+                    keyring_id,
+                    Arc::new(Keyring {
+                        id: keyring_id,
+                        active: stored.active,
+                        key,
+                    }),
+                );
+
+                return Ok(stored.active);
+            }
+        }
+
+        let (key_id, key) = self.create_data_key(&keyring_id, extensions).await?;
+
+        let mut keys = stored.map(|x| x.keys).unwrap_or_default();
+        keys.push(key_id);
+
+        let stored = StoredKeyring {
+            namespace: namespace.to_string(),
+            active: key_id,
+            keys,
+        };
+
+        self.store_keyring(&keyring_id, &stored, extensions).await?;
+
+        tracing::info!(
+            "rotated data key of namespace '{}' to {:08x}",
+            namespace,
+            key_id
+        );
+
+        self.keyrings.insert(
+            keyring_id,
+            Arc::new(Keyring {
+                id: keyring_id,
+                active: key_id,
+                key,
+            }),
+        );
+
+        Ok(key_id)
+    }
+
+    /// Re-encrypts a blob stored whole with the active data key of its namespace, storing it
+    /// again with `ttl`. Blobs stored before encryption was enabled are encrypted. Returns
+    /// whether the blob was rewritten.
+    pub async fn reencrypt_blob(
+        &self,
+        handle: String,
+        ttl: u64,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<bool> {
+        Self::check_handle(&handle)?;
+
+        let data = self.inner.load(handle.clone(), extensions.clone()).await?;
+
+        let keyring = self.get_active_keyring(&extensions).await?;
+
+        let stale = data
+            .chunks(SLOT_SIZE as usize)
+            .any(|x| Self::is_stale_chunk(x, &keyring));
+
+        if !stale {
+            return Ok(false);
+        }
+
+        let data = self.decrypt_blob(&handle, data, &extensions).await?;
+
+        let spec = StorageSpec {
+            data: self.encrypt_blob(&handle, &data, &extensions).await?,
+            handle,
+            ttl,
+            extensions,
+        };
+
+        self.inner.store(spec).await?;
+
+        Ok(true)
+    }
+
+    /// Re-encrypts the chunks of a file written in parts which are not encrypted with the
+    /// active data key of its namespace, in place. Returns whether the file was rewritten.
+    pub async fn reencrypt_file(
+        &self,
+        handle: String,
+        extensions: HashMap<String, String>,
+    ) -> types::Result<bool> {
+        Self::check_handle(&handle)?;
+
+        let _guard = self.lock_file(&handle).await;
+
+        if self.encrypt_plaintext_file(&handle, &extensions).await? {
+            return Ok(true);
+        }
+
+        let data_len = self
+            .inner
+            .get_file_part_count(handle.clone(), 1, extensions.clone())
+            .await?;
+
+        if data_len == 0 {
+            return Ok(false);
+        }
+
+        let keyring = self.get_active_keyring(&extensions).await?;
+
+        let chunk_count = data_len / SLOT_SIZE + (data_len % SLOT_SIZE > 0) as u64;
+        let mut rewritten = false;
+
+        for index in 0..chunk_count {
+            let chunk = self
+                .inner
+                .load_file_part(handle.clone(), index, SLOT_SIZE, extensions.clone())
+                .await?;
+
+            if !Self::is_stale_chunk(&chunk, &keyring) {
+                continue;
+            }
+
+            let data = self.open_chunk(&handle, index, &chunk, &extensions).await?;
+
+            self.store_chunk(&handle, index, 0, &data, &keyring, &extensions)
+                .await?;
+
+            rewritten = true;
+        }
+
+        Ok(rewritten)
+    }
+
+    fn check_handle(handle: &String) -> types::Result<()> {
+        if handle.contains(KEYRING_EXT.as_str()) {
+            return Err(err_unsupported_op!(
+                "handles of encryption keyrings cannot be accessed"
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn get_namespace(extensions: &HashMap<String, String>) -> String {
+        extensions
+            .get(&Constants::ChannelNamespace.to_string())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn get_keyring_id(namespace: &str) -> KeyringId {
+        let mut hasher = Sha256::new();
+        hasher.update(namespace);
+
+        let mut keyring_id = KeyringId::default();
+        keyring_id.copy_from_slice(&hasher.finalize()[..KEYRING_ID_LEN]);
+
+        keyring_id
+    }
+
+    fn gen_keyring_handle(keyring_id: &KeyringId) -> String {
+        format!("/{}.{}", KEYRING_EXT.as_str(), hex::encode(keyring_id))
+    }
+
+    fn gen_data_key_handle(keyring_id: &KeyringId, key_id: u32) -> String {
+        format!(
+            "/{}.{}.{:08x}",
+            KEYRING_EXT.as_str(),
+            hex::encode(keyring_id),
+            key_id
+        )
+    }
+
+    /// Keyrings only keep the storage selector, so that they are stored next to the data
+    /// without inheriting its expiry.
+    fn get_keyring_extensions(extensions: &HashMap<String, String>) -> HashMap<String, String> {
+        let selector = StorageControlConstants::StorageSelectorQuery.to_string();
+
+        extensions
+            .iter()
+            .filter(|(key, _)| **key == selector)
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    async fn load_stored_keyring(
+        &self,
+        keyring_id: &KeyringId,
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<Option<StoredKeyring>> {
+        let handle = Self::gen_keyring_handle(keyring_id);
+        let extensions = Self::get_keyring_extensions(extensions);
+
+        if !self
+            .inner
+            .exists(handle.clone(), extensions.clone())
+            .await?
+        {
+            return Ok(None);
+        }
+
+        let data = self.inner.load(handle, extensions).await?;
+
+        Ok(Some(serde_json::from_slice(&data).to_unknown_err_result()?))
+    }
+
+    async fn store_keyring(
+        &self,
+        keyring_id: &KeyringId,
+        stored: &StoredKeyring,
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<()> {
+        let spec = StorageSpec {
+            handle: Self::gen_keyring_handle(keyring_id),
+            data: serde_json::to_vec(stored).to_unknown_err_result()?,
+            ttl: KEYRING_TTL,
+            extensions: Self::get_keyring_extensions(extensions),
+        };
+
+        self.inner.store(spec).await
+    }
+
+    async fn store_data_key(
+        &self,
+        keyring_id: &KeyringId,
+        stored: &StoredDataKey,
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<()> {
+        let spec = StorageSpec {
+            handle: Self::gen_data_key_handle(keyring_id, stored.id),
+            data: serde_json::to_vec(stored).to_unknown_err_result()?,
+            ttl: KEYRING_TTL,
+            extensions: Self::get_keyring_extensions(extensions),
+        };
+
+        self.inner.store(spec).await
+    }
+
+    fn get_key_aad(keyring_id: &KeyringId, key_id: u32) -> Vec<u8> {
+        let mut aad = keyring_id.to_vec();
+        aad.extend(key_id.to_be_bytes());
+
+        aad
+    }
+
+    fn wrap_key(
+        &self,
+        keyring_id: &KeyringId,
+        key_id: u32,
+        key: &[u8],
+    ) -> types::Result<StoredDataKey> {
+        let master_key = self.master_keys.get(&self.active_master_key).unwrap();
+
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let aad = Self::get_key_aad(keyring_id, key_id);
+
+        let mut wrapped_key = nonce.to_vec();
+        wrapped_key.extend(
+            master_key
+                .encrypt(
+                    &nonce,
+                    Payload {
+                        msg: key,
+                        aad: &aad,
+                    },
+                )
+                .to_unknown_err_result()?,
+        );
+
+        Ok(StoredDataKey {
+            id: key_id,
+            master_key: self.active_master_key.clone(),
+            wrapped_key: hex::encode(wrapped_key),
+            created_at: Utc::now(),
+        })
+    }
+
+    fn unwrap_key(&self, keyring_id: &KeyringId, stored: &StoredDataKey) -> types::Result<Vec<u8>> {
+        let master_key = self
+            .master_keys
+            .get(&stored.master_key)
+            .ok_or(err_unknown!(format!(
+                "master key '{}' was not found",
+                stored.master_key
+            )))?;
+
+        let wrapped_key = hex::decode(&stored.wrapped_key).to_unknown_err_result()?;
+
+        if wrapped_key.len() < NONCE_LEN {
+            return Err(err_unknown!("wrapped data key is corrupted"));
+        }
+
+        let (nonce, wrapped_key) = wrapped_key.split_at(NONCE_LEN);
+        let aad = Self::get_key_aad(keyring_id, stored.id);
+
+        master_key
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: wrapped_key,
+                    aad: &aad,
+                },
+            )
+            .to_unknown_err_result()
+    }
+
+    /// Creates a data key under a random id which is not taken yet in the keyring.
+    async fn create_data_key(
+        &self,
+        keyring_id: &KeyringId,
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<(u32, Arc<DataKey>)> {
+        loop {
+            let key_id: u32 = rand::random();
+
+            if self
+                .inner
+                .exists(
+                    Self::gen_data_key_handle(keyring_id, key_id),
+                    Self::get_keyring_extensions(extensions),
+                )
+                .await?
+            {
+                continue;
+            }
+
+            let key = Aes256Gcm::generate_key(OsRng);
+            let stored = self.wrap_key(keyring_id, key_id, key.as_slice())?;
+
+            self.store_data_key(keyring_id, &stored, extensions).await?;
+
+            let data_key = Arc::new(DataKey {
+                cipher: Aes256Gcm::new(&key),
+                created_at: stored.created_at,
+            });
+
+            self.data_keys
+                .insert((*keyring_id, key_id), data_key.clone());
+
+            return Ok((key_id, data_key));
+        }
+    }
+
+    /// Gets a data key from the cache or the wrapped storage, rewrapping it with the active
+    /// master key if needed.
+    async fn get_data_key(
+        &self,
+        keyring_id: &KeyringId,
+        key_id: u32,
+        extensions: &HashMap<String, String>,
+        refresh: bool,
+    ) -> types::Result<Arc<DataKey>> {
+        if !refresh {
+            if let Some(data_key) = self.data_keys.get(&(*keyring_id, key_id)) {
+                return Ok(data_key.clone());
+            }
+        }
+
+        let handle = Self::gen_data_key_handle(keyring_id, key_id);
+        let keyring_extensions = Self::get_keyring_extensions(extensions);
+
+        if !self
+            .inner
+            .exists(handle.clone(), keyring_extensions.clone())
+            .await?
+        {
+            return Err(err_unknown!(format!(
+                "data key {:08x} of keyring '{}' was not found",
+                key_id,
+                hex::encode(keyring_id)
+            )));
+        }
+
+        let data = self.inner.load(handle, keyring_extensions).await?;
+        let stored: StoredDataKey = serde_json::from_slice(&data).to_unknown_err_result()?;
+
+        let key = self.unwrap_key(keyring_id, &stored)?;
+
+        if stored.master_key != self.active_master_key {
+            let mut rewrapped_key = self.wrap_key(keyring_id, key_id, &key)?;
+            rewrapped_key.created_at = stored.created_at;
+
+            self.store_data_key(keyring_id, &rewrapped_key, extensions)
+                .await?;
+
+            tracing::info!(
+                "rewrapped data key {:08x} of keyring '{}' with master key '{}'",
+                key_id,
+                hex::encode(keyring_id),
+                self.active_master_key
+            );
+        }
+
+        let data_key = Arc::new(DataKey {
+            cipher: Aes256Gcm::new_from_slice(&key).to_unknown_err_result()?,
+            created_at: stored.created_at,
+        });
+
+        self.data_keys
+            .insert((*keyring_id, key_id), data_key.clone());
+
+        Ok(data_key)
+    }
+
+    /// Gets the keyring of `namespace` from the cache or the wrapped storage, creating it when
+    /// it does not exist yet. Every data key of a keyring is loaded with it, so that they are
+    /// rewrapped once the active master key changes.
+    async fn get_keyring(
+        &self,
+        keyring_id: &KeyringId,
+        namespace: &str,
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<Arc<Keyring>> {
+        if let Some(keyring) = self.keyrings.get(keyring_id) {
+            return Ok(keyring.clone());
+        }
+
+        let guard = self.keyring_lock.lock().await;
+
+        if let Some(keyring) = self.keyrings.get(keyring_id) {
+            return Ok(keyring.clone());
+        }
+
+        let stored = match self.load_stored_keyring(keyring_id, extensions).await? {
+            Some(stored) => stored,
+            None => {
+                drop(guard);
+
+                self.rotate_keyring(namespace, extensions, Rotation::IfMissing)
+                    .await?;
+
+                return Ok(self.keyrings.get(keyring_id).unwrap().clone());
+            }
+        };
+
+        for key_id in stored.keys.iter() {
+            self.get_data_key(keyring_id, *key_id, extensions, false)
+                .await?;
+        }
+
+        let keyring = Arc::new(Keyring {
+            id: *keyring_id,
+            active: stored.active,
+            key: self
+                .get_data_key(keyring_id, stored.active, extensions, false)
+                .await?,
+        });
+
+        self.keyrings.insert(*keyring_id, keyring.clone());
+
+        Ok(keyring)
+    }
+
+    async fn get_active_keyring(
+        &self,
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<Arc<Keyring>> {
+        let namespace = Self::get_namespace(extensions);
+        let keyring_id = Self::get_keyring_id(&namespace);
+
+        let mut keyring = self
+            .get_keyring(&keyring_id, &namespace, extensions)
+            .await?;
+
+        if let Some(rotation_period) = self.rotation_period {
+            if Utc::now() - keyring.key.created_at >= rotation_period {
+                self.rotate_keyring(
+                    &namespace,
+                    extensions,
+                    Rotation::IfOlderThan(rotation_period),
+                )
+                .await?;
+
+                keyring = self.keyrings.get(&keyring_id).unwrap().clone();
+            }
+        }
+
+        Ok(keyring)
+    }
+
+    /// Chunks are bound to their handle, so that they cannot be swapped between blobs.
+    fn get_chunk_aad(header: &[u8], handle: &str, index: u64) -> Vec<u8> {
+        let mut aad = header[..HEADER_LEN - NONCE_LEN].to_vec();
+        aad.extend((handle.len() as u64).to_be_bytes());
+        aad.extend(handle.as_bytes());
+        aad.extend(index.to_be_bytes());
+
+        aad
+    }
+
+    fn seal_chunk(
+        handle: &str,
+        index: u64,
+        data: &[u8],
+        keyring: &Keyring,
+    ) -> types::Result<Vec<u8>> {
+        let key = &keyring.key.cipher;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+        let mut output = ENCRYPTION_MAGIC.to_vec();
+        output.extend(keyring.id);
+        output.extend(keyring.active.to_be_bytes());
+        output.extend(nonce.as_slice());
+
+        let aad = Self::get_chunk_aad(&output, handle, index);
+
+        output.extend(
+            key.encrypt(
+                &nonce,
+                Payload {
+                    msg: data,
+                    aad: &aad,
+                },
+            )
+            .to_unknown_err_result()?,
+        );
+
+        Ok(output)
+    }
+
+    fn parse_header(chunk: &[u8]) -> Option<(KeyringId, u32)> {
+        if chunk.len() < HEADER_LEN + TAG_LEN || !chunk.starts_with(ENCRYPTION_MAGIC) {
+            return None;
+        }
+
+        let offset = ENCRYPTION_MAGIC.len();
+
+        let mut keyring_id = KeyringId::default();
+        keyring_id.copy_from_slice(&chunk[offset..offset + KEYRING_ID_LEN]);
+
+        let mut key_id = [0u8; 4];
+        key_id.copy_from_slice(&chunk[offset + KEYRING_ID_LEN..offset + KEYRING_ID_LEN + 4]);
+
+        Some((keyring_id, u32::from_be_bytes(key_id)))
+    }
+
+    fn is_stale_chunk(chunk: &[u8], keyring: &Keyring) -> bool {
+        match Self::parse_header(chunk) {
+            Some((id, key_id)) => id != keyring.id || key_id != keyring.active,
+            None => true,
+        }
+    }
+
+    async fn open_chunk(
+        &self,
+        handle: &str,
+        index: u64,
+        chunk: &[u8],
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<Vec<u8>> {
+        let (keyring_id, key_id) = Self::parse_header(chunk).ok_or(err_unknown!(format!(
+            "encrypted chunk {} is corrupted",
+            index
+        )))?;
+
+        let (header, data) = chunk.split_at(HEADER_LEN);
+        let nonce = Nonce::from_slice(&header[HEADER_LEN - NONCE_LEN..]);
+        let aad = Self::get_chunk_aad(header, handle, index);
+
+        let key = self
+            .get_data_key(&keyring_id, key_id, extensions, false)
+            .await?;
+
+        if let Ok(output) = key.cipher.decrypt(
+            nonce,
+            Payload {
+                msg: data,
+                aad: &aad,
+            },
+        ) {
+            return Ok(output);
+        }
+
+        // The cached data key may be outdated if another runtime stored a data key under the
+        // same id, so it is loaded again before giving up.
+        let key = self
+            .get_data_key(&keyring_id, key_id, extensions, true)
+            .await?;
+
+        key.cipher
+            .decrypt(
+                nonce,
+                Payload {
+                    msg: data,
+                    aad: &aad,
+                },
+            )
+            .to_unknown_err_result()
+    }
+
+    async fn encrypt_blob(
+        &self,
+        handle: &str,
+        data: &[u8],
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<Vec<u8>> {
+        if data.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let keyring = self.get_active_keyring(extensions).await?;
+
+        let mut output = Vec::new();
+
+        for (index, chunk) in data.chunks(CHUNK_SIZE as usize).enumerate() {
+            output.extend(Self::seal_chunk(handle, index as u64, chunk, &keyring)?);
+        }
+
+        Ok(output)
+    }
+
+    /// Blobs stored before encryption was enabled are returned as they are.
+    async fn decrypt_blob(
+        &self,
+        handle: &str,
+        data: Vec<u8>,
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<Vec<u8>> {
+        if !data.starts_with(ENCRYPTION_MAGIC) {
+            return Ok(data);
+        }
+
+        let mut output = Vec::with_capacity(data.len());
+
+        for (index, chunk) in data.chunks(SLOT_SIZE as usize).enumerate() {
+            output.extend(
+                self.open_chunk(handle, index as u64, chunk, extensions)
+                    .await?,
+            );
+        }
+
+        Ok(output)
+    }
+
+    async fn lock_file(&self, handle: &String) -> tokio::sync::MutexGuard<'_, ()> {
+        let mut hasher = Sha256::new();
+        hasher.update(handle);
+
+        let index = hasher.finalize()[0] as usize % FILE_LOCK_COUNT;
+
+        self.file_locks[index].lock().await
+    }
+
+    /// Files written in parts before encryption was enabled have no header in their first slot.
+    async fn is_plaintext_file(
+        &self,
+        handle: &String,
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<bool> {
+        let head = self
+            .inner
+            .load_file_part(
+                handle.clone(),
+                0,
+                ENCRYPTION_MAGIC.len() as u64,
+                extensions.clone(),
+            )
+            .await?;
+
+        Ok(!head.is_empty() && !head.starts_with(ENCRYPTION_MAGIC))
+    }
+
+    /// Encrypts a file written before encryption was enabled in place, so that it can be
+    /// written in parts. Returns whether the file was rewritten.
+    async fn encrypt_plaintext_file(
+        &self,
+        handle: &String,
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<bool> {
+        if !self.is_plaintext_file(handle, extensions).await? {
+            return Ok(false);
+        }
+
+        let data_len = self
+            .inner
+            .get_file_part_count(handle.clone(), 1, extensions.clone())
+            .await?;
+
+        let data = self
+            .inner
+            .load_file_part(handle.clone(), 0, data_len, extensions.clone())
+            .await?;
+
+        self.inner
+            .truncate(handle.clone(), 0, extensions.clone())
+            .await?;
+
+        self.write_file(handle, 0, data, 0, extensions).await?;
+
+        Ok(true)
+    }
+
+    async fn get_plaintext_len(
+        &self,
+        handle: &String,
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<u64> {
+        let data_len = self
+            .inner
+            .get_file_part_count(handle.clone(), 1, extensions.clone())
+            .await?;
+
+        if self.is_plaintext_file(handle, extensions).await? {
+            return Ok(data_len);
+        }
+
+        let remainder = data_len % SLOT_SIZE;
+        let remainder = remainder.saturating_sub((HEADER_LEN + TAG_LEN) as u64);
+
+        Ok(data_len / SLOT_SIZE * CHUNK_SIZE + remainder)
+    }
+
+    async fn load_chunk(
+        &self,
+        handle: &String,
+        index: u64,
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<Vec<u8>> {
+        let chunk = self
+            .inner
+            .load_file_part(handle.clone(), index, SLOT_SIZE, extensions.clone())
+            .await?;
+
+        if chunk.is_empty() {
+            return Ok(chunk);
+        }
+
+        self.open_chunk(handle, index, &chunk, extensions).await
+    }
+
+    async fn store_chunk(
+        &self,
+        handle: &String,
+        index: u64,
+        ttl: u64,
+        data: &[u8],
+        keyring: &Keyring,
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<()> {
+        let chunk = Self::seal_chunk(handle, index, data, keyring)?;
+
+        self.inner
+            .store_file_part(
+                handle.clone(),
+                index,
+                SLOT_SIZE,
+                ttl,
+                chunk,
+                extensions.clone(),
+            )
+            .await
+    }
+
+    /// Writes `data` at `offset` of the plaintext, rewriting every chunk it touches. Writes
+    /// past the end of the file are zero filled from the end, so that every chunk but the
+    /// last one stays full.
+    async fn write_file(
+        &self,
+        handle: &String,
+        mut offset: u64,
+        mut data: Vec<u8>,
+        ttl: u64,
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<()> {
+        let data_len = self.get_plaintext_len(handle, extensions).await?;
+
+        if offset > data_len {
+            let mut padded = vec![0u8; (offset - data_len) as usize];
+            padded.extend(data);
+
+            data = padded;
+            offset = data_len;
+        }
+
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let keyring = self.get_active_keyring(extensions).await?;
+
+        let end = offset + data.len() as u64;
+        let first_index = offset / CHUNK_SIZE;
+        let last_index = (end - 1) / CHUNK_SIZE;
+
+        for index in first_index..=last_index {
+            let chunk_start = index * CHUNK_SIZE;
+
+            let start = offset.max(chunk_start) - chunk_start;
+            let stop = end.min(chunk_start + CHUNK_SIZE) - chunk_start;
+
+            let mut chunk = if chunk_start < data_len && (start > 0 || stop < CHUNK_SIZE) {
+                self.load_chunk(handle, index, extensions).await?
+            } else {
+                vec![]
+            };
+
+            if (chunk.len() as u64) < stop {
+                chunk.resize(stop as usize, 0);
+            }
+
+            let source_start = (chunk_start + start - offset) as usize;
+            let source_stop = (chunk_start + stop - offset) as usize;
+
+            chunk[start as usize..stop as usize].copy_from_slice(&data[source_start..source_stop]);
+
+            // Stores extend the expiry by `ttl`, so it is only applied once per write.
+            let chunk_ttl = if index == first_index { ttl } else { 0 };
+
+            self.store_chunk(handle, index, chunk_ttl, &chunk, &keyring, extensions)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Seals the chunks of the files moved or copied from `source_handle` again under their
+    /// new handle, walking directories.
+    fn rebind_path<'a>(
+        &'a self,
+        source_handle: String,
+        destination_handle: String,
+        extensions: &'a HashMap<String, String>,
+    ) -> Pin<Box<dyn Future<Output = types::Result<()>> + Send + 'a>> {
+        Box::pin(async move {
+            // Paths without metadata are directories if they can be listed.
+            let is_dir = match self
+                .inner
+                .get_metadata(destination_handle.clone(), extensions.clone())
+                .await
+            {
+                Ok(metadata) => metadata.file_type == NativeFileType::Dir,
+                Err(_) => self
+                    .inner
+                    .list(destination_handle.clone(), 0, 1, extensions.clone())
+                    .await
+                    .is_ok(),
+            };
+
+            if !is_dir {
+                return self
+                    .rebind_file(&source_handle, &destination_handle, extensions)
+                    .await;
+            }
+
+            let mut page_index = 0;
+
+            loop {
+                let items = self
+                    .inner
+                    .list(
+                        destination_handle.clone(),
+                        page_index,
+                        LIST_PAGE_SIZE,
+                        extensions.clone(),
+                    )
+                    .await?;
+
+                for item in items.iter() {
+                    self.rebind_path(
+                        format!("{}/{}", source_handle.trim_end_matches('/'), item),
+                        format!("{}/{}", destination_handle.trim_end_matches('/'), item),
+                        extensions,
+                    )
+                    .await?;
+                }
+
+                if (items.len() as u64) < LIST_PAGE_SIZE {
+                    return Ok(());
+                }
+
+                page_index += 1;
+            }
+        })
+    }
+
+    async fn rebind_file(
+        &self,
+        source_handle: &String,
+        destination_handle: &String,
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<()> {
+        let _guard = self.lock_file(destination_handle).await;
+
+        let data_len = self
+            .inner
+            .get_file_part_count(destination_handle.clone(), 1, extensions.clone())
+            .await?;
+
+        let chunk_count = data_len / SLOT_SIZE + (data_len % SLOT_SIZE > 0) as u64;
+
+        let mut keyring = None;
+
+        for index in 0..chunk_count {
+            let chunk = self
+                .inner
+                .load_file_part(
+                    destination_handle.clone(),
+                    index,
+                    SLOT_SIZE,
+                    extensions.clone(),
+                )
+                .await?;
+
+            // Files stored before encryption was enabled are not bound to their handle.
+            if !chunk.starts_with(ENCRYPTION_MAGIC) {
+                return Ok(());
+            }
+
+            let data = self
+                .open_chunk(source_handle, index, &chunk, extensions)
+                .await?;
+
+            if keyring.is_none() {
+                keyring = Some(self.get_active_keyring(extensions).await?);
+            }
+
+            self.store_chunk(
+                destination_handle,
+                index,
+                0,
+                &data,
+                keyring.as_ref().unwrap(),
+                extensions,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn read_file(
+        &self,
+        handle: &String,
+        offset: u64,
+        len: u64,
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<Vec<u8>> {
+        if len == 0 {
+            return Ok(vec![]);
+        }
+
+        let end = offset + len;
+        let first_index = offset / CHUNK_SIZE;
+        let last_index = (end - 1) / CHUNK_SIZE;
+
+        let mut data = Vec::new();
+
+        for index in first_index..=last_index {
+            let chunk = self.load_chunk(handle, index, extensions).await?;
+            let chunk_len = chunk.len() as u64;
+
+            data.extend(chunk);
+
+            if chunk_len < CHUNK_SIZE {
+                break;
+            }
+        }
+
+        let start = (offset - first_index * CHUNK_SIZE) as usize;
+
+        if start >= data.len() {
+            return Ok(vec![]);
+        }
+
+        data.truncate((end - first_index * CHUNK_SIZE) as usize);
+
+        Ok(data.split_off(start))
+    }
+}
@@ -3,6 +3,7 @@
 #![feature(async_closure)]
 
 pub mod conf;
+pub mod encrypted;
 mod local;
 mod memory;
 mod tikv;
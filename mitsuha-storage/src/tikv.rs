@@ -45,6 +45,42 @@ const NORMALIZED_FILE_PART_MAX_SIZE: usize = 0x20000;
 const LOWER_BOUND_SUFFIX: &str = "/";
 const UPPER_BOUND_SUFFIX: &str = "0";
 
+/// Maps errors of the TiKV client, so that those caused by unreachable stores or by regions
+/// moving between them are transient.
+trait ToTikvErrorResult<T> {
+    fn to_tikv_err_result(self) -> types::Result<T>;
+}
+
+impl<T> ToTikvErrorResult<T> for Result<T, tikv_client::Error> {
+    fn to_tikv_err_result(self) -> types::Result<T> {
+        self.map_err(|e| {
+            if is_transient_tikv_error(&e) {
+                Error::ServiceUnavailable {
+                    target: "tikv".to_string(),
+                    message: e.to_string(),
+                }
+            } else {
+                Error::UnknownWithMsgOnly {
+                    message: e.to_string(),
+                }
+            }
+        })
+    }
+}
+
+fn is_transient_tikv_error(error: &tikv_client::Error) -> bool {
+    match error {
+        tikv_client::Error::RegionError(_)
+        | tikv_client::Error::RegionForKeyNotFound { .. }
+        | tikv_client::Error::RegionNotFoundInResponse { .. }
+        | tikv_client::Error::LeaderNotFound { .. }
+        | tikv_client::Error::Grpc(_)
+        | tikv_client::Error::Io(_) => true,
+        tikv_client::Error::MultipleKeyErrors(errors) => errors.iter().all(is_transient_tikv_error),
+        _ => false,
+    }
+}
+
 #[derive(strum_macros::EnumString)]
 enum ConcurrencyMode {
     #[strum(serialize = "optimistic")]
@@ -72,12 +108,12 @@ impl RawStorage for TikvStorage {
         let mut tx = self.tx().await?;
 
         if let Err(e) = self.store_by_tx(&mut tx, spec).await {
-            tx.rollback().await.to_unknown_err_result()?;
+            tx.rollback().await.to_tikv_err_result()?;
 
             return Err(e);
         }
 
-        tx.commit().await.to_unknown_err_result()?;
+        tx.commit().await.to_tikv_err_result()?;
 
         Ok(())
     }
@@ -91,11 +127,11 @@ impl RawStorage for TikvStorage {
 
         match self.load_data(&mut tx, handle).await {
             Ok(data) => {
-                tx.commit().await.to_unknown_err_result()?;
+                tx.commit().await.to_tikv_err_result()?;
                 Ok(data)
             }
             Err(e) => {
-                tx.rollback().await.to_unknown_err_result()?;
+                tx.rollback().await.to_tikv_err_result()?;
                 Err(e)
             }
         }
@@ -110,11 +146,11 @@ impl RawStorage for TikvStorage {
 
         match self.exists_data(&mut tx, handle).await {
             Ok(data) => {
-                tx.commit().await.to_unknown_err_result()?;
+                tx.commit().await.to_tikv_err_result()?;
                 Ok(data)
             }
             Err(e) => {
-                tx.rollback().await.to_unknown_err_result()?;
+                tx.rollback().await.to_tikv_err_result()?;
                 Err(e)
             }
         }
@@ -130,11 +166,11 @@ impl RawStorage for TikvStorage {
 
         match self.persist_by_tx(&mut tx, handle, time, extensions).await {
             Ok(_) => {
-                tx.commit().await.to_unknown_err_result()?;
+                tx.commit().await.to_tikv_err_result()?;
                 Ok(())
             }
             Err(e) => {
-                tx.rollback().await.to_unknown_err_result()?;
+                tx.rollback().await.to_tikv_err_result()?;
                 Err(e)
             }
         }
@@ -149,11 +185,11 @@ impl RawStorage for TikvStorage {
 
         match self.clear_by_tx(&mut tx, handle, extensions).await {
             Ok(_) => {
-                tx.commit().await.to_unknown_err_result()?;
+                tx.commit().await.to_tikv_err_result()?;
                 Ok(())
             }
             Err(e) => {
-                tx.rollback().await.to_unknown_err_result()?;
+                tx.rollback().await.to_tikv_err_result()?;
                 Err(e)
             }
         }
@@ -188,14 +224,14 @@ impl GarbageCollectable for TikvStorage {
             let result = tx
                 .scan(lower_bound_key.clone()..upper_bound_key.clone(), page_size)
                 .await
-                .to_unknown_err_result();
+                .to_tikv_err_result();
 
             if let Err(e) = result {
-                tx.rollback().await.to_unknown_err_result()?;
+                tx.rollback().await.to_tikv_err_result()?;
                 return Err(e);
             }
 
-            tx.commit().await.to_unknown_err_result()?;
+            tx.commit().await.to_tikv_err_result()?;
 
             let kvs: Vec<KvPair> = result.unwrap().collect();
 
@@ -216,9 +252,9 @@ impl GarbageCollectable for TikvStorage {
                     if let Err(e) = tx
                         .delete(internal_metadata.handle.clone())
                         .await
-                        .to_unknown_err_result()
+                        .to_tikv_err_result()
                     {
-                        tx.rollback().await.to_unknown_err_result()?;
+                        tx.rollback().await.to_tikv_err_result()?;
                         tracing::error!(
                             "failed to perform gc operation on handle: '{}', error: {}",
                             &internal_metadata.handle,
@@ -230,9 +266,9 @@ impl GarbageCollectable for TikvStorage {
                     if let Err(e) = tx
                         .delete(lower_bound_key.clone())
                         .await
-                        .to_unknown_err_result()
+                        .to_tikv_err_result()
                     {
-                        tx.rollback().await.to_unknown_err_result()?;
+                        tx.rollback().await.to_tikv_err_result()?;
                         tracing::error!(
                             "failed to perform gc operation on handle: '{}', error: {}",
                             &internal_metadata.handle,
@@ -241,7 +277,7 @@ impl GarbageCollectable for TikvStorage {
                         continue;
                     }
 
-                    tx.commit().await.to_unknown_err_result()?;
+                    tx.commit().await.to_tikv_err_result()?;
 
                     let key_data = Vec::from(lower_bound_key.clone());
                     let key_str = String::from_utf8(key_data).to_unknown_err_result()?;
@@ -278,7 +314,7 @@ impl FileSystem for TikvStorage {
             .store_normalized_file_parts(&mut tx, &handle, ttl, offset, &data, extensions.clone())
             .await
         {
-            tx.rollback().await.to_unknown_err_result()?;
+            tx.rollback().await.to_tikv_err_result()?;
 
             return Err(e);
         }
@@ -287,12 +323,12 @@ impl FileSystem for TikvStorage {
             .store_existence_metadata(&mut tx, handle, ttl, extensions)
             .await
         {
-            tx.rollback().await.to_unknown_err_result()?;
+            tx.rollback().await.to_tikv_err_result()?;
 
             return Err(e);
         }
 
-        tx.commit().await.to_unknown_err_result()?;
+        tx.commit().await.to_tikv_err_result()?;
 
         Ok(())
     }
@@ -313,11 +349,11 @@ impl FileSystem for TikvStorage {
             .await
         {
             Ok(data) => {
-                tx.commit().await.to_unknown_err_result()?;
+                tx.commit().await.to_tikv_err_result()?;
                 Ok(data)
             }
             Err(e) => {
-                tx.rollback().await.to_unknown_err_result()?;
+                tx.rollback().await.to_tikv_err_result()?;
                 Err(e)
             }
         }
@@ -333,11 +369,11 @@ impl FileSystem for TikvStorage {
 
         let result = match self.load_total_parts_len(&mut tx, &handle).await {
             Ok(data) => {
-                tx.commit().await.to_unknown_err_result()?;
+                tx.commit().await.to_tikv_err_result()?;
                 Ok(data.unwrap_or_default())
             }
             Err(e) => {
-                tx.rollback().await.to_unknown_err_result()?;
+                tx.rollback().await.to_tikv_err_result()?;
                 Err(e)
             }
         };
@@ -356,11 +392,11 @@ impl FileSystem for TikvStorage {
 
         match self.load_native_metadata(&mut tx, handle).await {
             Ok(metadata) => {
-                tx.commit().await.to_unknown_err_result()?;
+                tx.commit().await.to_tikv_err_result()?;
                 Ok(metadata)
             }
             Err(e) => {
-                tx.rollback().await.to_unknown_err_result()?;
+                tx.rollback().await.to_tikv_err_result()?;
                 Err(e)
             }
         }
@@ -380,11 +416,11 @@ impl FileSystem for TikvStorage {
             .await
         {
             Ok(_) => {
-                tx.commit().await.to_unknown_err_result()?;
+                tx.commit().await.to_tikv_err_result()?;
                 Ok(())
             }
             Err(e) => {
-                tx.rollback().await.to_unknown_err_result()?;
+                tx.rollback().await.to_tikv_err_result()?;
                 Err(e)
             }
         }
@@ -421,11 +457,11 @@ impl FileSystem for TikvStorage {
 
         let result = match tx.scan_keys(lower_bound_key..upper_bound_key, limit).await {
             Ok(data) => {
-                tx.commit().await.to_unknown_err_result()?;
+                tx.commit().await.to_tikv_err_result()?;
                 Ok(data)
             }
             Err(e) => {
-                tx.rollback().await.to_unknown_err_result()?;
+                tx.rollback().await.to_tikv_err_result()?;
                 Err(e)
             }
         };
@@ -433,7 +469,7 @@ impl FileSystem for TikvStorage {
         let mut count = 0;
         let mut output = Vec::with_capacity(page_index as usize);
 
-        for key in result.to_unknown_err_result()?.into_iter() {
+        for key in result.to_tikv_err_result()?.into_iter() {
             count += 1;
 
             if start_offset >= count {
@@ -461,7 +497,7 @@ impl FileSystem for TikvStorage {
         let result = match self.load_internal_metadata(&mut tx, handle.clone()).await {
             Ok(data) => Ok(data),
             Err(e) => {
-                tx.rollback().await.to_unknown_err_result()?;
+                tx.rollback().await.to_tikv_err_result()?;
                 Err(e)
             }
         };
@@ -479,11 +515,11 @@ impl FileSystem for TikvStorage {
 
         match self.store_by_tx(&mut tx, spec).await {
             Ok(_) => {
-                tx.commit().await.to_unknown_err_result()?;
+                tx.commit().await.to_tikv_err_result()?;
                 Ok(())
             }
             Err(e) => {
-                tx.rollback().await.to_unknown_err_result()?;
+                tx.rollback().await.to_tikv_err_result()?;
                 Err(e)
             }
         }
@@ -515,11 +551,11 @@ impl FileSystem for TikvStorage {
 
         match result {
             Ok(_) => {
-                tx.commit().await.to_unknown_err_result()?;
+                tx.commit().await.to_tikv_err_result()?;
                 Ok(())
             }
             Err(e) => {
-                tx.rollback().await.to_unknown_err_result()?;
+                tx.rollback().await.to_tikv_err_result()?;
                 Err(e)
             }
         }
@@ -540,11 +576,11 @@ impl FileSystem for TikvStorage {
 
         match result {
             Ok(_) => {
-                tx.commit().await.to_unknown_err_result()?;
+                tx.commit().await.to_tikv_err_result()?;
                 Ok(())
             }
             Err(e) => {
-                tx.rollback().await.to_unknown_err_result()?;
+                tx.rollback().await.to_tikv_err_result()?;
                 Err(e)
             }
         }
@@ -564,11 +600,11 @@ impl FileSystem for TikvStorage {
 
         match result {
             Ok(_) => {
-                tx.commit().await.to_unknown_err_result()?;
+                tx.commit().await.to_tikv_err_result()?;
                 Ok(())
             }
             Err(e) => {
-                tx.rollback().await.to_unknown_err_result()?;
+                tx.rollback().await.to_tikv_err_result()?;
                 Err(e)
             }
         }
@@ -585,11 +621,11 @@ impl FileSystem for TikvStorage {
 
         match result {
             Ok(_) => {
-                tx.commit().await.to_unknown_err_result()?;
+                tx.commit().await.to_tikv_err_result()?;
                 Ok(())
             }
             Err(e) => {
-                tx.rollback().await.to_unknown_err_result()?;
+                tx.rollback().await.to_tikv_err_result()?;
                 Err(e)
             }
         }
@@ -602,7 +638,7 @@ impl TikvStorage {
 
         let client = TransactionClient::new(pd_endpoints.split(",").collect())
             .await
-            .to_unknown_err_result()?;
+            .to_tikv_err_result()?;
 
         let concurrency_mode = ConcurrencyMode::from_str(
             class
@@ -621,16 +657,12 @@ impl TikvStorage {
 
     async fn tx(&self) -> types::Result<Transaction> {
         let tx = match self.concurrency_mode {
-            ConcurrencyMode::Optimistic => self
-                .client
-                .begin_optimistic()
-                .await
-                .to_unknown_err_result()?,
-            ConcurrencyMode::Pessimistic => self
-                .client
-                .begin_pessimistic()
-                .await
-                .to_unknown_err_result()?,
+            ConcurrencyMode::Optimistic => {
+                self.client.begin_optimistic().await.to_tikv_err_result()?
+            }
+            ConcurrencyMode::Pessimistic => {
+                self.client.begin_pessimistic().await.to_tikv_err_result()?
+            }
         };
 
         Ok(tx)
@@ -686,7 +718,7 @@ impl TikvStorage {
         handle: String,
         data: Vec<u8>,
     ) -> types::Result<()> {
-        tx.put(handle, data).await.to_unknown_err_result()?;
+        tx.put(handle, data).await.to_tikv_err_result()?;
         Ok(())
     }
 
@@ -708,17 +740,17 @@ impl TikvStorage {
         tx: &mut Transaction,
         handle: String,
     ) -> types::Result<Option<Vec<u8>>> {
-        let data = tx.get(handle).await.to_unknown_err_result()?;
+        let data = tx.get(handle).await.to_tikv_err_result()?;
 
         Ok(data)
     }
 
     async fn exists_data(&self, tx: &mut Transaction, handle: String) -> types::Result<bool> {
-        tx.key_exists(handle).await.to_unknown_err_result()
+        tx.key_exists(handle).await.to_tikv_err_result()
     }
 
     async fn clear_data(&self, tx: &mut Transaction, handle: String) -> types::Result<()> {
-        tx.delete(handle).await.to_unknown_err_result()?;
+        tx.delete(handle).await.to_tikv_err_result()?;
         Ok(())
     }
 
@@ -983,7 +1015,7 @@ impl TikvStorage {
             .try_into()
             .map_err(|e: anyhow::Error| err_unknown!(e))?;
 
-        tx.put(metadata_handle, data).await.to_unknown_err_result()
+        tx.put(metadata_handle, data).await.to_tikv_err_result()
     }
 
     async fn load_internal_metadata(
@@ -996,7 +1028,7 @@ impl TikvStorage {
         let data = tx
             .get(metadata_handle)
             .await
-            .to_unknown_err_result()?
+            .to_tikv_err_result()?
             .ok_or(err_unknown!("failed to load internal metadata"))?;
 
         let value = musubi_api::types::Value::try_from(data).to_unknown_err_result()?;
@@ -1027,7 +1059,7 @@ impl TikvStorage {
         let data = tx
             .get(metadata_handle)
             .await
-            .to_unknown_err_result()?
+            .to_tikv_err_result()?
             .ok_or(err_unknown!("failed to load native file metadata"))?;
 
         let value = musubi_api::types::Value::try_from(data).to_unknown_err_result()?;
@@ -1116,7 +1148,7 @@ impl TikvStorage {
             for key in tx
                 .scan_keys(lower_bound_key.clone()..upper_bound_key.clone(), page_size)
                 .await
-                .to_unknown_err_result()?
+                .to_tikv_err_result()?
             {
                 lower_bound_key = key.clone();
 
@@ -1221,3 +1253,24 @@ impl TikvStorage {
         self.clear_by_tx(tx, lease_handle, extensions).await
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_transient_errors() {
+        let result: Result<(), tikv_client::Error> = Err(tikv_client::Error::Io(
+            std::io::Error::from(std::io::ErrorKind::ConnectionRefused),
+        ));
+
+        let err = result.to_tikv_err_result().unwrap_err();
+
+        assert!(matches!(err, Error::ServiceUnavailable { .. }));
+        assert!(err.is_transient());
+
+        let result: Result<(), tikv_client::Error> = Err(tikv_client::Error::Unimplemented);
+
+        assert!(!result.to_tikv_err_result().unwrap_err().is_transient());
+    }
+}
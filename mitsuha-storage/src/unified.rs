@@ -1,4 +1,10 @@
-use std::{collections::HashMap, num::ParseIntError, path::Path, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    num::ParseIntError,
+    path::Path,
+    sync::{Arc, Weak},
+    time::Duration,
+};
 
 use async_trait::async_trait;
 use mitsuha_core::errors::ToUnknownErrorResult;
@@ -9,8 +15,8 @@ use mitsuha_core::{
     errors::Error,
     selector::Label,
     storage::{
-        FileSystem, GarbageCollectable, RawStorage, Storage, StorageClass, StorageKind,
-        StorageLocality,
+        run_gc_hooks, FileSystem, GarbageCollectable, RawStorage, Storage, StorageClass,
+        StorageKind, StorageLocality,
     },
     types,
 };
@@ -21,7 +27,10 @@ use mitsuha_filesystem::{
     NativeFileLease, NativeFileMetadata,
 };
 
-use crate::{local::LocalStorage, memory::MemoryStorage, tikv::TikvStorage, util::StorageClassExt};
+use crate::{
+    encrypted::EncryptedStorage, local::LocalStorage, memory::MemoryStorage, tikv::TikvStorage,
+    util::StorageClassExt,
+};
 
 #[derive(Clone)]
 pub struct UnifiedStorage {
@@ -478,6 +487,15 @@ impl UnifiedStorage {
                 StorageKind::Tikv => TikvStorage::new(storage_class.clone()).await?,
             };
 
+            let storage_impl: Arc<Box<dyn Storage>> = if storage_class.should_enable_encryption() {
+                Arc::new(Box::new(EncryptedStorage::new(
+                    storage_impl,
+                    storage_class,
+                )?))
+            } else {
+                storage_impl
+            };
+
             let mut processed_storage_class = storage_class.clone();
 
             processed_storage_class.labels.push(Label {
@@ -495,7 +513,7 @@ impl UnifiedStorage {
 
         let output: Arc<Box<dyn Storage>> = Arc::new(Box::new(unified_storage));
 
-        Self::start_gc(output.clone());
+        Self::start_gc(Arc::downgrade(&output));
 
         Ok(output)
     }
@@ -935,15 +953,20 @@ impl UnifiedStorage {
         }
     }
 
-    fn start_gc(collectable: Arc<Box<dyn Storage>>) {
+    /// Runs a gc cycle every second for as long as the storage is in use.
+    fn start_gc(collectable: Weak<Box<dyn Storage>>) {
         tokio::task::spawn(async move {
-            loop {
+            while let Some(collectable) = collectable.upgrade() {
                 tracing::debug!("running gc cycle");
 
-                if let Err(e) = collectable.garbage_collect().await {
-                    tracing::debug!("failed to run gc cycle. error: {}", e);
+                match collectable.garbage_collect().await {
+                    Ok(handles) if !handles.is_empty() => run_gc_hooks(&handles).await,
+                    Ok(_) => {}
+                    Err(e) => tracing::debug!("failed to run gc cycle. error: {}", e),
                 }
 
+                drop(collectable);
+
                 tokio::time::sleep(Duration::from_secs(1)).await;
             }
         });
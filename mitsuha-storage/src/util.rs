@@ -40,6 +40,8 @@ pub fn get_expiry(
 
 pub trait StorageClassExt {
     fn should_enable_gc(&self) -> types::Result<bool>;
+
+    fn should_enable_encryption(&self) -> bool;
 }
 
 impl StorageClassExt for StorageClass {
@@ -50,4 +52,9 @@ impl StorageClassExt for StorageClass {
             Ok(false)
         }
     }
+
+    fn should_enable_encryption(&self) -> bool {
+        self.properties
+            .contains_key(&ConfKey::EncryptionActiveMasterKey.to_string())
+    }
 }
@@ -0,0 +1,552 @@
+#![feature(async_iterator)]
+
+use serial_test::serial;
+use std::{collections::HashMap, sync::Arc};
+
+use lazy_static::lazy_static;
+use mitsuha_core::{
+    config,
+    constants::{Constants, StorageControlConstants},
+    selector::Label,
+    storage::{FileSystem, RawStorage, Storage, StorageClass, StorageLocality},
+};
+use mitsuha_core_types::kernel::StorageSpec;
+use mitsuha_filesystem::constant::NativeFileSystemConstants;
+use mitsuha_storage::{conf::ConfKey, encrypted::EncryptedStorage, unified::UnifiedStorage};
+use sha2::{Digest, Sha256};
+
+mod fs;
+
+const PRIMARY_KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+const SECONDARY_KEY: &str = "f0e0d0c0b0a090807060504030201000f0e0d0c0b0a090807060504030201000";
+
+fn make_class(properties: &[(String, &str)]) -> StorageClass {
+    StorageClass {
+        kind: mitsuha_core::storage::StorageKind::Memory,
+        locality: StorageLocality::Solid { cache_name: None },
+        name: "solid_memory_1".to_string(),
+        labels: vec![Label {
+            key: "storage".to_string(),
+            value: "sample".to_string(),
+        }],
+        properties: properties
+            .iter()
+            .map(|(key, value)| (key.clone(), value.to_string()))
+            .collect(),
+    }
+}
+
+fn make_encrypted_class(active_master_key: &str, master_keys: &[(&str, &str)]) -> StorageClass {
+    let mut properties = vec![(
+        ConfKey::EncryptionActiveMasterKey.to_string(),
+        active_master_key,
+    )];
+
+    for (id, key) in master_keys {
+        properties.push((format!("{}.{}", ConfKey::EncryptionMasterKey, id), *key));
+    }
+
+    make_class(&properties)
+}
+
+fn make_basic_config() -> config::storage::Storage {
+    config::storage::Storage {
+        classes: vec![make_encrypted_class("primary", &[("primary", PRIMARY_KEY)])],
+    }
+}
+
+fn make_extensions(namespace: &str) -> HashMap<String, String> {
+    [
+        (
+            StorageControlConstants::StorageSelectorQuery.to_string(),
+            serde_json::to_string(&Label {
+                key: "storage".to_string(),
+                value: "sample".to_string(),
+            })
+            .unwrap(),
+        ),
+        (
+            Constants::ChannelNamespace.to_string(),
+            namespace.to_string(),
+        ),
+    ]
+    .into_iter()
+    .collect()
+}
+
+/// Returns a plain storage and an encrypted storage on top of it.
+async fn make_storages(class: StorageClass) -> (Arc<Box<dyn Storage>>, EncryptedStorage) {
+    let config = config::storage::Storage {
+        classes: vec![make_class(&[])],
+    };
+
+    let inner = UnifiedStorage::new(&config).await.unwrap();
+    let storage = EncryptedStorage::new(inner.clone(), &class).unwrap();
+
+    (inner, storage)
+}
+
+fn make_data(len: usize) -> Vec<u8> {
+    (0..len).map(|x| (x % 251) as u8).collect()
+}
+
+/// Reads the id of the data key the first chunk of an encrypted blob was sealed with.
+fn get_key_id(raw: &[u8]) -> u32 {
+    u32::from_be_bytes(raw[12..16].try_into().unwrap())
+}
+
+#[tokio::test]
+#[serial]
+async fn store_and_load() -> anyhow::Result<()> {
+    let storage = UnifiedStorage::new(&make_basic_config()).await?;
+
+    let spec = StorageSpec {
+        handle: "spec1".to_string(),
+        data: "Hello world!".bytes().collect(),
+        ttl: 100,
+        extensions: make_extensions("tenant1"),
+    };
+
+    storage.store(spec.clone()).await?;
+
+    let data = storage
+        .load(spec.handle.clone(), spec.extensions.clone())
+        .await?;
+
+    assert_eq!("Hello world!".to_string(), String::from_utf8(data).unwrap());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn encrypt_at_rest() -> anyhow::Result<()> {
+    let (inner, storage) =
+        make_storages(make_encrypted_class("primary", &[("primary", PRIMARY_KEY)])).await;
+
+    let extensions = make_extensions("tenant1");
+    let data = make_data(40_000);
+
+    storage
+        .store(StorageSpec {
+            handle: "spec1".to_string(),
+            data: data.clone(),
+            ttl: 100,
+            extensions: extensions.clone(),
+        })
+        .await?;
+
+    let raw = inner.load("spec1".to_string(), extensions.clone()).await?;
+
+    assert!(raw.len() > data.len());
+    assert!(!raw.windows(64).any(|x| x == &data[..64]));
+
+    assert_eq!(
+        storage
+            .load("spec1".to_string(), extensions.clone())
+            .await?,
+        data
+    );
+
+    // Blobs stored whole can be read in parts.
+    let part = storage
+        .load_file_part("spec1".to_string(), 3, 7000, extensions.clone())
+        .await?;
+
+    assert_eq!(part, data[21_000..28_000]);
+
+    assert_eq!(
+        storage
+            .get_file_part_count("spec1".to_string(), 7000, extensions.clone())
+            .await?,
+        6
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn write_and_truncate_file_parts() -> anyhow::Result<()> {
+    let (_, storage) =
+        make_storages(make_encrypted_class("primary", &[("primary", PRIMARY_KEY)])).await;
+
+    let extensions = make_extensions("tenant1");
+    let data = make_data(50_000);
+
+    storage
+        .store(StorageSpec {
+            handle: "file1".to_string(),
+            data: vec![],
+            ttl: 100,
+            extensions: extensions.clone(),
+        })
+        .await?;
+
+    for (index, part) in data.chunks(3000).enumerate() {
+        storage
+            .store_file_part(
+                "file1".to_string(),
+                index as u64,
+                3000,
+                0,
+                part.to_vec(),
+                extensions.clone(),
+            )
+            .await?;
+    }
+
+    assert_eq!(
+        storage
+            .load("file1".to_string(), extensions.clone())
+            .await?,
+        data
+    );
+
+    storage
+        .truncate("file1".to_string(), 20_000, extensions.clone())
+        .await?;
+
+    assert_eq!(
+        storage
+            .load("file1".to_string(), extensions.clone())
+            .await?,
+        data[..20_000]
+    );
+
+    storage
+        .truncate("file1".to_string(), 25_000, extensions.clone())
+        .await?;
+
+    let mut expected = data[..20_000].to_vec();
+    expected.resize(25_000, 0);
+
+    assert_eq!(
+        storage
+            .load("file1".to_string(), extensions.clone())
+            .await?,
+        expected
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn read_plaintext_file_parts() -> anyhow::Result<()> {
+    let (inner, storage) =
+        make_storages(make_encrypted_class("primary", &[("primary", PRIMARY_KEY)])).await;
+
+    let extensions = make_extensions("tenant1");
+    let data = make_data(50_000);
+
+    // Files written in parts before encryption was enabled are readable as they are.
+    inner
+        .store(StorageSpec {
+            handle: "file1".to_string(),
+            data: vec![],
+            ttl: 100,
+            extensions: extensions.clone(),
+        })
+        .await?;
+
+    for (index, part) in data.chunks(3000).enumerate() {
+        inner
+            .store_file_part(
+                "file1".to_string(),
+                index as u64,
+                3000,
+                0,
+                part.to_vec(),
+                extensions.clone(),
+            )
+            .await?;
+    }
+
+    assert_eq!(
+        storage
+            .get_file_part_count("file1".to_string(), 3000, extensions.clone())
+            .await?,
+        17
+    );
+
+    for (index, part) in data.chunks(3000).enumerate() {
+        assert_eq!(
+            storage
+                .load_file_part("file1".to_string(), index as u64, 3000, extensions.clone())
+                .await?,
+            part
+        );
+    }
+
+    // Writing a part encrypts the rest of the file.
+    storage
+        .store_file_part(
+            "file1".to_string(),
+            2,
+            3000,
+            0,
+            vec![7u8; 3000],
+            extensions.clone(),
+        )
+        .await?;
+
+    let mut expected = data.clone();
+    expected[6000..9000].fill(7);
+
+    assert_eq!(
+        storage
+            .load("file1".to_string(), extensions.clone())
+            .await?,
+        expected
+    );
+    assert!(!inner
+        .load("file1".to_string(), extensions.clone())
+        .await?
+        .windows(64)
+        .any(|x| x == &data[..64]));
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn rotate_and_reencrypt() -> anyhow::Result<()> {
+    let (inner, storage) =
+        make_storages(make_encrypted_class("primary", &[("primary", PRIMARY_KEY)])).await;
+
+    let extensions = make_extensions("tenant1");
+    let data = make_data(20_000);
+
+    storage
+        .store(StorageSpec {
+            handle: "spec1".to_string(),
+            data: data.clone(),
+            ttl: 100,
+            extensions: extensions.clone(),
+        })
+        .await?;
+
+    // Blobs stored before encryption was enabled are readable and can be encrypted.
+    inner
+        .store(StorageSpec {
+            handle: "legacy".to_string(),
+            data: data.clone(),
+            ttl: 100,
+            extensions: extensions.clone(),
+        })
+        .await?;
+
+    let key_id = get_key_id(&inner.load("spec1".to_string(), extensions.clone()).await?);
+
+    assert_ne!(
+        storage.rotate_data_key("tenant1", &extensions).await?,
+        key_id
+    );
+
+    assert_eq!(
+        storage
+            .load("spec1".to_string(), extensions.clone())
+            .await?,
+        data
+    );
+
+    assert!(
+        storage
+            .reencrypt_blob("spec1".to_string(), 100, extensions.clone())
+            .await?
+    );
+    assert!(
+        !storage
+            .reencrypt_blob("spec1".to_string(), 100, extensions.clone())
+            .await?
+    );
+
+    assert!(
+        storage
+            .reencrypt_blob("legacy".to_string(), 100, extensions.clone())
+            .await?
+    );
+    assert_ne!(
+        inner.load("legacy".to_string(), extensions.clone()).await?,
+        data
+    );
+
+    // Switching the active master key rewraps the data keys, so the previous master key can
+    // be dropped afterwards.
+    let rotated = EncryptedStorage::new(
+        inner.clone(),
+        &make_encrypted_class(
+            "secondary",
+            &[("primary", PRIMARY_KEY), ("secondary", SECONDARY_KEY)],
+        ),
+    )?;
+
+    assert_eq!(
+        rotated
+            .load("spec1".to_string(), extensions.clone())
+            .await?,
+        data
+    );
+
+    let retired = EncryptedStorage::new(
+        inner.clone(),
+        &make_encrypted_class("secondary", &[("secondary", SECONDARY_KEY)]),
+    )?;
+
+    for handle in ["spec1", "legacy"] {
+        assert_eq!(
+            retired.load(handle.to_string(), extensions.clone()).await?,
+            data
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn keyrings_and_chunks_are_protected() -> anyhow::Result<()> {
+    let (inner, storage) =
+        make_storages(make_encrypted_class("primary", &[("primary", PRIMARY_KEY)])).await;
+
+    let storage = Arc::new(storage);
+    let extensions = make_extensions("tenant1");
+    let data = make_data(20_000);
+
+    // Concurrent first writes of a namespace create a single data key.
+    let mut tasks = vec![];
+
+    for index in 0..8 {
+        let storage = storage.clone();
+        let spec = StorageSpec {
+            handle: format!("spec{}", index),
+            data: data.clone(),
+            ttl: 100,
+            extensions: extensions.clone(),
+        };
+
+        tasks.push(tokio::task::spawn(async move { storage.store(spec).await }));
+    }
+
+    for task in tasks {
+        task.await??;
+    }
+
+    let key_id = get_key_id(&inner.load("spec0".to_string(), extensions.clone()).await?);
+
+    for index in 1..8 {
+        let raw = inner
+            .load(format!("spec{}", index), extensions.clone())
+            .await?;
+
+        assert_eq!(get_key_id(&raw), key_id);
+    }
+
+    let keyring_handle = format!(
+        "/{}.encryption.keyring.0011223344556677",
+        NativeFileSystemConstants::MnfsSuffix
+    );
+
+    assert!(storage
+        .truncate(keyring_handle.clone(), 0, extensions.clone())
+        .await
+        .is_err());
+    assert!(storage
+        .delete_path(keyring_handle.clone(), extensions.clone())
+        .await
+        .is_err());
+    assert!(storage
+        .move_path("spec0".to_string(), keyring_handle, extensions.clone())
+        .await
+        .is_err());
+
+    // Chunks cannot be swapped between blobs.
+    let raw = inner.load("spec0".to_string(), extensions.clone()).await?;
+
+    inner
+        .store(StorageSpec {
+            handle: "spec1".to_string(),
+            data: raw,
+            ttl: 100,
+            extensions: extensions.clone(),
+        })
+        .await?;
+
+    assert!(storage
+        .load("spec1".to_string(), extensions.clone())
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn concurrent_rotations_keep_data_keys() -> anyhow::Result<()> {
+    let class = make_encrypted_class("primary", &[("primary", PRIMARY_KEY)]);
+    let (inner, storage) = make_storages(class.clone()).await;
+
+    let extensions = make_extensions("tenant1");
+    let data = make_data(20_000);
+
+    storage
+        .store(StorageSpec {
+            handle: "spec0".to_string(),
+            data: data.clone(),
+            ttl: 100,
+            extensions: extensions.clone(),
+        })
+        .await?;
+
+    let keyring_handle = format!(
+        "/{}.encryption.keyring.{}",
+        NativeFileSystemConstants::MnfsSuffix,
+        hex::encode(&Sha256::digest("tenant1")[..8])
+    );
+
+    let keyring = inner
+        .load(keyring_handle.clone(), extensions.clone())
+        .await?;
+
+    storage.rotate_data_key("tenant1", &extensions).await?;
+
+    storage
+        .store(StorageSpec {
+            handle: "spec1".to_string(),
+            data: data.clone(),
+            ttl: 100,
+            extensions: extensions.clone(),
+        })
+        .await?;
+
+    // Another runtime rotating the keyring at the same moment overwrites the rotation.
+    inner
+        .store(StorageSpec {
+            handle: keyring_handle,
+            data: keyring,
+            ttl: 100,
+            extensions: extensions.clone(),
+        })
+        .await?;
+
+    let other = EncryptedStorage::new(inner.clone(), &class)?;
+
+    for handle in ["spec0", "spec1"] {
+        assert_eq!(
+            other.load(handle.to_string(), extensions.clone()).await?,
+            data
+        );
+    }
+
+    Ok(())
+}
+
+// MNFS tests
+
+lazy_static! {
+    static ref CONFIG: config::storage::Storage = make_basic_config();
+    static ref EXTENSIONS: HashMap<String, String> = make_extensions("tenant1");
+}
+
+mnfs_test_suite!(&CONFIG, &EXTENSIONS);
@@ -0,0 +1,56 @@
+#[derive(strum_macros::Display)]
+pub enum ConfKey {
+    #[strum(serialize = "module_cache_capacity")]
+    ModuleCacheCapacity,
+
+    #[strum(serialize = "precompiled_module_selector")]
+    PrecompiledModuleSelector,
+
+    #[strum(serialize = "precompiled_module_ttl")]
+    PrecompiledModuleTTL,
+
+    #[strum(serialize = "precompiled_module_key")]
+    PrecompiledModuleKey,
+
+    #[strum(serialize = "instance_pre_cache_capacity")]
+    InstancePreCacheCapacity,
+
+    #[strum(serialize = "pooling_allocator")]
+    PoolingAllocator,
+
+    #[strum(serialize = "pooling_total_instances")]
+    PoolingTotalInstances,
+
+    #[strum(serialize = "pooling_memory_pages")]
+    PoolingMemoryPages,
+
+    #[strum(serialize = "export_concurrency")]
+    ExportConcurrency,
+
+    #[strum(serialize = "epoch_tick_interval_ms")]
+    EpochTickInterval,
+
+    #[strum(serialize = "default_cpu_budget_ms")]
+    DefaultCpuBudget,
+
+    #[strum(serialize = "profile_sample_interval_ms")]
+    ProfileSampleInterval,
+
+    #[strum(serialize = "memory64")]
+    Memory64,
+
+    #[strum(serialize = "native_modules")]
+    NativeModules,
+
+    #[strum(serialize = "native_max_output_size")]
+    NativeMaxOutputSize,
+
+    #[strum(serialize = "require_capability_manifest")]
+    RequireCapabilityManifest,
+
+    #[strum(serialize = "wasm_backtrace_details")]
+    WasmBacktraceDetails,
+
+    #[strum(serialize = "coredump_on_trap")]
+    CoreDumpOnTrap,
+}
@@ -2,4 +2,10 @@
 pub enum Constants {
     #[strum(serialize = "mitsuha.runtime")]
     RuntimeModuleName,
+
+    #[strum(serialize = "mitsuha.component")]
+    ComponentErrorCode,
+
+    #[strum(serialize = "mitsuha:runtime/kernel")]
+    ComponentKernelInterface,
 }
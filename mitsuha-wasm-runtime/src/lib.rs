@@ -1,5 +1,7 @@
 extern crate core;
 
+pub mod conf;
 pub mod constants;
+pub mod metric;
 pub mod resolver;
 pub mod wasmtime;
@@ -0,0 +1,24 @@
+use lazy_static::lazy_static;
+use prometheus::{Histogram, HistogramOpts, IntCounterVec, Opts};
+
+lazy_static! {
+    static ref MODULE_COMPILE_DURATION: Histogram = Histogram::with_opts(
+        HistogramOpts::new("module_compile_duration", "Module Compile Duration")
+            .namespace("mitsuha_wasm_runtime")
+    )
+    .expect("failed to initialize metric: MODULE_COMPILE_DURATION");
+    static ref MODULE_CACHE_REQUEST_COUNT: IntCounterVec = IntCounterVec::new(
+        Opts::new("module_cache_request_count", "Module Cache Request Count")
+            .namespace("mitsuha_wasm_runtime"),
+        &["cache", "result"]
+    )
+    .expect("failed to initialize metric: MODULE_CACHE_REQUEST_COUNT");
+}
+
+pub fn module_compile_duration_metric() -> &'static Histogram {
+    &MODULE_COMPILE_DURATION
+}
+
+pub fn module_cache_request_count_metric() -> &'static IntCounterVec {
+    &MODULE_CACHE_REQUEST_COUNT
+}
@@ -4,11 +4,13 @@ use async_trait::async_trait;
 use mitsuha_core::{resolver::Resolver, types};
 use mitsuha_core_types::module::ModuleInfo;
 
-use crate::wasmtime::WasmtimeModule;
+use crate::metric::module_cache_request_count_metric;
+use crate::wasmtime::{PrecompiledModuleStore, WasmtimeModule};
 
 pub struct WasmtimeModuleResolver {
     resolver: Arc<Box<dyn Resolver<ModuleInfo, Vec<u8>>>>,
     engine: wasmtime::Engine,
+    precompiled_store: Option<Arc<PrecompiledModuleStore>>,
 }
 
 impl WasmtimeModuleResolver {
@@ -16,7 +18,73 @@ impl WasmtimeModuleResolver {
         engine: wasmtime::Engine,
         resolver: Arc<Box<dyn Resolver<ModuleInfo, Vec<u8>>>>,
     ) -> Self {
-        Self { resolver, engine }
+        Self {
+            resolver,
+            engine,
+            precompiled_store: None,
+        }
+    }
+
+    pub fn with_precompiled_store(
+        mut self,
+        precompiled_store: Option<Arc<PrecompiledModuleStore>>,
+    ) -> Self {
+        self.precompiled_store = precompiled_store;
+        self
+    }
+
+    async fn resolve_precompiled(
+        &self,
+        store: &PrecompiledModuleStore,
+        key: &ModuleInfo,
+        data: Vec<u8>,
+    ) -> types::Result<WasmtimeModule> {
+        let artifact_key = PrecompiledModuleStore::get_key(&self.engine, data.as_slice());
+
+        match store.load(&artifact_key).await {
+            Ok(Some(artifact)) => {
+                match WasmtimeModule::deserialize(
+                    data.as_slice(),
+                    artifact.as_slice(),
+                    key.clone(),
+                    &self.engine,
+                ) {
+                    Ok(module) => {
+                        module_cache_request_count_metric()
+                            .with_label_values(&["persisted", "hit"])
+                            .inc();
+
+                        return Ok(module);
+                    }
+                    Err(e) => {
+                        tracing::warn!("failed to deserialize precompiled module: {}", e);
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                tracing::warn!("failed to load precompiled module: {}", e);
+            }
+        }
+
+        module_cache_request_count_metric()
+            .with_label_values(&["persisted", "miss"])
+            .inc();
+
+        let module = WasmtimeModule::new(data, key.clone(), &self.engine)?;
+
+        match module.serialize() {
+            Ok(artifact) => {
+                if let Err(e) = store.store(&artifact_key, artifact).await {
+                    tracing::warn!("failed to store precompiled module: {}", e);
+                }
+            }
+            Err(e) => {
+                tracing::warn!("failed to serialize module: {}", e);
+            }
+        }
+
+        Ok(module)
     }
 }
 
@@ -24,7 +92,11 @@ impl WasmtimeModuleResolver {
 impl Resolver<ModuleInfo, WasmtimeModule> for WasmtimeModuleResolver {
     async fn resolve(&self, key: &ModuleInfo) -> types::Result<WasmtimeModule> {
         let data = self.resolver.resolve(&key).await?;
-        WasmtimeModule::new(data, key.clone(), &self.engine)
+
+        match self.precompiled_store.as_ref() {
+            Some(store) => self.resolve_precompiled(store, key, data).await,
+            None => WasmtimeModule::new(data, key.clone(), &self.engine),
+        }
     }
 
     async fn register(&self, _key: &ModuleInfo, _value: &WasmtimeModule) -> types::Result<()> {
@@ -0,0 +1,226 @@
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use hmac::{Hmac, Mac};
+use mitsuha_core::{
+    errors::{Error, ToUnknownErrorResult},
+    kernel::LabelExtensionExt,
+    selector::Label,
+    storage::Storage,
+    types,
+};
+use mitsuha_core_types::kernel::StorageSpec;
+use sha2::{Digest, Sha256};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const TAG_SIZE: usize = 32;
+
+/// Persists precompiled wasmtime artifacts in a storage class so that modules
+/// survive linker cache evictions and process restarts without recompilation.
+///
+/// Artifacts are loaded back with `wasmtime::Module::deserialize`, which trusts
+/// its input. Each artifact is therefore stored with an HMAC-SHA256 tag over its
+/// handle and content, and artifacts whose tag does not match the runtime key are
+/// never handed to wasmtime.
+pub struct PrecompiledModuleStore {
+    storage: Arc<Box<dyn Storage>>,
+    selector: Label,
+    ttl: u64,
+    key: Vec<u8>,
+}
+
+struct Sha256Hasher(Sha256);
+
+impl Hasher for Sha256Hasher {
+    fn finish(&self) -> u64 {
+        let digest = self.0.clone().finalize();
+        u64::from_le_bytes(digest[..8].try_into().unwrap())
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+}
+
+impl PrecompiledModuleStore {
+    pub fn new(storage: Arc<Box<dyn Storage>>, selector: Label, ttl: u64, key: Vec<u8>) -> Self {
+        Self {
+            storage,
+            selector,
+            ttl,
+            key,
+        }
+    }
+
+    /// Computes the storage handle for an artifact, derived from the module content
+    /// hash and a fingerprint of the engine configuration.
+    pub fn get_key(engine: &wasmtime::Engine, data: &[u8]) -> String {
+        let content_hash = hex::encode(Sha256::digest(data));
+
+        let mut hasher = Sha256Hasher(Sha256::new());
+        engine.precompile_compatibility_hash().hash(&mut hasher);
+        let fingerprint = hex::encode(hasher.0.finalize());
+
+        format!(
+            "/mitsuha/wasmtime/precompiled/{}/{}",
+            content_hash, fingerprint
+        )
+    }
+
+    fn get_extensions(&self) -> HashMap<String, String> {
+        HashMap::<String, String>::new().with_selector(&self.selector)
+    }
+
+    fn make_mac(&self, key: &str, artifact: &[u8]) -> types::Result<HmacSha256> {
+        let mut mac = HmacSha256::new_from_slice(&self.key).to_unknown_err_result()?;
+
+        mac.update(key.as_bytes());
+        mac.update(artifact);
+
+        Ok(mac)
+    }
+
+    /// Returns the artifact stored under `key`, failing when its tag does not verify.
+    pub async fn load(&self, key: &str) -> types::Result<Option<Vec<u8>>> {
+        if !self
+            .storage
+            .exists(key.to_string(), self.get_extensions())
+            .await?
+        {
+            return Ok(None);
+        }
+
+        let mut data = self
+            .storage
+            .load(key.to_string(), self.get_extensions())
+            .await?;
+
+        let verified = data.len() >= TAG_SIZE && {
+            let tag = data.split_off(data.len() - TAG_SIZE);
+
+            self.make_mac(key, &data)?.verify_slice(&tag).is_ok()
+        };
+
+        if !verified {
+            return Err(Error::InvalidOperation {
+                message: format!("precompiled module artifact '{}' failed verification", key),
+            });
+        }
+
+        Ok(Some(data))
+    }
+
+    pub async fn store(&self, key: &str, mut data: Vec<u8>) -> types::Result<()> {
+        let tag = self.make_mac(key, &data)?.finalize().into_bytes();
+        data.extend_from_slice(&tag);
+
+        let spec = StorageSpec {
+            handle: key.to_string(),
+            data,
+            ttl: self.ttl,
+            extensions: self.get_extensions(),
+        };
+
+        self.storage.store(spec).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mitsuha_core::{
+        config,
+        storage::{StorageClass, StorageKind, StorageLocality},
+    };
+    use mitsuha_storage::UnifiedStorage;
+
+    use super::*;
+
+    fn make_label() -> Label {
+        Label {
+            key: "storage".to_string(),
+            value: "precompiled".to_string(),
+        }
+    }
+
+    async fn make_storage() -> Arc<Box<dyn Storage>> {
+        let storage_config = config::storage::Storage {
+            classes: vec![StorageClass {
+                kind: StorageKind::Memory,
+                locality: StorageLocality::Solid { cache_name: None },
+                name: "precompiled".to_string(),
+                labels: vec![make_label()],
+                properties: HashMap::new(),
+            }],
+        };
+
+        UnifiedStorage::new(&storage_config).await.unwrap()
+    }
+
+    fn make_store(storage: Arc<Box<dyn Storage>>, key: &[u8]) -> PrecompiledModuleStore {
+        PrecompiledModuleStore::new(storage, make_label(), 3600, key.to_vec())
+    }
+
+    async fn overwrite(storage: &Arc<Box<dyn Storage>>, handle: &str, data: Vec<u8>) {
+        storage
+            .store(StorageSpec {
+                handle: handle.to_string(),
+                data,
+                ttl: 3600,
+                extensions: HashMap::<String, String>::new().with_selector(&make_label()),
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_artifact_round_trip() {
+        let store = make_store(make_storage().await, b"key-1");
+
+        assert!(store.load("artifact").await.unwrap().is_none());
+
+        store.store("artifact", b"compiled".to_vec()).await.unwrap();
+
+        assert_eq!(
+            store.load("artifact").await.unwrap(),
+            Some(b"compiled".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_artifact_verification() {
+        let storage = make_storage().await;
+        let store = make_store(storage.clone(), b"key-1");
+
+        store.store("artifact", b"compiled".to_vec()).await.unwrap();
+
+        // Artifacts signed with another key are rejected.
+        assert!(make_store(storage.clone(), b"key-2")
+            .load("artifact")
+            .await
+            .is_err());
+
+        // So are artifacts written to the storage class by anyone else.
+        overwrite(&storage, "artifact", b"tampered".to_vec()).await;
+
+        assert!(store.load("artifact").await.is_err());
+
+        // And a valid artifact cannot be moved to another handle.
+        store.store("other", b"compiled".to_vec()).await.unwrap();
+
+        let moved = storage
+            .load(
+                "other".to_string(),
+                HashMap::<String, String>::new().with_selector(&make_label()),
+            )
+            .await
+            .unwrap();
+
+        overwrite(&storage, "artifact", moved).await;
+
+        assert!(store.load("artifact").await.is_err());
+    }
+}
@@ -0,0 +1,506 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use futures::FutureExt;
+use mitsuha_core::{
+    capability::Capabilities,
+    errors::{Error, ToUnknownErrorResult},
+    executor::ExecutorContext,
+    kernel::KernelBinding,
+    linker::{Linker, LinkerContext},
+    resolver::Resolver,
+    types,
+};
+use mitsuha_core_types::{
+    module::{ModuleInfo, ModuleType},
+    symbol::Symbol,
+};
+use musubi_api::types::Data;
+use tokio::sync::Mutex;
+use wasmtime::component::{types::ComponentItem, Component, Func, ResourceTable, Val};
+use wasmtime_wasi::{WasiCtx, WasiCtxBuilder, WasiView};
+
+use crate::constants::Constants;
+use crate::wasmtime::component::value;
+use crate::wasmtime::{JobMonitor, TrapRecorder, WasmtimeConfig};
+
+type ComponentCacheKey = (String, ModuleInfo);
+
+/// A compiled component along with the capability manifest embedded in it.
+#[derive(Clone)]
+struct CachedComponent {
+    component: Component,
+    manifest: Option<Capabilities>,
+}
+
+/// Store data of a component instance.
+pub struct ComponentContext {
+    wasi_ctx: WasiCtx,
+    table: ResourceTable,
+    kernel_binding: Arc<Box<dyn KernelBinding>>,
+    capabilities: Capabilities,
+}
+
+impl ComponentContext {
+    pub fn new(
+        wasi_ctx: WasiCtx,
+        kernel_binding: Arc<Box<dyn KernelBinding>>,
+        capabilities: Capabilities,
+    ) -> Self {
+        Self {
+            wasi_ctx,
+            table: ResourceTable::new(),
+            kernel_binding,
+            capabilities,
+        }
+    }
+}
+
+impl WasiView for ComponentContext {
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+
+    fn ctx(&mut self) -> &mut WasiCtx {
+        &mut self.wasi_ctx
+    }
+}
+
+/// Returns true if `data` is encoded as a component rather than a core module. Both start with
+/// the `\0asm` magic, the layer field that follows tells them apart.
+pub fn is_component_binary(data: &[u8]) -> bool {
+    data.len() >= 8 && data[0..4] == *b"\0asm" && data[6..8] == [0x01, 0x00]
+}
+
+/// Reads the capability manifest from the top level `mitsuha.capabilities` custom section of a
+/// component. Sections of the core modules nested in the component are ignored.
+fn get_capability_manifest(data: &[u8]) -> anyhow::Result<Option<Capabilities>> {
+    let mut sections: Vec<&[u8]> = vec![];
+    let mut depth = 0usize;
+
+    for payload in wasmparser::Parser::new(0).parse_all(data) {
+        match payload? {
+            wasmparser::Payload::ModuleSection { .. }
+            | wasmparser::Payload::ComponentSection { .. } => depth += 1,
+            wasmparser::Payload::End(_) if depth == 0 => break,
+            wasmparser::Payload::End(_) => depth -= 1,
+            wasmparser::Payload::CustomSection(s)
+                if depth == 0 && s.name() == "mitsuha.capabilities" =>
+            {
+                sections.push(s.data())
+            }
+            _ => {}
+        }
+    }
+
+    if sections.len() > 1 {
+        return Err(anyhow::anyhow!(
+            "duplicate capability manifest sections found in WASM component"
+        ));
+    }
+
+    Ok(sections
+        .first()
+        .map(|raw| serde_json::from_slice::<Capabilities>(raw))
+        .transpose()?)
+}
+
+/// Serves the bytes of one module which were already resolved, and delegates everything
+/// else to the resolver it wraps.
+struct ResolvedModule {
+    module_info: ModuleInfo,
+    data: Vec<u8>,
+    resolver: Arc<Box<dyn Resolver<ModuleInfo, Vec<u8>>>>,
+}
+
+#[async_trait]
+impl Resolver<ModuleInfo, Vec<u8>> for ResolvedModule {
+    async fn resolve(&self, key: &ModuleInfo) -> types::Result<Vec<u8>> {
+        if *key == self.module_info {
+            return Ok(self.data.clone());
+        }
+
+        self.resolver.resolve(key).await
+    }
+
+    async fn register(&self, key: &ModuleInfo, value: &Vec<u8>) -> types::Result<()> {
+        self.resolver.register(key, value).await
+    }
+}
+
+/// Links WASI preview 2 components. Symbols are the functions exported by the component (or
+/// `interface#function` for functions of exported interfaces), and inputs and outputs are
+/// converted between musubi data and component values. The kernel is provided to the guest
+/// through the `mitsuha:runtime/kernel` import, see `wit/kernel.wit`.
+///
+/// Like core modules, components may embed a capability manifest in a `mitsuha.capabilities`
+/// custom section. Components have no filesystem access, their kernel calls are checked
+/// against the manifest narrowed by the capabilities of the job.
+pub struct ComponentLinker {
+    engine: wasmtime::Engine,
+    linker: wasmtime::component::Linker<ComponentContext>,
+    component_cache: moka::future::Cache<ComponentCacheKey, CachedComponent>,
+    core_module_cache: moka::future::Cache<ComponentCacheKey, ()>,
+    require_capability_manifest: bool,
+}
+
+impl ComponentLinker {
+    pub fn new(engine: wasmtime::Engine, config: &WasmtimeConfig) -> types::Result<Self> {
+        let mut linker = wasmtime::component::Linker::new(&engine);
+
+        wasmtime_wasi::command::add_to_linker(&mut linker).to_unknown_err_result()?;
+
+        linker
+            .instance(&Constants::ComponentKernelInterface.to_string())
+            .to_unknown_err_result()?
+            .func_wrap_async(
+                "call",
+                |store: wasmtime::StoreContextMut<'_, ComponentContext>,
+                 (module, version, function, input): (String, String, String, Vec<u8>)| {
+                    let kernel_binding = store.data().kernel_binding.clone();
+                    let capabilities = store.data().capabilities.clone();
+
+                    Box::new(async move {
+                        let symbol = Symbol {
+                            name: function,
+                            module_info: ModuleInfo {
+                                name: module,
+                                version,
+                                modtype: ModuleType::WASM,
+                            },
+                        };
+
+                        let result = match capabilities.check_symbol(&symbol) {
+                            Ok(_) => kernel_binding.run(&symbol, input).await,
+                            Err(e) => Err(e),
+                        }
+                        .map_err(|e| e.to_string());
+
+                        Ok((result,))
+                    })
+                },
+            )
+            .to_unknown_err_result()?;
+
+        Ok(Self {
+            engine,
+            linker,
+            component_cache: moka::future::Cache::new(config.module_cache_capacity),
+            core_module_cache: moka::future::Cache::new(config.module_cache_capacity),
+            require_capability_manifest: config.require_capability_manifest,
+        })
+    }
+
+    fn get_cache_key(ctx: &LinkerContext, module_info: &ModuleInfo) -> ComponentCacheKey {
+        let resolver_prefix = ctx
+            .extensions
+            .get(&mitsuha_core::constants::Constants::ModuleResolverPrefix.to_string())
+            .cloned()
+            .unwrap_or(module_info.get_identifier());
+
+        (resolver_prefix, module_info.clone())
+    }
+
+    async fn compile(
+        &self,
+        data: Vec<u8>,
+        module_info: &ModuleInfo,
+    ) -> types::Result<CachedComponent> {
+        let manifest = get_capability_manifest(&data).map_err(|e| Error::WasmError {
+            message: "failed to parse capability manifest".to_string(),
+            inner: module_info.clone(),
+            source: e,
+        })?;
+
+        let engine = self.engine.clone();
+
+        let component = tokio::task::spawn_blocking(move || Component::from_binary(&engine, &data))
+            .await
+            .to_unknown_err_result()?
+            .map_err(|e| Error::ModuleLoadFailed {
+                message: "failed to compile wasm component".to_string(),
+                inner: module_info.clone(),
+                source: e,
+            })?;
+
+        Ok(CachedComponent {
+            component,
+            manifest,
+        })
+    }
+
+    /// Resolves the module and checks whether it is a component. Components are compiled and
+    /// cached, core modules are remembered so they are only resolved once: their bytes are
+    /// handed to the core linker through the resolver of `ctx`.
+    pub async fn is_component(
+        &self,
+        ctx: &mut LinkerContext,
+        module_info: &ModuleInfo,
+    ) -> types::Result<bool> {
+        let cache_key = Self::get_cache_key(ctx, module_info);
+
+        if self.component_cache.contains_key(&cache_key) {
+            return Ok(true);
+        }
+
+        if self.core_module_cache.contains_key(&cache_key) {
+            return Ok(false);
+        }
+
+        let data = ctx.module_resolver.resolve(module_info).await?;
+
+        if !is_component_binary(&data) {
+            self.core_module_cache.insert(cache_key, ()).await;
+
+            ctx.module_resolver = Arc::new(Box::new(ResolvedModule {
+                module_info: module_info.clone(),
+                data,
+                resolver: ctx.module_resolver.clone(),
+            }));
+
+            return Ok(false);
+        }
+
+        let component = self.compile(data, module_info).await?;
+        self.component_cache.insert(cache_key, component).await;
+
+        Ok(true)
+    }
+
+    async fn get_component(
+        &self,
+        ctx: &LinkerContext,
+        module_info: &ModuleInfo,
+    ) -> types::Result<CachedComponent> {
+        let cache_key = Self::get_cache_key(ctx, module_info);
+
+        if let Some(component) = self.component_cache.get(&cache_key) {
+            return Ok(component);
+        }
+
+        let data = ctx.module_resolver.resolve(module_info).await?;
+
+        if !is_component_binary(&data) {
+            return Err(Error::LinkerLoadFailed {
+                message: "module is not a wasm component".to_string(),
+                target: module_info.clone(),
+                source: anyhow::anyhow!(""),
+            });
+        }
+
+        let component = self.compile(data, module_info).await?;
+        self.component_cache
+            .insert(cache_key, component.clone())
+            .await;
+
+        Ok(component)
+    }
+
+    /// Capabilities of a component: those declared in its manifest, narrowed by the
+    /// capabilities of the job it is linked for.
+    fn get_capabilities(
+        &self,
+        context: &LinkerContext,
+        component: &CachedComponent,
+        module_info: &ModuleInfo,
+    ) -> types::Result<Capabilities> {
+        let manifest = match &component.manifest {
+            Some(manifest) => manifest.clone(),
+            None if self.require_capability_manifest => {
+                return Err(Error::LinkerLoadFailed {
+                    message: "component does not declare a capability manifest".to_string(),
+                    target: module_info.clone(),
+                    source: anyhow::anyhow!(""),
+                })
+            }
+            None => Capabilities::unrestricted(),
+        };
+
+        match Capabilities::from_extensions(&context.extensions)? {
+            Some(job_capabilities) => Ok(manifest.narrow(&job_capabilities)),
+            None => Ok(manifest),
+        }
+    }
+
+    fn construct_error(error: &str) -> Data {
+        musubi_api::DataBuilder::new()
+            .add(musubi_api::types::Value::Error {
+                code: Constants::RuntimeModuleName.to_string(),
+                message: error.to_string(),
+            })
+            .build()
+    }
+
+    async fn call_export(
+        store: &Mutex<wasmtime::Store<ComponentContext>>,
+        func: Func,
+        input: Vec<u8>,
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut store = store.lock().await;
+
+        let data = Data::try_from(input).map_err(|e| anyhow::anyhow!("{}", e))?;
+
+        let params = value::to_params(&data, &func.params(&*store))?;
+        let mut results = vec![Val::Bool(false); func.results(&*store).len()];
+
+        func.call_async(&mut *store, &params, &mut results).await?;
+        func.post_return_async(&mut *store).await?;
+
+        value::to_data(&results)?
+            .try_into()
+            .map_err(|e| anyhow::anyhow!("{}", e))
+    }
+
+    async fn run_export(
+        store: Arc<Mutex<wasmtime::Store<ComponentContext>>>,
+        traps: Arc<TrapRecorder>,
+        symbol: Symbol,
+        func: Func,
+        input: Vec<u8>,
+    ) -> Vec<u8> {
+        match Self::call_export(&store, func, input).await {
+            Ok(output) => output,
+            Err(e) => {
+                traps.record(&symbol.module_info, &e, &mut *store.lock().await);
+
+                Self::construct_error(
+                    format!(
+                        "failed to run exported function: {} with error: {}",
+                        symbol.name, e
+                    )
+                    .as_str(),
+                )
+                .try_into()
+                .unwrap()
+            }
+        }
+    }
+
+    /// Instantiates a component, reporting guest CPU time and traps to `monitor`. Components
+    /// are never profiled, wasmtime's guest profiler only supports core modules, so jobs
+    /// asking for a profile of one are rejected before linking.
+    pub async fn link_monitored(
+        &self,
+        context: &LinkerContext,
+        module_info: &ModuleInfo,
+        monitor: &JobMonitor,
+    ) -> types::Result<ExecutorContext> {
+        let cached = self.get_component(context, module_info).await?;
+        let capabilities = self.get_capabilities(context, &cached, module_info)?;
+        let component = cached.component;
+
+        let wasi_ctx = WasiCtxBuilder::new().build();
+
+        let mut store = wasmtime::Store::new(
+            &self.engine,
+            ComponentContext::new(wasi_ctx, context.kernel_binding.clone(), capabilities),
+        );
+
+        // Samples of a component store cannot be symbolized by the guest profiler.
+        JobMonitor {
+            profiler: None,
+            ..monitor.clone()
+        }
+        .observe(&mut store);
+
+        let instance = self
+            .linker
+            .instantiate_async(&mut store, &component)
+            .await
+            .map_err(|e| Error::LinkerLinkFailed {
+                message: "failed to instantiate wasm component".to_string(),
+                target: module_info.clone(),
+                source: e,
+            })?;
+
+        let mut exports: Vec<(String, Func)> = vec![];
+
+        for (name, item) in component.component_type().exports(&self.engine) {
+            match item {
+                ComponentItem::ComponentFunc(_) => {
+                    if let Some(func) = instance.get_func(&mut store, name) {
+                        exports.push((name.to_string(), func));
+                    }
+                }
+                ComponentItem::ComponentInstance(instance_type) => {
+                    for (function_name, item) in instance_type.exports(&self.engine) {
+                        if !matches!(item, ComponentItem::ComponentFunc(_)) {
+                            continue;
+                        }
+
+                        let func = instance
+                            .exports(&mut store)
+                            .instance(name)
+                            .and_then(|mut x| x.func(function_name));
+
+                        if let Some(func) = func {
+                            exports.push((format!("{}#{}", name, function_name), func));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let shared_store = Arc::new(Mutex::new(store));
+
+        let mut executor_context = ExecutorContext::new();
+
+        for (name, func) in exports {
+            let symbol = Symbol {
+                name,
+                module_info: module_info.clone(),
+            };
+
+            tracing::debug!("exporting component symbol: {:?}", symbol.clone());
+
+            let exported_store = shared_store.clone();
+            let exported_traps = monitor.traps.clone();
+            let exported_symbol = symbol.clone();
+
+            let exported_func = move |input: Vec<u8>| {
+                Self::run_export(
+                    exported_store.clone(),
+                    exported_traps.clone(),
+                    exported_symbol.clone(),
+                    func,
+                    input,
+                )
+                .boxed()
+            };
+
+            executor_context
+                .add_symbol(symbol, Arc::new(tokio::sync::RwLock::new(exported_func)))?;
+        }
+
+        Ok(executor_context)
+    }
+}
+
+#[async_trait]
+impl Linker for ComponentLinker {
+    async fn load(
+        &self,
+        context: &mut LinkerContext,
+        module_info: &ModuleInfo,
+    ) -> types::Result<()> {
+        let component = self.get_component(context, module_info).await?;
+        self.get_capabilities(context, &component, module_info)?;
+
+        // Components are self-contained, anything else is reached through the kernel import.
+        context
+            .dependency_graph
+            .insert(module_info.clone(), HashMap::new());
+
+        Ok(())
+    }
+
+    async fn link(
+        &self,
+        context: &mut LinkerContext,
+        module_info: &ModuleInfo,
+    ) -> types::Result<ExecutorContext> {
+        self.link_monitored(context, module_info, &JobMonitor::unlimited())
+            .await
+    }
+}
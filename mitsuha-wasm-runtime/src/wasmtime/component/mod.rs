@@ -0,0 +1,4 @@
+pub mod linker;
+pub mod value;
+
+pub use linker::{is_component_binary, ComponentContext, ComponentLinker};
@@ -0,0 +1,197 @@
+use anyhow::{anyhow, bail};
+use musubi_api::types::{Data, HashableValue, Value};
+use wasmtime::component::{Type, Val};
+
+/// Converts the values of a musubi [Data] into the parameters of a component function.
+pub fn to_params(data: &Data, params: &[Type]) -> anyhow::Result<Vec<Val>> {
+    let values = data.values();
+
+    if values.len() != params.len() {
+        bail!(
+            "expected {} input values, found {}",
+            params.len(),
+            values.len()
+        );
+    }
+
+    values
+        .iter()
+        .zip(params.iter())
+        .map(|(value, ty)| to_val(value, ty))
+        .collect()
+}
+
+/// Converts the results of a component function into a musubi [Data].
+pub fn to_data(results: &[Val]) -> anyhow::Result<Data> {
+    let mut builder = musubi_api::DataBuilder::new();
+
+    for result in results {
+        builder = builder.add(from_val(result)?);
+    }
+
+    Ok(builder.build())
+}
+
+/// Converts a musubi value into a component value of type `ty`. Signed integers are read
+/// from the two's complement representation used by [from_val].
+pub fn to_val(value: &Value, ty: &Type) -> anyhow::Result<Val> {
+    let val = match (ty, value) {
+        (Type::Bool, Value::Bool(x)) => Val::Bool(*x),
+        (Type::U8, Value::U64(x)) => Val::U8((*x).try_into()?),
+        (Type::U16, Value::U64(x)) => Val::U16((*x).try_into()?),
+        (Type::U32, Value::U64(x)) => Val::U32((*x).try_into()?),
+        (Type::U64, Value::U64(x)) => Val::U64(*x),
+        (Type::S8, Value::U64(x)) => Val::S8((*x as i64).try_into()?),
+        (Type::S16, Value::U64(x)) => Val::S16((*x as i64).try_into()?),
+        (Type::S32, Value::U64(x)) => Val::S32((*x as i64).try_into()?),
+        (Type::S64, Value::U64(x)) => Val::S64(*x as i64),
+        (Type::String, Value::String(x)) => Val::String(x.clone().into()),
+        (Type::Char, Value::String(x)) => {
+            let mut chars = x.chars();
+
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Val::Char(c),
+                _ => bail!("expected a single character, found '{}'", x),
+            }
+        }
+        (Type::List(list), Value::Bytes(x)) => {
+            list.new_val(x.iter().map(|b| Val::U8(*b)).collect())?
+        }
+        (Type::List(list), Value::Array(x)) => {
+            let ty = list.ty();
+
+            list.new_val(
+                x.iter()
+                    .map(|v| to_val(v, &ty))
+                    .collect::<anyhow::Result<_>>()?,
+            )?
+        }
+        (Type::Tuple(tuple), Value::Array(x)) => {
+            let types: Vec<Type> = tuple.types().collect();
+
+            if types.len() != x.len() {
+                bail!("expected {} tuple values, found {}", types.len(), x.len());
+            }
+
+            tuple.new_val(
+                x.iter()
+                    .zip(types.iter())
+                    .map(|(v, ty)| to_val(v, ty))
+                    .collect::<anyhow::Result<_>>()?,
+            )?
+        }
+        (Type::Record(record), Value::Map(x)) => {
+            let mut fields = vec![];
+
+            for field in record.fields() {
+                let value = x
+                    .iter()
+                    .find(|(key, _)| matches!(key, HashableValue::String(key) if key == field.name))
+                    .map(|(_, value)| value)
+                    .ok_or(anyhow!("missing record field '{}'", field.name))?;
+
+                fields.push((field.name, to_val(value, &field.ty)?));
+            }
+
+            record.new_val(fields)?
+        }
+        (Type::Enum(e), Value::String(x)) => e.new_val(x)?,
+        (Type::Option(option), Value::Null) => option.new_val(None)?,
+        (Type::Option(option), x) => option.new_val(Some(to_val(x, &option.ty())?))?,
+        (ty, value) => bail!("cannot convert {:?} to component type {:?}", value, ty),
+    };
+
+    Ok(val)
+}
+
+/// Converts a component value into a musubi value. Musubi integers are unsigned, so signed
+/// integers are sign extended to 64 bits and carried as their two's complement.
+pub fn from_val(val: &Val) -> anyhow::Result<Value> {
+    let value = match val {
+        Val::Bool(x) => Value::Bool(*x),
+        Val::U8(x) => Value::U64(*x as u64),
+        Val::U16(x) => Value::U64(*x as u64),
+        Val::U32(x) => Value::U64(*x as u64),
+        Val::U64(x) => Value::U64(*x),
+        Val::S8(x) => Value::U64(*x as i64 as u64),
+        Val::S16(x) => Value::U64(*x as i64 as u64),
+        Val::S32(x) => Value::U64(*x as i64 as u64),
+        Val::S64(x) => Value::U64(*x as u64),
+        Val::Char(x) => Value::String(x.to_string()),
+        Val::String(x) => Value::String(x.to_string()),
+        Val::List(list) if matches!(list.ty().ty(), Type::U8) => Value::Bytes(
+            list.iter()
+                .map(|v| match v {
+                    Val::U8(b) => Ok(*b),
+                    other => Err(anyhow!("expected u8 list element, found {:?}", other)),
+                })
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        Val::List(list) => Value::Array(list.iter().map(from_val).collect::<anyhow::Result<_>>()?),
+        Val::Tuple(tuple) => Value::Array(
+            tuple
+                .values()
+                .iter()
+                .map(from_val)
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        Val::Record(record) => Value::Map(
+            record
+                .fields()
+                .map(|(name, v)| Ok((HashableValue::String(name.to_string()), from_val(v)?)))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        Val::Enum(e) => Value::String(e.discriminant().to_string()),
+        Val::Option(option) => match option.value() {
+            Some(v) => from_val(v)?,
+            None => Value::Null,
+        },
+        Val::Result(result) => match result.value() {
+            Ok(Some(v)) => from_val(v)?,
+            Ok(None) => Value::Null,
+            Err(e) => Value::Error {
+                code: crate::constants::Constants::ComponentErrorCode.to_string(),
+                message: match e {
+                    Some(v) => format!("{:?}", from_val(v)?),
+                    None => "component function returned an error".to_string(),
+                },
+            },
+        },
+        other => bail!("unsupported component value {:?}", other),
+    };
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use musubi_api::types::Value;
+    use wasmtime::component::{Type, Val};
+
+    use super::{from_val, to_val};
+
+    #[test]
+    fn test_signed_round_trip() {
+        let cases = [
+            (Type::S8, Val::S8(i8::MIN)),
+            (Type::S16, Val::S16(-2)),
+            (Type::S32, Val::S32(-1)),
+            (Type::S64, Val::S64(i64::MIN)),
+            (Type::S64, Val::S64(i64::MAX)),
+        ];
+
+        for (ty, val) in cases {
+            let value = from_val(&val).unwrap();
+            assert_eq!(to_val(&value, &ty).unwrap(), val);
+        }
+
+        assert!(matches!(
+            from_val(&Val::S32(-1)).unwrap(),
+            Value::U64(u64::MAX)
+        ));
+
+        // Values outside of the range of the target type are rejected.
+        assert!(to_val(&Value::U64(128), &Type::S8).is_err());
+        assert!(to_val(&Value::U64(-129i64 as u64), &Type::S8).is_err());
+    }
+}
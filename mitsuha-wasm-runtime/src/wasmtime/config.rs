@@ -0,0 +1,286 @@
+use std::time::Duration;
+
+use mitsuha_core::{
+    errors::{Error, ToUnknownErrorResult},
+    selector::Label,
+    types,
+    types::Extensions,
+};
+
+use crate::conf::ConfKey;
+
+/// Tunables for the wasmtime engine and [crate::wasmtime::WasmtimeLinker], read from plugin properties.
+#[derive(Debug, Clone)]
+pub struct WasmtimeConfig {
+    /// Number of compiled modules kept in memory by the linker.
+    pub module_cache_capacity: u64,
+
+    /// Storage selector for persisting precompiled module artifacts. Persistence is disabled when absent.
+    pub precompiled_module_selector: Option<Label>,
+
+    /// TTL (in seconds) of persisted precompiled module artifacts.
+    pub precompiled_module_ttl: u64,
+
+    /// Secret used to sign persisted precompiled module artifacts, required when persistence
+    /// is enabled. Every runtime sharing the storage class must use the same key.
+    pub precompiled_module_key: Option<String>,
+
+    /// Number of pre-instantiated modules (per module and dependency set) kept by the linker.
+    pub instance_pre_cache_capacity: u64,
+
+    /// Use the pooling instance allocator instead of allocating instance resources on demand.
+    pub pooling_allocator: bool,
+
+    /// Maximum number of concurrently live instances when the pooling allocator is enabled.
+    pub pooling_total_instances: u32,
+
+    /// Maximum number of 64KiB pages of a linear memory when the pooling allocator is enabled.
+    pub pooling_memory_pages: u64,
+
+    /// Maximum number of concurrent calls into one linked module. Each concurrent call is
+    /// served by its own instance, so this also bounds the instances kept per linked module.
+    pub export_concurrency: usize,
+
+    /// Enable the memory64 proposal, required to link 64 bit modules.
+    pub memory64: bool,
+
+    /// Serve imports of the builtin native modules (`mitsuha.native.*`) with host functions.
+    /// Disabled by default, as guests can then spend host CPU and memory outside of their
+    /// fuel and memory limits.
+    pub native_modules: bool,
+
+    /// Maximum size (in bytes) of the output of a native module function, e.g. of gunzip.
+    pub native_max_output_size: u64,
+
+    /// Refuse to link modules which do not embed a capability manifest. Modules without one
+    /// are otherwise granted every capability the job allows.
+    pub require_capability_manifest: bool,
+
+    /// Symbolize guest backtraces with the DWARF debug info embedded in modules, at the cost
+    /// of parsing it while compiling.
+    pub wasm_backtrace_details: bool,
+
+    /// Capture a wasm core dump when a guest traps. Jobs choose where the dump is stored with
+    /// the `mitsuha.job.coredump.handle` extension.
+    pub coredump_on_trap: bool,
+
+    /// Interval (in milliseconds) of an epoch. This is the granularity at which guests yield
+    /// and at which CPU time budgets are enforced.
+    pub epoch_tick_interval: u64,
+
+    /// CPU time budget (in milliseconds) of jobs which do not set one. Unlimited when absent.
+    pub default_cpu_budget: Option<u64>,
+
+    /// Interval (in milliseconds) at which guest profiles are sampled. The engine is ticked at
+    /// this interval when it is finer than `epoch_tick_interval`, profiled stores are
+    /// interrupted on every tick while the others are only interrupted once per epoch.
+    pub profile_sample_interval: u64,
+}
+
+impl Default for WasmtimeConfig {
+    fn default() -> Self {
+        Self::from_properties(&Default::default()).unwrap()
+    }
+}
+
+impl WasmtimeConfig {
+    pub fn from_properties(properties: &Extensions) -> types::Result<Self> {
+        let module_cache_capacity: u64 = properties
+            .get(&ConfKey::ModuleCacheCapacity.to_string())
+            .unwrap_or(&"16".to_string())
+            .parse()
+            .to_unknown_err_result()?;
+
+        let precompiled_module_selector: Option<Label> = properties
+            .get(&ConfKey::PrecompiledModuleSelector.to_string())
+            .map(|x| serde_json::from_str(x.as_str()))
+            .transpose()
+            .to_unknown_err_result()?;
+
+        let precompiled_module_ttl: u64 = properties
+            .get(&ConfKey::PrecompiledModuleTTL.to_string())
+            .unwrap_or(&"604800".to_string())
+            .parse()
+            .to_unknown_err_result()?;
+
+        let precompiled_module_key: Option<String> = properties
+            .get(&ConfKey::PrecompiledModuleKey.to_string())
+            .cloned();
+
+        if precompiled_module_selector.is_some() && precompiled_module_key.is_none() {
+            return Err(Error::InvalidOperation {
+                message: format!(
+                    "{} is required when {} is set",
+                    ConfKey::PrecompiledModuleKey,
+                    ConfKey::PrecompiledModuleSelector
+                ),
+            });
+        }
+
+        let instance_pre_cache_capacity: u64 = properties
+            .get(&ConfKey::InstancePreCacheCapacity.to_string())
+            .unwrap_or(&"64".to_string())
+            .parse()
+            .to_unknown_err_result()?;
+
+        let pooling_allocator: bool = properties
+            .get(&ConfKey::PoolingAllocator.to_string())
+            .unwrap_or(&"false".to_string())
+            .parse()
+            .to_unknown_err_result()?;
+
+        let pooling_total_instances: u32 = properties
+            .get(&ConfKey::PoolingTotalInstances.to_string())
+            .unwrap_or(&"1000".to_string())
+            .parse()
+            .to_unknown_err_result()?;
+
+        let pooling_memory_pages: u64 = properties
+            .get(&ConfKey::PoolingMemoryPages.to_string())
+            .unwrap_or(&"160".to_string())
+            .parse()
+            .to_unknown_err_result()?;
+
+        let export_concurrency: usize = properties
+            .get(&ConfKey::ExportConcurrency.to_string())
+            .unwrap_or(&"1".to_string())
+            .parse()
+            .to_unknown_err_result()?;
+
+        if export_concurrency == 0 {
+            return Err(Error::InvalidOperation {
+                message: format!("{} must be greater than zero", ConfKey::ExportConcurrency),
+            });
+        }
+
+        let memory64: bool = properties
+            .get(&ConfKey::Memory64.to_string())
+            .unwrap_or(&"false".to_string())
+            .parse()
+            .to_unknown_err_result()?;
+
+        let native_modules: bool = properties
+            .get(&ConfKey::NativeModules.to_string())
+            .unwrap_or(&"false".to_string())
+            .parse()
+            .to_unknown_err_result()?;
+
+        let native_max_output_size: u64 = properties
+            .get(&ConfKey::NativeMaxOutputSize.to_string())
+            .unwrap_or(&"16777216".to_string())
+            .parse()
+            .to_unknown_err_result()?;
+
+        let require_capability_manifest: bool = properties
+            .get(&ConfKey::RequireCapabilityManifest.to_string())
+            .unwrap_or(&"false".to_string())
+            .parse()
+            .to_unknown_err_result()?;
+
+        let wasm_backtrace_details: bool = properties
+            .get(&ConfKey::WasmBacktraceDetails.to_string())
+            .unwrap_or(&"true".to_string())
+            .parse()
+            .to_unknown_err_result()?;
+
+        let coredump_on_trap: bool = properties
+            .get(&ConfKey::CoreDumpOnTrap.to_string())
+            .unwrap_or(&"false".to_string())
+            .parse()
+            .to_unknown_err_result()?;
+
+        let epoch_tick_interval: u64 = properties
+            .get(&ConfKey::EpochTickInterval.to_string())
+            .unwrap_or(&"1000".to_string())
+            .parse()
+            .to_unknown_err_result()?;
+
+        if epoch_tick_interval == 0 {
+            return Err(Error::InvalidOperation {
+                message: format!("{} must be greater than zero", ConfKey::EpochTickInterval),
+            });
+        }
+
+        let default_cpu_budget: Option<u64> = properties
+            .get(&ConfKey::DefaultCpuBudget.to_string())
+            .map(|x| x.parse())
+            .transpose()
+            .to_unknown_err_result()?;
+
+        let profile_sample_interval: u64 = properties
+            .get(&ConfKey::ProfileSampleInterval.to_string())
+            .unwrap_or(&"10".to_string())
+            .parse()
+            .to_unknown_err_result()?;
+
+        if profile_sample_interval == 0 {
+            return Err(Error::InvalidOperation {
+                message: format!(
+                    "{} must be greater than zero",
+                    ConfKey::ProfileSampleInterval
+                ),
+            });
+        }
+
+        Ok(Self {
+            module_cache_capacity,
+            precompiled_module_selector,
+            precompiled_module_ttl,
+            precompiled_module_key,
+            instance_pre_cache_capacity,
+            pooling_allocator,
+            pooling_total_instances,
+            pooling_memory_pages,
+            export_concurrency,
+            memory64,
+            native_modules,
+            native_max_output_size,
+            require_capability_manifest,
+            wasm_backtrace_details,
+            coredump_on_trap,
+            epoch_tick_interval,
+            default_cpu_budget,
+            profile_sample_interval,
+        })
+    }
+
+    /// Number of engine ticks in an epoch.
+    pub fn get_ticks_per_epoch(&self) -> u64 {
+        (self.epoch_tick_interval / self.profile_sample_interval).max(1)
+    }
+
+    /// Interval at which the engine epoch is incremented, the epoch split into
+    /// [WasmtimeConfig::get_ticks_per_epoch] ticks.
+    pub fn get_tick_interval(&self) -> Duration {
+        Duration::from_millis(self.epoch_tick_interval) / self.get_ticks_per_epoch() as u32
+    }
+
+    pub fn make_engine(&self) -> types::Result<wasmtime::Engine> {
+        let mut config = wasmtime::Config::default();
+        config.async_support(true);
+        config.epoch_interruption(true);
+        config.wasm_memory64(self.memory64);
+        config.wasm_component_model(true);
+        config.wasm_backtrace(true);
+        config.coredump_on_trap(self.coredump_on_trap);
+
+        if self.wasm_backtrace_details {
+            config.wasm_backtrace_details(wasmtime::WasmBacktraceDetails::Enable);
+        }
+
+        if self.pooling_allocator {
+            let mut pooling_config = wasmtime::PoolingAllocationConfig::default();
+            pooling_config
+                .total_core_instances(self.pooling_total_instances)
+                .total_memories(self.pooling_total_instances)
+                .total_tables(self.pooling_total_instances)
+                .memory_pages(self.pooling_memory_pages);
+
+            config.allocation_strategy(wasmtime::InstanceAllocationStrategy::Pooling(
+                pooling_config,
+            ));
+        }
+
+        wasmtime::Engine::new(&config).to_unknown_err_result()
+    }
+}
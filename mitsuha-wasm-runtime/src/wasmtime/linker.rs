@@ -1,41 +1,57 @@
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicBool, Ordering},
+    sync::Arc,
+    time::Duration,
+};
 
 use async_trait::async_trait;
+use futures::future::BoxFuture;
 use futures::FutureExt;
-use mitsuha_core::channel::MusubiKernelWrapper;
+use mitsuha_core::constants::Constants as CoreConstants;
 use mitsuha_core::errors::ToUnknownErrorResult;
 use mitsuha_core::{
+    capability::Capabilities,
     errors::Error,
     executor::ExecutorContext,
     kernel::KernelBinding,
     linker::{Linker, LinkerContext},
     module::Module,
+    native::NativeModuleRegistry,
+    registry::record_to_module_info,
     resolver::Resolver,
     symbol::SymbolExt,
     types::{self, SharedAsyncMany},
 };
-use mitsuha_core_types::kernel::AsyncKernel;
 use mitsuha_core_types::{
     module::{ModuleInfo, ModuleType},
     symbol::Symbol,
 };
-use mitsuha_filesystem::async_fs::{AsyncNativeFileSystem, AsyncNativeFileSystemBuilder};
+use mitsuha_filesystem::async_fs::AsyncNativeFileSystem;
+use musubi_api::types::Bitness;
 use num_traits::cast::FromPrimitive;
-use wasi_common::sync::{clocks_ctx, random_ctx, sched_ctx, WasiCtxBuilder};
-use wasi_common::{Table, WasiCtx, WasiDir};
-
-use crate::wasmtime::wasi::dir::Dir;
+use wasi_common::sync::WasiCtxBuilder;
+use wasi_common::{WasiCtx, WasiDir};
+
+use crate::metric::{module_cache_request_count_metric, module_compile_duration_metric};
+use crate::wasmtime::meter::CpuMeter;
+use crate::wasmtime::monitor::JobMonitor;
+use crate::wasmtime::pool::{InstancePool, InstanceScope};
+use crate::wasmtime::profiler::JobProfiler;
+use crate::wasmtime::{PrecompiledModuleStore, WasmtimeConfig};
 use crate::{constants::Constants, resolver::wasmtime::WasmtimeModuleResolver};
 
 #[derive(Clone)]
 pub struct WasmMetadata {
     spec: musubi_api::types::Spec,
+    capabilities: Option<Capabilities>,
 }
 
 impl WasmMetadata {
     pub fn new(mut data: &[u8]) -> anyhow::Result<Self> {
         let mut musubi_info_sections: Vec<Vec<u8>> = vec![];
         let mut musubi_header_sections: Vec<Vec<u8>> = vec![];
+        let mut capability_sections: Vec<Vec<u8>> = vec![];
         let mut parser = wasmparser::Parser::new(0);
 
         loop {
@@ -56,6 +72,7 @@ impl WasmMetadata {
                             ".msbh" | "msbh,unstable" => {
                                 musubi_header_sections.push(s.data().to_vec())
                             }
+                            "mitsuha.capabilities" => capability_sections.push(s.data().to_vec()),
                             _ => {}
                         },
                         wasmparser::Payload::End { .. } => {
@@ -91,14 +108,30 @@ impl WasmMetadata {
             }
         }
 
+        if capability_sections.len() > 1 {
+            return Err(anyhow::anyhow!(
+                "duplicate capability manifest sections found in WASM binary"
+            ));
+        }
+
+        let capabilities = capability_sections
+            .first()
+            .map(|raw| serde_json::from_slice::<Capabilities>(raw))
+            .transpose()?;
+
         let spec = musubi_api::types::Spec { info, headers };
 
-        Ok(Self { spec })
+        Ok(Self { spec, capabilities })
     }
 
     pub fn get_musubi_spec(&self) -> musubi_api::types::Spec {
         self.spec.clone()
     }
+
+    /// The capability manifest embedded in the `mitsuha.capabilities` custom section, if any.
+    pub fn get_capabilities(&self) -> Option<Capabilities> {
+        self.capabilities.clone()
+    }
 }
 
 #[derive(Clone)]
@@ -134,6 +167,8 @@ impl WasmtimeModule {
             source: e,
         })?;
 
+        let timer = module_compile_duration_metric().start_timer();
+
         let module = wasmtime::Module::from_binary(&engine, data.as_slice()).map_err(|e| {
             Error::ModuleLoadFailed {
                 message: "failed to load wasm module".to_string(),
@@ -142,30 +177,84 @@ impl WasmtimeModule {
             }
         })?;
 
+        timer.observe_duration();
+
+        Ok(Self {
+            metadata,
+            inner: module,
+            info: module_info,
+        })
+    }
+
+    /// Restores a module from an artifact produced by [WasmtimeModule::serialize].
+    ///
+    /// The artifact must come from a trusted source, see [crate::wasmtime::PrecompiledModuleStore]
+    /// which verifies the tag of every artifact it loads.
+    pub fn deserialize(
+        data: &[u8],
+        artifact: &[u8],
+        module_info: ModuleInfo,
+        engine: &wasmtime::Engine,
+    ) -> types::Result<Self> {
+        let metadata = WasmMetadata::new(data).map_err(|e| Error::WasmError {
+            message: "failed to parse musubi metadata".to_string(),
+            inner: module_info.clone(),
+            source: e,
+        })?;
+
+        // SAFETY: artifacts are only read through the precompiled module store, which rejects
+        // artifacts that were not signed by a runtime holding the configured key.
+        let module = unsafe { wasmtime::Module::deserialize(engine, artifact) }.map_err(|e| {
+            Error::ModuleLoadFailed {
+                message: "failed to deserialize precompiled wasm module".to_string(),
+                inner: module_info.clone(),
+                source: e,
+            }
+        })?;
+
         Ok(Self {
             metadata,
             inner: module,
             info: module_info,
         })
     }
+
+    pub fn serialize(&self) -> types::Result<Vec<u8>> {
+        self.inner.serialize().map_err(|e| Error::ModuleLoadFailed {
+            message: "failed to serialize wasm module".to_string(),
+            inner: self.info.clone(),
+            source: e,
+        })
+    }
 }
 
 #[derive(Clone)]
 pub struct WasmtimeContext {
     wasi_ctx: WasiCtx,
     kernel_binding: Arc<Box<dyn KernelBinding>>,
+    dependencies: Arc<HashMap<String, Arc<ExecutorContext>>>,
     instance: SharedAsyncMany<Option<wasmtime::Instance>>,
 }
 
 impl WasmtimeContext {
-    pub fn new(wasi_ctx: WasiCtx, kernel_binding: Arc<Box<dyn KernelBinding>>) -> Self {
+    pub fn new(
+        wasi_ctx: WasiCtx,
+        kernel_binding: Arc<Box<dyn KernelBinding>>,
+        dependencies: HashMap<String, Arc<ExecutorContext>>,
+    ) -> Self {
         Self {
             wasi_ctx,
             kernel_binding,
+            dependencies: Arc::new(dependencies),
             instance: Arc::new(tokio::sync::RwLock::new(None)),
         }
     }
 
+    /// Returns the in-process linked instance of a dependency, if any.
+    pub fn get_dependency(&self, module_name: &str) -> Option<Arc<ExecutorContext>> {
+        self.dependencies.get(module_name).cloned()
+    }
+
     pub async fn set_instance(&self, instance: wasmtime::Instance) {
         *self.instance.write().await = Some(instance);
     }
@@ -179,52 +268,155 @@ impl WasmtimeContext {
     }
 }
 
+type ModuleCacheKey = (String, ModuleInfo);
+
+type InstancePreCacheKey = (ModuleCacheKey, Vec<(String, ModuleInfo)>);
+
 pub struct WasmtimeLinker {
     engine: wasmtime::Engine,
-    module_cache: moka::future::Cache<(String, ModuleInfo), WasmtimeModule>,
-    ticker_handle: Option<tokio::task::JoinHandle<()>>,
+    module_cache: moka::future::Cache<ModuleCacheKey, WasmtimeModule>,
+    instance_pre_cache:
+        moka::future::Cache<InstancePreCacheKey, wasmtime::InstancePre<WasmtimeContext>>,
+    precompiled_store: Option<Arc<PrecompiledModuleStore>>,
+    native_modules: NativeModuleRegistry,
+    require_capability_manifest: bool,
+    export_concurrency: usize,
+    memory64: bool,
+    epoch_tick_interval: u64,
+    ticks_per_epoch: u64,
+    tick_interval: Duration,
+    default_cpu_budget: Option<u64>,
+    ticker_stopped: Arc<AtomicBool>,
 }
 
 impl WasmtimeLinker {
-    pub fn new(engine: wasmtime::Engine) -> types::Result<Self> {
+    pub fn new(engine: wasmtime::Engine, config: &WasmtimeConfig) -> types::Result<Self> {
         let mut obj = Self {
-            module_cache: moka::future::Cache::new(16),
+            module_cache: moka::future::Cache::new(config.module_cache_capacity),
+            instance_pre_cache: moka::future::Cache::new(config.instance_pre_cache_capacity),
             engine,
-            ticker_handle: None,
+            precompiled_store: None,
+            native_modules: Default::default(),
+            require_capability_manifest: config.require_capability_manifest,
+            export_concurrency: config.export_concurrency,
+            memory64: config.memory64,
+            epoch_tick_interval: config.epoch_tick_interval,
+            ticks_per_epoch: config.get_ticks_per_epoch(),
+            tick_interval: config.get_tick_interval(),
+            default_cpu_budget: config.default_cpu_budget,
+            ticker_stopped: Default::default(),
         };
 
         obj.start_ticker();
         Ok(obj)
     }
 
+    pub fn with_precompiled_store(mut self, store: Arc<PrecompiledModuleStore>) -> Self {
+        self.precompiled_store = Some(store);
+        self
+    }
+
+    /// Bind imports of the given native modules to host functions instead of WASM instances.
+    pub fn with_native_modules(mut self, native_modules: NativeModuleRegistry) -> Self {
+        self.native_modules = native_modules;
+        self
+    }
+
+    /// Increments the engine epoch on a blocking thread until the linker is dropped. Aborting
+    /// the task would not stop it once running, so the thread checks a flag instead.
     fn start_ticker(&mut self) {
         let engine = self.engine.clone();
+        let interval = self.tick_interval;
+        let stopped = self.ticker_stopped.clone();
+
+        tokio::task::spawn_blocking(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                tracing::debug!("incrementing wasmtime engine epoch");
+                engine.increment_epoch();
+                std::thread::sleep(interval);
+            }
+        });
+    }
 
-        self.ticker_handle = Some(tokio::task::spawn_blocking(move || loop {
-            tracing::debug!("incrementing wasmtime engine epoch");
-            engine.increment_epoch();
-            std::thread::sleep(Duration::from_millis(1000));
-        }));
+    /// Creates the [CpuMeter] shared by all instances linked for one job. The budget (in
+    /// milliseconds) is read from the job extensions, falling back to the configured default,
+    /// and rounded up to whole epoch ticks.
+    pub fn make_cpu_meter(
+        &self,
+        extensions: &HashMap<String, String>,
+    ) -> types::Result<Arc<CpuMeter>> {
+        let budget: Option<u64> = match extensions.get(&CoreConstants::JobCpuBudget.to_string()) {
+            Some(x) => Some(x.parse().to_unknown_err_result()?),
+            None => self.default_cpu_budget,
+        };
+
+        let budget_epochs = budget.map(|x| x.div_ceil(self.epoch_tick_interval));
+
+        Ok(Arc::new(
+            CpuMeter::new(budget_epochs).with_ticks_per_epoch(self.ticks_per_epoch),
+        ))
     }
 
-    async fn fetch_module(
+    /// Creates a [JobProfiler] covering every module in the dependency graph of the context,
+    /// if the job asked for a profile through [CoreConstants::JobProfileHandle]. Must be
+    /// called after loading.
+    pub async fn make_profiler(
         &self,
-        ctx: &LinkerContext,
+        context: &LinkerContext,
         module_info: &ModuleInfo,
-    ) -> types::Result<WasmtimeModule> {
+    ) -> types::Result<Option<Arc<JobProfiler>>> {
+        if !context
+            .extensions
+            .contains_key(&CoreConstants::JobProfileHandle.to_string())
+        {
+            return Ok(None);
+        }
+
+        let mut modules = vec![];
+
+        for loaded in context.dependency_graph.keys() {
+            let module = self.fetch_module(context, loaded).await?;
+            modules.push((loaded.get_identifier(), module.inner().clone()));
+        }
+
+        Ok(Some(Arc::new(JobProfiler::new(
+            &module_info.get_identifier(),
+            self.tick_interval,
+            modules,
+        ))))
+    }
+
+    fn get_cache_key(&self, ctx: &LinkerContext, module_info: &ModuleInfo) -> ModuleCacheKey {
         let resolver_prefix = ctx
             .extensions
             .get(&mitsuha_core::constants::Constants::ModuleResolverPrefix.to_string())
             .cloned()
             .unwrap_or(module_info.get_identifier());
 
-        let cache_key = (resolver_prefix, module_info.clone());
+        (resolver_prefix, module_info.clone())
+    }
+
+    async fn fetch_module(
+        &self,
+        ctx: &LinkerContext,
+        module_info: &ModuleInfo,
+    ) -> types::Result<WasmtimeModule> {
+        let cache_key = self.get_cache_key(ctx, module_info);
         if let Some(v) = self.module_cache.get(&cache_key) {
+            module_cache_request_count_metric()
+                .with_label_values(&["memory", "hit"])
+                .inc();
+
             return Ok(v);
         }
 
+        module_cache_request_count_metric()
+            .with_label_values(&["memory", "miss"])
+            .inc();
+
         let module_resolver =
-            WasmtimeModuleResolver::new(self.engine.clone(), ctx.module_resolver.clone());
+            WasmtimeModuleResolver::new(self.engine.clone(), ctx.module_resolver.clone())
+                .with_precompiled_store(self.precompiled_store.clone());
 
         let module = module_resolver.resolve(module_info).await?;
 
@@ -242,21 +434,47 @@ impl WasmtimeLinker {
             .build()
     }
 
-    async fn emit_wasm32_import_error<T>(
+    async fn write_import_output<T>(
+        instance: &wasmtime::Instance,
+        store: impl wasmtime::AsContextMut<Data = T>,
+        bitness: Bitness,
+        output_ptr: usize,
+        data: Vec<u8>,
+    ) -> anyhow::Result<i64>
+    where
+        T: Send,
+    {
+        match bitness {
+            Bitness::X32 => musubi_wasmtime::import::run_imported_function32_write_output(
+                instance, store, output_ptr, data,
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("{}", e)),
+            Bitness::X64 => musubi_wasmtime::import::run_imported_function64_write_output(
+                instance, store, output_ptr, data,
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("{}", e)),
+        }
+    }
+
+    async fn emit_import_error<T>(
         error: &str,
         instance: &wasmtime::Instance,
         store: impl wasmtime::AsContextMut<Data = T>,
-        output_ptr: i32,
+        bitness: Bitness,
+        output_ptr: usize,
     ) -> i64
     where
         T: Send,
     {
         let data = Self::construct_error(error);
 
-        let result = musubi_wasmtime::import::run_imported_function32_write_output(
+        let result = Self::write_import_output(
             instance,
             store,
-            output_ptr as usize,
+            bitness,
+            output_ptr,
             data.try_into().unwrap(),
         )
         .await;
@@ -272,37 +490,43 @@ impl WasmtimeLinker {
         result.unwrap()
     }
 
-    pub async fn run_wasm32_import<'a>(
+    async fn run_import<'a>(
         mut caller: wasmtime::Caller<'a, WasmtimeContext>,
         symbol: Symbol,
-        input_ptr: i32,
+        dependency_name: String,
+        bitness: Bitness,
+        input_ptr: usize,
         input_len: i64,
-        output_ptr: i32,
+        output_ptr: usize,
     ) -> i64 {
         let instance = caller.data().get_instance();
 
         let read_result = musubi_wasmtime::import::run_imported_function_read_input(
             instance.read().await.as_ref().unwrap(),
             &mut caller,
-            input_ptr as usize,
+            input_ptr,
             input_len,
         )
         .await;
 
         if read_result.is_err() {
-            return Self::emit_wasm32_import_error(
+            return Self::emit_import_error(
                 "failed to read input from memory",
                 instance.read().await.as_ref().unwrap(),
                 &mut caller,
+                bitness,
                 output_ptr,
             )
             .await;
         }
 
-        let result = caller.data().call(&symbol, read_result.unwrap()).await;
+        let result = match caller.data().get_dependency(&dependency_name) {
+            Some(executor_context) => executor_context.call(&symbol, read_result.unwrap()).await,
+            None => caller.data().call(&symbol, read_result.unwrap()).await,
+        };
 
         if result.is_err() {
-            return Self::emit_wasm32_import_error(
+            return Self::emit_import_error(
                 format!(
                     "failed to call symbol: {:?}, error: {}",
                     symbol.clone(),
@@ -311,24 +535,27 @@ impl WasmtimeLinker {
                 .as_str(),
                 instance.read().await.as_ref().unwrap(),
                 &mut caller,
+                bitness,
                 output_ptr,
             )
             .await;
         }
 
-        let write_result = musubi_wasmtime::import::run_imported_function32_write_output(
+        let write_result = Self::write_import_output(
             instance.read().await.as_ref().unwrap(),
             &mut caller,
-            output_ptr as usize,
+            bitness,
+            output_ptr,
             result.unwrap(),
         )
         .await;
 
         if write_result.is_err() {
-            return Self::emit_wasm32_import_error(
+            return Self::emit_import_error(
                 "failed to write output from memory",
                 instance.read().await.as_ref().unwrap(),
                 &mut caller,
+                bitness,
                 output_ptr,
             )
             .await;
@@ -337,23 +564,86 @@ impl WasmtimeLinker {
         write_result.unwrap()
     }
 
-    async fn run_wasm32_export(
-        context: WasmtimeContext,
-        shared_store: SharedAsyncMany<Option<wasmtime::Store<WasmtimeContext>>>,
+    pub async fn run_wasm32_import<'a>(
+        caller: wasmtime::Caller<'a, WasmtimeContext>,
+        symbol: Symbol,
+        dependency_name: String,
+        input_ptr: i32,
+        input_len: i64,
+        output_ptr: i32,
+    ) -> i64 {
+        Self::run_import(
+            caller,
+            symbol,
+            dependency_name,
+            Bitness::X32,
+            input_ptr as usize,
+            input_len,
+            output_ptr as usize,
+        )
+        .await
+    }
+
+    pub async fn run_wasm64_import<'a>(
+        caller: wasmtime::Caller<'a, WasmtimeContext>,
+        symbol: Symbol,
+        dependency_name: String,
+        input_ptr: i64,
+        input_len: i64,
+        output_ptr: i64,
+    ) -> i64 {
+        Self::run_import(
+            caller,
+            symbol,
+            dependency_name,
+            Bitness::X64,
+            input_ptr as usize,
+            input_len,
+            output_ptr as usize,
+        )
+        .await
+    }
+
+    async fn run_export(
+        pool: Arc<InstancePool>,
+        bitness: Bitness,
         function: String,
         input: Vec<u8>,
     ) -> Vec<u8> {
-        let mut guard = shared_store.write().await;
-
-        let store = guard.as_mut().unwrap();
-
-        let output_result = musubi_wasmtime::export::run_exported_function32_read_output(
-            context.get_instance().read().await.as_ref().unwrap(),
-            store,
-            function.as_str(),
-            input,
-        )
-        .await;
+        let function_name = function.clone();
+
+        let output_result = pool
+            .with_slot(move |slot| {
+                async move {
+                    let instance = slot.context.get_instance();
+                    let guard = instance.read().await;
+
+                    match bitness {
+                        Bitness::X32 => {
+                            musubi_wasmtime::export::run_exported_function32_read_output(
+                                guard.as_ref().unwrap(),
+                                &mut slot.store,
+                                function_name.as_str(),
+                                input,
+                            )
+                            .await
+                        }
+                        Bitness::X64 => {
+                            musubi_wasmtime::export::run_exported_function64_read_output(
+                                guard.as_ref().unwrap(),
+                                &mut slot.store,
+                                function_name.as_str(),
+                                input,
+                            )
+                            .await
+                        }
+                    }
+                    // Keep the error intact, traps and backtraces are recovered by downcasting.
+                    .map_err(anyhow::Error::from)
+                }
+                .boxed()
+            })
+            .await;
 
         if let Err(e) = output_result {
             return Self::construct_error(
@@ -371,14 +661,136 @@ impl WasmtimeLinker {
     }
 }
 
-#[async_trait]
-impl Linker for WasmtimeLinker {
-    async fn load(
+impl WasmtimeLinker {
+    /// Dependencies served by the kernel (such as `mitsuha.core`) or by other runtimes are
+    /// dispatched through the [KernelBinding]; everything else is linked in-process.
+    fn is_linkable_dependency(module_info: &ModuleInfo) -> bool {
+        module_info.modtype == ModuleType::WASM
+            && module_info.name != CoreConstants::CoreModuleName.to_string()
+    }
+
+    /// Linkable dependencies which have to be loaded as WASM, that is, which are not native.
+    fn is_wasm_dependency(&self, module_info: &ModuleInfo) -> bool {
+        Self::is_linkable_dependency(module_info) && self.native_modules.get(module_info).is_none()
+    }
+
+    /// Capabilities of a module: those declared in its manifest, narrowed by the capabilities
+    /// of the job it is linked for.
+    fn get_capabilities(
         &self,
-        context: &mut LinkerContext,
+        context: &LinkerContext,
+        module: &WasmtimeModule,
+        module_info: &ModuleInfo,
+    ) -> types::Result<Capabilities> {
+        let manifest = match module.metadata.get_capabilities() {
+            Some(manifest) => manifest,
+            None if self.require_capability_manifest => {
+                return Err(Error::LinkerLoadFailed {
+                    message: "module does not declare a capability manifest".to_string(),
+                    target: module_info.clone(),
+                    source: anyhow::anyhow!(""),
+                })
+            }
+            None => Capabilities::unrestricted(),
+        };
+
+        match Capabilities::from_extensions(&context.extensions)? {
+            Some(job_capabilities) => Ok(manifest.narrow(&job_capabilities)),
+            None => Ok(manifest),
+        }
+    }
+
+    /// Refuses imports of kernel functions and modules which were not granted.
+    fn check_imports(
+        module: &WasmtimeModule,
         module_info: &ModuleInfo,
+        capabilities: &Capabilities,
+    ) -> types::Result<()> {
+        for import in module.inner().imports() {
+            let Ok((module_name, function_name)) =
+                Symbol::parse_musubi_function_symbol(import.name())
+            else {
+                continue;
+            };
+
+            let symbol = Symbol {
+                name: function_name,
+                module_info: ModuleInfo {
+                    name: module_name,
+                    version: Default::default(),
+                    modtype: ModuleType::WASM,
+                },
+            };
+
+            capabilities
+                .check_symbol(&symbol)
+                .map_err(|e| Error::LinkerLinkFailed {
+                    message: format!("import '{}' is not permitted", import.name()),
+                    target: module_info.clone(),
+                    source: e.into(),
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// Describes the cycle closed by `module_info`, if it is already on the dependency stack.
+    fn find_cycle(stack: &[ModuleInfo], module_info: &ModuleInfo) -> Option<String> {
+        if !stack.contains(module_info) {
+            return None;
+        }
+
+        let cycle = stack
+            .iter()
+            .skip_while(|x| *x != module_info)
+            .chain(std::iter::once(module_info))
+            .map(|x| x.get_identifier())
+            .collect::<Vec<String>>()
+            .join(" -> ");
+
+        Some(cycle)
+    }
+
+    fn check_version_conflict(
+        context: &LinkerContext,
+        parent: &ModuleInfo,
+        dependency: &ModuleInfo,
     ) -> types::Result<()> {
-        let mut module = self.fetch_module(&context, &module_info).await?;
+        let known = context.dependency_graph.iter().flat_map(|(owner, deps)| {
+            deps.values()
+                .map(move |dep| (owner, dep))
+                .chain(std::iter::once((owner, owner)))
+        });
+
+        for (owner, other) in known {
+            if other.name == dependency.name
+                && other.modtype == dependency.modtype
+                && other.version != dependency.version
+            {
+                return Err(Error::LinkerLoadFailed {
+                    message: format!(
+                        "version conflict for module '{}': '{}' requires version '{}', which conflicts with version '{}' in the dependency graph of '{}'",
+                        dependency.name,
+                        parent.get_identifier(),
+                        dependency.version,
+                        other.version,
+                        owner.get_identifier()
+                    ),
+                    target: parent.clone(),
+                    source: anyhow::anyhow!(""),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn load_dependencies(
+        &self,
+        context: &LinkerContext,
+        module_info: &ModuleInfo,
+    ) -> types::Result<HashMap<String, ModuleInfo>> {
+        let mut module = self.fetch_module(context, module_info).await?;
         let mut spec = module
             .get_musubi_spec()
             .map_err(|e| Error::LinkerLoadFailed {
@@ -387,60 +799,172 @@ impl Linker for WasmtimeLinker {
                 source: e,
             })?;
 
+        let capabilities = self.get_capabilities(context, &module, module_info)?;
+
         let mut dependencies = HashMap::new();
 
         for dependency in spec.info.deps.drain(..) {
-            dependencies.insert(dependency.name.clone(), dependency.into());
-        }
+            let name = dependency.name.clone();
 
-        context
-            .dependency_graph
-            .insert(module_info.clone(), dependencies);
+            if name != CoreConstants::CoreModuleName.to_string()
+                && !capabilities.allows_module(&name)
+            {
+                return Err(Error::LinkerLoadFailed {
+                    message: format!("dependency '{}' is not permitted", name),
+                    target: module_info.clone(),
+                    source: anyhow::anyhow!(""),
+                });
+            }
 
-        Ok(())
-    }
+            let mut dependency_info: ModuleInfo = dependency.into();
+
+            if let Some(native_module) = self
+                .native_modules
+                .resolve(&dependency_info.name, &dependency_info.version)
+            {
+                dependency_info = native_module.get_module_info();
+            } else if let (true, Some(registry)) = (
+                Self::is_linkable_dependency(&dependency_info),
+                &context.module_registry,
+            ) {
+                let record = registry
+                    .resolve(
+                        dependency_info.name.clone(),
+                        dependency_info.version.clone(),
+                    )
+                    .await?;
+
+                dependency_info = record_to_module_info(&record, &dependency_info);
+            }
 
-    async fn link(
-        &self,
-        context: &mut LinkerContext,
-        module_info: &ModuleInfo,
-    ) -> types::Result<ExecutorContext> {
-        let mut module = self.fetch_module(&context, &module_info).await?;
-        let kernel = context.kernel_binding.get_kernel().await;
-        let musubi_kernel: Arc<Box<dyn AsyncKernel>> =
-            Arc::new(Box::new(MusubiKernelWrapper::new(Box::new(kernel))));
+            dependencies.insert(name, dependency_info);
+        }
 
-        let fs = Arc::new(AsyncNativeFileSystemBuilder::new(musubi_kernel).build());
+        Ok(dependencies)
+    }
 
-        let root_dir = Box::new(Dir::new(fs, "/"));
+    fn load_recursive<'a>(
+        &'a self,
+        context: &'a mut LinkerContext,
+        module_info: &'a ModuleInfo,
+        stack: &'a mut Vec<ModuleInfo>,
+    ) -> BoxFuture<'a, types::Result<()>> {
+        async move {
+            if let Some(cycle) = Self::find_cycle(stack, module_info) {
+                return Err(Error::LinkerLoadFailed {
+                    message: format!("dependency cycle detected: {}", cycle),
+                    target: module_info.clone(),
+                    source: anyhow::anyhow!(""),
+                });
+            }
 
-        let mut wasi_ctx = WasiCtx::new(random_ctx(), clocks_ctx(), sched_ctx(), Table::new());
+            if context.dependency_graph.contains_key(module_info) {
+                return Ok(());
+            }
 
-        wasi_ctx
-            .push_preopened_dir(root_dir, "/")
-            .to_unknown_err_result()?;
+            let dependencies = self.load_dependencies(context, module_info).await?;
 
-        let wasmtime_context = WasmtimeContext::new(wasi_ctx, context.kernel_binding.clone());
+            for dependency in dependencies.values() {
+                if self.is_wasm_dependency(dependency) {
+                    Self::check_version_conflict(context, module_info, dependency)?;
+                }
+            }
 
-        let mut store = wasmtime::Store::new(&self.engine, wasmtime_context.clone());
+            context
+                .dependency_graph
+                .insert(module_info.clone(), dependencies.clone());
 
-        store.epoch_deadline_async_yield_and_update(1);
+            stack.push(module_info.clone());
 
-        let mut linker = wasmtime::Linker::new(&self.engine);
+            for dependency in dependencies.values() {
+                if self.is_wasm_dependency(dependency) {
+                    self.load_recursive(context, dependency, stack).await?;
+                }
+            }
 
-        wasi_common::tokio::add_to_linker(&mut linker, |s: &mut WasmtimeContext| &mut s.wasi_ctx)
-            .to_unknown_err_result()?;
+            stack.pop();
 
-        let dep_map =
-            context
+            Ok(())
+        }
+        .boxed()
+    }
+
+    /// Links every in-process dependency of `module_info` into its own sibling instance,
+    /// reusing instances of modules that are shared by several dependants.
+    fn link_dependencies<'a>(
+        &'a self,
+        context: &'a LinkerContext,
+        module_info: &'a ModuleInfo,
+        stack: &'a mut Vec<ModuleInfo>,
+        linked: &'a mut HashMap<ModuleInfo, Arc<ExecutorContext>>,
+        monitor: &'a JobMonitor,
+    ) -> BoxFuture<'a, types::Result<HashMap<String, Arc<ExecutorContext>>>> {
+        async move {
+            let dep_map = context
                 .dependency_graph
-                .get(&module_info)
+                .get(module_info)
                 .ok_or(Error::LinkerLinkFailed {
                     message: "cannot find dependency in context".to_string(),
                     target: module_info.clone(),
                     source: anyhow::anyhow!(""),
-                })?;
+                })?
+                .clone();
+
+            stack.push(module_info.clone());
+
+            let mut linked_dependencies = HashMap::new();
+
+            for (name, dependency) in dep_map {
+                if let Some(native_module) = self.native_modules.get(&dependency) {
+                    linked_dependencies.insert(name, native_module.get_executor_context());
+                    continue;
+                }
 
+                if !Self::is_linkable_dependency(&dependency) {
+                    continue;
+                }
+
+                if let Some(cycle) = Self::find_cycle(stack, &dependency) {
+                    return Err(Error::LinkerLinkFailed {
+                        message: format!("dependency cycle detected: {}", cycle),
+                        target: module_info.clone(),
+                        source: anyhow::anyhow!(""),
+                    });
+                }
+
+                let executor_context = match linked.get(&dependency) {
+                    Some(v) => v.clone(),
+                    None => {
+                        let children = self
+                            .link_dependencies(context, &dependency, stack, linked, monitor)
+                            .await?;
+
+                        let executor_context = Arc::new(
+                            self.link_module(context, &dependency, children, monitor)
+                                .await?,
+                        );
+
+                        linked.insert(dependency.clone(), executor_context.clone());
+
+                        executor_context
+                    }
+                };
+
+                linked_dependencies.insert(name, executor_context);
+            }
+
+            stack.pop();
+
+            Ok(linked_dependencies)
+        }
+        .boxed()
+    }
+
+    fn get_bitness(
+        &self,
+        module: &mut WasmtimeModule,
+        module_info: &ModuleInfo,
+    ) -> types::Result<Bitness> {
         let bitness = module
             .get_musubi_spec()
             .map_err(|e| Error::LinkerLinkFailed {
@@ -455,14 +979,109 @@ impl Linker for WasmtimeLinker {
                 source: e,
             })?;
 
-        if bitness != musubi_api::types::Bitness::X32 {
+        if bitness == Bitness::X64 && !self.memory64 {
             return Err(Error::LinkerLinkFailed {
-                message: "only 32 bit wasm is supported".to_string(),
+                message: "64 bit wasm requires the memory64 option to be enabled".to_string(),
                 target: module_info.clone(),
                 source: anyhow::anyhow!(""),
             });
         }
 
+        Ok(bitness)
+    }
+
+    /// Defines the trampoline for an import of `symbol`, taking pointers of the given bitness.
+    fn define_import(
+        linker: &mut wasmtime::Linker<WasmtimeContext>,
+        bitness: Bitness,
+        module_name: &str,
+        import_name: &str,
+        symbol: Symbol,
+    ) -> anyhow::Result<()> {
+        let imported_module_name = module_name.to_string();
+
+        match bitness {
+            Bitness::X32 => linker.func_wrap3_async(
+                module_name,
+                import_name,
+                move |caller: wasmtime::Caller<'_, WasmtimeContext>,
+                      input_ptr: i32,
+                      input_len: i64,
+                      output_ptr: i32| {
+                    let fut = Self::run_wasm32_import(
+                        caller,
+                        symbol.clone(),
+                        imported_module_name.clone(),
+                        input_ptr,
+                        input_len,
+                        output_ptr,
+                    );
+
+                    Box::new(fut)
+                },
+            ),
+            Bitness::X64 => linker.func_wrap3_async(
+                module_name,
+                import_name,
+                move |caller: wasmtime::Caller<'_, WasmtimeContext>,
+                      input_ptr: i64,
+                      input_len: i64,
+                      output_ptr: i64| {
+                    let fut = Self::run_wasm64_import(
+                        caller,
+                        symbol.clone(),
+                        imported_module_name.clone(),
+                        input_ptr,
+                        input_len,
+                        output_ptr,
+                    );
+
+                    Box::new(fut)
+                },
+            ),
+        }?;
+
+        Ok(())
+    }
+
+    /// Returns a pre-instantiated module with all imports resolved. Per-job state such as the
+    /// linked dependencies is carried by the store data, so the result can be shared by all
+    /// jobs running the same module with the same dependency set.
+    async fn get_instance_pre(
+        &self,
+        context: &LinkerContext,
+        module_info: &ModuleInfo,
+    ) -> types::Result<wasmtime::InstancePre<WasmtimeContext>> {
+        let dep_map = context
+            .dependency_graph
+            .get(module_info)
+            .ok_or(Error::LinkerLinkFailed {
+                message: "cannot find dependency in context".to_string(),
+                target: module_info.clone(),
+                source: anyhow::anyhow!(""),
+            })?;
+
+        let mut dependency_set: Vec<(String, ModuleInfo)> = dep_map
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        dependency_set.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let cache_key = (self.get_cache_key(context, module_info), dependency_set);
+
+        if let Some(v) = self.instance_pre_cache.get(&cache_key) {
+            return Ok(v);
+        }
+
+        let mut module = self.fetch_module(context, module_info).await?;
+
+        let mut linker = wasmtime::Linker::new(&self.engine);
+
+        wasi_common::tokio::add_to_linker(&mut linker, |s: &mut WasmtimeContext| &mut s.wasi_ctx)
+            .to_unknown_err_result()?;
+
+        let bitness = self.get_bitness(&mut module, module_info)?;
+
         for import in module.inner().imports() {
             // Ignore if the import is not a musubi import
             let result = Symbol::parse_musubi_function_symbol(import.name());
@@ -493,48 +1112,68 @@ impl Linker for WasmtimeLinker {
                 source: e.into(),
             })?;
 
-            let imported_symbol = symbol.clone();
-            let imported_func = wasmtime::Func::wrap3_async(
-                &mut store,
-                move |caller: wasmtime::Caller<'_, WasmtimeContext>,
-                      input_ptr: i32,
-                      input_len: i64,
-                      output_ptr: i32| {
-                    let fut = Self::run_wasm32_import(
-                        caller,
-                        imported_symbol.clone(),
-                        input_ptr,
-                        input_len,
-                        output_ptr,
-                    );
-
-                    Box::new(fut)
-                },
-            );
-
             tracing::debug!("importing symbol {:?}", symbol.clone());
 
+            Self::define_import(
+                &mut linker,
+                bitness,
+                module_name.as_str(),
+                import.name(),
+                symbol,
+            )
+            .map_err(|e| Error::LinkerLinkFailed {
+                message: "failed to define import in wasmtime module".to_string(),
+                target: module_info.clone(),
+                source: e.into(),
+            })?;
+        }
+
+        let instance_pre =
             linker
-                .define(&store, module_name.as_str(), import.name(), imported_func)
+                .instantiate_pre(module.inner())
                 .map_err(|e| Error::LinkerLinkFailed {
-                    message: "failed to define import in wasmtime module".to_string(),
+                    message: "failed to pre-instantiate wasmtime module with imports".to_string(),
                     target: module_info.clone(),
-                    source: e.into(),
+                    source: e,
                 })?;
-        }
 
-        let instance = linker
-            .instantiate_async(&mut store, module.inner())
-            .await
-            .map_err(|e| Error::LinkerLinkFailed {
-                message: "failed to instantiate wasmtime module with imports".to_string(),
-                target: module_info.clone(),
-                source: e,
-            })?;
+        self.instance_pre_cache
+            .insert(cache_key, instance_pre.clone())
+            .await;
 
-        wasmtime_context.set_instance(instance).await;
+        Ok(instance_pre)
+    }
 
-        let shared_store = Arc::new(tokio::sync::RwLock::new(Some(store)));
+    async fn link_module(
+        &self,
+        context: &LinkerContext,
+        module_info: &ModuleInfo,
+        linked_dependencies: HashMap<String, Arc<ExecutorContext>>,
+        monitor: &JobMonitor,
+    ) -> types::Result<ExecutorContext> {
+        let mut module = self.fetch_module(context, module_info).await?;
+        let bitness = self.get_bitness(&mut module, module_info)?;
+
+        let capabilities = self.get_capabilities(context, &module, module_info)?;
+        Self::check_imports(&module, module_info, &capabilities)?;
+
+        let instance_pre = self.get_instance_pre(context, module_info).await?;
+
+        let pool = Arc::new(
+            InstancePool::new(
+                self.engine.clone(),
+                instance_pre,
+                module_info.clone(),
+                InstanceScope {
+                    kernel_binding: context.kernel_binding.clone(),
+                    dependencies: linked_dependencies,
+                    monitor: monitor.clone(),
+                    fs_paths: capabilities.fs,
+                },
+                self.export_concurrency,
+            )
+            .await?,
+        );
 
         let mut executor_context = ExecutorContext::new();
 
@@ -571,18 +1210,12 @@ impl Linker for WasmtimeLinker {
 
             tracing::debug!("exporting symbol: {:?}", symbol.clone());
 
-            let exported_store = shared_store.clone();
-            let exported_context = wasmtime_context.clone();
+            let exported_pool = pool.clone();
             let function_name = export.name().to_string();
 
             let exported_func = move |input: Vec<u8>| {
-                Self::run_wasm32_export(
-                    exported_context.clone(),
-                    exported_store.clone(),
-                    function_name.clone(),
-                    input,
-                )
-                .boxed()
+                Self::run_export(exported_pool.clone(), bitness, function_name.clone(), input)
+                    .boxed()
             };
 
             executor_context
@@ -591,12 +1224,224 @@ impl Linker for WasmtimeLinker {
 
         Ok(executor_context)
     }
+
+    /// Links a module and its in-process dependencies. Guest CPU time, traps and profiling
+    /// samples of every linked instance are reported to `monitor`.
+    pub async fn link_monitored(
+        &self,
+        context: &LinkerContext,
+        module_info: &ModuleInfo,
+        monitor: &JobMonitor,
+    ) -> types::Result<ExecutorContext> {
+        let linked_dependencies = self
+            .link_dependencies(
+                context,
+                module_info,
+                &mut vec![],
+                &mut HashMap::new(),
+                monitor,
+            )
+            .await?;
+
+        self.link_module(context, module_info, linked_dependencies, monitor)
+            .await
+    }
+}
+
+#[async_trait]
+impl Linker for WasmtimeLinker {
+    async fn load(
+        &self,
+        context: &mut LinkerContext,
+        module_info: &ModuleInfo,
+    ) -> types::Result<()> {
+        self.load_recursive(context, module_info, &mut vec![]).await
+    }
+
+    async fn link(
+        &self,
+        context: &mut LinkerContext,
+        module_info: &ModuleInfo,
+    ) -> types::Result<ExecutorContext> {
+        let monitor = JobMonitor::new(self.make_cpu_meter(&context.extensions)?);
+
+        self.link_monitored(context, module_info, &monitor).await
+    }
 }
 
 impl Drop for WasmtimeLinker {
     fn drop(&mut self) {
-        if let Some(ticker_handle) = self.ticker_handle.as_mut() {
-            ticker_handle.abort();
+        self.ticker_stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, sync::Arc};
+
+    use async_trait::async_trait;
+    use mitsuha_core::{
+        err_unsupported_op,
+        errors::Error,
+        kernel::{Kernel, KernelBinding},
+        linker::LinkerContext,
+        resolver::Resolver,
+        types,
+    };
+    use mitsuha_core_types::{
+        module::{ModuleInfo, ModuleType},
+        symbol::Symbol,
+    };
+
+    use musubi_api::types::Bitness;
+
+    use super::{WasmtimeContext, WasmtimeLinker};
+    use crate::wasmtime::WasmtimeConfig;
+
+    struct NullResolver;
+
+    #[async_trait]
+    impl Resolver<ModuleInfo, Vec<u8>> for NullResolver {
+        async fn resolve(&self, _key: &ModuleInfo) -> types::Result<Vec<u8>> {
+            Err(err_unsupported_op!("null resolver cannot resolve modules"))
+        }
+
+        async fn register(&self, _key: &ModuleInfo, _value: &Vec<u8>) -> types::Result<()> {
+            Err(err_unsupported_op!("null resolver cannot register modules"))
+        }
+    }
+
+    struct NullKernelBinding;
+
+    #[async_trait]
+    impl KernelBinding for NullKernelBinding {
+        async fn get_kernel(&self) -> Arc<Box<dyn Kernel>> {
+            unreachable!("the dependency checks do not use the kernel")
         }
+
+        async fn run(&self, _symbol: &Symbol, _input: Vec<u8>) -> types::Result<Vec<u8>> {
+            Err(err_unsupported_op!(
+                "null kernel binding cannot run symbols"
+            ))
+        }
+    }
+
+    fn make_module_info(name: &str, version: &str) -> ModuleInfo {
+        ModuleInfo {
+            name: name.to_string(),
+            version: version.to_string(),
+            modtype: ModuleType::WASM,
+        }
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        let a = make_module_info("mitsuha.test.a", "0.1.0");
+        let b = make_module_info("mitsuha.test.b", "0.1.0");
+        let c = make_module_info("mitsuha.test.c", "0.1.0");
+
+        let stack = vec![a.clone(), b.clone(), c.clone()];
+
+        assert_eq!(
+            WasmtimeLinker::find_cycle(&stack, &b),
+            Some(format!(
+                "{} -> {} -> {}",
+                b.get_identifier(),
+                c.get_identifier(),
+                b.get_identifier()
+            ))
+        );
+
+        assert_eq!(
+            WasmtimeLinker::find_cycle(&stack[..2], &make_module_info("mitsuha.test.c", "0.1.0")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_version_conflict() {
+        let app = make_module_info("mitsuha.test.app", "0.1.0");
+        let lib = make_module_info("mitsuha.test.lib", "1.0.0");
+        let plugin = make_module_info("mitsuha.test.plugin", "0.1.0");
+
+        let mut context = LinkerContext::new(
+            Arc::new(Box::new(NullKernelBinding)),
+            Arc::new(Box::new(NullResolver)),
+        );
+
+        context.dependency_graph.insert(
+            app.clone(),
+            HashMap::from([("lib".to_string(), lib.clone())]),
+        );
+
+        // The same version can be shared by several dependants.
+        assert!(WasmtimeLinker::check_version_conflict(&context, &plugin, &lib).is_ok());
+
+        let result = WasmtimeLinker::check_version_conflict(
+            &context,
+            &plugin,
+            &make_module_info("mitsuha.test.lib", "2.0.0"),
+        );
+
+        assert!(matches!(
+            result,
+            Err(Error::LinkerLoadFailed { message, target, .. })
+                if target == plugin && message.contains("version conflict")
+        ));
+
+        // Modules already in the graph conflict with other versions of themselves as well.
+        assert!(WasmtimeLinker::check_version_conflict(
+            &context,
+            &plugin,
+            &make_module_info("mitsuha.test.app", "0.2.0"),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_memory64_imports() {
+        let config = WasmtimeConfig::from_properties(&HashMap::from([(
+            "memory64".to_string(),
+            "true".to_string(),
+        )]))
+        .unwrap();
+
+        let engine = config.make_engine().unwrap();
+
+        let module = wasmtime::Module::new(
+            &engine,
+            r#"
+            (module
+              (import "mitsuha.test.lib" "run" (func (param i64 i64 i64) (result i64)))
+              (memory i64 1))
+            "#,
+        )
+        .unwrap();
+
+        let symbol = Symbol {
+            name: "run".to_string(),
+            module_info: make_module_info("mitsuha.test.lib", "0.1.0"),
+        };
+
+        // 64 bit modules pass 64 bit pointers to their imports.
+        let mut linker = wasmtime::Linker::<WasmtimeContext>::new(&engine);
+
+        WasmtimeLinker::define_import(
+            &mut linker,
+            Bitness::X64,
+            "mitsuha.test.lib",
+            "run",
+            symbol.clone(),
+        )
+        .unwrap();
+
+        assert!(linker.instantiate_pre(&module).is_ok());
+
+        let mut linker = wasmtime::Linker::<WasmtimeContext>::new(&engine);
+
+        WasmtimeLinker::define_import(&mut linker, Bitness::X32, "mitsuha.test.lib", "run", symbol)
+            .unwrap();
+
+        assert!(linker.instantiate_pre(&module).is_err());
     }
 }
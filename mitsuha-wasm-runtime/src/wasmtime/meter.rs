@@ -0,0 +1,91 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use wasmtime::UpdateDeadline;
+
+/// Tracks the CPU time consumed by all instances linked for one job, measured in engine epochs.
+///
+/// The epoch deadline callback of a store only fires while guest code is executing, so every
+/// invocation accounts for (roughly) one epoch tick of guest CPU time. Time spent waiting on
+/// host calls, such as kernel calls, is not counted against the budget.
+#[derive(Debug)]
+pub struct CpuMeter {
+    budget: Option<u64>,
+    consumed: AtomicU64,
+    ticks_per_epoch: u64,
+    ticks: AtomicU64,
+}
+
+impl CpuMeter {
+    /// Creates a meter that traps the guest once more than `budget` epochs are consumed.
+    pub fn new(budget: Option<u64>) -> Self {
+        Self {
+            budget,
+            consumed: AtomicU64::new(0),
+            ticks_per_epoch: 1,
+            ticks: AtomicU64::new(0),
+        }
+    }
+
+    /// Sets the number of engine ticks in an epoch, see [CpuMeter::on_tick].
+    pub fn with_ticks_per_epoch(mut self, ticks_per_epoch: u64) -> Self {
+        self.ticks_per_epoch = ticks_per_epoch.max(1);
+        self
+    }
+
+    pub fn get_ticks_per_epoch(&self) -> u64 {
+        self.ticks_per_epoch
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(None)
+    }
+
+    pub fn get_budget(&self) -> Option<u64> {
+        self.budget
+    }
+
+    pub fn get_consumed(&self) -> u64 {
+        self.consumed.load(Ordering::Relaxed)
+    }
+
+    pub fn is_exceeded(&self) -> bool {
+        match self.budget {
+            Some(budget) => self.get_consumed() > budget,
+            None => false,
+        }
+    }
+
+    /// Epoch deadline callback of stores interrupted once per epoch. Yields to the async
+    /// executor on every epoch, and traps once the budget is spent.
+    pub fn on_epoch(&self) -> anyhow::Result<UpdateDeadline> {
+        self.charge()?;
+
+        Ok(UpdateDeadline::Yield(self.ticks_per_epoch))
+    }
+
+    /// Epoch deadline callback of stores interrupted on every engine tick, such as profiled
+    /// ones. An epoch is charged, and the guest yields, once every `ticks_per_epoch` ticks.
+    pub fn on_tick(&self) -> anyhow::Result<UpdateDeadline> {
+        let ticks = self.ticks.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if ticks % self.ticks_per_epoch != 0 {
+            return Ok(UpdateDeadline::Continue(1));
+        }
+
+        self.charge()?;
+
+        Ok(UpdateDeadline::Yield(1))
+    }
+
+    fn charge(&self) -> anyhow::Result<()> {
+        let consumed = self.consumed.fetch_add(1, Ordering::Relaxed) + 1;
+
+        match self.budget {
+            Some(budget) if consumed > budget => Err(anyhow::anyhow!(
+                "cpu time budget of {} epochs exceeded",
+                budget
+            )),
+            _ => Ok(()),
+        }
+    }
+}
@@ -1,4 +1,21 @@
+pub mod artifact;
+pub mod component;
+pub mod config;
 pub mod linker;
+pub mod meter;
+pub mod monitor;
+pub mod pool;
+pub mod profiler;
+pub mod trap;
+pub mod validator;
 pub mod wasi;
 
+pub use artifact::PrecompiledModuleStore;
+pub use component::ComponentLinker;
+pub use config::WasmtimeConfig;
 pub use linker::{WasmtimeLinker, WasmtimeModule};
+pub use meter::CpuMeter;
+pub use monitor::JobMonitor;
+pub use profiler::JobProfiler;
+pub use trap::TrapRecorder;
+pub use validator::{ModuleValidator, ValidationReport};
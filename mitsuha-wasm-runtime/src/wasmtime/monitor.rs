@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use wasmtime::UpdateDeadline;
+
+use crate::wasmtime::{meter::CpuMeter, profiler::JobProfiler, trap::TrapRecorder};
+
+/// Per-job observers shared by the stores of every instance linked for the job.
+#[derive(Clone)]
+pub struct JobMonitor {
+    pub meter: Arc<CpuMeter>,
+    pub traps: Arc<TrapRecorder>,
+    pub profiler: Option<Arc<JobProfiler>>,
+}
+
+impl JobMonitor {
+    pub fn new(meter: Arc<CpuMeter>) -> Self {
+        Self {
+            meter,
+            traps: Arc::new(TrapRecorder::new()),
+            profiler: None,
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(Arc::new(CpuMeter::unlimited()))
+    }
+
+    pub fn with_profiler(mut self, profiler: Arc<JobProfiler>) -> Self {
+        self.profiler = Some(profiler);
+        self
+    }
+
+    /// Installs the epoch deadline callback on `store`, which charges the [CpuMeter] once per
+    /// epoch. Profiled stores are interrupted on every engine tick instead, to sample the
+    /// profiler at its own, finer interval.
+    pub fn observe<T>(&self, store: &mut wasmtime::Store<T>) {
+        let monitor = self.clone();
+
+        match &self.profiler {
+            Some(_) => store.set_epoch_deadline(1),
+            None => store.set_epoch_deadline(self.meter.get_ticks_per_epoch()),
+        }
+
+        store.epoch_deadline_callback(move |ctx| monitor.on_epoch(ctx));
+    }
+
+    fn on_epoch<T>(&self, ctx: wasmtime::StoreContextMut<'_, T>) -> anyhow::Result<UpdateDeadline> {
+        match &self.profiler {
+            Some(profiler) => {
+                profiler.sample(&ctx);
+
+                self.meter.on_tick()
+            }
+            None => self.meter.on_epoch(),
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use mitsuha_core::{
+    channel::MusubiKernelWrapper, errors::Error, errors::ToUnknownErrorResult,
+    executor::ExecutorContext, kernel::KernelBinding, types,
+};
+use mitsuha_core_types::{kernel::AsyncKernel, module::ModuleInfo};
+use mitsuha_filesystem::async_fs::AsyncNativeFileSystemBuilder;
+use tokio::sync::{Mutex, Semaphore};
+use wasi_common::sync::{clocks_ctx, random_ctx, sched_ctx};
+use wasi_common::{Table, WasiCtx};
+
+use crate::wasmtime::linker::WasmtimeContext;
+use crate::wasmtime::monitor::JobMonitor;
+use crate::wasmtime::wasi::dir::Dir;
+
+static NEXT_POOL_ID: AtomicU64 = AtomicU64::new(0);
+
+tokio::task_local! {
+    /// Pools serving a call on the current call stack, innermost last.
+    static ACTIVE_POOLS: Vec<u64>;
+}
+
+/// Per-job state shared by the instances of a pool.
+#[derive(Clone)]
+pub struct InstanceScope {
+    pub kernel_binding: Arc<Box<dyn KernelBinding>>,
+    pub dependencies: HashMap<String, Arc<ExecutorContext>>,
+    pub monitor: JobMonitor,
+
+    /// Filesystem paths visible to the guest, see [mitsuha_core::capability::Capabilities].
+    pub fs_paths: Option<Vec<String>>,
+}
+
+/// An instance with its own [wasmtime::Store], and so its own linear memory and globals.
+pub struct InstanceSlot {
+    pub context: WasmtimeContext,
+    pub store: wasmtime::Store<WasmtimeContext>,
+}
+
+/// A bounded pool of instances of one linked module. Each call into the module borrows an
+/// idle instance (creating one if none is idle), so up to `concurrency` calls can run in
+/// parallel against the same [ExecutorContext].
+///
+/// Slots do not share state: whatever a guest keeps in memory between calls is only seen
+/// by later calls which happen to borrow the same slot.
+pub struct InstancePool {
+    id: u64,
+    engine: wasmtime::Engine,
+    instance_pre: wasmtime::InstancePre<WasmtimeContext>,
+    module_info: ModuleInfo,
+    scope: InstanceScope,
+    permits: Semaphore,
+    idle: Mutex<Vec<InstanceSlot>>,
+}
+
+impl InstancePool {
+    pub async fn new(
+        engine: wasmtime::Engine,
+        instance_pre: wasmtime::InstancePre<WasmtimeContext>,
+        module_info: ModuleInfo,
+        scope: InstanceScope,
+        concurrency: usize,
+    ) -> types::Result<Self> {
+        let pool = Self {
+            id: NEXT_POOL_ID.fetch_add(1, Ordering::Relaxed),
+            engine,
+            instance_pre,
+            module_info,
+            scope,
+            permits: Semaphore::new(concurrency),
+            idle: Mutex::new(vec![]),
+        };
+
+        // Instantiate the first slot eagerly so that instantiation errors surface while linking.
+        let slot = pool.create_slot().await?;
+        pool.idle.lock().await.push(slot);
+
+        Ok(pool)
+    }
+
+    async fn create_slot(&self) -> types::Result<InstanceSlot> {
+        let kernel = self.scope.kernel_binding.get_kernel().await;
+        let musubi_kernel: Arc<Box<dyn AsyncKernel>> =
+            Arc::new(Box::new(MusubiKernelWrapper::new(Box::new(kernel))));
+
+        let fs = Arc::new(AsyncNativeFileSystemBuilder::new(musubi_kernel).build());
+
+        let mut root_dir = Dir::new(fs, "/");
+
+        if let Some(paths) = &self.scope.fs_paths {
+            root_dir = root_dir.with_allowed_paths(paths.clone());
+        }
+
+        let mut wasi_ctx = WasiCtx::new(random_ctx(), clocks_ctx(), sched_ctx(), Table::new());
+
+        wasi_ctx
+            .push_preopened_dir(Box::new(root_dir), "/")
+            .to_unknown_err_result()?;
+
+        let context = WasmtimeContext::new(
+            wasi_ctx,
+            self.scope.kernel_binding.clone(),
+            self.scope.dependencies.clone(),
+        );
+
+        let mut store = wasmtime::Store::new(&self.engine, context.clone());
+
+        self.scope.monitor.observe(&mut store);
+
+        let instance = self
+            .instance_pre
+            .instantiate_async(&mut store)
+            .await
+            .map_err(|e| Error::LinkerLinkFailed {
+                message: "failed to instantiate wasmtime module with imports".to_string(),
+                target: self.module_info.clone(),
+                source: e,
+            })?;
+
+        context.set_instance(instance).await;
+
+        Ok(InstanceSlot { context, store })
+    }
+
+    /// Borrows an instance for the duration of `f`. Instances whose call failed are dropped
+    /// instead of being returned to the pool, as a trap may leave them in an undefined state.
+    /// Traps are recorded with the trap recorder of the job before the instance is dropped.
+    ///
+    /// Calls back into the module from one of its own calls borrow another instance if one is
+    /// free, and are rejected otherwise, as they would wait for a permit held by the caller.
+    pub async fn with_slot<F, T>(&self, f: F) -> types::Result<T>
+    where
+        F: for<'a> FnOnce(
+            &'a mut InstanceSlot,
+        ) -> futures::future::BoxFuture<'a, anyhow::Result<T>>,
+    {
+        let mut active_pools = ACTIVE_POOLS.try_with(|x| x.clone()).unwrap_or_default();
+
+        let _permit = if active_pools.contains(&self.id) {
+            self.permits
+                .try_acquire()
+                .map_err(|_| Error::InvalidOperation {
+                    message: format!(
+                        "re-entrant call into {:?} while all of its instances are serving calls",
+                        self.module_info
+                    ),
+                })?
+        } else {
+            self.permits.acquire().await.to_unknown_err_result()?
+        };
+
+        active_pools.push(self.id);
+
+        let idle_slot = self.idle.lock().await.pop();
+        let mut slot = match idle_slot {
+            Some(slot) => slot,
+            None => self.create_slot().await?,
+        };
+
+        let result = ACTIVE_POOLS.scope(active_pools, f(&mut slot)).await;
+
+        match &result {
+            Ok(_) => self.idle.lock().await.push(slot),
+            Err(e) => self
+                .scope
+                .monitor
+                .traps
+                .record(&self.module_info, e, &mut slot.store),
+        }
+
+        result.map_err(|e| Error::ExecutorRunFailed {
+            message: format!("call into {:?} failed", self.module_info),
+            source: e,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use async_trait::async_trait;
+    use futures::FutureExt;
+    use mitsuha_core::{
+        channel::{ComputeChannel, ComputeKernel},
+        err_unsupported_op,
+        errors::Error,
+        kernel::{Kernel, KernelBinding},
+        types,
+    };
+    use mitsuha_core_types::{
+        channel::{ComputeInput, ComputeOutput},
+        module::{ModuleInfo, ModuleType},
+        symbol::Symbol,
+    };
+    use tokio::sync::Barrier;
+
+    use super::{InstancePool, InstanceScope};
+    use crate::wasmtime::{monitor::JobMonitor, WasmtimeConfig};
+
+    struct NullChannel;
+
+    #[async_trait]
+    impl ComputeChannel for NullChannel {
+        type Context = ();
+
+        fn id(&self) -> String {
+            "null".to_string()
+        }
+
+        async fn compute(&self, _ctx: (), _elem: ComputeInput) -> types::Result<ComputeOutput> {
+            Err(err_unsupported_op!("null channel cannot compute"))
+        }
+
+        async fn connect(&self, _next: Arc<Box<dyn ComputeChannel<Context = ()>>>) {}
+    }
+
+    struct NullKernelBinding(Arc<Box<dyn Kernel>>);
+
+    #[async_trait]
+    impl KernelBinding for NullKernelBinding {
+        async fn get_kernel(&self) -> Arc<Box<dyn Kernel>> {
+            self.0.clone()
+        }
+
+        async fn run(&self, _symbol: &Symbol, _input: Vec<u8>) -> types::Result<Vec<u8>> {
+            Err(err_unsupported_op!(
+                "null kernel binding cannot run symbols"
+            ))
+        }
+    }
+
+    async fn make_pool(concurrency: usize) -> Arc<InstancePool> {
+        let engine = WasmtimeConfig::default().make_engine().unwrap();
+        let module = wasmtime::Module::new(&engine, "(module (memory 1))").unwrap();
+        let instance_pre = wasmtime::Linker::new(&engine)
+            .instantiate_pre(&module)
+            .unwrap();
+
+        let kernel: Arc<Box<dyn Kernel>> = Arc::new(Box::new(ComputeKernel::new(Arc::new(
+            Box::new(NullChannel),
+        ))));
+
+        let scope = InstanceScope {
+            kernel_binding: Arc::new(Box::new(NullKernelBinding(kernel))),
+            dependencies: Default::default(),
+            monitor: JobMonitor::unlimited(),
+            fs_paths: None,
+        };
+
+        let module_info = ModuleInfo {
+            name: "mitsuha.test.pool".to_string(),
+            version: "0.1.0".to_string(),
+            modtype: ModuleType::WASM,
+        };
+
+        Arc::new(
+            InstancePool::new(engine, instance_pre, module_info, scope, concurrency)
+                .await
+                .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn concurrent_calls_use_separate_slots() {
+        let pool = make_pool(2).await;
+        let barrier = Arc::new(Barrier::new(2));
+
+        // Both calls wait for each other, which only completes if they hold distinct slots.
+        let call = |barrier: Arc<Barrier>| {
+            pool.with_slot(move |_| {
+                async move {
+                    barrier.wait().await;
+                    Ok(())
+                }
+                .boxed()
+            })
+        };
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            futures::future::try_join(call(barrier.clone()), call(barrier.clone())),
+        )
+        .await
+        .unwrap();
+
+        assert!(result.is_ok());
+        assert_eq!(pool.idle.lock().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn reentrant_calls_fail_fast() {
+        let pool = make_pool(1).await;
+        let inner_pool = pool.clone();
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            pool.with_slot(move |_| {
+                async move { Ok(inner_pool.with_slot(|_| async { Ok(()) }.boxed()).await) }.boxed()
+            }),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert!(matches!(result, Err(Error::InvalidOperation { .. })));
+
+        // The rejected call leaves the pool usable.
+        assert!(pool.with_slot(|_| async { Ok(()) }.boxed()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn reentrant_calls_use_free_slots() {
+        let pool = make_pool(2).await;
+        let inner_pool = pool.clone();
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            pool.with_slot(move |_| {
+                async move { Ok(inner_pool.with_slot(|_| async { Ok(()) }.boxed()).await) }.boxed()
+            }),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert!(result.is_ok());
+        assert_eq!(pool.idle.lock().await.len(), 2);
+    }
+}
@@ -0,0 +1,79 @@
+use std::{sync::Mutex, time::Duration};
+
+use mitsuha_core::{errors::ToUnknownErrorResult, types};
+use wasmtime::{AsContext, GuestProfiler};
+
+/// Samples the guest stacks of all instances linked for one job on every engine tick, using
+/// wasmtime's [GuestProfiler]. The profile is written in the Firefox profiler JSON format.
+pub struct JobProfiler {
+    interval: Duration,
+    inner: Mutex<Option<GuestProfiler>>,
+}
+
+impl JobProfiler {
+    /// `modules` are the modules whose frames can be symbolized, `interval` is the engine tick
+    /// interval, at which samples are taken.
+    pub fn new(name: &str, interval: Duration, modules: Vec<(String, wasmtime::Module)>) -> Self {
+        Self {
+            interval,
+            inner: Mutex::new(Some(GuestProfiler::new(name, interval, modules))),
+        }
+    }
+
+    pub fn sample(&self, store: impl AsContext) {
+        if let Some(profiler) = self.inner.lock().unwrap().as_mut() {
+            profiler.sample(store, self.interval);
+        }
+    }
+
+    /// Stops profiling and returns the profile. Subsequent samples are dropped.
+    pub fn finish(&self) -> types::Result<Vec<u8>> {
+        let mut output = Vec::new();
+
+        if let Some(profiler) = self.inner.lock().unwrap().take() {
+            profiler.finish(&mut output).to_unknown_err_result()?;
+        }
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::JobProfiler;
+
+    fn make_module(engine: &wasmtime::Engine) -> wasmtime::Module {
+        wasmtime::Module::new(engine, "(module (memory 1))").unwrap()
+    }
+
+    #[test]
+    fn test_write_firefox_profile() {
+        let engine = wasmtime::Engine::default();
+        let module = make_module(&engine);
+
+        let profiler = JobProfiler::new(
+            "job",
+            Duration::from_millis(5),
+            vec![("module".to_string(), module.clone())],
+        );
+
+        let mut store = wasmtime::Store::new(&engine, ());
+        wasmtime::Instance::new(&mut store, &module, &[]).unwrap();
+
+        profiler.sample(&store);
+        profiler.sample(&store);
+
+        let profile: serde_json::Value =
+            serde_json::from_slice(&profiler.finish().unwrap()).unwrap();
+
+        assert!(profile["meta"].is_object());
+        assert_eq!(profile["threads"].as_array().unwrap().len(), 1);
+
+        // Samples taken after the profile was written are dropped.
+        profiler.sample(&store);
+
+        assert!(profiler.finish().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,182 @@
+use std::sync::Mutex;
+
+use mitsuha_core::trap::{GuestFrame, GuestTrap, SourceLocation};
+use mitsuha_core_types::module::ModuleInfo;
+
+/// A guest trap along with the core dump taken when it was raised.
+pub struct RecordedTrap {
+    pub trap: GuestTrap,
+    pub core_dump: Option<Vec<u8>>,
+}
+
+/// Records the first trap raised by any instance linked for one job.
+#[derive(Default)]
+pub struct TrapRecorder {
+    recorded: Mutex<Option<RecordedTrap>>,
+}
+
+impl TrapRecorder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Records `error` if it was raised by guest code. Core dumps are only attached to errors
+    /// when the engine is configured with `coredump_on_trap`.
+    pub fn record<T>(
+        &self,
+        module_info: &ModuleInfo,
+        error: &anyhow::Error,
+        store: &mut wasmtime::Store<T>,
+    ) {
+        let mut recorded = self.recorded.lock().unwrap();
+
+        if recorded.is_some() {
+            return;
+        }
+
+        let Some(trap) = capture_trap(module_info, error) else {
+            return;
+        };
+
+        let core_dump = error
+            .downcast_ref::<wasmtime::WasmCoreDump>()
+            .map(|x| x.serialize(store, &module_info.get_identifier()));
+
+        *recorded = Some(RecordedTrap { trap, core_dump });
+    }
+
+    pub fn take(&self) -> Option<RecordedTrap> {
+        self.recorded.lock().unwrap().take()
+    }
+}
+
+/// Extracts the trap kind and the symbolized wasm backtrace from an error returned by a call
+/// into a guest. Returns `None` for errors which did not originate from guest code.
+pub fn capture_trap(module_info: &ModuleInfo, error: &anyhow::Error) -> Option<GuestTrap> {
+    let kind = error.downcast_ref::<wasmtime::Trap>();
+    let backtrace = error.downcast_ref::<wasmtime::WasmBacktrace>();
+
+    if kind.is_none() && backtrace.is_none() {
+        return None;
+    }
+
+    let frames = backtrace
+        .map(|x| {
+            x.frames()
+                .iter()
+                .map(|frame| GuestFrame {
+                    module: frame.module().name().map(String::from),
+                    func_index: frame.func_index(),
+                    func_name: frame.func_name().map(String::from),
+                    module_offset: frame.module_offset(),
+                    locations: frame
+                        .symbols()
+                        .iter()
+                        .map(|symbol| SourceLocation {
+                            function: symbol.name().map(String::from),
+                            file: symbol.file().map(String::from),
+                            line: symbol.line(),
+                            column: symbol.column(),
+                        })
+                        .collect(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Some(GuestTrap {
+        module: module_info.get_identifier(),
+        kind: kind.map(|x| x.to_string()),
+        message: error.root_cause().to_string(),
+        frames,
+        core_dump_handle: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use mitsuha_core_types::module::{ModuleInfo, ModuleType};
+
+    use super::{capture_trap, TrapRecorder};
+
+    const TRAPPING_MODULE: &str = r#"
+    (module
+      (memory 1)
+      (func $inner (unreachable))
+      (func $boom (export "boom") (call $inner)))
+    "#;
+
+    fn make_module_info() -> ModuleInfo {
+        ModuleInfo {
+            name: "mitsuha.test.trap".to_string(),
+            version: "0.1.0".to_string(),
+            modtype: ModuleType::WASM,
+        }
+    }
+
+    fn call_boom(coredump_on_trap: bool) -> (wasmtime::Store<()>, anyhow::Error) {
+        let mut config = wasmtime::Config::default();
+        config.coredump_on_trap(coredump_on_trap);
+
+        let engine = wasmtime::Engine::new(&config).unwrap();
+        let module = wasmtime::Module::new(&engine, TRAPPING_MODULE).unwrap();
+
+        let mut store = wasmtime::Store::new(&engine, ());
+        let instance = wasmtime::Instance::new(&mut store, &module, &[]).unwrap();
+
+        let error = instance
+            .get_typed_func::<(), ()>(&mut store, "boom")
+            .unwrap()
+            .call(&mut store, ())
+            .unwrap_err();
+
+        (store, error)
+    }
+
+    #[test]
+    fn test_capture_trap() {
+        let (_, error) = call_boom(false);
+
+        let trap = capture_trap(&make_module_info(), &error).unwrap();
+
+        assert_eq!(trap.module, make_module_info().get_identifier());
+        assert_eq!(
+            trap.kind,
+            Some(wasmtime::Trap::UnreachableCodeReached.to_string())
+        );
+
+        // Innermost frame first.
+        assert_eq!(trap.frames.len(), 2);
+        assert_eq!(trap.frames[0].func_name.as_deref(), Some("inner"));
+        assert_eq!(trap.frames[1].func_name.as_deref(), Some("boom"));
+
+        assert!(capture_trap(&make_module_info(), &anyhow::anyhow!("host error")).is_none());
+    }
+
+    #[test]
+    fn test_record_trap_with_core_dump() {
+        let (mut store, error) = call_boom(true);
+
+        let recorder = TrapRecorder::new();
+
+        // Errors which did not originate from guest code are not recorded.
+        recorder.record(
+            &make_module_info(),
+            &anyhow::anyhow!("host error"),
+            &mut store,
+        );
+
+        assert!(recorder.take().is_none());
+
+        recorder.record(&make_module_info(), &error, &mut store);
+
+        // Later traps do not replace the first one, which carries the core dump.
+        let (mut other_store, other_error) = call_boom(false);
+        recorder.record(&make_module_info(), &other_error, &mut other_store);
+
+        let recorded = recorder.take().unwrap();
+
+        assert!(recorded.trap.kind.is_some());
+        assert!(recorded.core_dump.unwrap().starts_with(b"\0asm"));
+    }
+}
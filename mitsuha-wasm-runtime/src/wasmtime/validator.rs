@@ -0,0 +1,159 @@
+use std::collections::HashSet;
+
+use mitsuha_core::{symbol::SymbolExt, types};
+use mitsuha_core_types::{module::ModuleInfo, symbol::Symbol};
+use serde::Serialize;
+
+use crate::wasmtime::component::is_component_binary;
+use crate::wasmtime::linker::WasmMetadata;
+use crate::wasmtime::WasmtimeConfig;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationIssueKind {
+    InvalidMetadata,
+    CompilationFailed,
+    UnsupportedBitness,
+    UndeclaredDependency,
+    SpoofedExport,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationIssue {
+    pub kind: ValidationIssueKind,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationReport {
+    pub module: String,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    fn push(&mut self, kind: ValidationIssueKind, message: String) {
+        self.issues.push(ValidationIssue { kind, message });
+    }
+}
+
+/// Runs the checks that [crate::wasmtime::WasmtimeLinker] would otherwise only perform
+/// while linking, so that broken modules can be rejected before they are published.
+pub struct ModuleValidator {
+    engine: wasmtime::Engine,
+    memory64: bool,
+}
+
+impl ModuleValidator {
+    pub fn new(engine: wasmtime::Engine) -> Self {
+        Self {
+            engine,
+            memory64: false,
+        }
+    }
+
+    /// Creates a validator accepting the same modules as a linker built from `config`.
+    pub fn from_config(config: &WasmtimeConfig) -> types::Result<Self> {
+        Ok(Self {
+            engine: config.make_engine()?,
+            memory64: config.memory64,
+        })
+    }
+
+    pub fn validate(&self, data: &[u8], module_info: &ModuleInfo) -> ValidationReport {
+        let mut report = ValidationReport {
+            module: module_info.get_identifier(),
+            issues: vec![],
+        };
+
+        // Components carry no musubi metadata, compiling them is all there is to check.
+        if is_component_binary(data) {
+            if let Err(e) = wasmtime::component::Component::from_binary(&self.engine, data) {
+                report.push(
+                    ValidationIssueKind::CompilationFailed,
+                    format!("failed to compile wasm component: {:?}", e),
+                );
+            }
+
+            return report;
+        }
+
+        let spec = match WasmMetadata::new(data) {
+            Ok(metadata) => Some(metadata.get_musubi_spec()),
+            Err(e) => {
+                report.push(
+                    ValidationIssueKind::InvalidMetadata,
+                    format!("failed to parse musubi metadata: {}", e),
+                );
+                None
+            }
+        };
+
+        if let Some(spec) = &spec {
+            match spec.get_bitness() {
+                Ok(musubi_api::types::Bitness::X32) => {}
+                Ok(musubi_api::types::Bitness::X64) if self.memory64 => {}
+                Ok(_) => report.push(
+                    ValidationIssueKind::UnsupportedBitness,
+                    "64 bit wasm requires the memory64 option to be enabled".to_string(),
+                ),
+                Err(e) => report.push(
+                    ValidationIssueKind::InvalidMetadata,
+                    format!("failed to load WASM bitness: {}", e),
+                ),
+            }
+        }
+
+        let module = match wasmtime::Module::from_binary(&self.engine, data) {
+            Ok(module) => module,
+            Err(e) => {
+                report.push(
+                    ValidationIssueKind::CompilationFailed,
+                    format!("failed to compile wasm module: {:?}", e),
+                );
+                return report;
+            }
+        };
+
+        if let Some(spec) = &spec {
+            let declared_dependencies: HashSet<String> =
+                spec.info.deps.iter().map(|dep| dep.name.clone()).collect();
+
+            for import in module.imports() {
+                if let Ok((module_name, _)) = Symbol::parse_musubi_function_symbol(import.name()) {
+                    if !declared_dependencies.contains(&module_name) {
+                        report.push(
+                            ValidationIssueKind::UndeclaredDependency,
+                            format!(
+                                "import '{}' refers to module '{}' which is not a declared dependency",
+                                import.name(),
+                                module_name
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+
+        for export in module.exports() {
+            if let Ok((module_name, _)) = Symbol::parse_musubi_function_symbol(export.name()) {
+                if module_name != module_info.name {
+                    report.push(
+                        ValidationIssueKind::SpoofedExport,
+                        format!(
+                            "export '{}' belongs to module '{}', expected '{}'",
+                            export.name(),
+                            module_name,
+                            module_info.name
+                        ),
+                    );
+                }
+            }
+        }
+
+        report
+    }
+}
@@ -15,6 +15,7 @@ use wasi_common::{Error, ErrorExt, SystemTimeSpec, WasiDir};
 pub struct Dir {
     fs: Arc<AsyncNativeFileSystem>,
     path: PathBuf,
+    allowed_paths: Option<Arc<Vec<PathBuf>>>,
 }
 
 impl Dir {
@@ -22,6 +23,31 @@ impl Dir {
         Self {
             fs,
             path: Path::new(path).to_path_buf(),
+            allowed_paths: None,
+        }
+    }
+
+    /// Restricts access to the given absolute paths and everything below them.
+    pub fn with_allowed_paths(mut self, paths: Vec<String>) -> Self {
+        self.allowed_paths = Some(Arc::new(paths.into_iter().map(PathBuf::from).collect()));
+        self
+    }
+
+    fn check_path(&self, path: &str) -> Result<(), Error> {
+        let Some(allowed_paths) = &self.allowed_paths else {
+            return Ok(());
+        };
+
+        let absolute_pathbuf = self.path.join(path);
+        let absolute_path = absolute_pathbuf
+            .as_path()
+            .absolutize_virtually("/")
+            .map_err(|e| Error::trap(e.into()))?;
+
+        if allowed_paths.iter().any(|x| absolute_path.starts_with(x)) {
+            Ok(())
+        } else {
+            Err(Error::perm().context("path is not declared in the capability manifest"))
         }
     }
 }
@@ -41,6 +67,8 @@ impl WasiDir for Dir {
         write: bool,
         fdflags: FdFlags,
     ) -> Result<OpenResult, Error> {
+        self.check_path(path)?;
+
         let mut opts = OpenOptions::new();
 
         if oflags.contains(OFlags::CREATE | OFlags::EXCLUSIVE) {
@@ -114,6 +142,8 @@ impl WasiDir for Dir {
     }
 
     async fn create_dir(&self, path: &str) -> Result<(), Error> {
+        self.check_path(path)?;
+
         let absolute_pathbuf = self.path.join(path);
         let absolute_cow_path = absolute_pathbuf
             .as_path()
@@ -147,6 +177,8 @@ impl WasiDir for Dir {
     }
 
     async fn remove_dir(&self, path: &str) -> Result<(), Error> {
+        self.check_path(path)?;
+
         let absolute_pathbuf = self.path.join(path);
         let absolute_path = absolute_pathbuf.as_path();
 
@@ -0,0 +1,219 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use async_trait::async_trait;
+use mitsuha_core::{
+    err_unsupported_op,
+    errors::Error,
+    kernel::{Kernel, KernelBinding},
+    linker::{Linker, LinkerContext},
+    resolver::Resolver,
+    types,
+};
+use mitsuha_core_types::{
+    module::{ModuleInfo, ModuleType},
+    symbol::Symbol,
+};
+use mitsuha_wasm_runtime::wasmtime::{ComponentLinker, JobMonitor, WasmtimeConfig};
+use musubi_api::{
+    types::{Data, Value},
+    DataBuilder,
+};
+
+const ADD_COMPONENT: &str = r#"
+(component
+  (core module $m
+    (func (export "add") (param i32 i32) (result i32)
+      local.get 0
+      local.get 1
+      i32.add))
+  (core instance $i (instantiate $m))
+  (func (export "add") (param "a" s32) (param "b" s32) (result s32)
+    (canon lift (core func $i "add"))))
+"#;
+
+struct CountingResolver {
+    modules: HashMap<ModuleInfo, Vec<u8>>,
+    count: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl Resolver<ModuleInfo, Vec<u8>> for CountingResolver {
+    async fn resolve(&self, key: &ModuleInfo) -> types::Result<Vec<u8>> {
+        self.count.fetch_add(1, Ordering::SeqCst);
+
+        self.modules
+            .get(key)
+            .cloned()
+            .ok_or(Error::EntityNotFoundError {
+                name: key.get_identifier(),
+                kind: "Module".to_string(),
+            })
+    }
+
+    async fn register(&self, _key: &ModuleInfo, _value: &Vec<u8>) -> types::Result<()> {
+        Err(err_unsupported_op!("cannot register modules"))
+    }
+}
+
+/// Components reach the kernel through their imports only, which these tests do not use.
+struct UnusedKernelBinding;
+
+#[async_trait]
+impl KernelBinding for UnusedKernelBinding {
+    async fn get_kernel(&self) -> Arc<Box<dyn Kernel>> {
+        unreachable!("components do not use the kernel directly")
+    }
+
+    async fn run(&self, _symbol: &Symbol, _input: Vec<u8>) -> types::Result<Vec<u8>> {
+        Err(err_unsupported_op!("kernel calls are not supported"))
+    }
+}
+
+fn make_module_info(name: &str) -> ModuleInfo {
+    ModuleInfo {
+        name: name.to_string(),
+        version: "0.1.0".to_string(),
+        modtype: ModuleType::WASM,
+    }
+}
+
+/// Appends a `mitsuha.capabilities` custom section to a wasm binary.
+fn with_manifest(mut data: Vec<u8>, manifest: &str) -> Vec<u8> {
+    let name = b"mitsuha.capabilities";
+
+    let mut section = vec![name.len() as u8];
+    section.extend(name);
+    section.extend(manifest.as_bytes());
+
+    data.push(0);
+
+    let mut size = section.len();
+    loop {
+        let byte = (size & 0x7f) as u8;
+        size >>= 7;
+
+        if size == 0 {
+            data.push(byte);
+            break;
+        }
+
+        data.push(byte | 0x80);
+    }
+
+    data.extend(section);
+    data
+}
+
+fn make_context(modules: Vec<(ModuleInfo, Vec<u8>)>) -> (LinkerContext, Arc<AtomicUsize>) {
+    let count = Arc::new(AtomicUsize::new(0));
+
+    let resolver = CountingResolver {
+        modules: modules.into_iter().collect(),
+        count: count.clone(),
+    };
+
+    let ctx = LinkerContext::new(
+        Arc::new(Box::new(UnusedKernelBinding)),
+        Arc::new(Box::new(resolver)),
+    );
+
+    (ctx, count)
+}
+
+fn make_linker(properties: &[(&str, &str)]) -> ComponentLinker {
+    let config = WasmtimeConfig::from_properties(
+        &properties
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+    )
+    .unwrap();
+
+    ComponentLinker::new(config.make_engine().unwrap(), &config).unwrap()
+}
+
+#[tokio::test]
+async fn run_component_export() {
+    let module_info = make_module_info("mitsuha.test.component.add");
+    let (mut ctx, _) = make_context(vec![(
+        module_info.clone(),
+        wat::parse_str(ADD_COMPONENT).unwrap(),
+    )]);
+
+    let linker = make_linker(&[]);
+
+    assert!(linker.is_component(&mut ctx, &module_info).await.unwrap());
+
+    linker.load(&mut ctx, &module_info).await.unwrap();
+
+    let executor_context = linker
+        .link_monitored(&ctx, &module_info, &JobMonitor::unlimited())
+        .await
+        .unwrap();
+
+    let symbol = Symbol {
+        name: "add".to_string(),
+        module_info,
+    };
+
+    let input = DataBuilder::new()
+        .add(Value::U64(2))
+        .add(Value::U64(-5i64 as u64))
+        .build();
+
+    let output = executor_context
+        .call(&symbol, input.try_into().unwrap())
+        .await
+        .unwrap();
+
+    let output = Data::try_from(output).unwrap();
+
+    assert!(matches!(output.values().first(), Some(Value::U64(x)) if *x as i64 == -3));
+}
+
+#[tokio::test]
+async fn core_modules_are_resolved_once() {
+    let module_info = make_module_info("mitsuha.test.component.core");
+    let (mut ctx, count) = make_context(vec![(
+        module_info.clone(),
+        wat::parse_str("(module)").unwrap(),
+    )]);
+
+    let linker = make_linker(&[]);
+
+    assert!(!linker.is_component(&mut ctx, &module_info).await.unwrap());
+
+    // The core linker resolves the module through the same context.
+    ctx.module_resolver.resolve(&module_info).await.unwrap();
+
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn components_require_a_manifest() {
+    let unsigned = make_module_info("mitsuha.test.component.unsigned");
+    let declared = make_module_info("mitsuha.test.component.declared");
+
+    let data = wat::parse_str(ADD_COMPONENT).unwrap();
+
+    let (mut ctx, _) = make_context(vec![
+        (unsigned.clone(), data.clone()),
+        (declared.clone(), with_manifest(data, r#"{"kernel": []}"#)),
+    ]);
+
+    let linker = make_linker(&[("require_capability_manifest", "true")]);
+
+    assert!(linker.load(&mut ctx, &unsigned).await.is_err());
+
+    linker.load(&mut ctx, &declared).await.unwrap();
+    linker
+        .link_monitored(&ctx, &declared, &JobMonitor::unlimited())
+        .await
+        .unwrap();
+}
@@ -0,0 +1,44 @@
+use mitsuha_core_types::module::{ModuleInfo, ModuleType};
+use mitsuha_wasm_runtime::wasmtime::{
+    validator::ValidationIssueKind, ModuleValidator, WasmtimeConfig,
+};
+
+#[test]
+fn validate_modules() {
+    let wasm_echo: Vec<u8> = include_bytes!(
+        "../../mitsuha-runtime-test/target/wasm32-unknown-unknown/release/mitsuha_wasm_echo.wasm"
+    )
+    .to_vec();
+    let wasm_main: Vec<u8> = include_bytes!(
+        "../../mitsuha-runtime-test/target/wasm32-unknown-unknown/release/mitsuha_wasm_main.wasm"
+    )
+    .to_vec();
+
+    let validator = ModuleValidator::new(WasmtimeConfig::default().make_engine().unwrap());
+
+    let make_module_info = |name: &str| ModuleInfo {
+        name: name.to_string(),
+        version: "0.1.0".to_string(),
+        modtype: ModuleType::WASM,
+    };
+
+    assert!(validator
+        .validate(&wasm_echo, &make_module_info("mitsuha.test.echo"))
+        .is_valid());
+
+    assert!(validator
+        .validate(&wasm_main, &make_module_info("mitsuha.test.main"))
+        .is_valid());
+
+    let report = validator.validate(&wasm_echo, &make_module_info("mitsuha.test.spoofed"));
+    assert!(report
+        .issues
+        .iter()
+        .any(|issue| issue.kind == ValidationIssueKind::SpoofedExport));
+
+    let report = validator.validate(b"not a wasm module", &make_module_info("mitsuha.test.echo"));
+    assert!(report
+        .issues
+        .iter()
+        .any(|issue| issue.kind == ValidationIssueKind::CompilationFailed));
+}